@@ -1,7 +1,12 @@
+use crate::devices::audio::AudioRouting;
+use crate::notifications::{Notification, NotificationAction};
+use crate::video::filter_settings::FilterSettings;
+use crate::video::types::SourceKind;
 use crate::video::VideoFormat;
-use crate::{config, devices, ui, video, devices::filter_type::CrtFilter};
+use crate::{assets, config, devices, games, kiosk, looks, net, preflight, scenes, selftest, support, ui, video, devices::filter_type::{BfiMode, CrtFilter}};
 use anyhow::Context;
 use eframe::egui;
+use std::collections::HashMap;
 use std::sync::{Mutex, 
     atomic::{AtomicBool, AtomicU8, Ordering},
     Arc,
@@ -9,9 +14,35 @@ use std::sync::{Mutex,
 use std::thread::{self, JoinHandle};
 use std::time::Instant;
 
+/// How long to wait before retrying a capture device that another program is using.
+const BUSY_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
+/// Kiosk installations keep retrying a failed stream instead of showing the failure dialog.
+const KIOSK_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// Capture devices re-enumerate after a resume, so give them a moment before restarting.
+const RESUME_RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(3);
+/// How long a message stays on the video after e.g. switching filters with a hotkey.
+pub const OSD_DURATION: std::time::Duration = std::time::Duration::from_millis(1500);
+
+/// How often the loopback's latency is compared with where it started.
+const DRIFT_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+/// A freshly loaded loopback takes a few seconds to fill its buffers; its first reading is
+/// only taken after this.
+const DRIFT_SETTLE_TIME: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Caps the UI at ~30 FPS while the power saver is on.
+const POWER_SAVER_REPAINT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(33);
+
+/// Recording kept after a loud peak in a highlight clip.
+const HIGHLIGHT_TAIL: std::time::Duration = std::time::Duration::from_secs(5);
+/// Extra wait before cutting, so the encoder has written the tail to the file.
+const HIGHLIGHT_FLUSH_DELAY: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// The UI thread's half of a `run_pulse_task`.
+type PulseTask = Box<dyn FnOnce(&mut AppState, &egui::Context) + Send>;
+
 pub struct AppState {
     pub video_devices: Vec<String>,
-    pub usb_devices: Vec<(String, String)>,
+    pub usb_devices: Vec<devices::usb::UsbDevice>,
     pub selected_usb_device: Option<String>,
     pub selected_video_device: String,
     pub pulse_sources: Vec<(String, String)>,
@@ -19,16 +50,100 @@ pub struct AppState {
     pub selected_pulse_source_name: Option<String>,
     pub selected_pulse_sink_name: Option<String>,
     pub pulse_loopback_module_index: Option<u32>,
-    pub status_message: String,
+    pub pulse_limiter_module_index: Option<u32>,
+    /// A second output the capture is heard through, e.g. headphones, next to the main sink.
+    pub monitor_output_sink: Option<String>,
+    /// Playback volume of the monitor output, 1.0 being 100%.
+    pub monitor_output_volume: f32,
+    pub pulse_monitor_output_module_index: Option<u32>,
+    pub limiter_enabled: bool,
+    pub limiter_limit_db: f32,
+    pub loudness_meter_enabled: bool,
+    pub loudness_meter: Option<devices::loudness::LoudnessMeter>,
+    pub audio_routing: AudioRouting,
+    pub pulse_available: bool,
+    pub pulse_cards: Vec<devices::audio::PulseCard>,
+    pub selected_pulse_card: Option<String>,
+    /// The profile being switched to, and the devices afterwards.
+    pub card_profile_receiver: Option<(String, crossbeam_channel::Receiver<anyhow::Result<devices::audio::PulseDeviceLists>>)>,
+    /// PulseAudio calls can take seconds against a slow server, so they run on worker threads
+    /// and hand their results back through here; see `run_pulse_task`.
+    pulse_task_sender: crossbeam_channel::Sender<PulseTask>,
+    pulse_task_receiver: crossbeam_channel::Receiver<PulseTask>,
+    pulse_tasks_running: usize,
+    /// Set while the microphone filters load for a recording about to start.
+    recording_starting: bool,
+    /// Counts stream starts and stops, so modules loaded for an earlier stream aren't adopted.
+    stream_generation: u64,
+    pub alsa_captures: Vec<(String, String)>,
+    pub alsa_playbacks: Vec<(String, String)>,
+    pub alsa_enabled: bool,
+    pub selected_alsa_capture: Option<String>,
+    pub selected_alsa_playback: Option<String>,
+    pub alsa_loopback: Option<devices::alsa::AlsaLoopback>,
+    pub previous_default_source: Option<String>,
+    /// Watches for PulseAudio restarts; started once the devices are scanned.
+    pulse_monitor: Option<(devices::pulse_monitor::PulseMonitor, crossbeam_channel::Receiver<devices::pulse_monitor::PulseEvent>)>,
+    pub loopback_latency_msec: Option<u32>,
+    /// Playback volume of the PulseAudio loopback, 1.0 being 100%.
+    pub loopback_volume: f32,
+    pub loopback_options: devices::audio::LoopbackOptions,
+    pub measured_latency_msec: Option<u32>,
+    pub latency_measure_receiver: Option<crossbeam_channel::Receiver<anyhow::Result<u32>>>,
+    /// End-to-end video latency from the calibration wizard, per profile (the applied look).
+    pub photon_latency_msec: HashMap<String, u32>,
+    pub photon_latency_receiver: Option<crossbeam_channel::Receiver<(String, anyhow::Result<u32>)>>,
+    pub latency_video_path: String,
+    /// When the calibration pattern window was opened, while it is.
+    pub latency_pattern_started: Option<Instant>,
+    pub latency_overlay_enabled: bool,
+    /// Periodically compare the loopback's latency with its first reading.
+    pub drift_check_enabled: bool,
+    pub drift_threshold_msec: u32,
+    /// Reload the loopback when it drifts past the threshold, instead of only warning.
+    pub drift_auto_reload: bool,
+    /// Latency change of the running loopback since its first reading; positive means the
+    /// audio fell further behind.
+    pub audio_drift_msec: Option<i32>,
+    /// (module index, latency in ms) of the loopback's first reading.
+    drift_baseline: Option<(u32, i32)>,
+    drift_module: Option<u32>,
+    drift_next_check: Instant,
+    drift_receiver: Option<crossbeam_channel::Receiver<(u32, anyhow::Result<std::time::Duration>)>>,
+    /// The latest notification, shown in the status line.
+    pub status: Notification,
+    /// Notifications still shown as toasts, oldest first.
+    pub toasts: Vec<Notification>,
     pub supported_formats: Vec<VideoFormat>,
+    /// USB link speed of the selected capture device in Mbit/s, if it is a USB device.
+    pub video_usb_speed: Option<u32>,
+    /// Entry in the built-in card database for the selected capture device.
+    pub known_card: Option<&'static devices::card_db::KnownCard>,
+    /// Cards whose presets were applied once already, so they don't override later changes.
+    pub applied_card_presets: Vec<String>,
+    format_scan_receiver: Option<crossbeam_channel::Receiver<(String, anyhow::Result<Vec<VideoFormat>>)>>,
     pub selected_format_index: usize,
     pub selected_resolution: (u32, u32),
     pub selected_framerate: u32,
+    pub source_kind: SourceKind,
+    pub network_source_url: String,
+    pub file_source_path: String,
+    pub file_source_loop: bool,
+    pub playback_control: Arc<video::decoder::PlaybackControl>,
     pub video_thread: Option<JoinHandle<()>>,
     pub stop_video_thread: Option<Arc<AtomicBool>>,
-    pub video_texture: Option<egui::TextureHandle>,
+    /// Set while a stopped stream's resources are being released; yields the status message.
+    stream_stop_receiver: Option<crossbeam_channel::Receiver<Notification>>,
+    /// A start that waits for the previous stream to release its devices; `update` resumes it.
+    stream_start_pending: bool,
+    pub video_texture: Option<video::texture::VideoTexture>,
     pub frame_receiver: Option<crossbeam_channel::Receiver<Arc<egui::ColorImage>>>,
-    device_scan_receiver: Option<crossbeam_channel::Receiver<devices::DeviceScanResult>>,
+    pub frame_pacer: video::pacing::FramePacer,
+    pub pacing_mode: video::pacing::PacingMode,
+    pub interpolation_mode: video::interpolate::InterpolationMode,
+    /// Running while streaming with interpolation on; sits between the source frames and the pacer.
+    interpolator: Option<video::interpolate::Interpolator>,
+    pub device_scan_receiver: Option<crossbeam_channel::Receiver<devices::DeviceScanResult>>,
     pub logo_texture: Option<egui::TextureHandle>,
     last_fps_check: Instant,
     frames_since_last_check: u32,
@@ -36,31 +151,302 @@ pub struct AppState {
     video_frames_since_last_check: u32,
     pub is_fullscreen: bool,
     pub reset_usb_on_startup: bool,
+    /// Outcome of the USB reset running on a background thread; a power cycle takes seconds.
+    usb_reset_receiver: Option<crossbeam_channel::Receiver<Notification>>,
+    pub usb_reset_method: devices::usb::UsbResetMethod,
+    pub throttle_when_minimized: bool,
+    /// Uploads frames straight into the filter renderer's texture instead of through egui.
+    pub direct_upload_enabled: bool,
+    /// Applied to the video threads at the next stream start.
+    pub thread_priority: video::types::ThreadPriority,
+    pub idle_inhibit_policy: devices::idle_inhibit::IdleInhibitPolicy,
+    idle_inhibitor: Option<devices::idle_inhibit::IdleInhibitor>,
+    /// Turn on the desktop's do-not-disturb mode while streaming or recording.
+    pub do_not_disturb_enabled: bool,
+    do_not_disturb: Option<devices::do_not_disturb::DoNotDisturb>,
+    /// Integer scale of the video window when a stream starts.
+    pub window_scale: u32,
+    /// Applied at the next start, see `video::display`.
+    pub present_mode: video::display::PresentMode,
+    pub gpu_preference: video::display::GpuPreference,
+    sleep_monitor: Option<(devices::sleep_monitor::SleepMonitor, crossbeam_channel::Receiver<devices::sleep_monitor::SleepEvent>)>,
+    /// Restarts a stream that was stopped for a suspend once the system resumes.
+    pub restart_after_resume: bool,
+    stopped_for_sleep: bool,
+    /// The system waits to suspend until the stream stopped for it has released its devices.
+    sleep_ready_pending: bool,
+    /// Started when the diagnostics section is first opened.
+    pub system_sampler: Option<devices::sys_stats::SystemSampler>,
+    resume_restart_at: Option<Instant>,
+    /// Let `pick_auto_format` choose the capture format, resolution and framerate.
+    pub auto_format: bool,
+    /// Fourccs the auto format prefers, most wanted first, as typed (see `parse_format_priority`).
+    pub format_priority: String,
+    pub format_override: video::types::FormatOverride,
+    /// Collapsing sections in the controls, by title, and whether they were left open.
+    pub open_sections: HashMap<String, bool>,
+    /// Last windowed size of the main window, restored on the next start.
+    pub main_window_size: Option<(f32, f32)>,
+    /// False until the first device scan finished; later scans are user-triggered rescans.
+    pub devices_scanned: bool,
+    pub power_saver: Arc<AtomicBool>,
+    pub auto_downscale: Arc<AtomicBool>,
+    pub display_size: Arc<std::sync::atomic::AtomicU64>,
+    /// How long the capture buffer read last waited in the driver's queue, in µs, -1 if unknown.
+    pub buffer_age_us: Arc<std::sync::atomic::AtomicI64>,
+    /// `filter_settings`' color correction, read by the video thread. Neutral while a test card
+    /// is being analyzed.
+    color_adjust: Arc<Mutex<video::color::ColorAdjust>>,
+    /// Frames collected for the test card analysis, while it's collecting.
+    calibration_frames: Option<Vec<Arc<egui::ColorImage>>>,
+    calibration_receiver: Option<crossbeam_channel::Receiver<anyhow::Result<video::color::Calibration>>>,
+    pub calibration_result: Option<video::color::Calibration>,
     pub show_first_run_dialog: bool,
     pub show_quit_dialog: bool,
     pub show_stop_stream_dialog: bool,
+    /// Set when the video thread died on its own; shown with a restart button.
+    pub video_thread_failure: Option<video::types::VideoThreadFailure>,
+    /// Keep restarting the stream while the capture device is busy.
+    pub busy_retry_at: Option<Instant>,
+    /// Open preflight checklist; `preflight_dirty` re-runs the checks after a fix.
+    pub preflight_issues: Option<Vec<preflight::PreflightIssue>>,
+    pub preflight_dirty: bool,
+    /// The preflight fix rescans the formats; the defaults are picked once the scan is in.
+    pub pick_default_format_pending: bool,
+    pub stream_info: Option<video::types::StreamInfo>,
+    stream_info_receiver: Option<crossbeam_channel::Receiver<video::types::StreamInfo>>,
+    video_thread_failure_receiver: Option<crossbeam_channel::Receiver<video::types::VideoThreadFailure>>,
     pub video_window_open: bool,
     pub control_window_open: bool,
     pub pixelate_filter_enabled: bool,
+    pub filter_settings: FilterSettings,
+    /// What "Reset to Defaults" goes back to, per filter. The built-in values until the user
+    /// saves their own.
+    pub filter_defaults: FilterSettings,
+    // Filter parameter modulation
+    pub modulation_enabled: bool,
+    pub modulations: Vec<video::modulation::Modulation>,
+    pub modulation_osc_enabled: bool,
+    pub modulation_osc_port: u16,
+    pub osc_input: Option<video::modulation::OscInput>,
+    /// Where the LFO cycles start.
+    modulation_started: Instant,
+
+    // MIDI controller
+    /// Raw MIDI port read for the modulations and mappings, or `None` for no MIDI.
+    pub midi_device: Option<String>,
+    pub midi_input: Option<(devices::midi::MidiInput, crossbeam_channel::Receiver<devices::midi::MidiEvent>)>,
+    /// Latest position of every control that moved, from 0 to 1.
+    midi_values: HashMap<devices::midi::MidiControl, f32>,
+    pub midi_last_event: Option<devices::midi::MidiEvent>,
+    pub midi_mappings: Vec<devices::midi::MidiMapping>,
+    /// Mapping that takes the next control moved, for MIDI learn.
+    pub midi_learn: Option<usize>,
+    /// Filters the C key cycles through, as a mask of `CrtFilter::cycle_bit`s.
+    pub filter_cycle: u8,
+    /// Message shown on top of the video and when it was shown.
+    pub osd_message: Option<(String, Instant)>,
     pub crt_filter: Arc<AtomicU8>,
     pub crt_renderer: Option<Arc<Mutex<video::gpu_filter::CrtFilterRenderer>>>,
+    /// Set by the paint callbacks when the GL context was replaced and the renderer rebuilt.
+    pub gl_context_reset: Arc<AtomicBool>,
+
+    // Motion clarity
+    pub bfi_mode: BfiMode,
+    pub bfi_cycle: u32,
+    pub bfi_refresh_counter: u64,
+
+    // Pixel aspect ratio, as width:height of a single source pixel
+    pub pixel_aspect: (u32, u32),
+
+    // Bezel overlay, saved with the config and with each scene
+    pub overlay_enabled: bool,
+    pub overlay_opacity: f32,
+    /// The overlay image, empty for none.
+    pub overlay_path: String,
+    pub overlay_path_input: String,
+    pub overlay_texture: Option<egui::TextureHandle>,
+    pub overlay_loaded_path: Option<String>,
+    /// The overlay being decoded, and its path.
+    overlay_load_receiver: Option<(String, crossbeam_channel::Receiver<anyhow::Result<egui::ColorImage>>)>,
+
+    // Text overlay, from static text or a file rewritten by other tools
+    pub text_overlay_enabled: bool,
+    pub text_overlay_text: String,
+    pub text_overlay_file: String,
+    pub text_overlay_position: video::overlay::TextOverlayPosition,
+    pub text_overlay_size: f32,
+    pub text_overlay_source: video::overlay::TextFileSource,
+
+    // Speedrun timer overlay
+    pub timer_enabled: bool,
+    pub timer_source: video::timer::TimerSource,
+    pub timer_size: f32,
+    pub livesplit_address: String,
+    pub stopwatch: video::timer::Stopwatch,
+    livesplit: Option<(net::livesplit::LiveSplitClient, crossbeam_channel::Receiver<Option<net::livesplit::LiveSplitStatus>>)>,
+    /// Latest state of the LiveSplit timer, `None` while not connected.
+    pub livesplit_status: Option<net::livesplit::LiveSplitStatus>,
+
+    // Auto-splitter
+    pub autosplit_enabled: bool,
+    pub autosplit_triggers: Vec<video::autosplit::SplitTrigger>,
+    pub autosplitter: Option<(video::autosplit::AutoSplitter, crossbeam_channel::Receiver<video::autosplit::SplitEvent>)>,
+    /// The trigger that fired last and when, shown in the controls.
+    pub autosplit_last_event: Option<(String, Instant)>,
+
+    /// Set when started with `--kiosk`.
+    pub kiosk: Option<kiosk::Kiosk>,
+
+    /// Shows the unfiltered video in a second window next to the filtered one.
+    pub clean_feed_enabled: bool,
+
+    // Display wall
+    pub wall_enabled: bool,
+    pub wall_layout: video::wall::WallLayout,
+
+    // Network preview
+    pub preview_server: Option<net::mjpeg_server::MjpegServer>,
+    pub preview_enabled: bool,
+    pub preview_port: u16,
+    pub preview_quality: u8,
+    pub preview_max_fps: u32,
+    pub preview_username: String,
+    pub preview_password: String,
 
-    // Lottes Filter Params
-    pub crt_hard_scan: f32,
-    pub crt_warp_x: f32,
-    pub crt_warp_y: f32,
-    pub crt_shadow_mask: f32,
-    pub crt_brightboost: f32,
-    pub crt_hard_bloom_pix: f32,
-    pub crt_hard_bloom_scan: f32,
-    pub crt_bloom_amount: f32,
-    pub crt_shape: f32,
-    pub crt_hard_pix: f32,
+    // Remote control
+    pub remote_server: Option<(net::remote::RemoteServer, crossbeam_channel::Receiver<net::remote::RemoteCommand>)>,
+    pub remote_enabled: bool,
+    pub remote_port: u16,
+    pub remote_username: String,
+    pub remote_password: String,
+    pub command_server: Option<(net::command::CommandServer, crossbeam_channel::Receiver<net::remote::RemoteCommand>)>,
+    pub command_port_enabled: bool,
+    pub command_port: u16,
+
+    // Frame sharing with local tools
+    pub frame_share: Option<net::frame_share::FrameShare>,
+    pub frame_share_enabled: bool,
+
+    // OBS integration
+    pub obs_enabled: bool,
+    pub obs_settings: net::obs::ObsSettings,
+    obs_controller: Option<(net::obs::ObsController, crossbeam_channel::Receiver<anyhow::Result<String>>)>,
+
+    // Recording
+    pub recorder: Option<video::recorder::Recorder>,
+    /// The frame on screen, kept for screenshots.
+    last_frame: Option<Arc<egui::ColorImage>>,
+    screenshot_receiver: Option<crossbeam_channel::Receiver<anyhow::Result<std::path::PathBuf>>>,
+    /// A recording the previous run was killed in the middle of.
+    pub unfinished_recording: Option<std::path::PathBuf>,
+    pub recovery_receiver: Option<crossbeam_channel::Receiver<anyhow::Result<std::path::PathBuf>>>,
+    /// Recent files in the recordings folder, listed when the browser is first shown.
+    pub recording_list: Option<Vec<video::clips::RecordingFile>>,
+    pub trim_session: Option<video::clips::TrimSession>,
+    pub trim_preview_receiver: Option<crossbeam_channel::Receiver<(std::path::PathBuf, anyhow::Result<video::clips::ClipPreview>)>>,
+    pub trim_receiver: Option<crossbeam_channel::Receiver<anyhow::Result<std::path::PathBuf>>>,
+    pub ffmpeg_log_level: video::ffmpeg_log::FfmpegLogLevel,
+    pub recording_dir: String,
+    pub recording_file_template: String,
+    pub record_audio: bool,
+    pub record_audio_volume: f32,
+    pub record_mic_enabled: bool,
+    pub record_mic_source: Option<String>,
+    pub record_mic_volume: f32,
+    pub mic_highpass_enabled: bool,
+    pub mic_gate_enabled: bool,
+    /// The gate closes below this level, in dB.
+    pub mic_gate_threshold_db: f32,
+    /// Loaded while recording with a filtered microphone track.
+    mic_filters: Option<devices::audio::MicFilterChain>,
+    pub record_crop_enabled: bool,
+    pub record_crop: video::recorder::CropRect,
+    pub record_burn_in: bool,
+    pub timelapse_mode: video::recorder::TimelapseMode,
+    pub timelapse_interval: u32,
+    /// Cuts a clip out of the recording whenever the capture audio gets this loud.
+    pub highlight_clips_enabled: bool,
+    pub highlight_threshold_lufs: f32,
+    /// Seconds of the clip before the peak.
+    pub highlight_lead_secs: u32,
+    /// Recording position of the peak and when the clip can be cut.
+    highlight_pending: Option<(std::time::Duration, Instant)>,
+    last_highlight_at: Option<Instant>,
+    highlight_receiver: Option<crossbeam_channel::Receiver<anyhow::Result<std::path::PathBuf>>>,
+    /// Outcomes of recordings finished on a background thread by `stop_recording`.
+    recording_stopped_sender: crossbeam_channel::Sender<Notification>,
+    recording_stopped_receiver: crossbeam_channel::Receiver<Notification>,
+    pub record_encoder: video::recorder::VideoEncoder,
+    pub record_quality: video::recorder::RecordQuality,
+    pub vaapi_device: String,
+    /// Filled on first use, from `ffmpeg -encoders` on a worker thread.
+    available_encoders: Option<Vec<video::recorder::VideoEncoder>>,
+    encoder_probe_receiver: Option<crossbeam_channel::Receiver<Vec<video::recorder::VideoEncoder>>>,
+    /// Set when a new frame was uploaded, so the next paint reads the filtered result back once.
+    pub burn_in_readback_pending: bool,
+    pub filtered_frame_sender: crossbeam_channel::Sender<Arc<egui::ColorImage>>,
+    filtered_frame_receiver: crossbeam_channel::Receiver<Arc<egui::ColorImage>>,
+
+    /// OpenGL version and renderer, for the system check.
+    pub gl_info: Option<String>,
+    pub max_texture_size: u32,
+    /// Video memory the filter chain's render targets may take before filters are scaled back.
+    pub gpu_memory_cap_mb: u32,
+    /// Shown once when the filters had to be scaled back to fit `gpu_memory_cap_mb`.
+    pub memory_warning: Option<String>,
+    memory_warning_shown_for: Option<((u32, u32), Option<u32>)>,
+    pub system_check_receiver: Option<crossbeam_channel::Receiver<Vec<selftest::SystemCheck>>>,
+    pub system_check_results: Option<Vec<selftest::SystemCheck>>,
+    /// Set while a support bundle is being written.
+    pub support_bundle_receiver: Option<crossbeam_channel::Receiver<anyhow::Result<std::path::PathBuf>>>,
+    pub probe_receiver: Option<crossbeam_channel::Receiver<video::probe::ProbeResult>>,
+    pub probe_results: Option<Vec<video::probe::ProbeResult>>,
+    /// Number of modes being probed, for the progress display.
+    pub probe_total: usize,
+    probe_stop: Option<Arc<AtomicBool>>,
+    pub undo_history: config::UndoHistory,
+    pub config_save: config::SaveScheduler,
+    /// Only write settings when the user presses Save, instead of after every change.
+    pub manual_save: bool,
+    /// Signals edits made to the config file by something other than the app.
+    config_watch_receiver: Option<crossbeam_channel::Receiver<()>>,
+    /// User shaders, masks, bezels and LUTs from the assets directory, kept up to date by a watcher.
+    pub assets: Vec<assets::Asset>,
+    asset_watch_receiver: Option<crossbeam_channel::Receiver<Vec<assets::Asset>>>,
+    pub available_looks: Vec<(String, std::path::PathBuf)>,
+    /// Name of the last applied look, used for `{profile}` in recording file names.
+    pub active_look: Option<String>,
+    pub look_name_input: String,
+    pub look_import_path: String,
+    /// Shows only this part of the source frame, in source pixels.
+    pub display_crop_enabled: bool,
+    pub display_crop: video::recorder::CropRect,
+    pub auto_crop_mode: video::autocrop::AutoCropMode,
+    border_detector: video::autocrop::BorderDetector,
+    /// Black frames, signal drops and failures of the capture since the app started.
+    pub signal_log: video::signal_log::SignalLog,
+    /// A crop that would remove the black borders, offered in `AutoCropMode::Suggest`.
+    pub auto_crop_suggestion: Option<video::recorder::CropRect>,
+    /// The display crop was set by automatic cropping, so it may also take it away again.
+    auto_cropped: bool,
+    pub scenes: Vec<scenes::Scene>,
+    /// The scene last switched to or saved.
+    pub active_scene: Option<String>,
+    pub scene_name_input: String,
+    /// Switch to a game's scene when its title screen shows up.
+    pub game_detection_enabled: bool,
+    pub games: Vec<games::GameProfile>,
+    pub game_detector: games::GameDetector,
+    pub game_name_input: String,
     fullscreen_toggle_frame_count: Option<u8>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
+        let (filtered_frame_sender, filtered_frame_receiver) = crossbeam_channel::bounded(2);
+        let (recording_stopped_sender, recording_stopped_receiver) = crossbeam_channel::unbounded();
+        let (pulse_task_sender, pulse_task_receiver) = crossbeam_channel::unbounded();
         Self {
             video_devices: Vec::new(),
             usb_devices: Vec::new(),
@@ -71,15 +457,75 @@ impl Default for AppState {
             selected_pulse_source_name: None,
             selected_pulse_sink_name: None,
             pulse_loopback_module_index: None,
-            status_message: "Loading devices...".to_string(),
+            pulse_limiter_module_index: None,
+            monitor_output_sink: None,
+            monitor_output_volume: 1.0,
+            pulse_monitor_output_module_index: None,
+            pulse_monitor: None,
+            limiter_enabled: false,
+            limiter_limit_db: -3.0,
+            loudness_meter_enabled: false,
+            loudness_meter: None,
+            audio_routing: AudioRouting::Loopback,
+            pulse_available: true,
+            pulse_cards: Vec::new(),
+            selected_pulse_card: None,
+            card_profile_receiver: None,
+            pulse_task_sender,
+            pulse_task_receiver,
+            pulse_tasks_running: 0,
+            recording_starting: false,
+            stream_generation: 0,
+            alsa_captures: Vec::new(),
+            alsa_playbacks: Vec::new(),
+            alsa_enabled: false,
+            selected_alsa_capture: None,
+            selected_alsa_playback: None,
+            alsa_loopback: None,
+            previous_default_source: None,
+            loopback_latency_msec: None,
+            loopback_volume: 1.0,
+            loopback_options: devices::audio::LoopbackOptions::default(),
+            measured_latency_msec: None,
+            latency_measure_receiver: None,
+            photon_latency_msec: HashMap::new(),
+            photon_latency_receiver: None,
+            latency_video_path: String::new(),
+            latency_pattern_started: None,
+            latency_overlay_enabled: false,
+            drift_check_enabled: false,
+            drift_threshold_msec: 40,
+            drift_auto_reload: false,
+            audio_drift_msec: None,
+            drift_baseline: None,
+            drift_module: None,
+            drift_next_check: Instant::now(),
+            drift_receiver: None,
+            status: Notification::info("Loading devices..."),
+            toasts: Vec::new(),
             supported_formats: Vec::new(),
+            video_usb_speed: None,
+            known_card: None,
+            applied_card_presets: Vec::new(),
+            format_scan_receiver: None,
             selected_format_index: 0,
             selected_resolution: (0, 0),
             selected_framerate: 0,
+            source_kind: SourceKind::Capture,
+            network_source_url: String::new(),
+            file_source_path: String::new(),
+            file_source_loop: true,
+            playback_control: Arc::new(video::decoder::PlaybackControl::new()),
             video_thread: None,
             stop_video_thread: None,
+            stream_stop_receiver: None,
+            stream_start_pending: false,
             video_texture: None,
             frame_receiver: None,
+            frame_pacer: video::pacing::FramePacer::default(),
+            pacing_mode: video::pacing::PacingMode::Immediate,
+            interpolation_mode: video::interpolate::InterpolationMode::Off,
+            interpolator: None,
             device_scan_receiver: None,
             logo_texture: None,
             last_fps_check: Instant::now(),
@@ -88,34 +534,220 @@ impl Default for AppState {
             video_frames_since_last_check: 0,
             is_fullscreen: false,
             reset_usb_on_startup: false,
+            usb_reset_receiver: None,
+            usb_reset_method: devices::usb::UsbResetMethod::UsbReset,
+            throttle_when_minimized: false,
+            direct_upload_enabled: false,
+            thread_priority: video::types::ThreadPriority::Normal,
+            idle_inhibit_policy: devices::idle_inhibit::IdleInhibitPolicy::WhileStreaming,
+            idle_inhibitor: None,
+            do_not_disturb_enabled: false,
+            do_not_disturb: None,
+            window_scale: 1,
+            present_mode: video::display::PresentMode::Fifo,
+            gpu_preference: video::display::GpuPreference::Default,
+            sleep_monitor: None,
+            restart_after_resume: true,
+            stopped_for_sleep: false,
+            sleep_ready_pending: false,
+            system_sampler: None,
+            resume_restart_at: None,
+            auto_format: false,
+            format_priority: String::new(),
+            format_override: video::types::FormatOverride::default(),
+            open_sections: HashMap::new(),
+            main_window_size: None,
+            devices_scanned: false,
+            power_saver: Arc::new(AtomicBool::new(false)),
+            auto_downscale: Arc::new(AtomicBool::new(false)),
+            display_size: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            buffer_age_us: Arc::new(std::sync::atomic::AtomicI64::new(-1)),
+            color_adjust: Arc::default(),
+            calibration_frames: None,
+            calibration_receiver: None,
+            calibration_result: None,
             show_first_run_dialog: false,
             show_quit_dialog: false,
             show_stop_stream_dialog: false,
+            video_thread_failure: None,
+            busy_retry_at: None,
+            preflight_issues: None,
+            preflight_dirty: false,
+            pick_default_format_pending: false,
+            stream_info: None,
+            stream_info_receiver: None,
+            video_thread_failure_receiver: None,
             video_window_open: false,
             control_window_open: true,
             pixelate_filter_enabled: false,
+            filter_settings: FilterSettings::default(),
+            filter_defaults: FilterSettings::default(),
+            modulation_enabled: false,
+            modulations: Vec::new(),
+            modulation_osc_enabled: false,
+            modulation_osc_port: video::modulation::DEFAULT_OSC_PORT,
+            osc_input: None,
+            modulation_started: Instant::now(),
+            midi_device: None,
+            midi_input: None,
+            midi_values: HashMap::new(),
+            midi_last_event: None,
+            midi_mappings: Vec::new(),
+            midi_learn: None,
+            filter_cycle: CrtFilter::FULL_CYCLE,
+            osd_message: None,
             crt_filter: Arc::new(AtomicU8::new(CrtFilter::Scanlines as u8)),
             crt_renderer: None,
+            gl_context_reset: Arc::new(AtomicBool::new(false)),
+
+            // Motion clarity
+            bfi_mode: BfiMode::Off,
+            bfi_cycle: 2,
+            bfi_refresh_counter: 0,
+
+            pixel_aspect: (1, 1),
+
+            overlay_enabled: false,
+            overlay_opacity: 1.0,
+            overlay_path: String::new(),
+            overlay_path_input: String::new(),
+            overlay_texture: None,
+            overlay_loaded_path: None,
+            overlay_load_receiver: None,
+            text_overlay_enabled: false,
+            text_overlay_text: String::new(),
+            text_overlay_file: String::new(),
+            text_overlay_position: video::overlay::TextOverlayPosition::Bottom,
+            text_overlay_size: 24.0,
+            text_overlay_source: Default::default(),
+            timer_enabled: false,
+            timer_source: video::timer::TimerSource::Builtin,
+            timer_size: 36.0,
+            livesplit_address: net::livesplit::DEFAULT_ADDRESS.to_string(),
+            stopwatch: Default::default(),
+            livesplit: None,
+            livesplit_status: None,
+            autosplit_enabled: false,
+            autosplit_triggers: Vec::new(),
+            autosplitter: None,
+            autosplit_last_event: None,
+            kiosk: None,
+            clean_feed_enabled: false,
+            wall_enabled: false,
+            wall_layout: video::wall::WallLayout::default(),
+
+            preview_server: None,
+            preview_enabled: false,
+            preview_port: 8080,
+            preview_quality: 75,
+            preview_max_fps: 30,
+            preview_username: String::new(),
+            preview_password: String::new(),
+            remote_server: None,
+            remote_enabled: false,
+            remote_port: net::remote::DEFAULT_PORT,
+            remote_username: String::new(),
+            remote_password: String::new(),
+            command_server: None,
+            command_port_enabled: false,
+            command_port: net::command::DEFAULT_PORT,
+            frame_share: None,
+            frame_share_enabled: false,
+            obs_enabled: false,
+            obs_settings: net::obs::ObsSettings { address: net::obs::DEFAULT_ADDRESS.to_string(), ..Default::default() },
+            obs_controller: None,
 
-            // Lottes Filter Params
-            crt_hard_scan: -8.0,
-            crt_warp_x: 0.031,
-            crt_warp_y: 0.041,
-            crt_shadow_mask: 3.0,
-            crt_brightboost: 1.0,
-            crt_hard_bloom_pix: -1.5,
-            crt_hard_bloom_scan: -2.0,
-            crt_bloom_amount: 0.15,
-            crt_shape: 2.0,
-            crt_hard_pix: -3.0,
+            recorder: None,
+            last_frame: None,
+            screenshot_receiver: None,
+            unfinished_recording: None,
+            recovery_receiver: None,
+            recording_list: None,
+            trim_session: None,
+            trim_preview_receiver: None,
+            trim_receiver: None,
+            ffmpeg_log_level: video::ffmpeg_log::FfmpegLogLevel::Warning,
+            recording_dir: video::recorder::default_output_dir().to_string_lossy().into_owned(),
+            recording_file_template: video::recorder::DEFAULT_FILE_TEMPLATE.to_string(),
+            record_audio: true,
+            record_audio_volume: 1.0,
+            record_mic_enabled: false,
+            record_mic_source: None,
+            record_mic_volume: 1.0,
+            mic_highpass_enabled: false,
+            mic_gate_enabled: false,
+            mic_gate_threshold_db: -40.0,
+            mic_filters: None,
+            record_crop_enabled: false,
+            record_crop: (0, 0, 640, 480),
+            record_burn_in: false,
+            timelapse_mode: video::recorder::TimelapseMode::Off,
+            timelapse_interval: 10,
+            highlight_clips_enabled: false,
+            highlight_threshold_lufs: -12.0,
+            highlight_lead_secs: 20,
+            highlight_pending: None,
+            last_highlight_at: None,
+            highlight_receiver: None,
+            recording_stopped_sender,
+            recording_stopped_receiver,
+            record_encoder: video::recorder::VideoEncoder::X264,
+            record_quality: video::recorder::RecordQuality::High,
+            vaapi_device: video::recorder::DEFAULT_VAAPI_DEVICE.to_string(),
+            available_encoders: None,
+            encoder_probe_receiver: None,
+            burn_in_readback_pending: false,
+            filtered_frame_sender,
+            filtered_frame_receiver,
+
+            gl_info: None,
+            max_texture_size: 8192,
+            gpu_memory_cap_mb: 768,
+            memory_warning: None,
+            memory_warning_shown_for: None,
+            system_check_receiver: None,
+            system_check_results: None,
+            support_bundle_receiver: None,
+            probe_receiver: None,
+            probe_results: None,
+            probe_total: 0,
+            probe_stop: None,
+            undo_history: config::UndoHistory::default(),
+            config_save: config::SaveScheduler::default(),
+            manual_save: false,
+            config_watch_receiver: None,
+            assets: Vec::new(),
+            asset_watch_receiver: None,
+            available_looks: Vec::new(),
+            active_look: None,
+            look_name_input: String::new(),
+            look_import_path: String::new(),
+            display_crop_enabled: false,
+            display_crop: (0, 0, 640, 480),
+            auto_crop_mode: video::autocrop::AutoCropMode::Off,
+            border_detector: Default::default(),
+            signal_log: Default::default(),
+            auto_crop_suggestion: None,
+            auto_cropped: false,
+            scenes: Vec::new(),
+            active_scene: None,
+            scene_name_input: String::new(),
+            game_detection_enabled: false,
+            games: Vec::new(),
+            game_detector: Default::default(),
+            game_name_input: String::new(),
             fullscreen_toggle_frame_count: None,
         }
     }
 }
 
 impl AppState {
-    pub fn new(cc: &eframe::CreationContext) -> Self {
+    pub fn new(cc: &eframe::CreationContext, kiosk_options: Option<kiosk::KioskOptions>) -> Self {
         let mut app_state = AppState::default();
+        if let Some(options) = kiosk_options {
+            app_state.kiosk = Some(kiosk::Kiosk::new(options));
+            app_state.control_window_open = false;
+        }
 
         // Load UI Logo Texture
         let logo_image =
@@ -129,314 +761,2568 @@ impl AppState {
             .egui_ctx
             .load_texture("logo", logo_color_image, Default::default());
 
-        // Pre-allocate the video texture to prevent panics.
-        let video_texture = {
-            let tex_manager = cc.egui_ctx.tex_manager();
-            let tex_id = tex_manager.write().alloc(
-                "video_stream".to_string(),
-                egui::ImageData::Color(egui::ColorImage::new([1, 1], egui::Color32::BLACK).into()),
-                egui::TextureOptions::LINEAR,
-            );
-            egui::TextureHandle::new(tex_manager, tex_id)
-        };
-        app_state.video_texture = Some(video_texture);
+        app_state.video_texture = Some(video::texture::VideoTexture::new(&cc.egui_ctx));
 
         if let Some(gl) = cc.gl.as_ref() {
             app_state.crt_renderer = Some(Arc::new(Mutex::new(video::gpu_filter::CrtFilterRenderer::new(gl))));
+            app_state.gl_info = Some(selftest::describe_gl(gl));
+            app_state.max_texture_size = video::gpu_filter::max_texture_size(gl);
         }
 
         app_state.logo_texture = Some(logo_texture);
+        app_state.refresh_looks();
+        app_state.unfinished_recording = video::recorder::unfinished_recording();
 
-        // Asynchronous Device Scanning
-        let (tx, rx) = crossbeam_channel::unbounded();
-        app_state.device_scan_receiver = Some(rx);
-
-        let egui_ctx = cc.egui_ctx.clone();
-        std::thread::spawn(move || {
-            let video_result = devices::video::find_video_devices();
-            let pulse_result = devices::audio::find_pulse_devices();
-            let usb_result = devices::usb::find_usb_devices();
-
-            let result: devices::DeviceScanResult = (|| {
-                let video_devices = video_result.context("Failed to find video devices")?;
-                let (pulse_sources, pulse_sinks) =
-                    pulse_result.context("Failed to find PulseAudio devices")?;
-                let usb_devices = usb_result.context("Failed to find USB devices")?;
-                Ok((video_devices, pulse_sources, pulse_sinks, usb_devices))
-            })();
-
-            if let Err(e) = &result {
-                tracing::error!("Device scan failed: {:?}", e);
-            };
-            let _ = tx.send(result);
-            egui_ctx.request_repaint();
-        });
+        app_state.start_device_scan(&cc.egui_ctx);
+        app_state.config_watch_receiver = Some(config::watch_config_file(&cc.egui_ctx));
+        match assets::watch_assets(&cc.egui_ctx) {
+            Ok(rx) => app_state.asset_watch_receiver = Some(rx),
+            Err(e) => tracing::warn!("Asset manager unavailable: {:#}", e),
+        }
+        app_state.sleep_monitor = Some(devices::sleep_monitor::SleepMonitor::start(&cc.egui_ctx));
 
         // Request focus for the control window on startup
-        cc.egui_ctx.send_viewport_cmd_to(
-            egui::ViewportId::from_hash_of("control_window"),
-            egui::ViewportCommand::Focus
-        );
+        if app_state.kiosk.is_none() {
+            cc.egui_ctx.send_viewport_cmd_to(
+                egui::ViewportId::from_hash_of("control_window"),
+                egui::ViewportCommand::Focus
+            );
+        }
         app_state
     }
 
-    fn handle_device_scan_result(&mut self, result: devices::DeviceScanResult) -> bool {
-        let scan_successful = match result {
-            Ok((video_devices, pulse_sources, pulse_sinks, usb_devices)) => {
-                self.video_devices = video_devices;
-                self.selected_video_device = self.video_devices.first().cloned().unwrap_or_default();
-                self.pulse_sources = pulse_sources;
-                self.pulse_sinks = pulse_sinks;
-                self.usb_devices = usb_devices;
+    /// Files dropped onto either window become the playback source, restarting a running stream.
+    pub fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|i| i.raw.dropped_files.iter().find_map(|f| f.path.clone()));
+        let Some(path) = dropped else { return };
+        if looks::is_look_file(&path) {
+            self.import_look(&path);
+            return;
+        }
+        self.source_kind = SourceKind::File;
+        self.file_source_path = path.to_string_lossy().to_string();
+        config::mark_dirty(self);
+        self.notify(Notification::info(format!("Loaded file: {}", self.file_source_path)));
+        if self.video_thread.is_some() {
+            self.stop_stream_resources();
+            self.start_stream(ctx);
+        }
+    }
 
-                if let Ok(cfg) = confy::load::<config::MichadameConfig>("michadame", None) {
-                    config::apply_config(self, &cfg);
-                }
-                self.status_message = "Devices loaded successfully.".to_string();
-                true
+    pub fn refresh_looks(&mut self) {
+        match looks::find_looks() {
+            Ok(found) => self.available_looks = found,
+            Err(e) => tracing::warn!("Failed to list looks: {:#}", e),
+        }
+    }
+
+    pub fn import_look(&mut self, path: &std::path::Path) {
+        match looks::import_look(path) {
+            Ok(look) => {
+                look.apply(self);
+                self.notify(Notification::success(format!("Imported look '{}'", look.name)));
+                self.refresh_looks();
             }
-            Err(e) => {
-                self.status_message = format!("Error: {}", e);
-                false
+            Err(e) => self.notify(Notification::error(format!("Failed to import look: {:#}", e))),
+        }
+    }
+
+    /// Switches to the scene at `index`. Crop, look and overlays change on the next frame; a
+    /// scene on another capture device restarts the stream on that device.
+    pub fn switch_scene(&mut self, index: usize, ctx: &egui::Context) {
+        let Some(scene) = self.scenes.get(index).cloned() else { return };
+        if scene.needs_restart(self) {
+            let device = scene.device.clone().unwrap_or_default();
+            if !self.video_devices.contains(&device) {
+                self.notify(Notification::error(format!("Scene '{}' uses {}, which isn't connected.", scene.name, device)).with_action(NotificationAction::RescanDevices));
+                return;
+            }
+            let restart = self.video_thread.is_some();
+            if restart {
+                self.stop_stream_resources();
             }
+            self.source_kind = SourceKind::Capture;
+            self.select_video_device(&device, ctx);
+            if restart {
+                // Queued until the old stream lets go and the new device's formats are read.
+                self.start_stream(ctx);
+            }
+        }
+        self.display_crop_enabled = scene.crop.is_some();
+        if let Some(crop) = scene.crop {
+            self.display_crop = crop;
+        }
+        self.overlay_enabled = scene.overlay_enabled;
+        if let Some(path) = &scene.overlay {
+            self.overlay_path = path.clone();
+            self.overlay_path_input = path.clone();
+        }
+        self.text_overlay_enabled = scene.text_overlay_enabled;
+        if let Some(name) = &scene.look {
+            let path = self.available_looks.iter().find(|(n, _)| n == name).map(|(_, path)| path.clone());
+            match path.map(|path| looks::load_look(&path)) {
+                Some(Ok(look)) => look.apply(self),
+                Some(Err(e)) => self.notify(Notification::error(format!("Failed to load look: {:#}", e))),
+                None => self.notify(Notification::warning(format!("Scene '{}' uses the look '{}', which no longer exists.", scene.name, name))),
+            }
+        }
+        config::mark_dirty(self);
+        self.active_scene = Some(scene.name.clone());
+        self.notify(Notification::info(format!("Switched to scene '{}'.", scene.name)));
+    }
+
+    /// Saves the current device, crop, look and overlays as a scene, replacing one of the same name.
+    pub fn save_scene(&mut self, name: &str) {
+        let scene = scenes::Scene::from_state(self, name);
+        match self.scenes.iter_mut().find(|s| s.name == name) {
+            Some(existing) => *existing = scene,
+            None => self.scenes.push(scene),
+        }
+        self.active_scene = Some(name.to_string());
+        config::mark_dirty(self);
+    }
+
+    /// Trains game detection on the current frame, e.g. a title screen, and saves the current
+    /// setup as the game's scene. Adding more screens to a game makes it easier to recognize.
+    pub fn remember_game(&mut self, name: &str) {
+        let Some(fingerprint) = self.last_frame.as_deref().and_then(games::Fingerprint::of) else {
+            self.notify(Notification::warning("Show the game's title screen first. The current picture is missing or too plain to recognize."));
+            return;
         };
-        self.device_scan_receiver = None;
-        scan_successful
+        self.save_scene(name);
+        let hex = fingerprint.to_hex();
+        match self.games.iter_mut().find(|game| game.name == name) {
+            Some(game) if game.fingerprints.contains(&hex) => {}
+            Some(game) => game.fingerprints.push(hex),
+            None => self.games.push(games::GameProfile { name: name.to_string(), scene: name.to_string(), fingerprints: vec![hex] }),
+        }
+        config::mark_dirty(self);
+        let screens = self.games.iter().find(|game| game.name == name).map_or(0, |game| game.fingerprints.len());
+        self.notify(Notification::success(format!("Remembered '{}' ({} screen{})", name, screens, if screens == 1 { "" } else { "s" })));
     }
 
-    fn update_fps_counters(&mut self, ctx: &egui::Context) {
-        self.frames_since_last_check += 1;
-        let now = Instant::now();
-        let elapsed_secs = (now - self.last_fps_check).as_secs_f32();
+    fn switch_to_game(&mut self, name: &str, ctx: &egui::Context) {
+        let Some(game) = self.games.iter().find(|game| game.name == name) else { return };
+        match self.scenes.iter().position(|scene| scene.name == game.scene) {
+            Some(index) => self.switch_scene(index, ctx),
+            None => {
+                let message = format!("Recognized '{}', but its scene '{}' no longer exists.", game.name, game.scene);
+                self.notify(Notification::warning(message));
+            }
+        }
+    }
 
-        if elapsed_secs >= 1.0 {
-            self.last_fps_check = now;
-            self.frames_since_last_check = 0;
+    /// Runs the preflight checks and only starts the stream right away if nothing is wrong.
+    pub fn request_start_stream(&mut self, ctx: &egui::Context) {
+        let issues = preflight::run_checks(self);
+        if issues.is_empty() {
+            self.start_stream(ctx);
+        } else {
+            self.preflight_issues = Some(issues);
         }
+    }
 
-        let video_elapsed_secs = (now - self.last_video_fps_check).as_secs_f32();
-        if video_elapsed_secs >= 1.0 {
-            self.last_video_fps_check = now;
-            self.video_frames_since_last_check = 0;
+    /// Resizes the video window to `window_scale` times the video size in device pixels, so the
+    /// scale stays an exact integer under fractional compositor scaling (e.g. 125% or 150%).
+    pub fn resize_to_video_scale(&self, ctx: &egui::Context, resolution: (u32, u32)) {
+        if resolution.0 == 0 || resolution.1 == 0 {
+            return;
         }
+        let pixels_per_point = ctx.input_for(egui::ViewportId::ROOT, |i| i.pixels_per_point());
+        let scale = self.window_scale.max(1) as f32;
+        let width_px = (resolution.0 as f32 * self.pixel_aspect_ratio()).round() * scale;
+        let height_px = resolution.1 as f32 * scale;
+        let new_size = egui::vec2(width_px / pixels_per_point, height_px / pixels_per_point);
+        ctx.send_viewport_cmd_to(egui::ViewportId::ROOT, egui::ViewportCommand::InnerSize(new_size));
+    }
 
-        let gui_fps = if elapsed_secs > 0.0 { self.frames_since_last_check as f32 / elapsed_secs } else { 0.0 };
-        let video_fps = if video_elapsed_secs > 0.0 { self.video_frames_since_last_check as f32 / video_elapsed_secs } else { 0.0 };
-        ctx.send_viewport_cmd(egui::ViewportCommand::Title(format!(
-            "Michadame Viewer | UI: {:.0} FPS | Video: {:.0} FPS",
-            gui_fps, video_fps
-        )));
+    pub fn pixel_aspect_ratio(&self) -> f32 {
+        if self.pixel_aspect.0 == 0 || self.pixel_aspect.1 == 0 {
+            return 1.0;
+        }
+        self.pixel_aspect.0 as f32 / self.pixel_aspect.1 as f32
     }
 
-    pub fn start_stream(&mut self, ctx: &egui::Context) {
-        match (&self.selected_pulse_source_name, &self.selected_pulse_sink_name) {
-            (Some(mic), Some(sink)) => {
-                match devices::audio::load_pulse_loopback(mic, sink) {
-                    Ok(index) => {
-                        self.pulse_loopback_module_index = Some(index);
-                        self.status_message = "PulseAudio loopback loaded.".to_string();
-                    }
+    /// The overlay image, if one is set.
+    pub fn current_overlay_path(&self) -> Option<&String> {
+        Some(&self.overlay_path).filter(|path| !path.is_empty())
+    }
+
+    /// Starts loading the overlay texture if it isn't loaded yet, and takes it over once it's
+    /// decoded. A failed load is remembered so it is not retried on every frame.
+    pub fn ensure_overlay_texture(&mut self, ctx: &egui::Context) {
+        let wanted = self.current_overlay_path().cloned();
+        if let Some((path, rx)) = &self.overlay_load_receiver {
+            if Some(path) != wanted.as_ref() {
+                self.overlay_load_receiver = None;
+            } else if let Ok(result) = rx.try_recv() {
+                self.overlay_load_receiver = None;
+                match result {
+                    Ok(image) => self.overlay_texture = Some(ctx.load_texture("overlay", image, egui::TextureOptions::LINEAR)),
                     Err(e) => {
-                        self.status_message = format!("Failed to load loopback: {}", e);
-                        return;
+                        tracing::error!("Failed to load overlay: {:?}", e);
+                        self.notify(Notification::error(format!("Failed to load overlay: {}", e)));
                     }
                 }
-            }
-            _ => {
-                self.status_message = "Cannot start: Missing PulseAudio devices.".to_string();
+                return;
+            } else {
                 return;
             }
         }
-
-        let format = if let Some(f) = self.supported_formats.get(self.selected_format_index) {
-            f
-        } else {
-            self.status_message = "Cannot start: No video format selected.".to_string();
+        if wanted == self.overlay_loaded_path {
             return;
-        };
-
-        let resolution = self.selected_resolution;
+        }
+        self.overlay_texture = None;
+        if let Some(path) = &wanted {
+            let (tx, rx) = crossbeam_channel::bounded(1);
+            let (load_path, egui_ctx) = (path.clone(), ctx.clone());
+            std::thread::spawn(move || {
+                let _ = tx.send(video::overlay::load_overlay_image(&load_path));
+                egui_ctx.request_repaint();
+            });
+            self.overlay_load_receiver = Some((path.clone(), rx));
+        }
+        self.overlay_loaded_path = wanted;
+    }
 
-        // Resize the main window to match the video stream resolution
-        // The command needs to be sent to the main viewport.
-        let new_size = egui::vec2(resolution.0 as f32, resolution.1 as f32);
-        ctx.send_viewport_cmd_to(
-            egui::ViewportId::ROOT, egui::ViewportCommand::InnerSize(new_size)
-        );
-        ctx.request_repaint(); // Force a repaint to ensure the new texture is drawn
+    /// Takes over a new asset index, warning about files that were added or changed and can't
+    /// be used.
+    fn update_assets(&mut self, assets: Vec<assets::Asset>) {
+        let new_problems: Vec<String> = assets
+            .iter()
+            .filter(|asset| !self.assets.contains(asset))
+            .filter_map(|asset| asset.check.as_ref().err().map(|problem| format!("{}: {}", asset.path.display(), problem)))
+            .collect();
+        for problem in new_problems {
+            tracing::warn!("Unusable asset {}", problem);
+            self.notify(Notification::warning(format!("Unusable asset {}", problem)));
+        }
+        self.assets = assets;
+    }
 
-        let stop_flag = Arc::new(AtomicBool::new(false));
-        self.stop_video_thread = Some(stop_flag.clone());
-        let device = self.selected_video_device.clone();
-        let format = format.clone();
-        let framerate = self.selected_framerate;
+    /// Starts measuring the loopback latency on a background thread.
+    pub fn start_latency_measurement(&mut self, ctx: &egui::Context) {
+        let (Some(source), Some(sink)) = (self.selected_pulse_source_name.clone(), self.selected_pulse_sink_name.clone()) else {
+            self.notify(Notification::warning("Cannot measure latency: Missing PulseAudio devices.").with_action(NotificationAction::RescanDevices));
+            return;
+        };
         let (tx, rx) = crossbeam_channel::bounded(1);
-        let crt_filter = self.crt_filter.clone();
-        self.frame_receiver = Some(rx);
-
-        let handle = thread::spawn(move || {
-            if let Err(e) =
-                video::decoder::video_thread_main(tx, stop_flag, device, format, resolution, framerate, crt_filter)
-            {
-                tracing::error!("Video thread error: {}", e);
-            }
+        self.latency_measure_receiver = Some(rx);
+        self.notify(Notification::info("Measuring audio latency..."));
+        let egui_ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = tx.send(devices::audio::measure_loopback_latency(&source, &sink));
+            egui_ctx.request_repaint();
         });
-        self.video_thread = Some(handle);
-        self.status_message = "Stream started.".to_string();
-        self.video_window_open = true;
-        self.control_window_open = false;
+    }
 
-        // Start the fullscreen toggle sequence to fix resizing issues.
-        self.fullscreen_toggle_frame_count = Some(0);
+    /// The profile video latency measurements are kept under: the applied look.
+    pub fn latency_profile(&self) -> String {
+        self.active_look.clone().unwrap_or_else(|| "default".to_string())
     }
 
-    pub fn stop_stream(&mut self, ctx: &egui::Context) {
-        if self.is_fullscreen {
-            self.is_fullscreen = false;
-            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
-        }
-        self.stop_stream_resources();
-        // Reset the texture to a black screen instead of removing it
-        if let Some(texture) = &mut self.video_texture {
-            texture.set(egui::ImageData::Color(egui::ColorImage::new([1, 1], egui::Color32::BLACK).into()), egui::TextureOptions::LINEAR);
-        }
-        self.video_window_open = false; // This now means "stream is not active"
-        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    /// Measures the delay between the pattern window and the viewer in the phone video.
+    pub fn start_photon_latency_analysis(&mut self, ctx: &egui::Context) {
+        let path = std::path::PathBuf::from(self.latency_video_path.trim());
+        let profile = self.latency_profile();
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.photon_latency_receiver = Some(rx);
+        self.notify(Notification::info(format!("Analyzing {}...", path.display())));
+        let egui_ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = tx.send((profile, video::photon_latency::measure_video(&path)));
+            egui_ctx.request_repaint();
+        });
     }
 
-    fn stop_stream_resources(&mut self) {
-        if let Some(stop_flag) = self.stop_video_thread.take() {
-            stop_flag.store(true, Ordering::Relaxed);
-        }
-        if let Some(handle) = self.video_thread.take() {
-            let _ = handle.join();
+    /// Starts collecting frames of the test card the console shows, with the color correction
+    /// turned off so the analysis sees the card as captured.
+    pub fn start_color_calibration(&mut self) {
+        if self.frame_receiver.is_none() {
+            self.notify(Notification::warning("Start the stream and show the test card first."));
+            return;
         }
+        self.calibration_result = None;
+        self.calibration_frames = Some(Vec::with_capacity(video::color::CALIBRATION_FRAMES));
+    }
 
-        if let Some(index) = self.pulse_loopback_module_index.take() {
-            if let Err(e) = devices::audio::unload_pulse_loopback(index) {
-                self.status_message = format!("Stream stopped, but failed to unload PulseAudio module: {}", e);
-            } else {
-                self.status_message = "Stream stopped and PulseAudio module unloaded.".to_string();
+    /// Acts on what the border detection found: `None` means the picture fills the frame.
+    fn apply_detected_crop(&mut self, crop: Option<video::recorder::CropRect>) {
+        let current = self.display_crop_enabled.then_some(self.display_crop);
+        match self.auto_crop_mode {
+            video::autocrop::AutoCropMode::Off => {}
+            video::autocrop::AutoCropMode::Suggest => self.auto_crop_suggestion = crop.filter(|crop| Some(*crop) != current),
+            video::autocrop::AutoCropMode::Automatic => {
+                // A crop set by hand stays when there are no borders.
+                if crop == current || (crop.is_none() && !self.auto_cropped) {
+                    return;
+                }
+                tracing::info!(?crop, "Auto-crop changed the display crop");
+                self.display_crop_enabled = crop.is_some();
+                if let Some(crop) = crop {
+                    self.display_crop = crop;
+                }
+                self.auto_cropped = crop.is_some();
             }
-        } else {
-            self.status_message = "Stream stopped.".to_string();
         }
-
-        self.frame_receiver = None;
-        self.video_window_open = false;
     }
-}
 
-impl eframe::App for AppState {
-    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        if let Some(gl) = _gl {
-            if let Some(renderer) = self.crt_renderer.as_ref() {
-                renderer.lock().unwrap().destroy(gl);
-            }
+    /// Takes the offered crop, see `AutoCropMode::Suggest`.
+    pub fn accept_auto_crop(&mut self) {
+        if let Some(crop) = self.auto_crop_suggestion.take() {
+            self.display_crop_enabled = true;
+            self.display_crop = crop;
+            config::mark_dirty(self);
         }
-        self.stop_stream_resources();
     }
 
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut repaint_requested = false;
-
-        // --- Control Window (Secondary) ---
-        if self.control_window_open {
-            ctx.show_viewport_immediate(
-                egui::ViewportId::from_hash_of("control_window"),
-                egui::ViewportBuilder::default()
-                    .with_title("Michadame Controls")
-                    .with_inner_size([640.0, 500.0]),
-                |ctx, class| {
-                    assert!(
-                        class == egui::ViewportClass::Immediate,
-                        "This egui backend doesn't support multiple viewports"
-                    );
-
-                    repaint_requested |= ui::draw_main_ui(self, ctx);
+    pub fn color_calibration_running(&self) -> bool {
+        self.calibration_frames.is_some() || self.calibration_receiver.is_some()
+    }
 
-                    if ctx.input(|i| i.viewport().close_requested()) {
-                        self.control_window_open = false;
-                    }
-                },
-            );
+    /// Hands the color correction to the video thread, and runs the test card analysis once
+    /// enough frames are collected.
+    fn update_color_calibration(&mut self, ctx: &egui::Context) {
+        if self.calibration_frames.is_some() && self.frame_receiver.is_none() {
+            self.calibration_frames = None;
+            self.notify(Notification::warning("Calibration cancelled, the stream stopped."));
         }
-
-        // --- Video Window (Primary) ---
-        egui::CentralPanel::default().frame(egui::Frame::none()).show(ctx, |ui| {
-            ui::draw_video_player(self, ui, ctx);
-
-            if self.show_stop_stream_dialog {
-                ui::dialogs::show_stop_stream_dialog(self, ctx, ui, ctx);
-            }
-
-            if self.show_quit_dialog {
-                ui::dialogs::show_quit_dialog(self, ctx, ui);
+        let adjust = if self.calibration_frames.is_some() { Default::default() } else { self.filter_settings.color_adjust() };
+        {
+            let mut shared = self.color_adjust.lock().unwrap();
+            if *shared != adjust {
+                *shared = adjust;
             }
-        });
-
-        // Handle the fullscreen toggle sequence to fix window sizing on stream start.
-        if let Some(count) = self.fullscreen_toggle_frame_count {
-            match count {
-                0 => {
-                    // Frame 1 (after start): Wait one frame for stream to initialize.
-                    self.fullscreen_toggle_frame_count = Some(1);
-                }
-                1 => {
-                    // Frame 2: Go fullscreen.
-                    ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
-                    self.fullscreen_toggle_frame_count = Some(2);
-                }
-                2 => {
-                    // Frame 3: Go back to windowed and end the sequence.
-                    ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
-                    self.fullscreen_toggle_frame_count = None;
+        }
+        if self.calibration_frames.as_ref().is_some_and(|frames| frames.len() >= video::color::CALIBRATION_FRAMES) {
+            let frames = self.calibration_frames.take().unwrap_or_default();
+            let region = self.display_crop_enabled.then_some(self.display_crop);
+            let (tx, rx) = crossbeam_channel::bounded(1);
+            self.calibration_receiver = Some(rx);
+            let egui_ctx = ctx.clone();
+            thread::spawn(move || {
+                let _ = tx.send(video::color::analyze_test_card(&frames, region));
+                egui_ctx.request_repaint();
+            });
+        }
+        if let Some(rx) = &self.calibration_receiver {
+            if let Ok(result) = rx.try_recv() {
+                self.calibration_receiver = None;
+                match result {
+                    Ok(calibration) => self.calibration_result = Some(calibration),
+                    Err(e) => self.notify(Notification::error(format!("Calibration failed: {:#}", e))),
                 }
-                _ => self.fullscreen_toggle_frame_count = None, // Should not happen.
             }
-            repaint_requested = true;
         }
+    }
 
-        // Handle keyboard shortcuts for the main video window
-        if ctx.input(|i| i.key_pressed(egui::Key::F)) {
-            let is_fullscreen = !ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
-            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(is_fullscreen));
-        }
-        if ctx.input(|i| i.key_pressed(egui::Key::C)) {
-            let current_filter = CrtFilter::from_u8(self.crt_filter.load(Ordering::Relaxed));
-            let next_filter = current_filter.next();
-            self.crt_filter.store(next_filter as u8, Ordering::Relaxed);
-            config::save_config(self);
-            self.status_message = format!("CRT filter set to: {}", next_filter.to_string());
+    /// Watches the loudness meter while recording and, once the tail after a peak has been
+    /// written, cuts it with the lead-in into a clip next to the recording. The recording
+    /// stands in for a replay buffer: Matroska clusters are flushed every second, so the file
+    /// can be read while it grows.
+    fn check_highlights(&mut self, ctx: &egui::Context) {
+        if let Some(rx) = &self.highlight_receiver {
+            if let Ok(result) = rx.try_recv() {
+                self.highlight_receiver = None;
+                self.notify(match result {
+                    Ok(path) => Notification::success(format!("Highlight saved to {}", path.display())),
+                    Err(e) => Notification::error(format!("Saving the highlight failed: {:#}", e)),
+                });
+                self.recording_list = None;
+            }
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::G)) {
-            self.pixelate_filter_enabled = !self.pixelate_filter_enabled;
-            let status = if self.pixelate_filter_enabled { "enabled" } else { "disabled" };
-            self.status_message = format!("480p Pixelate filter {}.", status);
-            config::save_config(self);
+        let Some(recorder) = &self.recorder else {
+            self.highlight_pending = None;
+            return;
+        };
+        if !self.highlight_clips_enabled || self.timelapse_mode != video::recorder::TimelapseMode::Off {
+            return;
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
-            // Allow Esc to exit fullscreen on the video window
-            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+        let lead = std::time::Duration::from_secs(self.highlight_lead_secs as u64);
+        match self.highlight_pending {
+            Some((peak, due)) => {
+                let now = Instant::now();
+                if now < due {
+                    ctx.request_repaint_after(due - now);
+                    return;
+                }
+                self.highlight_pending = None;
+                let path = recorder.output_path().to_path_buf();
+                let start = peak.saturating_sub(lead).as_secs_f64();
+                let end = (peak + HIGHLIGHT_TAIL).as_secs_f64();
+                let (tx, rx) = crossbeam_channel::bounded(1);
+                self.highlight_receiver = Some(rx);
+                let egui_ctx = ctx.clone();
+                thread::spawn(move || {
+                    let _ = tx.send(video::clips::trim_recording(&path, start, end, "highlight"));
+                    egui_ctx.request_repaint();
+                });
+            }
+            None => {
+                let Some(meter) = &self.loudness_meter else { return };
+                // One clip per moment: a long cheer would otherwise trigger again and again.
+                let cooled_down = self.last_highlight_at.is_none_or(|at| at.elapsed() >= lead + HIGHLIGHT_TAIL);
+                if meter.momentary() >= self.highlight_threshold_lufs && cooled_down && self.highlight_receiver.is_none() {
+                    tracing::info!(loudness = meter.momentary(), "Loud moment, saving a highlight");
+                    self.last_highlight_at = Some(Instant::now());
+                    self.highlight_pending = Some((recorder.position(), Instant::now() + HIGHLIGHT_TAIL + HIGHLIGHT_FLUSH_DELAY));
+                }
+            }
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::Q)) {
-            if self.video_window_open && !self.show_stop_stream_dialog {
-                self.show_stop_stream_dialog = true;
+    }
+
+    /// Queries the loopback's latency every `DRIFT_CHECK_INTERVAL` on a background thread and
+    /// acts on the result once it arrives.
+    fn check_audio_drift(&mut self, ctx: &egui::Context) {
+        if let Some(rx) = &self.drift_receiver {
+            let Ok((index, result)) = rx.try_recv() else { return };
+            self.drift_receiver = None;
+            match result {
+                // The loopback may have been reloaded while the query ran.
+                Ok(latency) if self.pulse_loopback_module_index == Some(index) => self.handle_drift_reading(index, latency),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Audio drift check failed: {:#}", e),
             }
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::M)) {
-            self.control_window_open = !self.control_window_open;
+        let module = self.pulse_loopback_module_index.filter(|_| self.drift_check_enabled && self.audio_routing == AudioRouting::Loopback);
+        if module != self.drift_module {
+            self.drift_module = module;
+            self.drift_baseline = None;
+            self.audio_drift_msec = None;
+            self.drift_next_check = Instant::now() + DRIFT_SETTLE_TIME;
         }
-
-        // Handle window close request (e.g., from the 'X' button)
-        if ctx.input(|i| i.viewport().close_requested()) {
-            if self.video_window_open && !self.show_quit_dialog {
+        let Some(index) = module else { return };
+        if self.drift_receiver.is_some() || Instant::now() < self.drift_next_check {
+            return;
+        }
+        self.drift_next_check = Instant::now() + DRIFT_CHECK_INTERVAL;
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.drift_receiver = Some(rx);
+        let egui_ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = tx.send((index, devices::audio::query_loopback_latency(index)));
+            egui_ctx.request_repaint();
+        });
+    }
+
+    fn handle_drift_reading(&mut self, index: u32, latency: std::time::Duration) {
+        let latency_msec = latency.as_millis() as i32;
+        let Some((_, baseline)) = self.drift_baseline.filter(|(module, _)| *module == index) else {
+            tracing::info!(latency_msec, "Loopback latency baseline");
+            self.drift_baseline = Some((index, latency_msec));
+            self.audio_drift_msec = Some(0);
+            return;
+        };
+        let drift = latency_msec - baseline;
+        let was_drifted = self.audio_drift_msec.is_some_and(|d| d.unsigned_abs() > self.drift_threshold_msec);
+        self.audio_drift_msec = Some(drift);
+        if drift.unsigned_abs() <= self.drift_threshold_msec {
+            return;
+        }
+        tracing::warn!(drift_msec = drift, "Audio loopback drifted");
+        if self.drift_auto_reload {
+            self.reload_pulse_loopback(drift);
+        } else if !was_drifted {
+            self.notify(Notification::warning(format!("Audio has drifted by {} ms since the stream started. Restart the stream to resync.", drift)));
+        }
+    }
+
+    /// Replaces the loopback with a fresh one, which starts again at the configured latency.
+    fn reload_pulse_loopback(&mut self, drift: i32) {
+        let (Some(index), Some(source), Some(sink)) = (self.pulse_loopback_module_index, self.selected_pulse_source_name.clone(), self.selected_pulse_sink_name.clone()) else {
+            self.notify(Notification::error(format!("Audio drifted by {} ms, but no loopback is loaded to reload.", drift)));
+            return;
+        };
+        let sink = if self.pulse_limiter_module_index.is_some() { devices::audio::LIMITER_SINK_NAME.to_string() } else { sink };
+        self.pulse_loopback_module_index = None;
+        let (latency_msec, options, generation) = (self.loopback_latency_msec, self.loopback_options.clone(), self.stream_generation);
+        self.run_pulse_task(
+            move || {
+                devices::audio::unload_pulse_module(index)?;
+                devices::audio::load_pulse_loopback(&source, &sink, latency_msec, &options)
+            },
+            move |state, _, result| match result {
+                Ok(index) if generation != state.stream_generation => {
+                    thread::spawn(move || devices::audio::unload_pulse_module(index));
+                }
+                Ok(index) => {
+                    state.pulse_loopback_module_index = Some(index);
+                    state.notify(Notification::info(format!("Audio drifted by {} ms, loopback reloaded.", drift)));
+                }
+                Err(e) => state.notify(Notification::error(format!("Audio drifted by {} ms, reloading the loopback failed: {:#}", drift, e))),
+            },
+        );
+    }
+
+    /// Runs `work` on a worker thread and then `finish` with its result on the UI thread, from
+    /// `update`. For PulseAudio calls, which wait up to `PULSE_OP_TIMEOUT` on a hung server.
+    fn run_pulse_task<T: Send + 'static>(
+        &mut self,
+        work: impl FnOnce() -> T + Send + 'static,
+        finish: impl FnOnce(&mut AppState, &egui::Context, T) + Send + 'static,
+    ) {
+        self.pulse_tasks_running += 1;
+        let tx = self.pulse_task_sender.clone();
+        thread::spawn(move || {
+            let result = work();
+            let task: PulseTask = Box::new(move |state, ctx| finish(state, ctx, result));
+            let _ = tx.send(task);
+        });
+    }
+
+    /// Runs the system check in the background; results arrive via `system_check_receiver`.
+    pub fn start_system_check(&mut self, ctx: &egui::Context) {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.system_check_receiver = Some(rx);
+        self.system_check_results = Some(Vec::new());
+        let gl_info = self.gl_info.clone();
+        let egui_ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(selftest::run_system_check(gl_info));
+            egui_ctx.request_repaint();
+        });
+    }
+
+    /// What the Diagnostics section shows, plus the stream's negotiated format, as text.
+    fn diagnostics_report(&self) -> String {
+        let mut report = format!("michadame {}\n", env!("CARGO_PKG_VERSION"));
+        report += &format!("Streaming: {}, recording: {}\n", self.video_thread.is_some(), self.recorder.is_some());
+        report += &format!("Requested: {:?} at {} fps\n", self.selected_resolution, self.selected_framerate);
+        match &self.stream_info {
+            Some(info) => report += &format!("\nStream: {:#?}\n", info),
+            None => report += "\nNo stream info\n",
+        }
+        if let Some(texture) = &self.video_texture {
+            report += &format!("\nUpload: {:#?}\n", *texture.stats.lock().unwrap());
+        }
+        match self.system_sampler.as_ref().and_then(|sampler| sampler.snapshot()) {
+            Some(stats) => report += &format!("\nSystem: {:#?}\n", stats),
+            None => report += "\nSystem: not sampled, open Diagnostics first\n",
+        }
+        report += "\n";
+        report += &self.signal_log.report();
+        report
+    }
+
+    /// Writes a zip of the logs, the config without passwords or serial numbers, a system check
+    /// and the diagnostics into the recording directory, for attaching to bug reports.
+    pub fn create_support_bundle(&mut self, ctx: &egui::Context) {
+        let config = support::sanitized_config(config::current_config(self));
+        if let Err(e) = &config {
+            tracing::error!("Leaving the config out of the support bundle: {:#}", e);
+        }
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.support_bundle_receiver = Some(rx);
+        let dir = std::path::PathBuf::from(&self.recording_dir);
+        let diagnostics = self.diagnostics_report();
+        let gl_info = self.gl_info.clone();
+        let egui_ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(support::create_bundle(&dir, config, diagnostics, gl_info));
+            egui_ctx.request_repaint();
+        });
+    }
+
+    /// Tries every advertised mode of the selected capture device; see `video::probe`.
+    pub fn start_device_probe(&mut self) {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let stop = Arc::new(AtomicBool::new(false));
+        self.probe_receiver = Some(rx);
+        self.probe_results = Some(Vec::new());
+        self.probe_total = self.supported_formats.iter().flat_map(|f| &f.resolutions).map(|r| r.framerates.len()).sum();
+        self.probe_stop = Some(stop.clone());
+        let device = self.selected_video_device.clone();
+        let formats = self.supported_formats.clone();
+        std::thread::spawn(move || video::probe::probe_device(&device, &formats, tx, stop));
+    }
+
+    pub fn stop_device_probe(&mut self) {
+        if let Some(stop) = self.probe_stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.probe_receiver = None;
+    }
+
+    /// True in kiosk mode until the passphrase is entered.
+    pub fn kiosk_locked(&self) -> bool {
+        self.kiosk.as_ref().is_some_and(|k| !k.unlocked)
+    }
+
+    /// Starts the stream once the devices and formats are known, applying the kiosk look first.
+    fn kiosk_auto_start(&mut self, ctx: &egui::Context) {
+        if !self.kiosk_locked() || !self.devices_scanned || self.formats_loading() {
+            return;
+        }
+        let Some(kiosk) = self.kiosk.as_mut().filter(|k| !k.started) else { return };
+        kiosk.started = true;
+        let look_name = kiosk.options.look.clone();
+        self.show_first_run_dialog = false;
+        if let Some(name) = look_name {
+            let path = self.available_looks.iter().find(|(n, _)| *n == name).map(|(_, path)| path.clone());
+            match path.map(|path| looks::load_look(&path)) {
+                Some(Ok(look)) => look.apply(self),
+                Some(Err(e)) => tracing::warn!("Failed to load kiosk look '{}': {:#}", name, e),
+                None => tracing::warn!("Kiosk look '{}' not found", name),
+            }
+        }
+        self.start_stream(ctx);
+    }
+
+    /// Starts or pauses the timer shown by the timer overlay.
+    pub fn toggle_timer(&mut self) {
+        match self.timer_source {
+            video::timer::TimerSource::Builtin => self.stopwatch.toggle(),
+            video::timer::TimerSource::LiveSplit => {
+                use net::livesplit::LiveSplitCommand;
+                let Some((client, _)) = &self.livesplit else { return };
+                let command = match self.livesplit_status.as_ref().map(|s| s.phase.as_str()) {
+                    Some("Running") => LiveSplitCommand::Pause,
+                    Some("Paused") => LiveSplitCommand::Resume,
+                    _ => LiveSplitCommand::StartOrSplit,
+                };
+                client.send(command);
+            }
+        }
+    }
+
+    pub fn reset_timer(&mut self) {
+        match self.timer_source {
+            video::timer::TimerSource::Builtin => self.stopwatch.reset(),
+            video::timer::TimerSource::LiveSplit => {
+                if let Some((client, _)) = &self.livesplit {
+                    client.send(net::livesplit::LiveSplitCommand::Reset);
+                }
+            }
+        }
+    }
+
+    pub fn show_osd(&mut self, text: String) {
+        self.osd_message = Some((text, Instant::now()));
+    }
+
+    /// Logs the notification and shows it in the status line and, unless it's only
+    /// information, as a toast.
+    pub fn notify(&mut self, notification: Notification) {
+        notification.log();
+        if notification.is_toast() {
+            if self.toasts.len() >= crate::notifications::MAX_TOASTS {
+                self.toasts.remove(0);
+            }
+            self.toasts.push(notification.clone());
+        }
+        self.status = notification;
+    }
+
+    pub fn run_notification_action(&mut self, action: NotificationAction, ctx: &egui::Context) {
+        match action {
+            NotificationAction::StartStream => {
+                if !self.video_window_open {
+                    self.request_start_stream(ctx);
+                }
+            }
+            NotificationAction::RescanDevices => {
+                self.start_device_scan(ctx);
+                self.notify(Notification::info("Re-scanning devices..."));
+            }
+            NotificationAction::ScanFormats => {
+                let device = self.selected_video_device.clone();
+                self.select_video_device(&device, ctx);
+            }
+            NotificationAction::StartRecording => self.start_recording(),
+            NotificationAction::StartPreviewServer => {
+                self.preview_enabled = true;
+                config::mark_dirty(self);
+                self.apply_preview_server();
+            }
+            NotificationAction::MeasureLatency => self.start_latency_measurement(ctx),
+        }
+    }
+
+    /// The text shown by the timer overlay, if there is anything to show.
+    pub fn timer_text(&self) -> Option<String> {
+        match self.timer_source {
+            video::timer::TimerSource::Builtin => Some(video::timer::format_time(self.stopwatch.elapsed())),
+            video::timer::TimerSource::LiveSplit => self.livesplit_status.as_ref().map(|s| s.time.clone()),
+        }
+    }
+
+    /// Has OBS react to the stream starting or stopping, if the integration is on.
+    pub fn send_obs_event(&mut self, event: net::obs::ObsEvent, ctx: &egui::Context) {
+        if !self.obs_enabled {
+            return;
+        }
+        let (controller, _) = self.obs_controller.get_or_insert_with(|| net::obs::ObsController::start(ctx));
+        controller.send(self.obs_settings.clone(), event);
+    }
+
+    /// Keeps the controller running while the integration is on, so a stream that was already
+    /// running when it got enabled still has its stop reach OBS.
+    fn poll_obs_results(&mut self, ctx: &egui::Context) {
+        if !self.obs_enabled {
+            self.obs_controller = None;
+        } else if self.obs_controller.is_none() {
+            self.obs_controller = Some(net::obs::ObsController::start(ctx));
+        }
+        let results: Vec<_> = self.obs_controller.as_ref().map(|(_, rx)| rx.try_iter().collect()).unwrap_or_default();
+        for result in results {
+            self.notify(match result {
+                Ok(message) => Notification::info(message),
+                Err(e) => Notification::error(format!("{:#}", e)),
+            });
+        }
+    }
+
+    /// Connects to LiveSplit while its timer is shown or the auto-splitter runs, and applies the
+    /// status it reports.
+    fn update_livesplit(&mut self, ctx: &egui::Context) {
+        let wanted = (self.timer_enabled && self.timer_source == video::timer::TimerSource::LiveSplit) || self.autosplit_enabled;
+        let address = self.livesplit_address.trim();
+        if self.livesplit.as_ref().is_some_and(|(client, _)| !wanted || client.address != address) {
+            self.livesplit = None;
+            self.livesplit_status = None;
+        }
+        if wanted && self.livesplit.is_none() && !address.is_empty() {
+            self.livesplit = Some(net::livesplit::LiveSplitClient::start(address, ctx));
+        }
+        if let Some((_, rx)) = &self.livesplit {
+            if let Some(status) = rx.try_iter().last() {
+                self.livesplit_status = status;
+            }
+        }
+    }
+
+    /// The filter settings with the modulations applied, for the renderer. `filter_settings`
+    /// keeps the values set with the sliders, which the modulations swing around.
+    pub fn modulated_filter_settings(&self) -> FilterSettings {
+        let mut settings = self.filter_settings.clone();
+        if self.modulation_enabled {
+            let mut inputs = self.osc_input.as_ref().map(|input| input.values()).unwrap_or_default();
+            inputs.midi = self.midi_values.iter()
+                .filter(|(control, _)| control.kind == devices::midi::MidiControlKind::ControlChange)
+                .map(|(control, value)| ((control.channel, control.number), *value))
+                .collect();
+            video::modulation::apply(&self.modulations, &mut settings, self.modulation_started.elapsed().as_secs_f64(), &inputs);
+        }
+        settings
+    }
+
+    fn osc_input_wanted(&self) -> bool {
+        self.modulation_enabled && self.modulation_osc_enabled
+    }
+
+    /// Starts or stops listening for OSC to match the modulation settings, restarting so a
+    /// changed port takes effect.
+    pub fn apply_osc_input(&mut self, ctx: &egui::Context) {
+        self.osc_input = None;
+        if !self.osc_input_wanted() {
+            return;
+        }
+        match video::modulation::OscInput::start(self.modulation_osc_port, ctx) {
+            Ok(input) => self.osc_input = Some(input),
+            Err(e) => {
+                self.modulation_osc_enabled = false;
+                self.notify(Notification::error(format!("Failed to start OSC input: {:#}", e)));
+            }
+        }
+    }
+
+    /// Opens the MIDI port in `midi_device`, or closes it for none.
+    pub fn apply_midi_input(&mut self, ctx: &egui::Context) {
+        self.midi_input = None;
+        self.midi_values.clear();
+        let Some(device) = &self.midi_device else { return };
+        match devices::midi::MidiInput::start(std::path::Path::new(device), ctx) {
+            Ok(input) => self.midi_input = Some(input),
+            Err(e) => {
+                self.midi_device = None;
+                self.notify(Notification::error(format!("Failed to open MIDI controller: {:#}", e)));
+            }
+        }
+    }
+
+    /// Feeds MIDI events to MIDI learn, the mapped sliders and commands, and the modulations.
+    fn update_midi(&mut self, ctx: &egui::Context) {
+        use devices::midi::MidiTarget;
+        if self.midi_device.is_some() != self.midi_input.is_some() {
+            self.apply_midi_input(ctx);
+        }
+        let events: Vec<_> = self.midi_input.as_ref().map(|(_, rx)| rx.try_iter().collect()).unwrap_or_default();
+        for event in events {
+            let previous = self.midi_values.insert(event.control, event.value).unwrap_or(0.0);
+            self.midi_last_event = Some(event);
+            if let Some(index) = self.midi_learn.take() {
+                if let Some(mapping) = self.midi_mappings.get_mut(index) {
+                    mapping.control = event.control;
+                    config::mark_dirty(self);
+                }
+                continue;
+            }
+            let pressed = previous < 0.5 && event.value >= 0.5;
+            let targets: Vec<_> = self.midi_mappings.iter().filter(|m| m.control == event.control).map(|m| (m.target, m.param)).collect();
+            for (target, param) in targets {
+                match target {
+                    MidiTarget::Slider => {
+                        param.set(&mut self.filter_settings, event.value);
+                        config::mark_dirty(self);
+                    }
+                    _ if !pressed => {}
+                    MidiTarget::ToggleStream if self.video_window_open => self.stop_stream(ctx),
+                    MidiTarget::ToggleStream | MidiTarget::StartStream => {
+                        if !self.video_window_open && !self.stream_stopping() {
+                            self.request_start_stream(ctx);
+                        }
+                    }
+                    MidiTarget::StopStream => {
+                        if self.video_window_open {
+                            self.stop_stream(ctx);
+                        }
+                    }
+                    MidiTarget::Screenshot => self.save_screenshot(ctx),
+                    MidiTarget::NextFilter => self.cycle_crt_filter(),
+                    MidiTarget::ToggleRecording if self.recorder.is_some() => self.stop_recording(),
+                    MidiTarget::ToggleRecording => self.start_recording(),
+                }
+            }
+        }
+    }
+
+    /// Starts or stops the auto-splitter to match `autosplit_enabled`, reloading the triggers
+    /// and their images.
+    pub fn apply_autosplitter(&mut self, ctx: &egui::Context) {
+        self.autosplitter = None;
+        if !self.autosplit_enabled {
+            return;
+        }
+        if self.autosplit_triggers.is_empty() {
+            self.autosplit_enabled = false;
+            self.notify(Notification::warning("Add a trigger before turning on the auto-splitter."));
+            return;
+        }
+        match video::autosplit::AutoSplitter::start(&self.autosplit_triggers, ctx) {
+            Ok(splitter) => self.autosplitter = Some(splitter),
+            Err(e) => {
+                self.autosplit_enabled = false;
+                self.notify(Notification::error(format!("Failed to start the auto-splitter: {:#}", e)));
+            }
+        }
+    }
+
+    /// Saves the trigger's region of the current frame as its reference image. Returns whether
+    /// the trigger changed.
+    pub fn capture_autosplit_reference(&mut self, index: usize) -> bool {
+        let Some(image) = self.last_frame.clone() else {
+            self.notify(Notification::warning("No video to capture a reference image from."));
+            return false;
+        };
+        let trigger = &self.autosplit_triggers[index];
+        let name = if trigger.name.is_empty() { format!("trigger{}", index + 1) } else { trigger.name.clone() };
+        match video::autosplit::capture_reference(&image, trigger.region, &name) {
+            Ok(path) => {
+                self.notify(Notification::success(format!("Saved reference image to {}", path.display())));
+                self.autosplit_triggers[index].image_path = path.to_string_lossy().into_owned();
+                true
+            }
+            Err(e) => {
+                self.notify(Notification::error(format!("Failed to capture the reference image: {:#}", e)));
+                false
+            }
+        }
+    }
+
+    /// Sends the matched triggers to LiveSplit.
+    fn update_autosplitter(&mut self, ctx: &egui::Context) {
+        if self.autosplit_enabled != self.autosplitter.is_some() {
+            self.apply_autosplitter(ctx);
+        }
+        let events: Vec<_> = self.autosplitter.as_ref().map(|(_, rx)| rx.try_iter().collect()).unwrap_or_default();
+        for event in events {
+            use net::livesplit::LiveSplitCommand;
+            use video::autosplit::SplitAction;
+            let command = match event.action {
+                SplitAction::StartOrSplit => LiveSplitCommand::StartOrSplit,
+                SplitAction::Reset => LiveSplitCommand::Reset,
+                SplitAction::Pause => LiveSplitCommand::Pause,
+                SplitAction::Resume => LiveSplitCommand::Resume,
+            };
+            match &self.livesplit {
+                Some((client, _)) if self.livesplit_status.is_some() => client.send(command),
+                _ => tracing::warn!(trigger = %event.trigger, "Auto-split trigger matched while LiveSplit is not connected"),
+            }
+            self.show_osd(format!("{}: {}", event.action.to_string(), event.trigger));
+            self.autosplit_last_event = Some((event.trigger, Instant::now()));
+        }
+    }
+
+    /// Scans video, audio and USB devices in the background; results arrive via `device_scan_receiver`.
+    pub fn start_device_scan(&mut self, ctx: &egui::Context) {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.device_scan_receiver = Some(rx);
+
+        let egui_ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let video_result = devices::video::find_video_devices();
+            let pulse_result = devices::audio::find_pulse_devices();
+            let usb_result = devices::usb::find_usb_devices();
+
+            let result: devices::DeviceScanResult = (|| {
+                let video_devices = video_result.context("Failed to find video devices")?;
+                let usb_devices = usb_result.context("Failed to find USB devices")?;
+                let mut data = devices::DeviceScanResultData {
+                    video_devices,
+                    pulse_sources: Vec::new(),
+                    pulse_sinks: Vec::new(),
+                    pulse_cards: Vec::new(),
+                    usb_devices,
+                    pulse_error: None,
+                    alsa_captures: Vec::new(),
+                    alsa_playbacks: Vec::new(),
+                };
+                match pulse_result {
+                    Ok((pulse_sources, pulse_sinks)) => {
+                        data.pulse_sources = pulse_sources;
+                        data.pulse_sinks = pulse_sinks;
+                        // Card profiles are a nice-to-have; don't fail the scan over them.
+                        match devices::audio::find_pulse_cards() {
+                            Ok(cards) => data.pulse_cards = cards,
+                            Err(e) => tracing::warn!("Failed to list PulseAudio cards: {:?}", e),
+                        }
+                    }
+                    Err(e) => {
+                        // Without PulseAudio we can still offer an in-app ALSA loopback.
+                        tracing::warn!("PulseAudio unavailable, falling back to ALSA: {:?}", e);
+                        data.pulse_error = Some(e.to_string());
+                        let (captures, playbacks) = devices::alsa::find_alsa_devices()
+                            .context("Failed to find PulseAudio or ALSA devices")?;
+                        data.alsa_captures = captures;
+                        data.alsa_playbacks = playbacks;
+                    }
+                }
+                Ok(data)
+            })();
+
+            if let Err(e) = &result {
+                tracing::error!("Device scan failed: {:?}", e);
+            };
+            let _ = tx.send(result);
+            egui_ctx.request_repaint();
+        });
+    }
+
+    fn handle_device_scan_result(&mut self, result: devices::DeviceScanResult) -> bool {
+        let scan_successful = match result {
+            Ok(data) => {
+                self.video_devices = data.video_devices;
+                self.selected_video_device = self.video_devices.first().cloned().unwrap_or_default();
+                self.pulse_sources = data.pulse_sources;
+                self.pulse_sinks = data.pulse_sinks;
+                self.pulse_cards = data.pulse_cards;
+                self.usb_devices = data.usb_devices;
+                self.pulse_available = data.pulse_error.is_none();
+                self.alsa_captures = data.alsa_captures;
+                self.alsa_playbacks = data.alsa_playbacks;
+
+                if let Ok(cfg) = confy::load::<config::MichadameConfig>("michadame", None) {
+                    config::apply_config(self, &cfg);
+                }
+                self.undo_history.mark_saved(config::current_config(self));
+                self.devices_scanned = true;
+                if self.preview_enabled && self.preview_server.is_none() {
+                    self.apply_preview_server();
+                }
+                self.notify(match &data.pulse_error {
+                    None => Notification::info("Devices loaded successfully."),
+                    Some(e) => Notification::warning(format!("PulseAudio unavailable ({}). ALSA fallback available.", e)),
+                });
+                self.apply_known_card_presets();
+                true
+            }
+            Err(e) => {
+                self.notify(Notification::error(format!("Error: {}", e)).with_action(NotificationAction::RescanDevices));
+                false
+            }
+        };
+        self.device_scan_receiver = None;
+        scan_successful
+    }
+
+    pub fn undo_config_change(&mut self, ctx: &egui::Context) {
+        // A change still waiting to be saved is the one to undo.
+        config::save_if_pending(self);
+        let current = config::current_config(self);
+        let Some(cfg) = self.undo_history.undo(&current) else {
+            self.notify(Notification::info("Nothing to undo."));
+            return;
+        };
+        self.restore_config(&cfg, ctx);
+        if self.manual_save {
+            // Only a step back; the file keeps what was last saved until Save.
+            config::mark_dirty(self);
+            config::record_undo_step(self);
+        } else {
+            config::save_config(self);
+        }
+        self.notify(Notification::info("Undid the last settings change."));
+    }
+
+    /// Applies a config from the undo history or the saved file. Another video device goes
+    /// through `select_video_device`, so its formats are scanned again.
+    fn restore_config(&mut self, cfg: &config::MichadameConfig, ctx: &egui::Context) {
+        let device = self.selected_video_device.clone();
+        config::apply_config(self, cfg);
+        if self.selected_video_device != device {
+            let restored = std::mem::replace(&mut self.selected_video_device, device);
+            self.select_video_device(&restored, ctx);
+        }
+        if self.preview_enabled != self.preview_server.is_some() {
+            self.apply_preview_server();
+        }
+    }
+
+    /// Throws away the changes made since the last save in manual save mode.
+    pub fn revert_config_changes(&mut self, ctx: &egui::Context) {
+        let cfg = match confy::load::<config::MichadameConfig>("michadame", None) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                self.notify(Notification::error(format!("Failed to read the saved settings: {}", e)));
+                return;
+            }
+        };
+        let current = config::current_config(self);
+        self.restore_config(&config::MichadameConfig {
+            open_sections: current.open_sections,
+            main_window_size: current.main_window_size,
+            has_shown_first_run_warning: current.has_shown_first_run_warning,
+            ..cfg
+        }, ctx);
+        self.config_save = config::SaveScheduler::default();
+        self.undo_history.mark_saved(config::current_config(self));
+        self.notify(Notification::info("Reverted to the saved settings."));
+    }
+
+    /// Applies the idle inhibit policy. The inhibitor is only started once it's first needed.
+    fn update_idle_inhibit(&mut self, ctx: &egui::Context) {
+        use devices::idle_inhibit::{IdleInhibitPolicy, IdleInhibitor};
+        let inhibit = self.video_thread.is_some() && match self.idle_inhibit_policy {
+            IdleInhibitPolicy::WhileStreaming => true,
+            IdleInhibitPolicy::WhenFocused => ctx.input(|i| i.viewport().focused.unwrap_or(false)),
+            IdleInhibitPolicy::Never => false,
+        };
+        if inhibit || self.idle_inhibitor.is_some() {
+            self.idle_inhibitor.get_or_insert_with(IdleInhibitor::start).set(inhibit);
+        }
+    }
+
+    /// Keeps notification popups out of the capture while it's live or being recorded.
+    fn update_do_not_disturb(&mut self) {
+        let enable = self.do_not_disturb_enabled && (self.video_thread.is_some() || self.recorder.is_some());
+        if enable || self.do_not_disturb.is_some() {
+            self.do_not_disturb.get_or_insert_with(devices::do_not_disturb::DoNotDisturb::start).set(enable);
+        }
+    }
+
+    /// Starts, restarts or stops the interpolator to match the stream and the chosen mode.
+    fn update_interpolator(&mut self, ctx: &egui::Context) {
+        let wanted = self.frame_receiver.is_some() && self.interpolation_mode != video::interpolate::InterpolationMode::Off;
+        match &self.interpolator {
+            Some(interpolator) if wanted && interpolator.mode() == self.interpolation_mode => {}
+            _ if wanted => self.interpolator = Some(video::interpolate::Interpolator::start(ctx, self.interpolation_mode)),
+            _ => self.interpolator = None,
+        }
+    }
+
+    /// Resuming with a stale v4l2 handle wedges the decoder, so the stream is stopped before
+    /// suspending and started again after resuming.
+    fn handle_sleep_event(&mut self, event: devices::sleep_monitor::SleepEvent) {
+        use devices::sleep_monitor::SleepEvent;
+        match event {
+            SleepEvent::Suspending => {
+                if self.video_thread.is_some() {
+                    self.stop_stream_resources();
+                    if let Some(texture) = &mut self.video_texture {
+                        texture.clear();
+                    }
+                    self.stopped_for_sleep = true;
+                    self.notify(Notification::info("Stopping the stream for suspend..."));
+                }
+                self.resume_restart_at = None;
+                self.stream_start_pending = false;
+                if self.stream_stopping() {
+                    self.sleep_ready_pending = true;
+                } else if let Some((monitor, _)) = &self.sleep_monitor {
+                    monitor.ready();
+                }
+            }
+            SleepEvent::Resumed => {
+                if std::mem::take(&mut self.stopped_for_sleep) && (self.restart_after_resume || self.kiosk_locked()) {
+                    self.resume_restart_at = Some(Instant::now() + RESUME_RESTART_DELAY);
+                    self.notify(Notification::info("Resumed, restarting the stream..."));
+                }
+            }
+        }
+    }
+
+    fn handle_pulse_event(&mut self, event: devices::pulse_monitor::PulseEvent) {
+        use devices::pulse_monitor::PulseEvent;
+        let streaming_pulse_audio = self.video_thread.is_some() && self.pulse_available;
+        match event {
+            PulseEvent::Disconnected => {
+                if streaming_pulse_audio {
+                    self.notify(Notification::warning("Lost connection to PulseAudio, waiting for it to come back..."));
+                }
+            }
+            PulseEvent::Reconnected => {
+                self.run_pulse_task(
+                    || (devices::audio::find_pulse_devices(), devices::audio::find_pulse_cards()),
+                    |state, _, (devices, cards)| {
+                        if let Ok((sources, sinks)) = devices {
+                            state.pulse_sources = sources;
+                            state.pulse_sinks = sinks;
+                        }
+                        if let Ok(cards) = cards {
+                            state.pulse_cards = cards;
+                        }
+                    },
+                );
+                if !streaming_pulse_audio {
+                    return;
+                }
+                // The restarted server has none of our modules, so there's nothing to unload.
+                self.pulse_loopback_module_index = None;
+                self.pulse_limiter_module_index = None;
+                self.pulse_monitor_output_module_index = None;
+                self.mic_filters = None;
+                self.loudness_meter = None;
+                self.start_audio_routing(true);
+            }
+            PulseEvent::ModuleRemoved(index) => {
+                if self.pulse_loopback_module_index == Some(index) {
+                    self.pulse_loopback_module_index = None;
+                    self.notify(Notification::warning("The audio loopback was unloaded outside of Michadame."));
+                }
+                if self.pulse_limiter_module_index == Some(index) {
+                    self.pulse_limiter_module_index = None;
+                }
+                if self.pulse_monitor_output_module_index == Some(index) {
+                    self.pulse_monitor_output_module_index = None;
+                }
+            }
+        }
+    }
+
+    fn reload_live_settings(&mut self) {
+        match confy::load::<config::MichadameConfig>("michadame", None) {
+            Ok(cfg) => {
+                config::apply_live_settings(self, &cfg);
+                self.undo_history.mark_saved(config::current_config(self));
+                if self.preview_enabled != self.preview_server.is_some() {
+                    self.apply_preview_server();
+                }
+                self.notify(Notification::info("Reloaded settings changed outside the app."));
+            }
+            // Usually a half-written file; the next write triggers another reload.
+            Err(e) => tracing::warn!("Ignoring unreadable config file change: {}", e),
+        }
+    }
+
+    fn update_fps_counters(&mut self, ctx: &egui::Context) {
+        self.frames_since_last_check += 1;
+        let now = Instant::now();
+        let elapsed_secs = (now - self.last_fps_check).as_secs_f32();
+
+        if elapsed_secs >= 1.0 {
+            self.last_fps_check = now;
+            self.frames_since_last_check = 0;
+        }
+
+        let video_elapsed_secs = (now - self.last_video_fps_check).as_secs_f32();
+        if video_elapsed_secs >= 1.0 {
+            self.last_video_fps_check = now;
+            self.video_frames_since_last_check = 0;
+        }
+
+        let gui_fps = if elapsed_secs > 0.0 { self.frames_since_last_check as f32 / elapsed_secs } else { 0.0 };
+        let video_fps = if video_elapsed_secs > 0.0 { self.video_frames_since_last_check as f32 / video_elapsed_secs } else { 0.0 };
+        ctx.send_viewport_cmd(egui::ViewportCommand::Title(format!(
+            "Michadame Viewer | UI: {:.0} FPS | Video: {:.0} FPS",
+            gui_fps, video_fps
+        )));
+    }
+
+    /// Sets up audio according to the selected routing mode. The PulseAudio modules load on a
+    /// worker thread and `finish_audio_routing` takes them over. Returns false if the stream
+    /// can't start. `restoring` is set when PulseAudio restarted during the stream.
+    fn start_audio_routing(&mut self, restoring: bool) -> bool {
+        if !self.pulse_available {
+            return self.start_alsa_loopback();
+        }
+        let Some(source) = self.selected_pulse_source_name.clone() else {
+            self.notify(Notification::error("Cannot start: Missing PulseAudio devices.").with_action(NotificationAction::RescanDevices));
+            return false;
+        };
+        let sink = self.selected_pulse_sink_name.clone().unwrap_or_default();
+        if self.audio_routing == AudioRouting::Loopback && sink.is_empty() {
+            self.notify(Notification::error("Cannot start: Missing PulseAudio devices.").with_action(NotificationAction::RescanDevices));
+            return false;
+        }
+        let request = devices::audio::RoutingRequest {
+            routing: self.audio_routing,
+            source,
+            sink,
+            limiter_limit_db: self.limiter_enabled.then_some(self.limiter_limit_db),
+            latency_msec: self.loopback_latency_msec,
+            options: self.loopback_options.clone(),
+            volume: self.loopback_volume,
+            monitor_sink: self.monitor_output_sink.clone(),
+            monitor_volume: self.monitor_output_volume,
+        };
+        let generation = self.stream_generation;
+        self.run_pulse_task(
+            move || {
+                let result = devices::audio::set_up_routing(&request);
+                (request, result)
+            },
+            move |state, ctx, (request, result)| state.finish_audio_routing(ctx, generation, restoring, &request, result),
+        );
+        true
+    }
+
+    /// Takes over the modules `start_audio_routing` loaded, unless their stream is gone.
+    fn finish_audio_routing(
+        &mut self,
+        ctx: &egui::Context,
+        generation: u64,
+        restoring: bool,
+        request: &devices::audio::RoutingRequest,
+        result: anyhow::Result<devices::audio::RoutingModules>,
+    ) {
+        if generation != self.stream_generation || self.video_thread.is_none() {
+            if let Ok(modules) = result {
+                thread::spawn(move || modules.unload());
+            }
+            return;
+        }
+        let modules = match result {
+            Ok(modules) => modules,
+            Err(e) if restoring => {
+                self.notify(Notification::error(format!("PulseAudio restarted, but restoring audio failed: {:#}", e)));
+                return;
+            }
+            Err(e) => {
+                self.notify(Notification::error(format!("{:#}", e)).with_action(NotificationAction::StartStream));
+                self.stop_stream(ctx);
+                return;
+            }
+        };
+        self.pulse_loopback_module_index = modules.loopback;
+        self.pulse_limiter_module_index = modules.limiter;
+        self.pulse_monitor_output_module_index = modules.monitor_output;
+        // After a PulseAudio restart, keep the default input from before the stream, not the
+        // one we set ourselves.
+        if let Some(previous) = modules.previous_default_source {
+            self.previous_default_source.get_or_insert(previous);
+        }
+        self.notify(if restoring {
+            Notification::success("PulseAudio restarted, audio routing restored.")
+        } else {
+            match request.routing {
+                AudioRouting::Loopback => Notification::info("PulseAudio loopback loaded."),
+                AudioRouting::MonitorOnly => Notification::info("Virtual 'Michadame Capture' input created."),
+                AudioRouting::DefaultSource => Notification::info("Capture source set as default input."),
+            }
+        });
+        for warning in modules.warnings {
+            self.notify(Notification::warning(warning));
+        }
+        if self.loudness_meter_enabled || self.highlight_clips_enabled {
+            // Measure what is actually heard when the limiter is in the path.
+            let metered = if modules.limiter.is_some() {
+                format!("{}.monitor", devices::audio::LIMITER_SINK_NAME)
+            } else {
+                request.source.clone()
+            };
+            match devices::loudness::LoudnessMeter::start(&metered) {
+                Ok(meter) => self.loudness_meter = Some(meter),
+                Err(e) => tracing::error!("Failed to start loudness meter: {:#}", e),
+            }
+        }
+    }
+
+    /// Restarts the monitor output of a running stream, after its device was changed. Failing
+    /// only costs the monitor, so the stream goes on.
+    pub fn apply_monitor_output(&mut self) {
+        let previous = self.pulse_monitor_output_module_index.take();
+        let source = self.selected_pulse_source_name.clone().filter(|_| self.video_thread.is_some() && self.pulse_available);
+        let sink = self.monitor_output_sink.clone();
+        let (latency_msec, options, volume) = (self.loopback_latency_msec, self.loopback_options.clone(), self.monitor_output_volume);
+        let generation = self.stream_generation;
+        self.run_pulse_task(
+            move || {
+                if let Some(index) = previous {
+                    if let Err(e) = devices::audio::unload_pulse_module(index) {
+                        tracing::error!("Failed to unload monitor output: {}", e);
+                    }
+                }
+                let (source, sink) = (source?, sink?);
+                Some(devices::audio::load_monitor_output(&source, &sink, latency_msec, &options, volume))
+            },
+            move |state, _, result| match result {
+                Some(Ok(index)) if generation != state.stream_generation => {
+                    thread::spawn(move || devices::audio::unload_pulse_module(index));
+                }
+                Some(Ok(index)) => {
+                    // A newer change may have finished first.
+                    if let Some(replaced) = state.pulse_monitor_output_module_index.replace(index) {
+                        thread::spawn(move || devices::audio::unload_pulse_module(replaced));
+                    }
+                }
+                Some(Err(e)) => state.notify(Notification::warning(format!("Failed to start the monitor output: {:#}", e))),
+                None => {}
+            },
+        );
+    }
+
+    /// Applies `monitor_output_volume` to the running monitor output, if there is one.
+    pub fn apply_monitor_output_volume(&self) {
+        let Some(index) = self.pulse_monitor_output_module_index else { return };
+        let volume = self.monitor_output_volume;
+        thread::spawn(move || {
+            if let Err(e) = devices::audio::set_loopback_volume(index, volume) {
+                tracing::error!("Failed to set monitor output volume: {:#}", e);
+            }
+        });
+    }
+
+    /// Switches a card to another profile on a background thread; see `finish_card_profile_switch`.
+    pub fn switch_card_profile(&mut self, ctx: &egui::Context, card: &str, profile: &str) {
+        if self.card_profile_receiver.is_some() {
+            return;
+        }
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.card_profile_receiver = Some((profile.to_string(), rx));
+        let (card, profile, egui_ctx) = (card.to_string(), profile.to_string(), ctx.clone());
+        thread::spawn(move || {
+            let _ = tx.send(devices::audio::switch_card_profile(&card, &profile));
+            egui_ctx.request_repaint();
+        });
+    }
+
+    /// Takes over the sources/sinks the card provides after a profile switch.
+    fn finish_card_profile_switch(&mut self, profile: &str, result: anyhow::Result<devices::audio::PulseDeviceLists>) {
+        let lists = match result {
+            Ok(lists) => lists,
+            Err(e) => {
+                tracing::error!("Failed to switch card profile: {:#}", e);
+                self.notify(Notification::error(format!("Failed to switch card profile: {:#}", e)));
+                return;
+            }
+        };
+        self.pulse_sources = lists.sources;
+        self.pulse_sinks = lists.sinks;
+        self.pulse_cards = lists.cards;
+        if !self.pulse_sources.iter().any(|(_, name)| Some(name) == self.selected_pulse_source_name.as_ref()) {
+            self.selected_pulse_source_name = None;
+        }
+        if !self.pulse_sinks.iter().any(|(_, name)| Some(name) == self.selected_pulse_sink_name.as_ref()) {
+            self.selected_pulse_sink_name = None;
+        }
+        if !self.pulse_sinks.iter().any(|(_, name)| Some(name) == self.monitor_output_sink.as_ref()) {
+            self.monitor_output_sink = None;
+        }
+        self.notify(Notification::success(format!("Switched card profile to '{}'.", profile)));
+    }
+
+    /// Starts or stops the LAN preview server to match `preview_enabled`, restarting it so
+    /// changed settings take effect.
+    pub fn apply_preview_server(&mut self) {
+        if let Some(server) = self.preview_server.take() {
+            server.stop();
+        }
+        if !self.preview_enabled {
+            return;
+        }
+        let credentials = if self.preview_username.is_empty() {
+            None
+        } else {
+            Some((self.preview_username.clone(), self.preview_password.clone()))
+        };
+        let settings = net::mjpeg_server::MjpegServerSettings {
+            port: self.preview_port,
+            quality: self.preview_quality,
+            max_fps: self.preview_max_fps,
+            credentials,
+        };
+        let serves_lan = settings.serves_lan();
+        match net::mjpeg_server::MjpegServer::start(settings) {
+            Ok(server) => {
+                self.preview_server = Some(server);
+                if serves_lan {
+                    self.notify(Notification::success(format!("Network preview available at http://<this-machine>:{}/", self.preview_port)));
+                } else {
+                    self.notify(Notification::success(format!("Network preview available at http://localhost:{}/. Set a password to watch from other machines.", self.preview_port)));
+                }
+            }
+            Err(e) => {
+                self.preview_enabled = false;
+                self.notify(Notification::error(format!("Failed to start network preview: {}", e)).with_action(NotificationAction::StartPreviewServer));
+            }
+        }
+    }
+
+    pub fn start_recording(&mut self) {
+        if self.recorder.is_some() || self.frame_receiver.is_none() || self.recording_starting {
+            return;
+        }
+        let mic_source = self.record_mic_source.clone().filter(|_| self.pulse_available && self.record_mic_enabled);
+        match mic_source {
+            Some(source) if self.mic_highpass_enabled || self.mic_gate_enabled => {
+                // The filters are PulseAudio modules, so the recorder starts once they're loaded.
+                let (highpass, gate) = (self.mic_highpass_enabled, self.mic_gate_enabled.then_some(self.mic_gate_threshold_db));
+                self.recording_starting = true;
+                self.run_pulse_task(
+                    move || devices::audio::load_mic_filters(&source, highpass, gate).map_err(|e| (source, e)),
+                    |state, _, result| {
+                        state.recording_starting = false;
+                        match result {
+                            Ok(chain) if state.recorder.is_some() || state.frame_receiver.is_none() => {
+                                thread::spawn(move || chain.unload());
+                            }
+                            Ok(chain) => {
+                                let source = chain.source.clone();
+                                state.mic_filters = Some(chain);
+                                state.start_recorder(Some(source));
+                            }
+                            // Better an unfiltered commentary track than none.
+                            Err((source, e)) => {
+                                state.notify(Notification::warning(format!("Recording the microphone unfiltered: {:#}", e)));
+                                state.start_recorder(Some(source));
+                            }
+                        }
+                    },
+                );
+            }
+            mic_source => self.start_recorder(mic_source),
+        }
+    }
+
+    /// Starts the recorder, with `mic_source` as the commentary track.
+    fn start_recorder(&mut self, mic_source: Option<String>) {
+        if self.recorder.is_some() || self.frame_receiver.is_none() {
+            return;
+        }
+        let framerate = if self.source_kind == SourceKind::Capture && self.selected_framerate > 0 {
+            self.selected_framerate
+        } else {
+            self.frame_pacer.source_rate().round().clamp(1.0, 240.0) as u32
+        };
+        let mut audio_tracks = Vec::new();
+        if self.pulse_available {
+            if let Some(source) = self.selected_pulse_source_name.clone().filter(|_| self.record_audio) {
+                audio_tracks.push(video::recorder::AudioTrack { title: "Capture".to_string(), source, volume: self.record_audio_volume });
+            }
+            if let Some(source) = mic_source {
+                audio_tracks.push(video::recorder::AudioTrack { title: "Microphone".to_string(), source, volume: self.record_mic_volume });
+            }
+        }
+        let settings = video::recorder::RecorderSettings {
+            output_path: self.next_recording_path(),
+            framerate,
+            crop: self.record_crop_enabled.then_some(self.record_crop),
+            audio_tracks,
+            encoder: self.record_encoder,
+            vaapi_device: self.vaapi_device.clone(),
+            quality: self.record_quality,
+            timelapse: self.timelapse_mode,
+            timelapse_interval: self.timelapse_interval,
+        };
+        match video::recorder::Recorder::start(settings) {
+            Ok(recorder) => {
+                self.notify(Notification::success(format!("Recording to {}", recorder.output_path().display())));
+                self.recorder = Some(recorder);
+            }
+            Err(e) => {
+                if let Some(chain) = self.mic_filters.take() {
+                    thread::spawn(move || chain.unload());
+                }
+                self.notify(Notification::error(format!("Failed to start recording: {:#}", e)).with_action(NotificationAction::StartRecording));
+            }
+        }
+    }
+
+    /// The encoders the installed ffmpeg was built with, or `None` while they're still being
+    /// listed. The first call starts listing them on a background thread.
+    pub fn available_encoders(&mut self, ctx: &egui::Context) -> Option<&Vec<video::recorder::VideoEncoder>> {
+        if self.available_encoders.is_none() {
+            match &self.encoder_probe_receiver {
+                Some(rx) => {
+                    if let Ok(encoders) = rx.try_recv() {
+                        self.available_encoders = Some(encoders);
+                        self.encoder_probe_receiver = None;
+                    }
+                }
+                None => {
+                    let (tx, rx) = crossbeam_channel::bounded(1);
+                    self.encoder_probe_receiver = Some(rx);
+                    let egui_ctx = ctx.clone();
+                    thread::spawn(move || {
+                        let _ = tx.send(video::recorder::available_encoders());
+                        egui_ctx.request_repaint();
+                    });
+                }
+            }
+        }
+        self.available_encoders.as_ref()
+    }
+
+    /// Expands the file name template for a recording started now.
+    pub fn next_recording_path(&self) -> std::path::PathBuf {
+        let source = match self.source_kind {
+            SourceKind::Capture => self.selected_video_device.rsplit('/').next().unwrap_or("capture").to_string(),
+            SourceKind::File => std::path::Path::new(&self.file_source_path).file_stem()
+                .map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "file".to_string()),
+            SourceKind::Network => "network".to_string(),
+            SourceKind::Screen => "screen".to_string(),
+        };
+        let resolution = self.stream_info.as_ref().map(|info| info.resolution).unwrap_or(self.selected_resolution);
+        let fields = video::recorder::FileNameFields {
+            profile: self.active_look.as_deref().unwrap_or("default"),
+            resolution,
+            source: &source,
+        };
+        let stem = video::recorder::expand_file_template(&self.recording_file_template, &fields);
+        video::recorder::unique_output_path(std::path::Path::new(&self.recording_dir), &stem, video::recorder::FILE_EXTENSION)
+    }
+
+    /// Saves the frame on screen as a PNG next to the recordings, named like them.
+    pub fn save_screenshot(&mut self, ctx: &egui::Context) {
+        let Some(image) = self.last_frame.clone() else {
+            self.notify(Notification::warning("No video to take a screenshot of."));
+            return;
+        };
+        let path = self.next_recording_path().with_extension("png");
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.screenshot_receiver = Some(rx);
+        let egui_ctx = ctx.clone();
+        thread::spawn(move || {
+            let rgba: Vec<u8> = image.pixels.iter().flat_map(|p| p.to_array()).collect();
+            let result = std::fs::create_dir_all(path.parent().unwrap_or(std::path::Path::new(".")))
+                .map_err(anyhow::Error::from)
+                .and_then(|_| Ok(image::save_buffer(&path, &rgba, image.size[0] as u32, image.size[1] as u32, image::ExtendedColorType::Rgba8)?))
+                .with_context(|| format!("Failed to save '{}'", path.display()))
+                .map(|_| path);
+            let _ = tx.send(result);
+            egui_ctx.request_repaint();
+        });
+    }
+
+    /// Switches to the next filter of the filter cycle.
+    pub fn cycle_crt_filter(&mut self) {
+        let current_filter = CrtFilter::from_u8(self.crt_filter.load(Ordering::Relaxed));
+        match current_filter.next_in_cycle(self.filter_cycle) {
+            Some(next_filter) => {
+                self.crt_filter.store(next_filter as u8, Ordering::Relaxed);
+                config::mark_dirty(self);
+                self.notify(Notification::info(format!("CRT filter set to: {}", next_filter.to_string())));
+                self.show_osd(format!("Filter: {}", next_filter.to_string()));
+            }
+            None => self.notify(Notification::warning("No other filters are enabled in the filter cycle.")),
+        }
+    }
+
+    /// Applies `loopback_volume` to the running loopback, if there is one.
+    pub fn apply_loopback_volume(&self) {
+        let Some(index) = self.pulse_loopback_module_index.filter(|_| self.audio_routing == AudioRouting::Loopback) else { return };
+        let volume = self.loopback_volume;
+        // PulseAudio can take a moment to answer, so don't hold up the UI.
+        thread::spawn(move || {
+            if let Err(e) = devices::audio::set_loopback_volume(index, volume) {
+                tracing::error!("Failed to set loopback volume: {:#}", e);
+            }
+        });
+    }
+
+    /// Starts or stops the remote control server to match `remote_enabled`, restarting it so
+    /// changed settings take effect.
+    pub fn apply_remote_server(&mut self, ctx: &egui::Context) {
+        if let Some((server, _)) = self.remote_server.take() {
+            server.stop();
+        }
+        if !self.remote_enabled {
+            return;
+        }
+        let credentials = (!self.remote_username.is_empty()).then(|| (self.remote_username.clone(), self.remote_password.clone()));
+        let serves_lan = net::remote::serves_lan(credentials.as_ref());
+        match net::remote::RemoteServer::start(self.remote_port, credentials, ctx) {
+            Ok(server) => {
+                self.remote_server = Some(server);
+                if serves_lan {
+                    self.notify(Notification::success(format!("Remote control available at http://<this-machine>:{}/", self.remote_port)));
+                } else {
+                    self.notify(Notification::success(format!("Remote control available at http://localhost:{}/. Set a password to use it from other devices.", self.remote_port)));
+                }
+            }
+            Err(e) => {
+                self.remote_enabled = false;
+                self.notify(Notification::error(format!("Failed to start remote control: {:#}", e)));
+            }
+        }
+    }
+
+    /// Starts or stops sharing frames with local programs to match `frame_share_enabled`.
+    pub fn apply_frame_share(&mut self) {
+        if let Some(share) = self.frame_share.take() {
+            share.stop();
+        }
+        if !self.frame_share_enabled {
+            return;
+        }
+        let socket_path = net::frame_share::default_socket_path();
+        match net::frame_share::FrameShare::start(&socket_path) {
+            Ok(share) => {
+                self.frame_share = Some(share);
+                self.notify(Notification::success(format!("Sharing frames on {}", socket_path.display())));
+            }
+            Err(e) => {
+                self.frame_share_enabled = false;
+                self.notify(Notification::error(format!("Failed to start frame sharing: {:#}", e)));
+            }
+        }
+    }
+
+    fn run_remote_command(&mut self, command: net::remote::RemoteCommand, ctx: &egui::Context) {
+        use net::remote::RemoteCommand;
+        match command {
+            RemoteCommand::ToggleStream if self.video_window_open => self.stop_stream(ctx),
+            RemoteCommand::StartStream | RemoteCommand::ToggleStream => {
+                if !self.video_window_open && !self.stream_stopping() {
+                    self.request_start_stream(ctx);
+                }
+            }
+            RemoteCommand::StopStream => {
+                if self.video_window_open {
+                    self.stop_stream(ctx);
+                }
+            }
+            RemoteCommand::ApplyLook(name) => {
+                let Some((_, path)) = self.available_looks.iter().find(|(n, _)| n.eq_ignore_ascii_case(&name)) else {
+                    self.notify(Notification::warning(format!("No look named '{}'", name)));
+                    return;
+                };
+                match looks::load_look(path) {
+                    Ok(look) => {
+                        look.apply(self);
+                        self.notify(Notification::success(format!("Applied look '{}'", look.name)));
+                    }
+                    Err(e) => self.notify(Notification::error(format!("Failed to load look: {:#}", e))),
+                }
+            }
+            RemoteCommand::SetVolume(volume) => {
+                self.loopback_volume = volume.clamp(0.0, 1.5);
+                self.apply_loopback_volume();
+                config::mark_dirty(self);
+            }
+            RemoteCommand::Screenshot => self.save_screenshot(ctx),
+            RemoteCommand::NextFilter => self.cycle_crt_filter(),
+            RemoteCommand::SetFilter(filter) => {
+                self.crt_filter.store(filter as u8, Ordering::Relaxed);
+                config::mark_dirty(self);
+                self.show_osd(format!("Filter: {}", filter.to_string()));
+            }
+            RemoteCommand::SwitchScene(name) => {
+                let index = match name.parse::<usize>() {
+                    Ok(number) => number.checked_sub(1),
+                    Err(_) => self.scenes.iter().position(|scene| scene.name.eq_ignore_ascii_case(&name)),
+                };
+                match index.filter(|index| *index < self.scenes.len()) {
+                    Some(index) => self.switch_scene(index, ctx),
+                    None => self.notify(Notification::warning(format!("No scene '{}'", name))),
+                }
+            }
+            RemoteCommand::ToggleRecording if self.recorder.is_some() => self.stop_recording(),
+            RemoteCommand::StartRecording | RemoteCommand::ToggleRecording => {
+                if self.recorder.is_none() {
+                    self.start_recording();
+                }
+            }
+            RemoteCommand::StopRecording => {
+                if self.recorder.is_some() {
+                    self.stop_recording();
+                }
+            }
+        }
+    }
+
+    /// Starts or stops the localhost command port to match `command_port_enabled`, restarting
+    /// it so a changed port takes effect.
+    pub fn apply_command_server(&mut self, ctx: &egui::Context) {
+        if let Some((server, _)) = self.command_server.take() {
+            server.stop();
+        }
+        if !self.command_port_enabled {
+            return;
+        }
+        match net::command::CommandServer::start(self.command_port, ctx) {
+            Ok(server) => self.command_server = Some(server),
+            Err(e) => {
+                self.command_port_enabled = false;
+                self.notify(Notification::error(format!("Failed to start the command port: {:#}", e)));
+            }
+        }
+    }
+
+    fn update_command_server(&mut self, ctx: &egui::Context) {
+        if self.command_port_enabled != self.command_server.is_some() {
+            self.apply_command_server(ctx);
+        }
+        let commands: Vec<_> = self.command_server.as_ref().map(|(_, rx)| rx.try_iter().collect()).unwrap_or_default();
+        for command in commands {
+            self.run_remote_command(command, ctx);
+        }
+    }
+
+    /// Runs the commands from the remote control page and tells it the current state.
+    fn update_remote_server(&mut self, ctx: &egui::Context) {
+        if self.remote_enabled != self.remote_server.is_some() {
+            self.apply_remote_server(ctx);
+        }
+        let commands: Vec<_> = self.remote_server.as_ref().map(|(_, rx)| rx.try_iter().collect()).unwrap_or_default();
+        for command in commands {
+            self.run_remote_command(command, ctx);
+        }
+        if let Some((server, _)) = &self.remote_server {
+            server.set_status(net::remote::RemoteStatus {
+                streaming: self.video_window_open,
+                look: self.active_look.clone(),
+                looks: self.available_looks.iter().map(|(name, _)| name.clone()).collect(),
+                volume: self.loopback_volume,
+                message: self.status.message.clone(),
+            });
+        }
+    }
+
+    pub fn add_chapter_marker(&mut self) {
+        if let Some(recorder) = &mut self.recorder {
+            let chapter = recorder.add_chapter();
+            let secs = recorder.elapsed().as_secs();
+            self.notify(Notification::info(format!("Chapter marker {} added at {:02}:{:02}:{:02}.", chapter, secs / 3600, secs / 60 % 60, secs % 60)));
+        }
+    }
+
+    /// Whether frames take the direct upload path. The wall, the clean feed and burned-in
+    /// recording draw from egui's textures, so they need the regular path.
+    pub fn direct_upload_active(&self) -> bool {
+        self.direct_upload_enabled && self.crt_renderer.is_some() && !self.wall_enabled && !self.clean_feed_enabled && !self.burn_in_active()
+    }
+
+    /// Recording the filtered output only makes a difference when a GPU filter is running.
+    pub fn burn_in_active(&self) -> bool {
+        let gpu_pass = CrtFilter::from_u8(self.crt_filter.load(Ordering::Relaxed)).is_gpu_pass()
+            && !self.power_saver.load(Ordering::Relaxed);
+        self.recorder.is_some() && self.record_burn_in && (self.pixelate_filter_enabled || self.filter_settings.sharpen_enabled || self.filter_settings.phosphor_enabled || gpu_pass)
+    }
+
+    /// Forgets the unfinished recording, leaving its file as it is.
+    pub fn dismiss_unfinished_recording(&mut self) -> Option<std::path::PathBuf> {
+        // A recording started since then has replaced the journal with its own.
+        if self.recorder.is_none() {
+            video::recorder::remove_journal();
+        }
+        self.unfinished_recording.take()
+    }
+
+    /// Remuxes the unfinished recording on a background thread.
+    pub fn start_recording_recovery(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.dismiss_unfinished_recording() else { return };
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.recovery_receiver = Some(rx);
+        self.notify(Notification::info(format!("Recovering {}...", path.display())));
+        let egui_ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = tx.send(video::recorder::recover_recording(&path));
+            egui_ctx.request_repaint();
+        });
+    }
+
+    /// Loads the duration and thumbnail strip of `path` for the trim editor.
+    pub fn open_trim(&mut self, path: std::path::PathBuf, ctx: &egui::Context) {
+        self.trim_session = None;
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.trim_preview_receiver = Some(rx);
+        let egui_ctx = ctx.clone();
+        thread::spawn(move || {
+            let preview = video::clips::load_preview(&path);
+            let _ = tx.send((path, preview));
+            egui_ctx.request_repaint();
+        });
+    }
+
+    /// Saves the part of the open recording between the cut points as a new file.
+    pub fn start_trim(&mut self, ctx: &egui::Context) {
+        let Some(session) = &self.trim_session else { return };
+        let (path, start, end) = (session.path.clone(), session.start, session.end);
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.trim_receiver = Some(rx);
+        self.notify(Notification::info(format!("Trimming {}...", path.display())));
+        let egui_ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = tx.send(video::clips::trim_recording(&path, start, end, "trim"));
+            egui_ctx.request_repaint();
+        });
+    }
+
+    fn poll_trim_results(&mut self, ctx: &egui::Context) {
+        if let Some(rx) = &self.trim_preview_receiver {
+            if let Ok((path, result)) = rx.try_recv() {
+                self.trim_preview_receiver = None;
+                match result {
+                    Ok(preview) => {
+                        let thumbnails = preview.thumbnails.into_iter().enumerate()
+                            .map(|(i, (time, image))| (time, ctx.load_texture(format!("trim_thumbnail_{}", i), image, egui::TextureOptions::LINEAR)))
+                            .collect();
+                        self.trim_session = Some(video::clips::TrimSession { path, duration: preview.duration, start: 0.0, end: preview.duration, thumbnails });
+                    }
+                    Err(e) => self.notify(Notification::error(format!("Failed to open the recording: {:#}", e))),
+                }
+            }
+        }
+        if let Some(rx) = &self.trim_receiver {
+            if let Ok(result) = rx.try_recv() {
+                self.trim_receiver = None;
+                self.notify(match result {
+                    Ok(path) => Notification::success(format!("Trimmed clip saved to {}", path.display())),
+                    Err(e) => Notification::error(format!("Trimming failed: {:#}", e)),
+                });
+                self.recording_list = None;
+            }
+        }
+    }
+
+    /// Scales the filters back to fit the GPU memory cap for a `resolution` source, warning
+    /// once per source and upscale factor when that changes anything.
+    pub fn fit_gpu_memory(&mut self, resolution: (u32, u32), upscale_factor: Option<u32>) -> video::gpu_filter::MemoryFit {
+        let budget = self.gpu_memory_cap_mb as u64 * 1024 * 1024;
+        let fit = video::gpu_filter::fit_to_memory(resolution, upscale_factor, budget, self.max_texture_size);
+        let key = (resolution, upscale_factor);
+        if (!fit.filters || fit.upscale_factor != upscale_factor) && self.memory_warning_shown_for != Some(key) {
+            self.memory_warning_shown_for = Some(key);
+            let needed = video::gpu_filter::render_target_bytes(resolution, upscale_factor) / (1024 * 1024);
+            let downgrade = match fit.upscale_factor {
+                _ if !fit.filters => "The GPU filters are turned off".to_string(),
+                Some(factor) => format!("The upscale factor was lowered to {}x", factor),
+                None => "The upscaler is turned off".to_string(),
+            };
+            let message = format!(
+                "The filters need about {} MB of video memory for {}x{}, more than the {} MB cap or the GPU's {} px texture limit. {} until the source or the settings change.",
+                needed, resolution.0, resolution.1, self.gpu_memory_cap_mb, self.max_texture_size, downgrade
+            );
+            tracing::warn!("{}", message);
+            self.memory_warning = Some(message);
+        }
+        fit
+    }
+
+    /// Resets the selected USB device with the chosen method on a background thread.
+    pub fn reset_usb_device(&mut self, on_startup: bool) {
+        let Some(device) = self.selected_usb_device.clone() else { return };
+        if self.usb_reset_receiver.is_some() {
+            return;
+        }
+        let method = self.usb_reset_method;
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.usb_reset_receiver = Some(rx);
+        thread::spawn(move || {
+            let _ = tx.send(match (devices::usb::reset_usb_device_with(&device, method), on_startup) {
+                (Ok(_), false) => Notification::success("USB device reset successfully."),
+                (Ok(_), true) => {
+                    tracing::info!("USB device reset on startup as requested.");
+                    Notification::success("Auto-reset USB device successfully.")
+                }
+                (Err(e), false) => Notification::error(format!("Failed to reset USB: {}", e)),
+                (Err(e), true) => Notification::error(format!("Failed to auto-reset USB: {}", e)),
+            });
+        });
+    }
+
+    pub fn usb_reset_running(&self) -> bool {
+        self.usb_reset_receiver.is_some()
+    }
+
+    /// Finishes the recording on a background thread, since the encoder has to drain and the
+    /// chapters are muxed in afterwards.
+    pub fn stop_recording(&mut self) {
+        let Some(recorder) = self.recorder.take() else { return };
+        let mic_filters = self.mic_filters.take();
+        let tx = self.recording_stopped_sender.clone();
+        thread::spawn(move || {
+            let _ = tx.send(match recorder.stop() {
+                Ok(path) => Notification::success(format!("Recording saved to {}", path.display())),
+                Err(e) => Notification::error(format!("Recording failed: {:#}", e)),
+            });
+            // Only once the recorder stopped reading from it.
+            if let Some(chain) = mic_filters {
+                chain.unload();
+            }
+        });
+    }
+
+    /// Starts the in-app ALSA loopback. Without PulseAudio, video-only streaming is allowed
+    /// when the fallback is disabled.
+    fn start_alsa_loopback(&mut self) -> bool {
+        if !self.alsa_enabled {
+            self.notify(Notification::warning("PulseAudio unavailable, starting without audio."));
+            return true;
+        }
+        let (Some(capture), Some(playback)) = (&self.selected_alsa_capture, &self.selected_alsa_playback) else {
+            self.notify(Notification::error("Cannot start: Missing ALSA devices.").with_action(NotificationAction::RescanDevices));
+            return false;
+        };
+        match devices::alsa::AlsaLoopback::start(capture, playback, self.loopback_latency_msec.unwrap_or(50)) {
+            Ok(loopback) => {
+                self.alsa_loopback = Some(loopback);
+                self.notify(Notification::info("ALSA loopback started."));
+                true
+            }
+            Err(e) => {
+                self.notify(Notification::error(format!("Failed to start ALSA loopback: {}", e)).with_action(NotificationAction::StartStream));
+                false
+            }
+        }
+    }
+
+    pub fn start_stream(&mut self, ctx: &egui::Context) {
+        // Restarts stop and start in one go, so the old stream may still be releasing the device.
+        // A device switch may also still be reading the new device's formats.
+        if self.stream_stopping() || self.formats_loading() {
+            self.stream_start_pending = true;
+            return;
+        }
+        self.stream_start_pending = false;
+        self.stream_generation += 1;
+
+        let (input, format, resolution, framerate) = match self.source_kind {
+            SourceKind::Capture => {
+                let format = if let Some(f) = self.supported_formats.get(self.selected_format_index) {
+                    f
+                } else {
+                    self.notify(Notification::error("Cannot start: No video format selected."));
+                    return;
+                };
+                (self.selected_video_device.clone(), format.clone(), self.selected_resolution, self.selected_framerate)
+            }
+            SourceKind::Network => {
+                if self.network_source_url.trim().is_empty() {
+                    self.notify(Notification::error("Cannot start: No stream URL entered."));
+                    return;
+                }
+                (self.network_source_url.trim().to_string(), VideoFormat::default(), (0, 0), 0)
+            }
+            SourceKind::File => {
+                if !std::path::Path::new(&self.file_source_path).is_file() {
+                    self.notify(Notification::error("Cannot start: File not found."));
+                    return;
+                }
+                (self.file_source_path.clone(), VideoFormat::default(), (0, 0), 0)
+            }
+            // The portal session is negotiated on the video thread.
+            SourceKind::Screen => (String::new(), VideoFormat::default(), (0, 0), 0),
+        };
+        // Only once the source checks passed, so an early return leaves no routing behind.
+        if !self.start_audio_routing(false) {
+            return;
+        }
+        let (info_sender, info_receiver) = crossbeam_channel::bounded(1);
+        self.stream_info_receiver = Some(info_receiver);
+        self.stream_info = None;
+        self.playback_control = Arc::new(video::decoder::PlaybackControl::new());
+        self.playback_control.looping.store(self.file_source_loop, Ordering::Relaxed);
+        let settings = video::types::CaptureSettings {
+            source_kind: self.source_kind,
+            input,
+            format,
+            resolution,
+            framerate,
+            playback: self.playback_control.clone(),
+            power_saver: self.power_saver.clone(),
+            info_sender,
+            auto_downscale: self.auto_downscale.clone(),
+            display_size: self.display_size.clone(),
+            format_override: if self.source_kind == SourceKind::Capture { self.format_override.clone() } else { Default::default() },
+            thread_priority: self.thread_priority,
+            buffer_age_us: self.buffer_age_us.clone(),
+            color_adjust: self.color_adjust.clone(),
+        };
+
+        // Resize the main window to match the video stream resolution
+        // The command needs to be sent to the main viewport.
+        // Network streams, files and screencasts only reveal their size once decoding starts, so they keep the current size.
+        self.resize_to_video_scale(ctx, resolution);
+        ctx.request_repaint(); // Force a repaint to ensure the new texture is drawn
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        self.stop_video_thread = Some(stop_flag.clone());
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        let crt_filter = self.crt_filter.clone();
+        self.frame_receiver = Some(rx);
+
+        let (failure_tx, failure_rx) = crossbeam_channel::bounded(1);
+        self.video_thread_failure_receiver = Some(failure_rx);
+        self.video_thread_failure = None;
+
+        // Named so the diagnostics can tell the pipeline stages apart. FFmpeg's decoder threads inherit the name.
+        let handle = thread::Builder::new().name("video-decode".to_string()).spawn(move || {
+            // Catch panics too, so the UI can tear down audio routing instead of staying half-streaming.
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                video::decoder::video_thread_main(tx, stop_flag, settings, crt_filter)
+            }));
+            let failure = match result {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => video::types::VideoThreadFailure {
+                    message: format!("{:#}", e),
+                    busy: e.downcast_ref::<video::decoder::DeviceBusyError>()
+                        .map(|busy| video::types::DeviceBusyDetails::gather(&busy.device)),
+                },
+                Err(payload) => video::types::VideoThreadFailure {
+                    message: format!("panicked: {}", panic_message(payload.as_ref())),
+                    busy: None,
+                },
+            };
+            tracing::error!("Video thread error: {}", failure.message);
+            let _ = failure_tx.send(failure);
+        }).expect("failed to spawn the video thread");
+        self.video_thread = Some(handle);
+        self.notify(Notification::info("Stream started."));
+        self.send_obs_event(net::obs::ObsEvent::StreamStarted, ctx);
+        self.video_window_open = true;
+        self.control_window_open = false;
+
+        // Start the fullscreen toggle sequence to fix resizing issues.
+        // Kiosk windows start fullscreen and have to stay that way.
+        if self.kiosk.is_none() {
+            self.fullscreen_toggle_frame_count = Some(0);
+        }
+    }
+
+    pub fn stop_stream(&mut self, ctx: &egui::Context) {
+        if self.is_fullscreen {
+            self.is_fullscreen = false;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+        }
+        self.stop_stream_resources();
+        // Reset the texture to a black screen instead of removing it
+        if let Some(texture) = &mut self.video_texture {
+            texture.clear();
+        }
+        self.video_window_open = false; // This now means "stream is not active"
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+
+    /// Stops the stream. Joining the video thread and unloading the PulseAudio modules can take
+    /// a while, so that happens on a background thread; `stream_stopping` is true until it's done.
+    fn stop_stream_resources(&mut self) {
+        if let Some(stop_flag) = self.stop_video_thread.take() {
+            stop_flag.store(true, Ordering::Relaxed);
+        }
+        self.stream_generation += 1;
+        let recorder = self.recorder.take();
+        let video_thread = self.video_thread.take();
+        self.last_frame = None;
+        // The controller runs whenever the integration is on, so no context is needed to create it here.
+        if let Some((controller, _)) = self.obs_controller.as_ref().filter(|_| self.obs_enabled && video_thread.is_some()) {
+            controller.send(self.obs_settings.clone(), net::obs::ObsEvent::StreamStopped);
+        }
+        let loudness_meter = self.loudness_meter.take();
+        let alsa_loopback = self.alsa_loopback.take();
+        let previous_default_source = self.previous_default_source.take();
+        let loopback_module_index = self.pulse_loopback_module_index.take();
+        let limiter_module_index = self.pulse_limiter_module_index.take();
+        let monitor_output_module_index = self.pulse_monitor_output_module_index.take();
+        let mic_filters = self.mic_filters.take();
+
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        std::thread::spawn(move || {
+            let recording = recorder.map(|recorder| match recorder.stop() {
+                Ok(path) => Notification::success(format!("Recording saved to {}", path.display())),
+                Err(e) => Notification::error(format!("Recording failed: {:#}", e)),
+            });
+            if let Some(chain) = mic_filters {
+                chain.unload();
+            }
+            if let Some(handle) = video_thread {
+                let _ = handle.join();
+            }
+
+            drop(loudness_meter);
+            if let Some(loopback) = alsa_loopback {
+                loopback.stop();
+            }
+            if let Some(previous) = previous_default_source {
+                if let Err(e) = devices::audio::set_default_source(&previous) {
+                    tracing::error!("Failed to restore default source '{}': {}", previous, e);
+                }
+            }
+            let mut notification = match loopback_module_index.map(devices::audio::unload_pulse_module) {
+                Some(Err(e)) => Notification::warning(format!("Stream stopped, but failed to unload PulseAudio module: {}", e)),
+                Some(Ok(_)) => Notification::info("Stream stopped and PulseAudio module unloaded."),
+                None => Notification::info("Stream stopped."),
+            };
+            if let Some(index) = monitor_output_module_index {
+                if let Err(e) = devices::audio::unload_pulse_module(index) {
+                    tracing::error!("Failed to unload monitor output: {}", e);
+                }
+            }
+            // The limiter can only go once the loopback feeding it is gone.
+            if let Some(index) = limiter_module_index {
+                if let Err(e) = devices::audio::unload_pulse_module(index) {
+                    tracing::error!("Failed to unload limiter module: {}", e);
+                }
+            }
+
+            if let Some(recording) = recording {
+                // The worse of the two decides how the combined message is shown.
+                notification.level = notification.level.max(recording.level);
+                notification.message = format!("{} {}", notification.message, recording.message);
+            }
+            let _ = tx.send(notification);
+        });
+        self.stream_stop_receiver = Some(rx);
+        self.notify(Notification::info("Stopping stream…"));
+
+        self.frame_receiver = None;
+        self.frame_pacer.reset();
+        self.border_detector.reset();
+        self.signal_log.stream_stopped();
+        self.game_detector.reset();
+        self.auto_crop_suggestion = None;
+        self.video_thread_failure_receiver = None;
+        self.stream_info_receiver = None;
+        self.stream_info = None;
+        self.video_window_open = false;
+    }
+
+    pub fn stream_stopping(&self) -> bool {
+        self.stream_stop_receiver.is_some()
+    }
+
+    /// Starts the stream `start_stream` queued once nothing holds it up any more.
+    fn resume_pending_start(&mut self, ctx: &egui::Context) {
+        if self.stream_start_pending && !self.stream_stopping() && !self.formats_loading() {
+            self.start_stream(ctx);
+        }
+    }
+
+    /// Blocks until a previous stop has released the devices, for quitting.
+    fn finish_stream_stop(&mut self) {
+        if let Some(rx) = self.stream_stop_receiver.take() {
+            if let Ok(notification) = rx.recv() {
+                self.notify(notification);
+            }
+        }
+    }
+
+    /// Cleans up after a video thread that exited on its own and keeps the reason for the restart dialog.
+    fn handle_video_thread_failure(&mut self, ctx: &egui::Context, failure: video::types::VideoThreadFailure) {
+        // A frozen fullscreen frame hides that anything went wrong, so drop back to a window
+        // (also cancelling the start-up fullscreen toggle, which would re-enter fullscreen).
+        self.fullscreen_toggle_frame_count = None;
+        self.signal_log.stream_failed(&failure.message);
+        if !self.kiosk_locked() {
+            self.is_fullscreen = false;
+            if ctx.input(|i| i.viewport().fullscreen.unwrap_or(false)) {
+                ctx.send_viewport_cmd_to(egui::ViewportId::ROOT, egui::ViewportCommand::Fullscreen(false));
+            }
+            ctx.send_viewport_cmd_to(egui::ViewportId::ROOT, egui::ViewportCommand::Focus);
+        }
+        self.stop_stream_resources();
+        if let Some(texture) = &mut self.video_texture {
+            texture.clear();
+        }
+        self.notify(Notification::error(format!("Video thread stopped: {}", failure.message)));
+        if self.busy_retry_at.is_some() {
+            if failure.busy.is_some() {
+                self.busy_retry_at = Some(Instant::now() + BUSY_RETRY_INTERVAL);
+            } else {
+                self.busy_retry_at = None;
+            }
+        }
+        if self.kiosk_locked() {
+            self.busy_retry_at = Some(Instant::now() + KIOSK_RETRY_INTERVAL);
+        }
+        self.video_thread_failure = Some(failure);
+    }
+
+    /// Switches the capture device, re-reading its formats and the saved format settings.
+    pub fn select_video_device(&mut self, device: &str, ctx: &egui::Context) {
+        self.selected_video_device = device.to_string();
+        config::mark_dirty(self);
+        self.supported_formats.clear();
+        self.selected_format_index = 0;
+        self.selected_resolution = (0, 0);
+
+        // v4l2-ctl can take a while on some devices, so don't block the UI on it.
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.format_scan_receiver = Some(rx);
+        self.notify(Notification::info(format!("Scanning formats for {}...", device)));
+        let device = device.to_string();
+        let egui_ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let result = devices::video::find_video_formats(&device);
+            let _ = tx.send((device, result));
+            egui_ctx.request_repaint();
+        });
+    }
+
+    pub fn formats_loading(&self) -> bool {
+        self.format_scan_receiver.is_some()
+    }
+
+    fn handle_format_scan_result(&mut self, device: String, result: anyhow::Result<Vec<VideoFormat>>, ctx: &egui::Context) {
+        self.format_scan_receiver = None;
+        // A newer selection has its own scan running.
+        if device != self.selected_video_device {
+            return;
+        }
+        let pick_default_format = std::mem::take(&mut self.pick_default_format_pending);
+        self.video_usb_speed = devices::video::usb_speed_mbps(&device);
+        match result {
+            Ok(formats) => {
+                self.notify(Notification::info(format!("Found {} formats for {}.", formats.len(), device)));
+                self.supported_formats = formats;
+                if let Some(res) = self.supported_formats.first().and_then(|f| f.resolutions.first()) {
+                    self.selected_resolution = (res.width, res.height);
+                    self.selected_framerate = res.framerates.first().cloned().unwrap_or(0);
+                }
+                // After loading formats, try to apply the saved config for them.
+                if let Ok(cfg) = confy::load::<config::MichadameConfig>("michadame", None) {
+                    video::types::select_saved_format(self, &cfg);
+                }
+                self.apply_known_card_presets();
+                if pick_default_format {
+                    self.selected_format_index = 0;
+                    if let Some(res) = self.supported_formats.first().and_then(|f| f.resolutions.first()) {
+                        self.selected_resolution = (res.width, res.height);
+                        self.selected_framerate = res.framerates.iter().max().cloned().unwrap_or(0);
+                    }
+                    config::mark_dirty(self);
+                }
+            }
+            Err(e) => {
+                self.notify(Notification::error(format!("Failed to scan formats: {}", e)).with_action(NotificationAction::ScanFormats));
+            }
+        }
+        self.resume_pending_start(ctx);
+    }
+
+    /// Looks the capture device up in the card database and applies its presets the first
+    /// time it is seen; after that the user's own choices win.
+    fn apply_known_card_presets(&mut self) {
+        use devices::card_db::CardQuirk;
+        self.known_card = devices::video::usb_id(&self.selected_video_device).and_then(|id| devices::card_db::find_known_card(&id));
+        let Some(card) = self.known_card else { return };
+        if self.applied_card_presets.iter().any(|id| id == card.id) {
+            return;
+        }
+        self.applied_card_presets.push(card.id.to_string());
+        for quirk in card.quirks {
+            match quirk {
+                CardQuirk::NeedsReset => {
+                    if self.selected_usb_device.is_none() {
+                        self.selected_usb_device = Some(card.id.to_string());
+                    }
+                }
+                CardQuirk::MjpegOnly => {
+                    let mjpeg = self.supported_formats.iter().position(|f| f.fourcc.trim_end_matches('\0') == "MJPG");
+                    if let Some(idx) = mjpeg {
+                        let candidates = [self.supported_formats[idx].clone()];
+                        if let Some((_, resolution, fps)) = video::types::pick_auto_format(&candidates, &[]) {
+                            self.selected_format_index = idx;
+                            self.selected_resolution = resolution;
+                            self.selected_framerate = fps;
+                        }
+                    }
+                }
+                CardQuirk::Broken5994 => self.pacing_mode = video::pacing::PacingMode::VsyncLocked,
+            }
+        }
+        config::mark_dirty(self);
+        let quirks: Vec<&str> = card.quirks.iter().map(|q| q.to_string()).collect();
+        self.notify(Notification::success(format!("Detected {}: applied presets ({}).", card.name, quirks.join(", "))));
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+impl eframe::App for AppState {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Some(gl) = _gl {
+            if let Some(renderer) = self.crt_renderer.as_ref() {
+                renderer.lock().unwrap().destroy(gl);
+            }
+        }
+        self.stop_stream_resources();
+        self.finish_stream_stop();
+        if let Some(server) = self.preview_server.take() {
+            server.stop();
+        }
+        if let Some(share) = self.frame_share.take() {
+            share.stop();
+        }
+        // Restores the desktop's setting before the process ends.
+        self.do_not_disturb = None;
+        // Before the first scan the state still holds defaults, which would overwrite the saved config.
+        // Unsaved changes in manual save mode were turned down in the quit dialog, so they're dropped.
+        if self.devices_scanned && !(self.manual_save && config::has_unsaved_changes(self)) {
+            config::save_config(self);
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut repaint_requested = false;
+
+        // --- Control Window (Secondary) ---
+        if self.control_window_open {
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of("control_window"),
+                egui::ViewportBuilder::default()
+                    .with_title("Michadame Controls")
+                    .with_inner_size([640.0, 500.0]),
+                |ctx, class| {
+                    assert!(
+                        class == egui::ViewportClass::Immediate,
+                        "This egui backend doesn't support multiple viewports"
+                    );
+
+                    repaint_requested |= ui::draw_main_ui(self, ctx);
+                    ui::draw_toasts(self, ctx);
+                    self.handle_dropped_files(ctx);
+                    ui::hotkeys::handle_config_shortcuts(self, ctx);
+                    // Typing into the settings mustn't trigger the stream shortcuts.
+                    if self.video_window_open && !ctx.wants_keyboard_input() {
+                        ui::hotkeys::handle_shortcuts(self, ctx);
+                    }
+
+                    if ctx.input(|i| i.viewport().close_requested()) {
+                        self.control_window_open = false;
+                    }
+                },
+            );
+        }
+
+        if self.gl_context_reset.swap(false, Ordering::Relaxed) {
+            // Textures from the old context are gone; the next frames fill the new ones.
+            self.video_texture = Some(video::texture::VideoTexture::new(ctx));
+            self.overlay_texture = None;
+            self.overlay_loaded_path = None;
+            self.notify(Notification::warning("Graphics context was reset, video output rebuilt."));
+        }
+
+        let direct_upload = self.direct_upload_active();
+        if let Some(texture) = &mut self.video_texture {
+            texture.set_direct(direct_upload);
+        }
+
+        // --- Clean Feed Window (Secondary) ---
+        if self.video_window_open && self.clean_feed_enabled {
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of("clean_feed_window"),
+                egui::ViewportBuilder::default()
+                    .with_title("Michadame Clean Feed")
+                    .with_inner_size([640.0, 480.0]),
+                |ctx, _class| {
+                    egui::CentralPanel::default().frame(egui::Frame::none()).show(ctx, |ui| {
+                        ui::draw_clean_feed(self, ui, ctx);
+                    });
+                    if ctx.input(|i| i.key_pressed(egui::Key::F)) {
+                        let is_fullscreen = !ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(is_fullscreen));
+                    }
+                    if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+                    }
+                    if ctx.input(|i| i.viewport().close_requested()) {
+                        self.clean_feed_enabled = false;
+                        config::mark_dirty(self);
+                    }
+                },
+            );
+        }
+
+        // --- Latency Calibration Pattern (Secondary) ---
+        if let Some(started) = self.latency_pattern_started {
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of("latency_pattern_window"),
+                egui::ViewportBuilder::default()
+                    .with_title("Michadame Latency Pattern")
+                    .with_inner_size([480.0, 360.0]),
+                |ctx, _class| {
+                    let elapsed = started.elapsed();
+                    let (fill, text) = if video::photon_latency::pattern_lit(elapsed) {
+                        (egui::Color32::WHITE, egui::Color32::BLACK)
+                    } else {
+                        (egui::Color32::BLACK, egui::Color32::WHITE)
+                    };
+                    egui::CentralPanel::default().frame(egui::Frame::none().fill(fill)).show(ctx, |ui| {
+                        ui.label(egui::RichText::new(format!("{:.3}", elapsed.as_secs_f64())).monospace().size(32.0).color(text));
+                    });
+                    if ctx.input(|i| i.key_pressed(egui::Key::F)) {
+                        let is_fullscreen = !ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(is_fullscreen));
+                    }
+                    if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+                    }
+                    if ctx.input(|i| i.viewport().close_requested()) {
+                        self.latency_pattern_started = None;
+                    }
+                    ctx.request_repaint();
+                },
+            );
+        }
+
+        // --- Display Wall Tiles (Secondary) ---
+        // Tile 0 is drawn in the main video window below.
+        if self.video_window_open && self.wall_enabled {
+            for index in 1..self.wall_layout.tile_count() {
+                ctx.show_viewport_immediate(
+                    egui::ViewportId::from_hash_of(("wall_tile", index)),
+                    egui::ViewportBuilder::default()
+                        .with_title(format!("Michadame Wall {}", index + 1))
+                        .with_inner_size([640.0, 480.0]),
+                    |ctx, _class| {
+                        egui::CentralPanel::default().frame(egui::Frame::none()).show(ctx, |ui| {
+                            ui::draw_wall_tile(self, ui, ctx, index);
+                        });
+                        if ctx.input(|i| i.key_pressed(egui::Key::F)) {
+                            let is_fullscreen = !ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+                            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(is_fullscreen));
+                        }
+                        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+                        }
+                        // Closing a tile window turns the wall off instead of leaving a gap.
+                        if ctx.input(|i| i.viewport().close_requested()) {
+                            self.wall_enabled = false;
+                            config::mark_dirty(self);
+                        }
+                    },
+                );
+            }
+        }
+
+        // --- Video Window (Primary) ---
+        egui::CentralPanel::default().frame(egui::Frame::none()).show(ctx, |ui| {
+            ui::draw_video_player(self, ui, ctx);
+
+            if self.show_stop_stream_dialog {
+                ui::dialogs::show_stop_stream_dialog(self, ctx, ui, ctx);
+            }
+
+            if self.show_quit_dialog {
+                ui::dialogs::show_quit_dialog(self, ctx, ui);
+            }
+
+            if self.video_thread_failure.is_some() && !self.kiosk_locked() {
+                ui::dialogs::show_video_failure_dialog(self, ctx, ui);
+            }
+        });
+        // Toasts go where the user is looking; kiosk displays never show them.
+        if !self.control_window_open && !self.kiosk_locked() {
+            ui::draw_toasts(self, ctx);
+        }
+
+        // Handle the fullscreen toggle sequence to fix window sizing on stream start.
+        if let Some(count) = self.fullscreen_toggle_frame_count {
+            match count {
+                0 => {
+                    // Frame 1 (after start): Wait one frame for stream to initialize.
+                    self.fullscreen_toggle_frame_count = Some(1);
+                }
+                1 => {
+                    // Frame 2: Go fullscreen.
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+                    self.fullscreen_toggle_frame_count = Some(2);
+                }
+                2 => {
+                    // Frame 3: Go back to windowed and end the sequence.
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+                    self.fullscreen_toggle_frame_count = None;
+                }
+                _ => self.fullscreen_toggle_frame_count = None, // Should not happen.
+            }
+            repaint_requested = true;
+        }
+
+        self.handle_dropped_files(ctx);
+
+        let (inner_rect, fullscreen) = ctx.input(|i| (i.viewport().inner_rect, i.viewport().fullscreen.unwrap_or(false)));
+        if let (Some(rect), false) = (inner_rect, fullscreen) {
+            self.main_window_size = Some((rect.width(), rect.height()));
+        }
+
+        if let Some(kiosk) = self.kiosk.as_mut().filter(|k| !k.unlocked) {
+            if kiosk.check_passphrase(ctx) {
+                self.notify(Notification::info("Kiosk mode unlocked."));
+                self.control_window_open = true;
+            }
+        }
+        self.kiosk_auto_start(ctx);
+        if self.kiosk_locked() {
+            self.control_window_open = false;
+            ctx.set_cursor_icon(egui::CursorIcon::None);
+        }
+
+        // Handle keyboard shortcuts for the main video window; ignored while kiosk mode is locked.
+        if !self.kiosk_locked() {
+            ui::hotkeys::handle_config_shortcuts(self, ctx);
+            ui::hotkeys::handle_shortcuts(self, ctx);
+        }
+
+        // Handle window close request (e.g., from the 'X' button)
+        if ctx.input(|i| i.viewport().close_requested()) {
+            if self.kiosk_locked() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            } else if !self.show_quit_dialog && (self.video_window_open || (self.manual_save && config::has_unsaved_changes(self))) {
                 ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
                 self.show_quit_dialog = true;
-            } // If no stream, or dialog is already open, allow the default close behavior.
+            } // If no stream or unsaved changes, or dialog is already open, allow the default close behavior.
             repaint_requested = true;
         }
 
+        if let Some(rx) = &self.system_check_receiver {
+            if let Ok(results) = rx.try_recv() {
+                tracing::info!("System check:\n{}", selftest::format_report(&results));
+                self.system_check_results = Some(results);
+                self.system_check_receiver = None;
+            }
+        }
+
+        if let Some(rx) = &self.support_bundle_receiver {
+            if let Ok(result) = rx.try_recv() {
+                self.support_bundle_receiver = None;
+                match result {
+                    Ok(path) => self.notify(Notification::success(format!("Saved support bundle to {}", path.display()))),
+                    Err(e) => self.notify(Notification::error(format!("Failed to create support bundle: {:#}", e))),
+                }
+            }
+        }
+
+        if let Some(rx) = &self.config_watch_receiver {
+            // Before the device scan finishes, the initial apply_config picks up the file anyway.
+            if rx.try_recv().is_ok() && self.devices_scanned {
+                self.reload_live_settings();
+            }
+        }
+
+        if let Some(assets) = self.asset_watch_receiver.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.update_assets(assets);
+        }
+
         if let Some(rx) = &self.device_scan_receiver {
             if let Ok(scan_result) = rx.try_recv() {
                 repaint_requested |= self.handle_device_scan_result(scan_result);
@@ -446,19 +3332,288 @@ impl eframe::App for AppState {
             }
         }
 
-        if let Some(rx) = &self.frame_receiver {
-            if let Ok(image) = rx.try_recv() {
-                self.video_texture.as_mut().unwrap().set(image, egui::TextureOptions::LINEAR);
-                self.video_frames_since_last_check += 1;
+        if let Some((profile, rx)) = &self.card_profile_receiver {
+            if let Ok(result) = rx.try_recv() {
+                let profile = profile.clone();
+                self.card_profile_receiver = None;
+                self.finish_card_profile_switch(&profile, result);
+            }
+        }
+
+        while let Ok(task) = self.pulse_task_receiver.try_recv() {
+            self.pulse_tasks_running -= 1;
+            task(self, ctx);
+        }
+        // Workers don't have the context to wake the UI when they're done.
+        repaint_requested |= self.pulse_tasks_running > 0;
+
+        if let Some(rx) = &self.usb_reset_receiver {
+            match rx.try_recv() {
+                Ok(notification) => {
+                    self.usb_reset_receiver = None;
+                    self.notify(notification);
+                }
+                Err(crossbeam_channel::TryRecvError::Empty) => repaint_requested = true,
+                Err(crossbeam_channel::TryRecvError::Disconnected) => self.usb_reset_receiver = None,
+            }
+        }
+
+        while let Ok(notification) = self.recording_stopped_receiver.try_recv() {
+            self.notify(notification);
+            self.recording_list = None;
+        }
+
+        if let Some(rx) = &self.latency_measure_receiver {
+            if let Ok(result) = rx.try_recv() {
+                match result {
+                    Ok(latency) => {
+                        self.measured_latency_msec = Some(latency);
+                        self.notify(Notification::success(format!("Measured audio latency: {} ms.", latency)));
+                    }
+                    Err(e) => self.notify(Notification::error(format!("Latency measurement failed: {}", e)).with_action(NotificationAction::MeasureLatency)),
+                }
+                self.latency_measure_receiver = None;
+            }
+            repaint_requested = true;
+        }
+
+        if let Some(rx) = &self.photon_latency_receiver {
+            if let Ok((profile, result)) = rx.try_recv() {
+                self.photon_latency_receiver = None;
+                match result {
+                    Ok(latency) => {
+                        self.photon_latency_msec.insert(profile.clone(), latency);
+                        config::mark_dirty(self);
+                        self.notify(Notification::success(format!("Measured video latency: {} ms for '{}'.", latency, profile)));
+                    }
+                    Err(e) => self.notify(Notification::error(format!("Latency calibration failed: {:#}", e))),
+                }
+            }
+        }
+
+        self.check_audio_drift(ctx);
+        self.check_highlights(ctx);
+
+        if let Some(rx) = &self.recovery_receiver {
+            if let Ok(result) = rx.try_recv() {
+                self.notify(match result {
+                    Ok(path) => Notification::success(format!("Recovered recording saved to {}", path.display())),
+                    Err(e) => Notification::error(format!("Recovering the recording failed: {:#}", e)),
+                });
+                self.recovery_receiver = None;
+            }
+        }
+
+        // egui doesn't report occlusion, so a minimized window is the only reliable "hidden" signal.
+        let display_hidden = self.throttle_when_minimized && ctx.input(|i| i.viewport().minimized.unwrap_or(false));
+        if self.devices_scanned && self.pulse_available && self.pulse_monitor.is_none() {
+            self.pulse_monitor = Some(devices::pulse_monitor::PulseMonitor::start(ctx));
+        }
+        let pulse_events: Vec<_> = self.pulse_monitor.as_ref().map(|(_, rx)| rx.try_iter().collect()).unwrap_or_default();
+        for event in pulse_events {
+            self.handle_pulse_event(event);
+        }
+
+        self.update_idle_inhibit(ctx);
+        self.update_do_not_disturb();
+
+        let sleep_events: Vec<_> = self.sleep_monitor.as_ref().map(|(_, rx)| rx.try_iter().collect()).unwrap_or_default();
+        for event in sleep_events {
+            self.handle_sleep_event(event);
+        }
+        if let Some(restart_at) = self.resume_restart_at {
+            if Instant::now() >= restart_at {
+                self.resume_restart_at = None;
+                if self.video_thread.is_none() {
+                    self.start_stream(ctx);
+                }
+            } else {
+                ctx.request_repaint_after(restart_at - Instant::now());
+            }
+        }
+
+        self.update_livesplit(ctx);
+        self.update_autosplitter(ctx);
+        self.update_color_calibration(ctx);
+        if self.osc_input_wanted() != self.osc_input.is_some() {
+            self.apply_osc_input(ctx);
+        }
+        self.update_midi(ctx);
+        self.poll_obs_results(ctx);
+        self.update_remote_server(ctx);
+        self.update_command_server(ctx);
+        if self.frame_share_enabled != self.frame_share.is_some() {
+            self.apply_frame_share();
+        }
+        self.poll_trim_results(ctx);
+
+        if let Some(rx) = &self.screenshot_receiver {
+            if let Ok(result) = rx.try_recv() {
+                self.notify(match result {
+                    Ok(path) => Notification::success(format!("Screenshot saved to {}", path.display())),
+                    Err(e) => Notification::error(format!("{:#}", e)),
+                });
+                self.screenshot_receiver = None;
+            }
+        }
+        if self.timer_enabled && self.stopwatch.is_running() && self.timer_source == video::timer::TimerSource::Builtin {
+            repaint_requested = true;
+        }
+
+        if let Some(rx) = &self.probe_receiver {
+            let results = self.probe_results.get_or_insert_with(Vec::new);
+            loop {
+                match rx.try_recv() {
+                    Ok(result) => results.push(result),
+                    Err(crossbeam_channel::TryRecvError::Empty) => {
+                        repaint_requested = true;
+                        break;
+                    }
+                    Err(crossbeam_channel::TryRecvError::Disconnected) => {
+                        self.probe_receiver = None;
+                        self.probe_stop = None;
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(rx) = &self.format_scan_receiver {
+            match rx.try_recv() {
+                Ok((device, result)) => self.handle_format_scan_result(device, result, ctx),
+                Err(_) => repaint_requested = true,
+            }
+        }
+
+        if let Some(rx) = &self.stream_stop_receiver {
+            match rx.try_recv() {
+                Ok(notification) => {
+                    // After a failure, the failure stays the status; where a recording went still shows.
+                    let failure_status = self.video_thread_failure.is_some().then(|| self.status.clone());
+                    self.notify(notification);
+                    if let Some(status) = failure_status {
+                        self.status = status;
+                    }
+                    self.stream_stop_receiver = None;
+                    if std::mem::take(&mut self.sleep_ready_pending) {
+                        if let Some((monitor, _)) = &self.sleep_monitor {
+                            monitor.ready();
+                        }
+                    }
+                    self.resume_pending_start(ctx);
+                }
+                Err(_) => repaint_requested = true,
+            }
+        }
+
+        if let Some(rx) = &self.stream_info_receiver {
+            if let Ok(info) = rx.try_recv() {
+                tracing::info!(?info, "Negotiated stream format");
+                self.stream_info = Some(info);
+            }
+        }
+
+        if let Some(retry_at) = self.busy_retry_at {
+            if self.video_thread_failure.is_some() && Instant::now() >= retry_at {
+                self.video_thread_failure = None;
+                self.start_stream(ctx);
             }
-            // Always repaint when video is playing to show new frames
             repaint_requested = true;
         }
 
+        if let Some(rx) = &self.video_thread_failure_receiver {
+            if let Ok(failure) = rx.try_recv() {
+                self.handle_video_thread_failure(ctx, failure);
+                repaint_requested = true;
+            }
+        }
+
+        if self.recorder.as_ref().is_some_and(|r| r.has_failed()) {
+            self.stop_recording();
+        }
+
+        let burn_in = self.burn_in_active();
+        if let Some(recorder) = &self.recorder {
+            for image in self.filtered_frame_receiver.try_iter() {
+                recorder.push_frame(image);
+            }
+        }
+
+        self.update_interpolator(ctx);
+        let is_capture = self.source_kind == SourceKind::Capture;
+        if let Some(rx) = &self.frame_receiver {
+            for image in rx.try_iter() {
+                if is_capture {
+                    self.signal_log.push_frame(&image);
+                }
+                if let Some(server) = &self.preview_server {
+                    server.push_frame(image.clone());
+                }
+                if let Some(share) = &self.frame_share {
+                    share.push_frame(image.clone());
+                }
+                if let Some((splitter, _)) = &self.autosplitter {
+                    splitter.push_frame(image.clone());
+                }
+                if let Some(frames) = &mut self.calibration_frames {
+                    frames.push(image.clone());
+                }
+                if let Some(recorder) = self.recorder.as_ref().filter(|_| !burn_in) {
+                    recorder.push_frame(image.clone());
+                }
+                match &self.interpolator {
+                    Some(interpolator) => interpolator.push(image),
+                    None => self.frame_pacer.push(image),
+                }
+            }
+            if let Some(interpolator) = &self.interpolator {
+                for image in interpolator.frames() {
+                    self.frame_pacer.push(image);
+                }
+            }
+            if is_capture {
+                self.signal_log.check();
+            }
+            let frame_dt = ctx.input(|i| i.unstable_dt);
+            if let Some(image) = self.frame_pacer.next_frame(self.pacing_mode, frame_dt) {
+                self.last_frame = Some(image.clone());
+                if self.auto_crop_mode != video::autocrop::AutoCropMode::Off {
+                    if let Some(crop) = self.border_detector.push(&image) {
+                        self.apply_detected_crop(crop);
+                    }
+                }
+                if self.game_detection_enabled {
+                    if let Some(game) = self.game_detector.push(&image, &self.games) {
+                        self.switch_to_game(&game, ctx);
+                    }
+                }
+                if !display_hidden {
+                    self.video_texture.as_mut().unwrap().upload(image);
+                    self.video_frames_since_last_check += 1;
+                    self.burn_in_readback_pending = burn_in;
+                }
+            }
+            if display_hidden && self.preview_server.is_none() && self.frame_share.is_none() && self.recorder.is_none() {
+                // Nobody is watching: let the video thread drop frames and only poll occasionally.
+                ctx.request_repaint_after(std::time::Duration::from_millis(250));
+            } else {
+                // Always repaint when video is playing to show new frames
+                repaint_requested = true;
+            }
+        }
+
         self.update_fps_counters(ctx);
 
+        if let Some(wait) = config::save_if_due(self) {
+            ctx.request_repaint_after(wait);
+        }
+
         if repaint_requested {
-            ctx.request_repaint();
+            if self.power_saver.load(Ordering::Relaxed) {
+                ctx.request_repaint_after(POWER_SAVER_REPAINT_INTERVAL);
+            } else {
+                ctx.request_repaint();
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use eframe::egui;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const ASSET_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Masks are tiled over the picture, so anything bigger is probably a bezel in the wrong folder.
+const MAX_MASK_SIZE: u32 = 256;
+const MAX_SHADER_BYTES: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AssetKind {
+    Shader = 0,
+    Mask = 1,
+    Bezel = 2,
+    Lut = 3,
+}
+
+impl AssetKind {
+    pub const ALL: [AssetKind; 4] = [AssetKind::Shader, AssetKind::Mask, AssetKind::Bezel, AssetKind::Lut];
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            AssetKind::Shader => "Shaders",
+            AssetKind::Mask => "Masks",
+            AssetKind::Bezel => "Bezels",
+            AssetKind::Lut => "LUTs",
+        }
+    }
+
+    /// Subdirectory of the assets directory holding this kind.
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            AssetKind::Shader => "shaders",
+            AssetKind::Mask => "masks",
+            AssetKind::Bezel => "bezels",
+            AssetKind::Lut => "luts",
+        }
+    }
+
+    /// Only bezels go anywhere yet, through the Overlay picker. The renderer has no slot for a
+    /// user shader, mask tile or LUT, so those are listed and checked but can't be chosen.
+    pub fn has_picker(&self) -> bool {
+        *self == AssetKind::Bezel
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            AssetKind::Shader => &["glsl", "frag"],
+            AssetKind::Mask | AssetKind::Bezel => &["png", "jpg", "jpeg", "webp"],
+            AssetKind::Lut => &["cube"],
+        }
+    }
+}
+
+/// A file in the assets directory, checked when it was indexed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asset {
+    pub kind: AssetKind,
+    /// File name without the extension.
+    pub name: String,
+    pub path: PathBuf,
+    /// What was found, e.g. the image size, or why the file can't be used.
+    pub check: Result<String, String>,
+}
+
+impl Asset {
+    pub fn is_valid(&self) -> bool {
+        self.check.is_ok()
+    }
+}
+
+/// Assets live next to the confy config file, like looks, with a subdirectory per kind.
+pub fn assets_dir() -> Result<PathBuf> {
+    let config_path = confy::get_configuration_file_path("michadame", None).context("Failed to locate config directory")?;
+    let dir = config_path.parent().context("Config path has no parent directory")?.join("assets");
+    for kind in AssetKind::ALL {
+        let sub = dir.join(kind.dir_name());
+        std::fs::create_dir_all(&sub).with_context(|| format!("Failed to create '{}'", sub.display()))?;
+    }
+    Ok(dir)
+}
+
+fn check_image(path: &Path, max_size: Option<u32>) -> Result<String> {
+    let (width, height) = image::image_dimensions(path).context("Not a readable image")?;
+    if let Some(max) = max_size.filter(|max| width > *max || height > *max) {
+        anyhow::bail!("{}x{} is too big for a mask tile, the limit is {}x{}", width, height, max, max);
+    }
+    Ok(format!("{}x{}", width, height))
+}
+
+/// Checks a fragment shader as far as possible without compiling it: the driver only gets to
+/// see it once a filter uses it.
+fn check_shader(path: &Path) -> Result<String> {
+    let size = std::fs::metadata(path)?.len();
+    if size > MAX_SHADER_BYTES {
+        anyhow::bail!("{} KiB is too large for a shader", size / 1024);
+    }
+    let source = std::fs::read_to_string(path).context("Not a UTF-8 text file")?;
+    if !source.contains("void main") {
+        anyhow::bail!("No main() function");
+    }
+    Ok(format!("{} lines", source.lines().count()))
+}
+
+/// Checks an Adobe/Resolve `.cube` LUT: its size line and that it has that many RGB rows.
+fn check_lut(path: &Path) -> Result<String> {
+    let contents = std::fs::read_to_string(path).context("Not a text file")?;
+    let (mut size, mut three_d, mut rows) = (None, false, 0usize);
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let first = words.next().unwrap_or_default();
+        match first {
+            "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                three_d = first == "LUT_3D_SIZE";
+                size = Some(words.next().and_then(|n| n.parse::<usize>().ok()).filter(|n| (2..=256).contains(n)).context("Invalid LUT size")?);
+            }
+            "TITLE" | "DOMAIN_MIN" | "DOMAIN_MAX" | "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => {}
+            _ => {
+                let values: Vec<f32> = line.split_whitespace().filter_map(|v| v.parse().ok()).collect();
+                if values.len() != 3 || values.iter().any(|v| !v.is_finite()) {
+                    anyhow::bail!("Line {} isn't an RGB triple", number + 1);
+                }
+                rows += 1;
+            }
+        }
+    }
+    let size = size.context("No LUT_1D_SIZE or LUT_3D_SIZE line")?;
+    let expected = if three_d { size.pow(3) } else { size };
+    if rows != expected {
+        anyhow::bail!("Has {} entries, expected {}", rows, expected);
+    }
+    Ok(if three_d { format!("{}x{}x{} 3D", size, size, size) } else { format!("{} entry 1D", size) })
+}
+
+fn check(kind: AssetKind, path: &Path) -> Result<String, String> {
+    let result = match kind {
+        AssetKind::Shader => check_shader(path),
+        AssetKind::Mask => check_image(path, Some(MAX_MASK_SIZE)),
+        AssetKind::Bezel => check_image(path, None),
+        AssetKind::Lut => check_lut(path),
+    };
+    result.map_err(|e| format!("{:#}", e))
+}
+
+/// Files of one kind, sorted by name, with their size and modification time for change
+/// detection.
+fn list(dir: &Path, kind: AssetKind) -> Vec<(PathBuf, u64, Option<SystemTime>)> {
+    let Ok(entries) = std::fs::read_dir(dir.join(kind.dir_name())) else { return Vec::new() };
+    let mut files: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().and_then(|e| e.to_str()).is_some_and(|e| kind.extensions().contains(&e.to_ascii_lowercase().as_str()))
+        })
+        .map(|path| {
+            let metadata = std::fs::metadata(&path).ok();
+            let len = metadata.as_ref().map_or(0, |m| m.len());
+            let modified = metadata.and_then(|m| m.modified().ok());
+            (path, len, modified)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Indexes and checks every asset.
+pub fn scan(dir: &Path) -> Vec<Asset> {
+    AssetKind::ALL
+        .into_iter()
+        .flat_map(|kind| {
+            list(dir, kind).into_iter().map(move |(path, _, _)| Asset {
+                kind,
+                name: path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default(),
+                check: check(kind, &path),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Indexes the assets directory, then polls it and sends a new index whenever files are added,
+/// removed or changed. The thread ends when the receiver is dropped.
+pub fn watch_assets(ctx: &egui::Context) -> Result<crossbeam_channel::Receiver<Vec<Asset>>> {
+    let dir = assets_dir()?;
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let mut known = None;
+        loop {
+            let current: Vec<_> = AssetKind::ALL.into_iter().map(|kind| list(&dir, kind)).collect();
+            if known.as_ref() != Some(&current) {
+                known = Some(current);
+                let assets = scan(&dir);
+                tracing::debug!(count = assets.len(), "Indexed assets");
+                if tx.send(assets).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+            std::thread::sleep(ASSET_POLL_INTERVAL);
+        }
+    });
+    Ok(rx)
+}
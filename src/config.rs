@@ -1,40 +1,300 @@
-use crate::{app::AppState, devices, video::types as video_types};
+use crate::{app::AppState, devices, secrets, games::GameProfile, scenes::Scene, devices::audio::AudioRouting, devices::filter_type::{BfiMode, CrtFilter}, devices::idle_inhibit::IdleInhibitPolicy, devices::usb::UsbResetMethod, video::autocrop::AutoCropMode, video::display::{GpuPreference, PresentMode}, video::filter_settings::{FilterSettings, LegacyFilterFields}, video::ffmpeg_log::{self, FfmpegLogLevel}, video::overlay::TextOverlayPosition, video::timer::TimerSource, video::autosplit::SplitTrigger, video::modulation::Modulation, devices::midi::MidiMapping, video::types as video_types, video::interpolate::InterpolationMode, video::pacing::PacingMode, video::recorder::{RecordQuality, TimelapseMode, VideoEncoder}, video::types::SourceKind};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
-#[derive(Default, Serialize, Deserialize, Clone)]
+#[derive(Default, Serialize, Deserialize, Clone, PartialEq)]
 pub struct MichadameConfig {
     pub video_device: Option<String>,
     pub usb_device: Option<String>,
     pub pulse_source: Option<String>,
     pub pulse_sink: Option<String>,
+    pub loopback_latency_msec: Option<u32>,
+    pub loopback_volume: Option<f32>,
+    pub monitor_output_sink: Option<String>,
+    pub monitor_output_volume: Option<f32>,
+    pub loopback_adjust_time: Option<u32>,
+    pub loopback_max_latency_msec: Option<u32>,
+    pub loopback_channels: Option<u8>,
+    pub loopback_extra_args: Option<String>,
+    pub limiter_enabled: Option<bool>,
+    pub limiter_limit_db: Option<f32>,
+    pub drift_check_enabled: Option<bool>,
+    pub drift_threshold_msec: Option<u32>,
+    pub drift_auto_reload: Option<bool>,
+    pub loudness_meter_enabled: Option<bool>,
+    pub audio_routing: Option<u8>,
+    pub pulse_card: Option<String>,
+    pub alsa_enabled: Option<bool>,
+    pub alsa_capture_device: Option<String>,
+    pub alsa_playback_device: Option<String>,
     pub video_format_fourcc: Option<String>,
     pub video_resolution: Option<(u32, u32)>,
     pub video_framerate: Option<u32>,
+    pub auto_format: Option<bool>,
+    pub format_priority: Option<String>,
+    pub ffmpeg_input_format: Option<String>,
+    pub ffmpeg_pixel_format: Option<String>,
+    pub source_kind: Option<u8>,
+    pub network_source_url: Option<String>,
+    pub file_source_path: Option<String>,
+    pub file_source_loop: Option<bool>,
     pub reset_usb_on_startup: Option<bool>,
+    pub usb_reset_method: Option<u8>,
+    pub applied_card_presets: Option<Vec<String>>,
     pub has_shown_first_run_warning: Option<bool>, // Add this line
+    pub manual_save: Option<bool>,
     pub crt_filter: Option<u8>,
     pub pixelate_filter_enabled: Option<bool>,
+    pub filter_cycle: Option<u8>,
+    pub throttle_when_minimized: Option<bool>,
+    pub direct_upload_enabled: Option<bool>,
+    pub thread_priority: Option<u8>,
+    pub idle_inhibit_policy: Option<u8>,
+    pub do_not_disturb_enabled: Option<bool>,
+    pub restart_after_resume: Option<bool>,
+    pub window_scale: Option<u32>,
+    pub present_mode: Option<u8>,
+    pub gpu_preference: Option<u8>,
+    pub main_window_size: Option<(f32, f32)>,
+    pub power_saver: Option<bool>,
+    pub auto_downscale: Option<bool>,
+    pub gpu_memory_cap_mb: Option<u32>,
+    pub pacing_mode: Option<u8>,
+    pub interpolation_mode: Option<u8>,
 
-    // Lottes params
-    pub crt_hard_scan: Option<f32>,
-    pub crt_warp_x: Option<f32>,
-    pub crt_warp_y: Option<f32>,
-    pub crt_shadow_mask: Option<f32>,
-    pub crt_brightboost: Option<f32>,
-    pub crt_hard_bloom_pix: Option<f32>,
-    pub crt_hard_bloom_scan: Option<f32>,
-    pub crt_bloom_amount: Option<f32>,
-    pub crt_shape: Option<f32>,
-    pub crt_hard_pix: Option<f32>,
-}
-
-pub fn save_config(state: &AppState) {
-    let cfg = MichadameConfig {
+    /// Filter parameters saved by older versions, carried over when there's no `filter` table.
+    #[serde(flatten, skip_serializing)]
+    pub legacy_filter: LegacyFilterFields,
+
+    // Motion clarity
+    pub bfi_mode: Option<u8>,
+    pub bfi_cycle: Option<u32>,
+    pub pixel_aspect: Option<(u32, u32)>,
+    pub display_crop_enabled: Option<bool>,
+    pub display_crop: Option<(u32, u32, u32, u32)>,
+    pub auto_crop_mode: Option<u8>,
+
+    // Bezel overlay
+    pub overlay_enabled: Option<bool>,
+    pub overlay_opacity: Option<f32>,
+    pub overlay_path: Option<String>,
+
+    pub text_overlay_enabled: Option<bool>,
+    pub text_overlay_text: Option<String>,
+    pub text_overlay_file: Option<String>,
+    pub text_overlay_position: Option<u8>,
+    pub text_overlay_size: Option<f32>,
+    pub timer_enabled: Option<bool>,
+    pub timer_source: Option<u8>,
+    pub timer_size: Option<f32>,
+    pub livesplit_address: Option<String>,
+    pub autosplit_enabled: Option<bool>,
+    pub game_detection_enabled: Option<bool>,
+    pub modulation_enabled: Option<bool>,
+    pub modulation_osc_enabled: Option<bool>,
+    pub modulation_osc_port: Option<u16>,
+    pub midi_device: Option<String>,
+    pub clean_feed_enabled: Option<bool>,
+    pub latency_overlay_enabled: Option<bool>,
+
+    // Display wall
+    pub wall_enabled: Option<bool>,
+    pub wall_columns: Option<u32>,
+    pub wall_rows: Option<u32>,
+    pub wall_bezel_percent: Option<f32>,
+
+    // Network preview
+    pub preview_enabled: Option<bool>,
+    pub preview_port: Option<u16>,
+    pub preview_quality: Option<u8>,
+    pub preview_max_fps: Option<u32>,
+    pub preview_username: Option<String>,
+    /// Kept in the secrets file; only read here from configs of older versions.
+    #[serde(skip_serializing)]
+    pub preview_password: Option<String>,
+
+    // Remote control
+    pub remote_enabled: Option<bool>,
+    pub remote_port: Option<u16>,
+    pub remote_username: Option<String>,
+    /// Kept in the secrets file; only read here from configs of older versions.
+    #[serde(skip_serializing)]
+    pub remote_password: Option<String>,
+    pub command_port_enabled: Option<bool>,
+    pub command_port: Option<u16>,
+
+    // Frame sharing
+    pub frame_share_enabled: Option<bool>,
+
+    // OBS integration
+    pub obs_enabled: Option<bool>,
+    pub obs_address: Option<String>,
+    /// Kept in the secrets file; only read here from configs of older versions.
+    #[serde(skip_serializing)]
+    pub obs_password: Option<String>,
+    pub obs_start_scene: Option<String>,
+    pub obs_stop_scene: Option<String>,
+    pub obs_control_recording: Option<bool>,
+
+    // Recording
+    pub ffmpeg_log_level: Option<u8>,
+    pub recording_dir: Option<String>,
+    pub recording_file_template: Option<String>,
+    pub active_look: Option<String>,
+    pub record_audio: Option<bool>,
+    pub record_audio_volume: Option<f32>,
+    pub record_mic_enabled: Option<bool>,
+    pub record_mic_source: Option<String>,
+    pub record_mic_volume: Option<f32>,
+    pub mic_highpass_enabled: Option<bool>,
+    pub mic_gate_enabled: Option<bool>,
+    pub mic_gate_threshold_db: Option<f32>,
+    pub record_crop_enabled: Option<bool>,
+    pub record_crop: Option<(u32, u32, u32, u32)>,
+    pub record_burn_in: Option<bool>,
+    pub timelapse_mode: Option<u8>,
+    pub timelapse_interval: Option<u32>,
+    pub highlight_clips_enabled: Option<bool>,
+    pub highlight_threshold_lufs: Option<f32>,
+    pub highlight_lead_secs: Option<u32>,
+    pub record_encoder: Option<u8>,
+    pub record_quality: Option<u8>,
+    pub vaapi_device: Option<String>,
+
+    // TOML needs tables after all plain values, so these have to stay last. An empty list is a
+    // plain value, so lists are left out while they're empty.
+    pub open_sections: Option<HashMap<String, bool>>,
+    /// Overlays per video device, saved by older versions. Read when there's no `overlay_path`.
+    #[serde(skip_serializing)]
+    pub overlay_paths: Option<HashMap<String, String>>,
+    pub scenes: Option<Vec<Scene>>,
+    pub games: Option<Vec<GameProfile>>,
+    pub autosplit_triggers: Option<Vec<SplitTrigger>>,
+    pub modulations: Option<Vec<Modulation>>,
+    pub midi_mappings: Option<Vec<MidiMapping>>,
+    pub photon_latency_msec: Option<HashMap<String, u32>>,
+    pub filter: Option<FilterSettings>,
+    pub filter_defaults: Option<FilterSettings>,
+}
+
+/// Debounces config writes so dragging a slider doesn't write the file on every frame.
+#[derive(Default)]
+pub struct SaveScheduler {
+    first_change: Option<Instant>,
+    last_change: Option<Instant>,
+    /// Changes since the config was last written. With manual saving they stay after their
+    /// undo step was recorded, until the user saves or reverts.
+    unwritten: bool,
+}
+
+impl SaveScheduler {
+    pub fn is_pending(&self) -> bool {
+        self.first_change.is_some()
+    }
+
+    /// Due once changes have settled, or after `SAVE_MAX_DELAY` of continuous changes.
+    fn is_due(&self) -> bool {
+        match (self.first_change, self.last_change) {
+            (Some(first), Some(last)) => last.elapsed() >= SAVE_DEBOUNCE || first.elapsed() >= SAVE_MAX_DELAY,
+            _ => false,
+        }
+    }
+}
+
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+const SAVE_MAX_DELAY: Duration = Duration::from_secs(3);
+
+/// Schedules a config write; `save_if_due` performs it from `AppState::update`.
+pub fn mark_dirty(state: &mut AppState) {
+    let now = Instant::now();
+    state.config_save.first_change.get_or_insert(now);
+    state.config_save.last_change = Some(now);
+    state.config_save.unwritten = true;
+}
+
+/// Writes the config if a scheduled save is due. Returns how long to wait for the next check.
+/// With manual saving, changes only become undo steps until the user saves or reverts them.
+pub fn save_if_due(state: &mut AppState) -> Option<Duration> {
+    if !state.config_save.is_pending() {
+        return None;
+    }
+    if state.config_save.is_due() {
+        save_if_pending(state);
+        return None;
+    }
+    Some(SAVE_DEBOUNCE)
+}
+
+/// Writes pending changes, or with manual saving takes them into the undo history.
+pub fn save_if_pending(state: &mut AppState) {
+    if !state.config_save.is_pending() {
+        return;
+    }
+    if state.manual_save {
+        record_undo_step(state);
+    } else {
+        save_config(state);
+    }
+}
+
+/// Takes the current settings into the undo history without writing them.
+pub fn record_undo_step(state: &mut AppState) {
+    state.config_save.first_change = None;
+    state.config_save.last_change = None;
+    let cfg = current_config(state);
+    state.undo_history.record(&cfg);
+}
+
+/// Whether settings differ from the last saved config. Section and window state don't count.
+pub fn has_unsaved_changes(state: &AppState) -> bool {
+    state.config_save.unwritten
+        && state.undo_history.written.as_ref().is_none_or(|written| without_ui_state(written) != without_ui_state(&current_config(state)))
+}
+
+/// Writes the config right away. Most callers should use `mark_dirty`.
+pub fn save_config(state: &mut AppState) {
+    state.config_save = SaveScheduler::default();
+    let cfg = current_config(state);
+    state.undo_history.record(&cfg);
+    state.undo_history.written = Some(cfg.clone());
+    if let Err(e) = secrets::store(&secrets::Secrets::from_config(&cfg)) {
+        tracing::error!("Failed to save passwords: {:#}", e);
+    }
+    if let Err(e) = confy::store("michadame", None, cfg) {
+        tracing::error!("Failed to save configuration: {}", e);
+    }
+    // Remember our own write so the watcher doesn't report it as an external edit.
+    *LAST_SAVED_MTIME.lock().unwrap() = config_mtime();
+}
+
+pub fn current_config(state: &AppState) -> MichadameConfig {
+    MichadameConfig {
         video_device: Some(state.selected_video_device.clone()),
         usb_device: state.selected_usb_device.clone(),
         pulse_source: state.selected_pulse_source_name.clone(),
         pulse_sink: state.selected_pulse_sink_name.clone(),
+        loopback_latency_msec: state.loopback_latency_msec,
+        loopback_volume: Some(state.loopback_volume),
+        monitor_output_sink: state.monitor_output_sink.clone(),
+        monitor_output_volume: Some(state.monitor_output_volume),
+        loopback_adjust_time: state.loopback_options.adjust_time,
+        loopback_max_latency_msec: state.loopback_options.max_latency_msec,
+        loopback_channels: state.loopback_options.channels,
+        loopback_extra_args: Some(state.loopback_options.extra_args.clone()),
+        limiter_enabled: Some(state.limiter_enabled),
+        limiter_limit_db: Some(state.limiter_limit_db),
+        drift_check_enabled: Some(state.drift_check_enabled),
+        drift_threshold_msec: Some(state.drift_threshold_msec),
+        drift_auto_reload: Some(state.drift_auto_reload),
+        loudness_meter_enabled: Some(state.loudness_meter_enabled),
+        audio_routing: Some(state.audio_routing as u8),
+        pulse_card: state.selected_pulse_card.clone(),
+        alsa_enabled: Some(state.alsa_enabled),
+        alsa_capture_device: state.selected_alsa_capture.clone(),
+        alsa_playback_device: state.selected_alsa_playback.clone(),
         video_format_fourcc: state
             .supported_formats
             .get(state.selected_format_index)
@@ -45,28 +305,295 @@ pub fn save_config(state: &AppState) {
             None
         },
         video_framerate: if state.selected_framerate > 0 { Some(state.selected_framerate) } else { None },
+        auto_format: Some(state.auto_format),
+        format_priority: Some(state.format_priority.clone()),
+        ffmpeg_input_format: state.format_override.input_format.clone(),
+        ffmpeg_pixel_format: state.format_override.pixel_format.clone(),
+        source_kind: Some(state.source_kind as u8),
+        network_source_url: Some(state.network_source_url.clone()),
+        file_source_path: Some(state.file_source_path.clone()),
+        file_source_loop: Some(state.file_source_loop),
         reset_usb_on_startup: Some(state.reset_usb_on_startup),
+        usb_reset_method: Some(state.usb_reset_method as u8),
+        applied_card_presets: Some(state.applied_card_presets.clone()),
         has_shown_first_run_warning: Some(!state.show_first_run_dialog),
+        manual_save: Some(state.manual_save),
         crt_filter: Some(state.crt_filter.load(Ordering::Relaxed)),
         pixelate_filter_enabled: Some(state.pixelate_filter_enabled),
+        filter_cycle: Some(state.filter_cycle),
+        throttle_when_minimized: Some(state.throttle_when_minimized),
+        direct_upload_enabled: Some(state.direct_upload_enabled),
+        thread_priority: Some(state.thread_priority as u8),
+        idle_inhibit_policy: Some(state.idle_inhibit_policy as u8),
+        do_not_disturb_enabled: Some(state.do_not_disturb_enabled),
+        restart_after_resume: Some(state.restart_after_resume),
+        window_scale: Some(state.window_scale),
+        present_mode: Some(state.present_mode as u8),
+        gpu_preference: Some(state.gpu_preference as u8),
+        open_sections: Some(state.open_sections.clone()),
+        main_window_size: state.main_window_size,
+        power_saver: Some(state.power_saver.load(Ordering::Relaxed)),
+        auto_downscale: Some(state.auto_downscale.load(Ordering::Relaxed)),
+        gpu_memory_cap_mb: Some(state.gpu_memory_cap_mb),
+        pacing_mode: Some(state.pacing_mode as u8),
+        interpolation_mode: Some(state.interpolation_mode as u8),
+        legacy_filter: LegacyFilterFields::default(),
+        filter: Some(state.filter_settings.clone()),
+        filter_defaults: Some(state.filter_defaults.clone()),
 
-        crt_hard_scan: Some(state.crt_hard_scan),
-        crt_warp_x: Some(state.crt_warp_x),
-        crt_warp_y: Some(state.crt_warp_y),
-        crt_shadow_mask: Some(state.crt_shadow_mask),
-        crt_brightboost: Some(state.crt_brightboost),
-        crt_hard_bloom_pix: Some(state.crt_hard_bloom_pix),
-        crt_hard_bloom_scan: Some(state.crt_hard_bloom_scan),
-        crt_bloom_amount: Some(state.crt_bloom_amount),
-        crt_shape: Some(state.crt_shape),
-        crt_hard_pix: Some(state.crt_hard_pix),
-    };
+        bfi_mode: Some(state.bfi_mode as u8),
+        bfi_cycle: Some(state.bfi_cycle),
+        pixel_aspect: Some(state.pixel_aspect),
+        display_crop_enabled: Some(state.display_crop_enabled),
+        display_crop: Some(state.display_crop),
+        auto_crop_mode: Some(state.auto_crop_mode as u8),
 
-    if let Err(e) = confy::store("michadame", None, cfg) {
-        tracing::error!("Failed to save configuration: {}", e);
+        overlay_enabled: Some(state.overlay_enabled),
+        overlay_opacity: Some(state.overlay_opacity),
+        overlay_path: Some(state.overlay_path.clone()),
+        overlay_paths: None,
+        scenes: (!state.scenes.is_empty()).then(|| state.scenes.clone()),
+        game_detection_enabled: Some(state.game_detection_enabled),
+        games: (!state.games.is_empty()).then(|| state.games.clone()),
+        photon_latency_msec: Some(state.photon_latency_msec.clone()),
+
+        text_overlay_enabled: Some(state.text_overlay_enabled),
+        text_overlay_text: Some(state.text_overlay_text.clone()),
+        text_overlay_file: Some(state.text_overlay_file.clone()),
+        text_overlay_position: Some(state.text_overlay_position as u8),
+        text_overlay_size: Some(state.text_overlay_size),
+        timer_enabled: Some(state.timer_enabled),
+        timer_source: Some(state.timer_source as u8),
+        timer_size: Some(state.timer_size),
+        livesplit_address: Some(state.livesplit_address.clone()),
+        autosplit_enabled: Some(state.autosplit_enabled),
+        autosplit_triggers: (!state.autosplit_triggers.is_empty()).then(|| state.autosplit_triggers.clone()),
+        modulation_enabled: Some(state.modulation_enabled),
+        modulation_osc_enabled: Some(state.modulation_osc_enabled),
+        modulation_osc_port: Some(state.modulation_osc_port),
+        midi_device: state.midi_device.clone(),
+        midi_mappings: (!state.midi_mappings.is_empty()).then(|| state.midi_mappings.clone()),
+        modulations: (!state.modulations.is_empty()).then(|| state.modulations.clone()),
+        clean_feed_enabled: Some(state.clean_feed_enabled),
+        latency_overlay_enabled: Some(state.latency_overlay_enabled),
+
+        wall_enabled: Some(state.wall_enabled),
+        wall_columns: Some(state.wall_layout.columns),
+        wall_rows: Some(state.wall_layout.rows),
+        wall_bezel_percent: Some(state.wall_layout.bezel_percent),
+
+        preview_enabled: Some(state.preview_enabled),
+        preview_port: Some(state.preview_port),
+        preview_quality: Some(state.preview_quality),
+        preview_max_fps: Some(state.preview_max_fps),
+        preview_username: Some(state.preview_username.clone()),
+        preview_password: Some(state.preview_password.clone()),
+        remote_enabled: Some(state.remote_enabled),
+        remote_port: Some(state.remote_port),
+        remote_username: Some(state.remote_username.clone()),
+        remote_password: Some(state.remote_password.clone()),
+        command_port_enabled: Some(state.command_port_enabled),
+        command_port: Some(state.command_port),
+        frame_share_enabled: Some(state.frame_share_enabled),
+        obs_enabled: Some(state.obs_enabled),
+        obs_address: Some(state.obs_settings.address.clone()),
+        obs_password: Some(state.obs_settings.password.clone()),
+        obs_start_scene: Some(state.obs_settings.start_scene.clone()),
+        obs_stop_scene: Some(state.obs_settings.stop_scene.clone()),
+        obs_control_recording: Some(state.obs_settings.control_recording),
+        ffmpeg_log_level: Some(state.ffmpeg_log_level as u8),
+        recording_dir: Some(state.recording_dir.clone()),
+        recording_file_template: Some(state.recording_file_template.clone()),
+        active_look: state.active_look.clone(),
+        record_audio: Some(state.record_audio),
+        record_audio_volume: Some(state.record_audio_volume),
+        record_mic_enabled: Some(state.record_mic_enabled),
+        record_mic_source: state.record_mic_source.clone(),
+        record_mic_volume: Some(state.record_mic_volume),
+        mic_highpass_enabled: Some(state.mic_highpass_enabled),
+        mic_gate_enabled: Some(state.mic_gate_enabled),
+        mic_gate_threshold_db: Some(state.mic_gate_threshold_db),
+        record_crop_enabled: Some(state.record_crop_enabled),
+        record_crop: Some(state.record_crop),
+        record_burn_in: Some(state.record_burn_in),
+        timelapse_mode: Some(state.timelapse_mode as u8),
+        timelapse_interval: Some(state.timelapse_interval),
+        highlight_clips_enabled: Some(state.highlight_clips_enabled),
+        highlight_threshold_lufs: Some(state.highlight_threshold_lufs),
+        highlight_lead_secs: Some(state.highlight_lead_secs),
+        record_encoder: Some(state.record_encoder as u8),
+        record_quality: Some(state.record_quality as u8),
+        vaapi_device: Some(state.vaapi_device.clone()),
     }
 }
 
+const MAX_UNDO_STEPS: usize = 20;
+/// Saves closer together than this are merged into one undo step, so a slider drag
+/// (which saves every frame) is undone in one go.
+const UNDO_MERGE_WINDOW: Duration = Duration::from_millis(500);
+
+/// Earlier configs, so an accidental settings change can be undone with Ctrl+Z.
+#[derive(Default)]
+pub struct UndoHistory {
+    saved: Option<MichadameConfig>,
+    /// What the config file holds. Differs from `saved` while manual saving has unsaved steps.
+    written: Option<MichadameConfig>,
+    steps: VecDeque<MichadameConfig>,
+    last_step_at: Option<Instant>,
+}
+
+impl UndoHistory {
+    /// Sets the baseline without adding an undo step, e.g. after loading the config.
+    pub fn mark_saved(&mut self, cfg: MichadameConfig) {
+        self.written = Some(cfg.clone());
+        self.saved = Some(cfg);
+    }
+
+    fn record(&mut self, cfg: &MichadameConfig) {
+        let Some(previous) = self.saved.replace(cfg.clone()) else {
+            return;
+        };
+        if without_ui_state(&previous) == without_ui_state(cfg) {
+            return;
+        }
+        let merge = self.last_step_at.is_some_and(|t| t.elapsed() < UNDO_MERGE_WINDOW);
+        self.last_step_at = Some(Instant::now());
+        if merge {
+            return;
+        }
+        if self.steps.len() == MAX_UNDO_STEPS {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(previous);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    /// Pops the last step. Section and window state is kept as it is now.
+    pub fn undo(&mut self, current: &MichadameConfig) -> Option<MichadameConfig> {
+        let step = self.steps.pop_back()?;
+        // The save that follows the undo becomes the new baseline instead of another step.
+        self.saved = None;
+        self.last_step_at = None;
+        Some(MichadameConfig {
+            open_sections: current.open_sections.clone(),
+            main_window_size: current.main_window_size,
+            has_shown_first_run_warning: current.has_shown_first_run_warning,
+            ..step
+        })
+    }
+}
+
+/// Drops fields that change without the user touching a setting.
+fn without_ui_state(cfg: &MichadameConfig) -> MichadameConfig {
+    MichadameConfig { open_sections: None, main_window_size: None, has_shown_first_run_warning: None, ..cfg.clone() }
+}
+
+static LAST_SAVED_MTIME: Mutex<Option<SystemTime>> = Mutex::new(None);
+/// Used when inotify can't watch the config directory, e.g. before it's first created.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Lets the writer finish, and `save_config` note its own write, before the change is looked at.
+const CONFIG_SETTLE_TIME: Duration = Duration::from_millis(200);
+
+fn config_mtime() -> Option<SystemTime> {
+    let path = confy::get_configuration_file_path("michadame", None).ok()?;
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// An inotify watch on the config directory, through the plain libc calls rather than the
+/// `notify` crate, which would be a new dependency for this one file. The directory is watched
+/// so editors that save by renaming a new file over the old one are noticed too.
+struct ConfigInotify {
+    fd: libc::c_int,
+    file_name: Vec<u8>,
+}
+
+impl ConfigInotify {
+    fn new(path: &std::path::Path) -> Option<Self> {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = std::ffi::CString::new(path.parent()?.as_os_str().as_bytes()).ok()?;
+        let file_name = path.file_name()?.as_bytes().to_vec();
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE | libc::IN_DELETE;
+        if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+            unsafe { libc::close(fd) };
+            return None;
+        }
+        Some(Self { fd, file_name })
+    }
+
+    /// Blocks until the directory changes and returns whether the config file was involved.
+    fn wait(&self) -> bool {
+        let mut buf = [0u8; 4096];
+        let read = unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), buf.len()) };
+        if read <= 0 {
+            std::thread::sleep(CONFIG_POLL_INTERVAL);
+            return true;
+        }
+        // Each event is a `struct inotify_event` followed by a NUL padded name of `len` bytes.
+        let header = std::mem::size_of::<libc::inotify_event>();
+        let mut events = &buf[..read as usize];
+        let mut involved = false;
+        while events.len() >= header {
+            let len = u32::from_ne_bytes([events[12], events[13], events[14], events[15]]) as usize;
+            let end = (header + len).min(events.len());
+            let name = events[header..end].split(|&b| b == 0).next().unwrap_or_default();
+            involved |= name == self.file_name.as_slice();
+            events = &events[end..];
+        }
+        involved
+    }
+}
+
+impl Drop for ConfigInotify {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Watches the config file and signals when something other than `save_config` changed it.
+/// The thread ends when the receiver is dropped.
+pub fn watch_config_file(ctx: &egui::Context) -> crossbeam_channel::Receiver<()> {
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let inotify = confy::get_configuration_file_path("michadame", None).ok().and_then(|path| ConfigInotify::new(&path));
+        if inotify.is_none() {
+            tracing::debug!("Can't watch the config directory, polling the config file instead");
+        }
+        let mut known = config_mtime();
+        loop {
+            match &inotify {
+                Some(inotify) => {
+                    if !inotify.wait() {
+                        continue;
+                    }
+                    std::thread::sleep(CONFIG_SETTLE_TIME);
+                }
+                None => std::thread::sleep(CONFIG_POLL_INTERVAL),
+            }
+            let current = config_mtime();
+            if current == known {
+                continue;
+            }
+            known = current;
+            if current == *LAST_SAVED_MTIME.lock().unwrap() {
+                continue;
+            }
+            match tx.try_send(()) {
+                Err(crossbeam_channel::TrySendError::Disconnected(_)) => break,
+                _ => ctx.request_repaint(),
+            }
+        }
+    });
+    rx
+}
+
 pub fn apply_config(state: &mut AppState, cfg: &MichadameConfig) {
     if let Some(saved_device) = &cfg.video_device {
         if state.video_devices.contains(saved_device) {
@@ -74,7 +601,7 @@ pub fn apply_config(state: &mut AppState, cfg: &MichadameConfig) {
         }
     }
     if let Some(saved_usb) = &cfg.usb_device {
-        if state.usb_devices.iter().any(|(id, _)| id == saved_usb) {
+        if state.usb_devices.iter().any(|d| &d.id == saved_usb) {
             state.selected_usb_device = Some(saved_usb.clone());
         }
     }
@@ -88,56 +615,427 @@ pub fn apply_config(state: &mut AppState, cfg: &MichadameConfig) {
             state.selected_pulse_sink_name = Some(saved_sink.clone());
         }
     }
+    state.loopback_latency_msec = cfg.loopback_latency_msec;
+    if let Some(val) = cfg.loopback_volume {
+        state.loopback_volume = val.clamp(0.0, 1.5);
+    }
+    if let Some(sink) = &cfg.monitor_output_sink {
+        if state.pulse_sinks.iter().any(|(_, name)| name == sink) {
+            state.monitor_output_sink = Some(sink.clone());
+        }
+    }
+    if let Some(val) = cfg.monitor_output_volume {
+        state.monitor_output_volume = val.clamp(0.0, 1.5);
+    }
+    state.loopback_options = devices::audio::LoopbackOptions {
+        adjust_time: cfg.loopback_adjust_time,
+        max_latency_msec: cfg.loopback_max_latency_msec,
+        channels: cfg.loopback_channels,
+        extra_args: cfg.loopback_extra_args.clone().unwrap_or_default(),
+    };
+    state.limiter_enabled = cfg.limiter_enabled.unwrap_or(false);
+    if let Some(val) = cfg.limiter_limit_db {
+        state.limiter_limit_db = val.clamp(-24.0, 0.0);
+    }
+    state.drift_check_enabled = cfg.drift_check_enabled.unwrap_or(false);
+    if let Some(val) = cfg.drift_threshold_msec {
+        state.drift_threshold_msec = val.clamp(5, 1000);
+    }
+    state.drift_auto_reload = cfg.drift_auto_reload.unwrap_or(false);
+    state.loudness_meter_enabled = cfg.loudness_meter_enabled.unwrap_or(false);
+    if let Some(saved_card) = &cfg.pulse_card {
+        if state.pulse_cards.iter().any(|card| &card.name == saved_card) {
+            state.selected_pulse_card = Some(saved_card.clone());
+        }
+    }
+    if let Some(saved_capture) = &cfg.alsa_capture_device {
+        if state.alsa_captures.iter().any(|(_, dev)| dev == saved_capture) {
+            state.selected_alsa_capture = Some(saved_capture.clone());
+        }
+    }
+    if let Some(saved_playback) = &cfg.alsa_playback_device {
+        if state.alsa_playbacks.iter().any(|(_, dev)| dev == saved_playback) {
+            state.selected_alsa_playback = Some(saved_playback.clone());
+        }
+    }
+    state.alsa_enabled = cfg.alsa_enabled.unwrap_or(!state.pulse_available);
+    if let Some(val) = cfg.audio_routing {
+        state.audio_routing = AudioRouting::from_u8(val);
+    }
+    state.auto_format = cfg.auto_format.unwrap_or(false);
+    state.format_priority = cfg.format_priority.clone().unwrap_or_default();
+    state.format_override = video_types::FormatOverride {
+        input_format: cfg.ffmpeg_input_format.clone().filter(|s| !s.trim().is_empty()),
+        pixel_format: cfg.ffmpeg_pixel_format.clone().filter(|s| !s.trim().is_empty()),
+    };
     if !state.selected_video_device.is_empty() {
         video_types::apply_saved_format_config(state, cfg);
     }
+    if let Some(val) = cfg.source_kind {
+        state.source_kind = SourceKind::from_u8(val);
+    }
+    if let Some(val) = &cfg.network_source_url {
+        state.network_source_url = val.clone();
+    }
+    if let Some(val) = &cfg.file_source_path {
+        state.file_source_path = val.clone();
+    }
+    if let Some(val) = cfg.file_source_loop {
+        state.file_source_loop = val;
+    }
     state.reset_usb_on_startup = cfg.reset_usb_on_startup.unwrap_or(false);
-    if state.reset_usb_on_startup {
-        if let Some(device_to_reset) = &state.selected_usb_device {
-            state.status_message = match devices::usb::reset_usb_device(device_to_reset) {
-                Ok(_) => "Auto-reset USB device successfully.".to_string(),
-                Err(e) => format!("Failed to auto-reset USB: {}", e),
-            };
-            tracing::info!("USB device reset on startup as requested.");
-        }
+    state.applied_card_presets = cfg.applied_card_presets.clone().unwrap_or_default();
+    if let Some(val) = cfg.usb_reset_method {
+        state.usb_reset_method = UsbResetMethod::from_u8(val);
+    }
+    if state.reset_usb_on_startup && !state.devices_scanned {
+        state.reset_usb_device(true);
     }
     if !cfg.has_shown_first_run_warning.unwrap_or(false) {
         state.show_first_run_dialog = true;
     }
+    state.manual_save = cfg.manual_save.unwrap_or(false);
+    apply_live_settings(state, cfg);
+}
+
+/// Applies the settings that can change while streaming (filters, overlay, preview and
+/// recording options). Devices and audio routing are left alone, so this is also used to
+/// pick up edits made to the config file while the app is running.
+pub fn apply_live_settings(state: &mut AppState, cfg: &MichadameConfig) {
     if let Some(filter) = cfg.crt_filter {
         state.crt_filter.store(filter, Ordering::Relaxed);
     }
     if let Some(val) = cfg.pixelate_filter_enabled {
         state.pixelate_filter_enabled = val;
     }
-    if let Some(val) = cfg.crt_hard_scan {
-        state.crt_hard_scan = val;
+    if let Some(settings) = cfg.filter.clone().or_else(|| cfg.legacy_filter.to_settings()) {
+        state.filter_settings = settings.validated();
+    }
+    if let Some(defaults) = cfg.filter_defaults.clone() {
+        state.filter_defaults = defaults.validated();
+    }
+    if let Some(val) = cfg.filter_cycle {
+        state.filter_cycle = val & CrtFilter::FULL_CYCLE;
+    }
+    if let Some(val) = cfg.throttle_when_minimized {
+        state.throttle_when_minimized = val;
+    }
+    if let Some(val) = cfg.direct_upload_enabled {
+        state.direct_upload_enabled = val;
+    }
+    if let Some(val) = cfg.thread_priority {
+        state.thread_priority = video_types::ThreadPriority::from_u8(val);
+    }
+    if let Some(val) = cfg.idle_inhibit_policy {
+        state.idle_inhibit_policy = IdleInhibitPolicy::from_u8(val);
+    }
+    if let Some(val) = cfg.do_not_disturb_enabled {
+        state.do_not_disturb_enabled = val;
+    }
+    if let Some(val) = cfg.restart_after_resume {
+        state.restart_after_resume = val;
+    }
+    if let Some(val) = cfg.window_scale {
+        state.window_scale = val.clamp(1, 4);
+    }
+    if let Some(val) = cfg.present_mode {
+        state.present_mode = PresentMode::from_u8(val);
+    }
+    if let Some(val) = cfg.gpu_preference {
+        state.gpu_preference = GpuPreference::from_u8(val);
+    }
+    if let Some(val) = &cfg.open_sections {
+        state.open_sections = val.clone();
+    }
+    if let Some(val) = cfg.power_saver {
+        state.power_saver.store(val, Ordering::Relaxed);
+    }
+    if let Some(val) = cfg.auto_downscale {
+        state.auto_downscale.store(val, Ordering::Relaxed);
+    }
+    if let Some(val) = cfg.gpu_memory_cap_mb {
+        state.gpu_memory_cap_mb = val.clamp(128, 16384);
+    }
+    if let Some(val) = cfg.pacing_mode {
+        state.pacing_mode = PacingMode::from_u8(val);
+    }
+    if let Some(val) = cfg.interpolation_mode {
+        state.interpolation_mode = InterpolationMode::from_u8(val);
+    }
+    if let Some(val) = cfg.bfi_mode {
+        state.bfi_mode = BfiMode::from_u8(val);
+    }
+    if let Some(val) = cfg.bfi_cycle {
+        state.bfi_cycle = val.clamp(2, 4);
+    }
+    if let Some((num, den)) = cfg.pixel_aspect {
+        if num > 0 && den > 0 {
+            state.pixel_aspect = (num, den);
+        }
+    }
+    if let Some(val) = cfg.display_crop_enabled {
+        state.display_crop_enabled = val;
+    }
+    if let Some(val) = cfg.display_crop {
+        state.display_crop = val;
+    }
+    if let Some(val) = cfg.auto_crop_mode {
+        state.auto_crop_mode = AutoCropMode::from_u8(val);
+    }
+    if let Some(val) = cfg.overlay_enabled {
+        state.overlay_enabled = val;
+    }
+    if let Some(val) = cfg.overlay_opacity {
+        state.overlay_opacity = val.clamp(0.0, 1.0);
+    }
+    if let Some(scenes) = &cfg.scenes {
+        state.scenes = scenes.clone();
+    }
+    if let Some(val) = cfg.game_detection_enabled {
+        state.game_detection_enabled = val;
+    }
+    if let Some(games) = &cfg.games {
+        state.games = games.clone();
+    }
+    if let Some(path) = &cfg.overlay_path {
+        state.overlay_path = path.clone();
+    } else if let Some(path) = cfg.overlay_paths.as_ref().zip(cfg.video_device.as_ref()).and_then(|(paths, device)| paths.get(device)) {
+        state.overlay_path = path.clone();
+    }
+    state.overlay_path_input = state.overlay_path.clone();
+    if let Some(val) = cfg.text_overlay_enabled {
+        state.text_overlay_enabled = val;
+    }
+    if let Some(val) = &cfg.text_overlay_text {
+        state.text_overlay_text = val.clone();
+    }
+    if let Some(val) = &cfg.text_overlay_file {
+        state.text_overlay_file = val.clone();
+    }
+    if let Some(val) = cfg.text_overlay_position {
+        state.text_overlay_position = TextOverlayPosition::from_u8(val);
+    }
+    if let Some(val) = cfg.text_overlay_size {
+        state.text_overlay_size = val.clamp(8.0, 96.0);
+    }
+    if let Some(val) = cfg.timer_enabled {
+        state.timer_enabled = val;
+    }
+    if let Some(val) = cfg.timer_source {
+        state.timer_source = TimerSource::from_u8(val);
+    }
+    if let Some(val) = cfg.timer_size {
+        state.timer_size = val.clamp(8.0, 128.0);
+    }
+    if let Some(val) = &cfg.livesplit_address {
+        state.livesplit_address = val.clone();
+    }
+    if let Some(val) = cfg.autosplit_enabled {
+        state.autosplit_enabled = val;
+    }
+    if let Some(triggers) = &cfg.autosplit_triggers {
+        state.autosplit_triggers = triggers.clone();
+    }
+    if let Some(val) = cfg.modulation_enabled {
+        state.modulation_enabled = val;
+    }
+    if let Some(val) = cfg.modulation_osc_enabled {
+        state.modulation_osc_enabled = val;
     }
-    if let Some(val) = cfg.crt_hard_pix {
-        state.crt_hard_pix = val;
+    if let Some(val) = cfg.modulation_osc_port {
+        state.modulation_osc_port = val;
     }
-    if let Some(val) = cfg.crt_brightboost {
-        state.crt_brightboost = val;
+    state.midi_device = cfg.midi_device.clone();
+    if let Some(mappings) = &cfg.midi_mappings {
+        state.midi_mappings = mappings.clone();
     }
-    if let Some(val) = cfg.crt_warp_x {
-        state.crt_warp_x = val;
+    if let Some(modulations) = &cfg.modulations {
+        state.modulations = modulations.clone();
     }
-    if let Some(val) = cfg.crt_warp_y {
-        state.crt_warp_y = val;
+    if let Some(val) = cfg.clean_feed_enabled {
+        state.clean_feed_enabled = val;
     }
-    if let Some(val) = cfg.crt_shadow_mask {
-        state.crt_shadow_mask = val;
+    if let Some(val) = cfg.latency_overlay_enabled {
+        state.latency_overlay_enabled = val;
     }
-    if let Some(val) = cfg.crt_hard_bloom_pix {
-        state.crt_hard_bloom_pix = val;
+    if let Some(latencies) = &cfg.photon_latency_msec {
+        state.photon_latency_msec = latencies.clone();
     }
-    if let Some(val) = cfg.crt_hard_bloom_scan {
-        state.crt_hard_bloom_scan = val;
+    if let Some(val) = cfg.wall_enabled {
+        state.wall_enabled = val;
     }
-    if let Some(val) = cfg.crt_bloom_amount {
-        state.crt_bloom_amount = val;
+    if let Some(val) = cfg.wall_columns {
+        state.wall_layout.columns = val.clamp(1, 4);
     }
-    if let Some(val) = cfg.crt_shape {
-        state.crt_shape = val;
+    if let Some(val) = cfg.wall_rows {
+        state.wall_layout.rows = val.clamp(1, 4);
     }
-}
\ No newline at end of file
+    if let Some(val) = cfg.wall_bezel_percent {
+        state.wall_layout.bezel_percent = val.clamp(0.0, 20.0);
+    }
+    if let Some(val) = cfg.preview_enabled {
+        state.preview_enabled = val;
+    }
+    if let Some(val) = cfg.preview_port {
+        state.preview_port = val;
+    }
+    if let Some(val) = cfg.preview_quality {
+        state.preview_quality = val.clamp(1, 100);
+    }
+    if let Some(val) = cfg.preview_max_fps {
+        state.preview_max_fps = val.max(1);
+    }
+    if let Some(val) = &cfg.preview_username {
+        state.preview_username = val.clone();
+    }
+    // Configs loaded from disk don't have the passwords; those from the undo history do.
+    let saved_secrets = secrets::load();
+    if let Some(val) = cfg.preview_password.clone().or(saved_secrets.preview_password) {
+        state.preview_password = val;
+    }
+    if let Some(val) = cfg.remote_enabled {
+        state.remote_enabled = val;
+    }
+    if let Some(val) = cfg.remote_port {
+        state.remote_port = val;
+    }
+    if let Some(val) = &cfg.remote_username {
+        state.remote_username = val.clone();
+    }
+    if let Some(val) = cfg.remote_password.clone().or(saved_secrets.remote_password) {
+        state.remote_password = val;
+    }
+    if let Some(val) = cfg.command_port_enabled {
+        state.command_port_enabled = val;
+    }
+    if let Some(val) = cfg.command_port {
+        state.command_port = val;
+    }
+    if let Some(val) = cfg.frame_share_enabled {
+        state.frame_share_enabled = val;
+    }
+    if let Some(val) = cfg.obs_enabled {
+        state.obs_enabled = val;
+    }
+    if let Some(val) = &cfg.obs_address {
+        state.obs_settings.address = val.clone();
+    }
+    if let Some(val) = cfg.obs_password.clone().or(saved_secrets.obs_password) {
+        state.obs_settings.password = val;
+    }
+    if let Some(val) = &cfg.obs_start_scene {
+        state.obs_settings.start_scene = val.clone();
+    }
+    if let Some(val) = &cfg.obs_stop_scene {
+        state.obs_settings.stop_scene = val.clone();
+    }
+    if let Some(val) = cfg.obs_control_recording {
+        state.obs_settings.control_recording = val;
+    }
+    if let Some(val) = cfg.ffmpeg_log_level {
+        state.ffmpeg_log_level = FfmpegLogLevel::from_u8(val);
+    }
+    ffmpeg_log::set_level(state.ffmpeg_log_level);
+    if let Some(val) = &cfg.recording_dir {
+        state.recording_dir = val.clone();
+    }
+    if let Some(val) = &cfg.recording_file_template {
+        state.recording_file_template = val.clone();
+    }
+    if let Some(val) = &cfg.active_look {
+        state.active_look = Some(val.clone());
+    }
+    if let Some(val) = cfg.record_audio {
+        state.record_audio = val;
+    }
+    if let Some(val) = cfg.record_audio_volume {
+        state.record_audio_volume = val.clamp(0.0, 2.0);
+    }
+    if let Some(val) = cfg.record_mic_enabled {
+        state.record_mic_enabled = val;
+    }
+    if let Some(val) = &cfg.record_mic_source {
+        state.record_mic_source = Some(val.clone());
+    }
+    if let Some(val) = cfg.record_mic_volume {
+        state.record_mic_volume = val.clamp(0.0, 2.0);
+    }
+    if let Some(val) = cfg.mic_highpass_enabled {
+        state.mic_highpass_enabled = val;
+    }
+    if let Some(val) = cfg.mic_gate_enabled {
+        state.mic_gate_enabled = val;
+    }
+    if let Some(val) = cfg.mic_gate_threshold_db {
+        state.mic_gate_threshold_db = val.clamp(-70.0, 0.0);
+    }
+    if let Some(val) = cfg.record_crop_enabled {
+        state.record_crop_enabled = val;
+    }
+    if let Some(val) = cfg.record_crop {
+        state.record_crop = val;
+    }
+    if let Some(val) = cfg.record_burn_in {
+        state.record_burn_in = val;
+    }
+    if let Some(val) = cfg.timelapse_mode {
+        state.timelapse_mode = TimelapseMode::from_u8(val);
+    }
+    if let Some(val) = cfg.timelapse_interval {
+        state.timelapse_interval = val.max(1);
+    }
+    if let Some(val) = cfg.highlight_clips_enabled {
+        state.highlight_clips_enabled = val;
+    }
+    if let Some(val) = cfg.highlight_threshold_lufs {
+        state.highlight_threshold_lufs = val.clamp(-40.0, 0.0);
+    }
+    if let Some(val) = cfg.highlight_lead_secs {
+        state.highlight_lead_secs = val.clamp(5, 120);
+    }
+    if let Some(val) = cfg.record_encoder {
+        state.record_encoder = VideoEncoder::from_u8(val);
+    }
+    if let Some(val) = cfg.record_quality {
+        state.record_quality = RecordQuality::from_u8(val);
+    }
+    if let Some(val) = cfg.vaapi_device.as_ref().filter(|val| !val.is_empty()) {
+        state.vaapi_device = val.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_serializes() {
+        let text = toml::to_string(&current_config(&AppState::default())).unwrap();
+        toml::from_str::<MichadameConfig>(&text).unwrap();
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        let mut state = AppState::default();
+        state.scenes.push(Scene::default());
+        state.games.push(GameProfile::default());
+        state.autosplit_triggers.push(SplitTrigger {
+            name: "Stage clear".to_string(),
+            image_path: "clear.png".to_string(),
+            region: (0, 0, 64, 16),
+            threshold: 0.9,
+            action: Default::default(),
+            cooldown_secs: 5.0,
+        });
+        state.modulations.push(Modulation::default());
+        state.midi_mappings.push(MidiMapping::default());
+        state.photon_latency_msec.insert("a".into(), 1);
+        let text = toml::to_string(&current_config(&state)).unwrap();
+        let parsed: MichadameConfig = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.scenes.unwrap(), state.scenes);
+        assert_eq!(parsed.games.unwrap(), state.games);
+        assert_eq!(parsed.autosplit_triggers.unwrap(), state.autosplit_triggers);
+        assert_eq!(parsed.modulations.unwrap(), state.modulations);
+        assert_eq!(parsed.midi_mappings.unwrap(), state.midi_mappings);
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Context, Result};
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+
+const SAMPLE_RATE: u32 = 48000;
+const CHANNELS: u32 = 2;
+// 5 ms of S16_LE stereo audio per ring buffer slot.
+const CHUNK_BYTES: usize = (SAMPLE_RATE as usize / 200) * CHANNELS as usize * 2;
+
+/// Lists ALSA PCM devices as (description, device) pairs using `arecord -l` or `aplay -l`.
+fn list_alsa_devices(tool: &str) -> Result<Vec<(String, String)>> {
+    let output = Command::new(tool)
+        .arg("-l")
+        .output()
+        .with_context(|| format!("Failed to execute '{}'. Is alsa-utils installed?", tool))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("{} failed: {}", tool, stderr));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.lines().filter_map(parse_alsa_device_line).collect())
+}
+
+/// Parses lines like `card 1: MS2109 [MS2109], device 0: USB Audio [USB Audio]`.
+fn parse_alsa_device_line(line: &str) -> Option<(String, String)> {
+    let rest = line.strip_prefix("card ")?;
+    let (card, rest) = rest.split_once(':')?;
+    let (card_desc, rest) = rest.split_once(", device ")?;
+    let (device, device_desc) = rest.split_once(':')?;
+    let card: u32 = card.trim().parse().ok()?;
+    let device: u32 = device.trim().parse().ok()?;
+    let desc = format!("{} - {}", card_desc.trim(), device_desc.trim());
+    // plughw lets ALSA convert to our fixed rate/format if the card doesn't support it natively.
+    Some((desc, format!("plughw:{},{}", card, device)))
+}
+
+pub fn find_alsa_devices() -> Result<(Vec<(String, String)>, Vec<(String, String)>)> {
+    Ok((list_alsa_devices("arecord")?, list_alsa_devices("aplay")?))
+}
+
+/// An in-app audio loopback for systems without PulseAudio: `arecord` feeds a small
+/// ring buffer that is drained into `aplay`.
+pub struct AlsaLoopback {
+    capture: Child,
+    playback: Child,
+    stop_flag: Arc<AtomicBool>,
+    relay_threads: Vec<JoinHandle<()>>,
+}
+
+impl AlsaLoopback {
+    pub fn start(capture_device: &str, playback_device: &str, latency_msec: u32) -> Result<Self> {
+        let format_args = ["-f", "S16_LE", "-r", "48000", "-c", "2", "-t", "raw", "-q"];
+        let buffer_time = format!("--buffer-time={}", latency_msec.max(10) * 1000 / 2);
+
+        let mut capture = Command::new("arecord")
+            .args(["-D", capture_device])
+            .args(format_args)
+            .arg(&buffer_time)
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Failed to execute 'arecord'. Is alsa-utils installed?")?;
+        let mut playback = match Command::new("aplay")
+            .args(["-D", playback_device])
+            .args(format_args)
+            .arg(&buffer_time)
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                let _ = capture.kill();
+                return Err(anyhow!(e).context("Failed to execute 'aplay'. Is alsa-utils installed?"));
+            }
+        };
+
+        let mut capture_out = capture.stdout.take().context("Failed to capture arecord output")?;
+        let mut playback_in = playback.stdin.take().context("Failed to open aplay input")?;
+
+        // Keep at most `latency_msec` of audio queued; anything beyond that is dropped
+        // so a slow playback device can't make the delay grow without bound.
+        let slots = (latency_msec.max(10) / 5) as usize;
+        let (tx, rx) = crossbeam_channel::bounded::<Vec<u8>>(slots);
+        let stop_flag = Arc::new(AtomicBool::new(false));
+
+        let reader_stop = stop_flag.clone();
+        let reader = thread::spawn(move || {
+            let mut chunk = vec![0u8; CHUNK_BYTES];
+            while !reader_stop.load(Ordering::Relaxed) {
+                if capture_out.read_exact(&mut chunk).is_err() {
+                    break;
+                }
+                match tx.try_send(chunk.clone()) {
+                    Ok(()) => {}
+                    Err(crossbeam_channel::TrySendError::Full(_)) => {
+                        tracing::debug!("ALSA ring buffer full, dropping a chunk.");
+                    }
+                    Err(crossbeam_channel::TrySendError::Disconnected(_)) => break,
+                }
+            }
+            tracing::info!("ALSA capture relay finished.");
+        });
+
+        let writer_stop = stop_flag.clone();
+        let writer = thread::spawn(move || {
+            while !writer_stop.load(Ordering::Relaxed) {
+                match rx.recv_timeout(std::time::Duration::from_millis(100)) {
+                    Ok(chunk) => {
+                        if playback_in.write_all(&chunk).is_err() {
+                            break;
+                        }
+                    }
+                    Err(crossbeam_channel::RecvTimeoutError::Timeout) => continue,
+                    Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            tracing::info!("ALSA playback relay finished.");
+        });
+
+        Ok(Self { capture, playback, stop_flag, relay_threads: vec![reader, writer] })
+    }
+
+    pub fn stop(mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        // Killing the processes unblocks the relay threads' reads and writes.
+        let _ = self.capture.kill();
+        let _ = self.playback.kill();
+        let _ = self.capture.wait();
+        let _ = self.playback.wait();
+        for handle in self.relay_threads.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
@@ -3,47 +3,90 @@ use libpulse_binding::callbacks::ListResult;
 use libpulse_binding::context::{Context as PulseContext, FlagSet as PulseContextFlagSet, State as PulseContextState};
 use libpulse_binding::mainloop::standard::{IterateResult, Mainloop};
 use libpulse_binding::operation::State as OperationState;
+use libpulse_binding::volume::Volume;
 use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
 use std::rc::Rc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+/// How long a whole PulseAudio operation, including connecting, may take.
+const PULSE_OP_TIMEOUT: Duration = Duration::from_secs(5);
+const PULSE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Bounds the mainloop waits of one operation by its deadline and cancellation flag.
+struct PulseWait {
+    deadline: Instant,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PulseWait {
+    /// Runs the mainloop until `done` returns true. Polls instead of blocking in `iterate`,
+    /// since a hung server would otherwise never wake us up.
+    fn until(&self, mainloop: &mut Mainloop, what: &str, mut done: impl FnMut() -> bool) -> Result<()> {
+        while !done() {
+            if self.cancelled.load(Ordering::Relaxed) {
+                return Err(anyhow!("Cancelled while {}", what));
+            }
+            if Instant::now() > self.deadline {
+                return Err(anyhow!("Timed out while {}", what));
+            }
+            match mainloop.iterate(false) {
+                IterateResult::Err(e) => return Err(anyhow!("Mainloop iterate error while {}: {}", what, e)),
+                IterateResult::Quit(_) => return Err(anyhow!("Mainloop quit while {}", what)),
+                IterateResult::Success(0) => std::thread::sleep(PULSE_POLL_INTERVAL),
+                IterateResult::Success(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs `op_logic` on its own connection in a worker thread. If the server doesn't answer
+/// within `PULSE_OP_TIMEOUT` the caller gets an error and the worker is cancelled, so a
+/// misbehaving server can't wedge stream start/stop.
 fn run_pulse_op<F, T>(op_logic: F) -> Result<T>
 where
-    F: FnOnce(&mut PulseContext, &mut Mainloop) -> Result<T>,
+    F: FnOnce(&mut PulseContext, &mut Mainloop, &PulseWait) -> Result<T> + Send + 'static,
+    T: Send + 'static,
 {
-    let mut mainloop = Mainloop::new().context("Failed to create mainloop")?;
-    let mut context = PulseContext::new(&mainloop, "pa-client").context("Failed to create context")?;
-
-    context.connect(None, PulseContextFlagSet::empty(), None).context("Failed to connect context")?;
-
-    let start_time = std::time::Instant::now();
-    loop {
-        // Use a timeout to avoid blocking forever
-        match mainloop.iterate(true) {
-            IterateResult::Err(e) => return Err(anyhow!("Mainloop iterate error: {}", e)),
-            IterateResult::Quit(_) => return Err(anyhow!("Mainloop quit unexpectedly")),
-            _ => {}
-        }
-        match context.get_state() {
-            PulseContextState::Ready => break,
-            PulseContextState::Failed | PulseContextState::Terminated => {
+    let wait = PulseWait { deadline: Instant::now() + PULSE_OP_TIMEOUT, cancelled: Arc::new(AtomicBool::new(false)) };
+    let cancelled = wait.cancelled.clone();
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    std::thread::spawn(move || {
+        let result = (|| {
+            let mut mainloop = Mainloop::new().context("Failed to create mainloop")?;
+            let mut context = PulseContext::new(&mainloop, "pa-client").context("Failed to create context")?;
+
+            context.connect(None, PulseContextFlagSet::empty(), None).context("Failed to connect context")?;
+            wait.until(&mut mainloop, "connecting to PulseAudio", || {
+                matches!(context.get_state(), PulseContextState::Ready | PulseContextState::Failed | PulseContextState::Terminated)
+            })?;
+            if context.get_state() != PulseContextState::Ready {
                 return Err(anyhow!("Context state failed or terminated"));
             }
-            _ => {}
-        }
 
-        if start_time.elapsed() > Duration::from_secs(5) {
-            return Err(anyhow!("Timeout waiting for PulseAudio context to be ready"));
+            let result = op_logic(&mut context, &mut mainloop, &wait);
+            context.disconnect();
+            result
+        })();
+        let _ = tx.send(result);
+    });
+    // The worker enforces the deadline itself; the grace period only covers a worker that is
+    // stuck outside the mainloop.
+    match rx.recv_timeout(PULSE_OP_TIMEOUT + Duration::from_secs(1)) {
+        Ok(result) => result,
+        Err(_) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Err(anyhow!("PulseAudio did not respond within {}s", PULSE_OP_TIMEOUT.as_secs()))
         }
     }
-
-    let result = op_logic(&mut context, &mut mainloop);
-    context.disconnect();
-    result
 }
 
 pub fn find_pulse_devices() -> Result<(Vec<(String, String)>, Vec<(String, String)>)> {
-    run_pulse_op(|context, mainloop| {
+    run_pulse_op(move |context, mainloop, wait| {
         let sources = Rc::new(RefCell::new(Vec::new()));
         let sinks = Rc::new(RefCell::new(Vec::new()));
         let lists_completed = Rc::new(RefCell::new(0));
@@ -83,11 +126,7 @@ pub fn find_pulse_devices() -> Result<(Vec<(String, String)>, Vec<(String, Strin
                 }
             });
 
-            while *lists_completed.borrow() < 2 {
-                if matches!(mainloop.iterate(true), IterateResult::Quit(_)) {
-                    return Err(anyhow!("Mainloop quit while getting devices"));
-                }
-            }
+            wait.until(mainloop, "getting devices", || *lists_completed.borrow() >= 2)?;
             drop(op_source);
             drop(op_sink);
         }
@@ -98,23 +137,124 @@ pub fn find_pulse_devices() -> Result<(Vec<(String, String)>, Vec<(String, Strin
     })
 }
 
-pub fn load_pulse_loopback(source: &str, sink: &str) -> Result<u32> {
-    let args = format!(r#"source="{}" sink="{}""#, source, sink);
-    run_pulse_op(|context, mainloop| {
+#[derive(Debug, Clone, PartialEq)]
+pub struct PulseCard {
+    pub name: String,
+    pub description: String,
+    /// (description, name) pairs, like the source/sink lists.
+    pub profiles: Vec<(String, String)>,
+    pub active_profile: Option<String>,
+    /// Descriptions of the card's ports, e.g. "Digital Input (S/PDIF)".
+    pub ports: Vec<String>,
+}
+
+pub fn find_pulse_cards() -> Result<Vec<PulseCard>> {
+    run_pulse_op(move |context, mainloop, wait| {
+        let cards = Rc::new(RefCell::new(Vec::new()));
+        let done = Rc::new(RefCell::new(false));
+        let op = context.introspect().get_card_info_list({
+            let cards = Rc::clone(&cards);
+            let done = Rc::clone(&done);
+            move |res| match res {
+                ListResult::Item(item) => {
+                    let Some(name) = item.name.as_ref().map(|n| n.to_string()) else { return };
+                    let description = item.proplist.get_str("device.description").unwrap_or_else(|| name.clone());
+                    let profiles = item.profiles.iter()
+                        .filter(|p| p.available)
+                        .filter_map(|p| Some((p.description.as_ref()?.to_string(), p.name.as_ref()?.to_string())))
+                        .collect();
+                    let active_profile = item.active_profile.as_ref().and_then(|p| p.name.as_ref()).map(|n| n.to_string());
+                    let ports = item.ports.iter()
+                        .filter_map(|p| p.description.as_ref().map(|d| d.to_string()))
+                        .collect();
+                    tracing::info!(card_name = %name, card_desc = %description, "Found PulseAudio Card");
+                    cards.borrow_mut().push(PulseCard { name, description, profiles, active_profile, ports });
+                }
+                _ => *done.borrow_mut() = true,
+            }
+        });
+        wait.until(mainloop, "getting cards", || *done.borrow())?;
+        drop(op);
+        let result = cards.borrow().clone();
+        Ok(result)
+    })
+}
+
+pub fn set_card_profile(card: &str, profile: &str) -> Result<()> {
+    let (card, profile) = (card.to_string(), profile.to_string());
+    run_pulse_op(move |context, mainloop, wait| {
+        let success = Rc::new(RefCell::new(false));
+        let op = context.introspect().set_card_profile_by_name(&card, &profile, Some(Box::new({
+            let success = Rc::clone(&success);
+            move |ok| *success.borrow_mut() = ok
+        })));
+        wait.until(mainloop, "setting card profile", || op.get_state() != OperationState::Running)?;
+        if *success.borrow() {
+            Ok(())
+        } else {
+            Err(anyhow!("PulseAudio refused to switch '{}' to profile '{}'", card, profile))
+        }
+    })
+}
+
+/// Sources, sinks and cards as they are after a profile switch.
+pub struct PulseDeviceLists {
+    pub sources: Vec<(String, String)>,
+    pub sinks: Vec<(String, String)>,
+    pub cards: Vec<PulseCard>,
+}
+
+/// Switches a card to another profile and re-reads the sources/sinks it now provides.
+/// Talks to PulseAudio several times, so it belongs on a worker thread.
+pub fn switch_card_profile(card: &str, profile: &str) -> Result<PulseDeviceLists> {
+    set_card_profile(card, profile)?;
+    let (sources, sinks) = find_pulse_devices().context("Failed to re-scan PulseAudio devices")?;
+    let cards = find_pulse_cards().context("Failed to re-scan PulseAudio cards")?;
+    Ok(PulseDeviceLists { sources, sinks, cards })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AudioRouting {
+    /// Play the capture source through the selected sink with module-loopback.
+    Loopback = 0,
+    /// Expose the capture source as a virtual "Michadame Capture" input with module-remap-source.
+    MonitorOnly = 1,
+    /// Make the capture source the default input (e.g. for Discord) while streaming.
+    DefaultSource = 2,
+}
+
+impl AudioRouting {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => AudioRouting::MonitorOnly,
+            2 => AudioRouting::DefaultSource,
+            _ => AudioRouting::Loopback,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            AudioRouting::Loopback => "Loopback to sink",
+            AudioRouting::MonitorOnly => "Monitor only (virtual input)",
+            AudioRouting::DefaultSource => "Set as default input",
+        }
+    }
+}
+
+fn load_pulse_module(name: &str, args: &str) -> Result<u32> {
+    let (name, args) = (name.to_string(), args.to_string());
+    run_pulse_op(move |context, mainloop, wait| {
         let index = Rc::new(RefCell::new(None));
         {
-            let op = context.introspect().load_module("module-loopback", &args, {
+            let op = context.introspect().load_module(&name, &args, {
                 let index_clone = Rc::clone(&index);
                 move |idx| {
                     *index_clone.borrow_mut() = Some(idx);
                 }
             });
 
-            while op.get_state() == OperationState::Running {
-                if matches!(mainloop.iterate(true), IterateResult::Quit(_)) {
-                    return Err(anyhow!("Mainloop quit while loading module"));
-                }
-            }
+            wait.until(mainloop, "loading module", || op.get_state() != OperationState::Running)?;
         }
         // Explicitly scope the borrow to ensure the RefMut guard is dropped before the closure ends.
         let result = index.borrow_mut().take();
@@ -122,14 +262,441 @@ pub fn load_pulse_loopback(source: &str, sink: &str) -> Result<u32> {
     })
 }
 
-pub fn unload_pulse_loopback(module_index: u32) -> Result<()> {
-    run_pulse_op(|context, mainloop| {
-        let op = context.introspect().unload_module(module_index, |_| {});
-        while op.get_state() == OperationState::Running {
-            if matches!(mainloop.iterate(true), IterateResult::Quit(_)) {
-                return Err(anyhow!("Mainloop quit while unloading module"));
+/// Extra module-loopback parameters, for users fighting clock drift or resampling artifacts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoopbackOptions {
+    /// Seconds between rate adjustments; 0 disables them.
+    pub adjust_time: Option<u32>,
+    pub max_latency_msec: Option<u32>,
+    pub channels: Option<u8>,
+    /// Appended as-is, e.g. `resample_method=soxr-vhq`.
+    pub extra_args: String,
+}
+
+pub fn load_pulse_loopback(source: &str, sink: &str, latency_msec: Option<u32>, options: &LoopbackOptions) -> Result<u32> {
+    let mut args = format!(r#"source="{}" sink="{}""#, source, sink);
+    if let Some(latency) = latency_msec {
+        args.push_str(&format!(" latency_msec={}", latency));
+    }
+    if let Some(adjust_time) = options.adjust_time {
+        args.push_str(&format!(" adjust_time={}", adjust_time));
+    }
+    if let Some(max_latency) = options.max_latency_msec {
+        args.push_str(&format!(" max_latency_msec={}", max_latency));
+    }
+    if let Some(channels) = options.channels {
+        args.push_str(&format!(" channels={}", channels));
+    }
+    let extra_args = options.extra_args.trim();
+    if !extra_args.is_empty() {
+        args.push(' ');
+        args.push_str(extra_args);
+    }
+    load_pulse_module("module-loopback", &args)
+}
+
+pub const LIMITER_SINK_NAME: &str = "michadame_limiter";
+
+/// Creates a sink that limits peaks to `limit_db` before passing audio on to `master`, using
+/// the fast lookahead limiter from the swh LADSPA plugins.
+pub fn load_pulse_limiter(master: &str, limit_db: f32) -> Result<u32> {
+    let args = format!(
+        r#"sink_name={} sink_master="{}" plugin=fast_lookahead_limiter_1913 label=fastLookaheadLimiter control=0,{:.1},0.1 sink_properties=device.description="Michadame\ Limiter""#,
+        LIMITER_SINK_NAME, master, limit_db
+    );
+    load_pulse_module("module-ladspa-sink", &args).context("Failed to load the limiter. Are the swh LADSPA plugins installed?")
+}
+
+pub const MIC_HIGHPASS_SOURCE_NAME: &str = "michadame_mic_highpass";
+pub const MIC_SINK_NAME: &str = "michadame_mic";
+const MIC_GATE_SINK_NAME: &str = "michadame_mic_gate";
+
+/// Modules that clean up the microphone for the commentary track. The recorder reads the
+/// result from `source`.
+#[derive(Debug)]
+pub struct MicFilterChain {
+    /// In load order, so each only feeds modules loaded before it.
+    modules: Vec<u32>,
+    pub source: String,
+}
+
+impl MicFilterChain {
+    pub fn unload(self) {
+        for index in self.modules.into_iter().rev() {
+            if let Err(e) = unload_pulse_module(index) {
+                tracing::error!("Failed to unload microphone filter module: {}", e);
             }
         }
+    }
+}
+
+/// Filters `mic`: a high-pass against rumble and desk thumps using the WebRTC processing of
+/// module-echo-cancel, which also takes out what the default output plays, and a noise gate
+/// muting everything quieter than `gate_threshold_db`, using the swh LADSPA gate. The gate
+/// needs a sink, so it plays into a null sink whose monitor is the filtered source.
+pub fn load_mic_filters(mic: &str, highpass: bool, gate_threshold_db: Option<f32>) -> Result<MicFilterChain> {
+    let mut chain = MicFilterChain { modules: Vec::new(), source: mic.to_string() };
+    let result = (|| {
+        if let Some(threshold_db) = gate_threshold_db {
+            let args = format!(r#"sink_name={} sink_properties=device.description="Michadame\ Microphone""#, MIC_SINK_NAME);
+            chain.modules.push(load_pulse_module("module-null-sink", &args)?);
+            // Key filter 80 Hz-12 kHz, attack 10 ms, hold 200 ms, decay 250 ms, full range.
+            let args = format!(
+                "sink_name={} sink_master={} plugin=gate_1410 label=gate control=80,12000,{:.1},10,200,250,-90,0",
+                MIC_GATE_SINK_NAME, MIC_SINK_NAME, threshold_db
+            );
+            chain.modules.push(load_pulse_module("module-ladspa-sink", &args).context("Failed to load the noise gate. Are the swh LADSPA plugins installed?")?);
+        }
+        if highpass {
+            let args = format!(
+                r#"source_master="{}" source_name={} sink_name={}_sink aec_method=webrtc aec_args="high_pass_filter=1 noise_suppression=1 analog_gain_control=0 digital_gain_control=0""#,
+                mic, MIC_HIGHPASS_SOURCE_NAME, MIC_HIGHPASS_SOURCE_NAME
+            );
+            chain.modules.push(load_pulse_module("module-echo-cancel", &args).context("Failed to load the high-pass filter. Is PulseAudio built with WebRTC audio processing?")?);
+            chain.source = MIC_HIGHPASS_SOURCE_NAME.to_string();
+        }
+        if gate_threshold_db.is_some() {
+            chain.modules.push(load_pulse_loopback(&chain.source, MIC_GATE_SINK_NAME, Some(30), &LoopbackOptions::default())?);
+            chain.source = format!("{}.monitor", MIC_SINK_NAME);
+        }
+        Ok(())
+    })();
+    match result {
+        Ok(()) => Ok(chain),
+        Err(e) => {
+            chain.unload();
+            Err(e)
+        }
+    }
+}
+
+pub fn load_pulse_remap_source(master: &str) -> Result<u32> {
+    let args = format!(
+        r#"master="{}" source_name=michadame_capture source_properties=device.description="Michadame\ Capture""#,
+        master
+    );
+    load_pulse_module("module-remap-source", &args)
+}
+
+pub fn unload_pulse_module(module_index: u32) -> Result<()> {
+    run_pulse_op(move |context, mainloop, wait| {
+        let op = context.introspect().unload_module(module_index, |_| {});
+        wait.until(mainloop, "unloading module", || op.get_state() != OperationState::Running)?;
         Ok(())
     })
+}
+
+/// What `set_up_routing` loads for a stream.
+#[derive(Debug, Clone)]
+pub struct RoutingRequest {
+    pub routing: AudioRouting,
+    pub source: String,
+    /// Only used by `AudioRouting::Loopback`.
+    pub sink: String,
+    /// The limit in dB, if the limiter is enabled.
+    pub limiter_limit_db: Option<f32>,
+    pub latency_msec: Option<u32>,
+    pub options: LoopbackOptions,
+    pub volume: f32,
+    pub monitor_sink: Option<String>,
+    pub monitor_volume: f32,
+}
+
+/// What `set_up_routing` loaded, to unload when the stream stops.
+#[derive(Debug, Default)]
+pub struct RoutingModules {
+    pub loopback: Option<u32>,
+    pub limiter: Option<u32>,
+    pub monitor_output: Option<u32>,
+    /// The default source from before the capture source was made the default.
+    pub previous_default_source: Option<String>,
+    /// What failed without keeping the stream from starting.
+    pub warnings: Vec<String>,
+}
+
+impl RoutingModules {
+    /// Unloads everything again, e.g. when the stream stopped while the modules were loading.
+    pub fn unload(self) {
+        // The limiter can only go once the loopback feeding it is gone.
+        for index in [self.monitor_output, self.loopback, self.limiter].into_iter().flatten() {
+            if let Err(e) = unload_pulse_module(index) {
+                tracing::error!("Failed to unload module {}: {:#}", index, e);
+            }
+        }
+        if let Some(previous) = &self.previous_default_source {
+            if let Err(e) = set_default_source(previous) {
+                tracing::error!("Failed to restore default source '{}': {:#}", previous, e);
+            }
+        }
+    }
+}
+
+/// Loads the modules for the requested routing. Talks to PulseAudio several times, so it
+/// belongs on a worker thread.
+pub fn set_up_routing(request: &RoutingRequest) -> Result<RoutingModules> {
+    let mut modules = RoutingModules::default();
+    match request.routing {
+        AudioRouting::Loopback => {
+            let mut sink = request.sink.clone();
+            if let Some(limit_db) = request.limiter_limit_db {
+                match load_pulse_limiter(&sink, limit_db) {
+                    Ok(index) => {
+                        modules.limiter = Some(index);
+                        sink = LIMITER_SINK_NAME.to_string();
+                    }
+                    // Better unprotected audio than none at all.
+                    Err(e) => {
+                        tracing::error!("{:#}", e);
+                        modules.warnings.push("The limiter failed to load (are the swh LADSPA plugins installed?).".to_string());
+                    }
+                }
+            }
+            let index = match load_pulse_loopback(&request.source, &sink, request.latency_msec, &request.options) {
+                Ok(index) => index,
+                Err(e) => {
+                    modules.unload();
+                    return Err(e.context("Failed to load loopback"));
+                }
+            };
+            modules.loopback = Some(index);
+            if request.volume != 1.0 {
+                if let Err(e) = set_loopback_volume(index, request.volume) {
+                    tracing::error!("Failed to set loopback volume: {:#}", e);
+                }
+            }
+        }
+        AudioRouting::MonitorOnly => {
+            modules.loopback = Some(load_pulse_remap_source(&request.source).context("Failed to create virtual input")?);
+        }
+        AudioRouting::DefaultSource => {
+            let previous = get_default_source().context("Failed to read default input")?;
+            set_default_source(&request.source).context("Failed to set default input")?;
+            modules.previous_default_source = previous;
+        }
+    }
+    if let Some(sink) = &request.monitor_sink {
+        match load_monitor_output(&request.source, sink, request.latency_msec, &request.options, request.monitor_volume) {
+            Ok(index) => modules.monitor_output = Some(index),
+            Err(e) => modules.warnings.push(format!("Failed to start the monitor output: {:#}", e)),
+        }
+    }
+    Ok(modules)
+}
+
+/// Plays `source` through `sink` with a loopback of its own, for a second output next to the
+/// main one.
+pub fn load_monitor_output(source: &str, sink: &str, latency_msec: Option<u32>, options: &LoopbackOptions, volume: f32) -> Result<u32> {
+    let index = load_pulse_loopback(source, sink, latency_msec, options)?;
+    if volume != 1.0 {
+        if let Err(e) = set_loopback_volume(index, volume) {
+            tracing::error!("Failed to set monitor output volume: {:#}", e);
+        }
+    }
+    Ok(index)
+}
+
+/// Current end-to-end delay of a module-loopback: what its source output and sink input have
+/// buffered, plus the latencies of the devices on both ends.
+pub fn query_loopback_latency(module_index: u32) -> Result<Duration> {
+    run_pulse_op(move |context, mainloop, wait| {
+        // (microseconds, streams found)
+        let total = Rc::new(RefCell::new((0u64, 0u32)));
+        let lists_done = Rc::new(RefCell::new(0));
+        let introspect = context.introspect();
+        let sink_inputs = introspect.get_sink_input_info_list({
+            let total = Rc::clone(&total);
+            let lists_done = Rc::clone(&lists_done);
+            move |res| match res {
+                ListResult::Item(item) if item.owner_module == Some(module_index) => {
+                    let mut total = total.borrow_mut();
+                    total.0 += item.buffer_usec.0 + item.sink_usec.0;
+                    total.1 += 1;
+                }
+                ListResult::Item(_) => {}
+                _ => *lists_done.borrow_mut() += 1,
+            }
+        });
+        let source_outputs = introspect.get_source_output_info_list({
+            let total = Rc::clone(&total);
+            let lists_done = Rc::clone(&lists_done);
+            move |res| match res {
+                ListResult::Item(item) if item.owner_module == Some(module_index) => {
+                    let mut total = total.borrow_mut();
+                    total.0 += item.buffer_usec.0 + item.source_usec.0;
+                    total.1 += 1;
+                }
+                ListResult::Item(_) => {}
+                _ => *lists_done.borrow_mut() += 1,
+            }
+        });
+        wait.until(mainloop, "querying loopback latency", || *lists_done.borrow() == 2)?;
+        drop((sink_inputs, source_outputs));
+        let (usec, streams) = *total.borrow();
+        if streams < 2 {
+            return Err(anyhow!("Loopback module {} has no running streams", module_index));
+        }
+        Ok(Duration::from_micros(usec))
+    })
+}
+
+/// Sets the playback volume of a module-loopback, 1.0 being 100%.
+pub fn set_loopback_volume(module_index: u32, volume: f32) -> Result<()> {
+    run_pulse_op(move |context, mainloop, wait| {
+        let sink_input = Rc::new(RefCell::new(None));
+        let list_done = Rc::new(RefCell::new(false));
+        let introspect = context.introspect();
+        let op = introspect.get_sink_input_info_list({
+            let sink_input = Rc::clone(&sink_input);
+            let list_done = Rc::clone(&list_done);
+            move |res| match res {
+                ListResult::Item(item) if item.owner_module == Some(module_index) => {
+                    *sink_input.borrow_mut() = Some((item.index, item.volume));
+                }
+                ListResult::Item(_) => {}
+                _ => *list_done.borrow_mut() = true,
+            }
+        });
+        wait.until(mainloop, "looking up the loopback stream", || *list_done.borrow())?;
+        drop(op);
+        let (index, mut volumes) = sink_input.borrow_mut().take()
+            .ok_or_else(|| anyhow!("Loopback module {} has no playback stream", module_index))?;
+        let channels = volumes.len();
+        volumes.set(channels, Volume((Volume::NORMAL.0 as f32 * volume.max(0.0)) as u32));
+        let success = Rc::new(RefCell::new(None));
+        let op = context.introspect().set_sink_input_volume(index, &volumes, Some(Box::new({
+            let success = Rc::clone(&success);
+            move |ok| *success.borrow_mut() = Some(ok)
+        })));
+        wait.until(mainloop, "setting loopback volume", || op.get_state() != OperationState::Running)?;
+        let ok = success.borrow().unwrap_or(false);
+        if ok {
+            Ok(())
+        } else {
+            Err(anyhow!("PulseAudio refused to change the loopback volume"))
+        }
+    })
+}
+
+pub fn get_default_source() -> Result<Option<String>> {
+    run_pulse_op(move |context, mainloop, wait| {
+        let default_source = Rc::new(RefCell::new(None));
+        let op = context.introspect().get_server_info({
+            let default_source = Rc::clone(&default_source);
+            move |info| {
+                *default_source.borrow_mut() = info.default_source_name.as_ref().map(|name| name.to_string());
+            }
+        });
+        wait.until(mainloop, "getting server info", || op.get_state() != OperationState::Running)?;
+        let result = default_source.borrow_mut().take();
+        Ok(result)
+    })
+}
+
+pub fn set_default_source(name: &str) -> Result<()> {
+    let name = name.to_string();
+    run_pulse_op(move |context, mainloop, wait| {
+        let success = Rc::new(RefCell::new(false));
+        let op = context.set_default_source(&name, {
+            let success = Rc::clone(&success);
+            move |ok| *success.borrow_mut() = ok
+        });
+        wait.until(mainloop, "setting default source", || op.get_state() != OperationState::Running)?;
+        if *success.borrow() {
+            Ok(())
+        } else {
+            Err(anyhow!("PulseAudio refused to set the default source to '{}'", name))
+        }
+    })
+}
+
+const LATENCY_SAMPLE_RATE: usize = 48000;
+
+/// Plays a short click into `sink` while recording `source` and returns the delay in
+/// milliseconds until the click is heard. Blocks for a couple of seconds.
+pub fn measure_loopback_latency(source: &str, sink: &str) -> Result<u32> {
+    let mut recorder = Command::new("parec")
+        .arg(format!("--device={}", source))
+        .args(["--format=s16le", "--rate=48000", "--channels=1", "--raw", "--latency-msec=5"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to execute 'parec'. Is pulseaudio-utils installed?")?;
+
+    let recorded = Arc::new(Mutex::new(Vec::<i16>::new()));
+    let mut stdout = recorder.stdout.take().context("Failed to capture parec output")?;
+    let reader = std::thread::spawn({
+        let recorded = Arc::clone(&recorded);
+        move || {
+            let mut buf = [0u8; 1024];
+            while let Ok(n) = stdout.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                let mut samples = recorded.lock().unwrap();
+                samples.extend(buf[..n].chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])));
+            }
+        }
+    });
+
+    let player = Command::new("pacat")
+        .arg(format!("--device={}", sink))
+        .args(["--format=s16le", "--rate=48000", "--channels=1", "--raw", "--latency-msec=5"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to execute 'pacat'. Is pulseaudio-utils installed?");
+    // Without a player there's nothing to measure, so don't leave the recorder running.
+    let mut player = match player {
+        Ok(player) => player,
+        Err(e) => {
+            let _ = recorder.kill();
+            let _ = recorder.wait();
+            let _ = reader.join();
+            return Err(e);
+        }
+    };
+    let played = play_click(&mut player, &recorded);
+    if played.is_err() {
+        let _ = player.kill();
+    }
+    let _ = player.wait();
+
+    if played.is_ok() {
+        std::thread::sleep(Duration::from_millis(1000));
+    }
+    let _ = recorder.kill();
+    let _ = recorder.wait();
+    let _ = reader.join();
+    let click_start = played?;
+
+    let samples = recorded.lock().unwrap();
+    let noise_floor = samples[..click_start.min(samples.len())].iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+    let threshold = (noise_floor as u32 * 4).clamp(2000, i16::MAX as u32 / 4) as u16;
+    let heard_at = samples.iter().skip(click_start).position(|s| s.unsigned_abs() > threshold)
+        .ok_or_else(|| anyhow!("No click detected on the source. Make sure the source can hear the sink."))?;
+
+    let latency_msec = (heard_at * 1000 / LATENCY_SAMPLE_RATE) as u32;
+    tracing::info!(latency_msec, noise_floor, "Measured loopback latency");
+    Ok(latency_msec)
+}
+
+/// Plays the warm-up silence and the click through pacat, returning how many samples the recorder
+/// had when the click started.
+fn play_click(player: &mut Child, recorded: &Mutex<Vec<i16>>) -> Result<usize> {
+    let mut stdin = player.stdin.take().context("Failed to open pacat input")?;
+
+    // Feed pacat silence until both it and the recorder are running, so the click is timed from
+    // when its first sample is played instead of from pacat's startup. The silence is written a
+    // little slower than real time, so the pipe never buffers ahead of what is being played.
+    let silence = vec![0u8; LATENCY_SAMPLE_RATE / 100 * 2];
+    let warm_up = Instant::now();
+    while warm_up.elapsed() < Duration::from_millis(500) {
+        stdin.write_all(&silence).context("Failed to write silence to pacat")?;
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut click: Vec<i16> = (0..LATENCY_SAMPLE_RATE * 5 / 1000).map(|i| if (i / 24) % 2 == 0 { i16::MAX / 2 } else { -i16::MAX / 2 }).collect();
+    click.extend(std::iter::repeat(0).take(LATENCY_SAMPLE_RATE / 10));
+    let click_start = recorded.lock().unwrap().len();
+    let bytes: Vec<u8> = click.iter().flat_map(|s| s.to_le_bytes()).collect();
+    stdin.write_all(&bytes).context("Failed to write click to pacat")?;
+    Ok(click_start)
 }
\ No newline at end of file
@@ -0,0 +1,40 @@
+/// Known problems of specific capture cards, each with a workaround applied as a preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardQuirk {
+    /// Hangs after a replug or suspend until it's reset; preselected for "Reset USB Device".
+    NeedsReset,
+    /// Uncompressed modes are too slow or broken; MJPEG is selected.
+    MjpegOnly,
+    /// Runs at 59.94 fps while reporting 60, so frames are paced to the measured source rate.
+    Broken5994,
+}
+
+impl CardQuirk {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            CardQuirk::NeedsReset => "needs a USB reset after replugging",
+            CardQuirk::MjpegOnly => "use MJPEG",
+            CardQuirk::Broken5994 => "delivers 59.94 fps at 60",
+        }
+    }
+}
+
+pub struct KnownCard {
+    /// USB `vendor:product`, lowercase as in sysfs.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub quirks: &'static [CardQuirk],
+}
+
+const KNOWN_CARDS: &[KnownCard] = &[
+    KnownCard { id: "534d:2109", name: "MacroSilicon MS2109", quirks: &[CardQuirk::MjpegOnly, CardQuirk::NeedsReset, CardQuirk::Broken5994] },
+    KnownCard { id: "345f:2130", name: "MacroSilicon MS2130", quirks: &[CardQuirk::Broken5994] },
+    KnownCard { id: "0fd9:0066", name: "Elgato Cam Link 4K", quirks: &[CardQuirk::NeedsReset] },
+    KnownCard { id: "0fd9:006a", name: "Elgato HD60 S+", quirks: &[CardQuirk::NeedsReset] },
+    KnownCard { id: "07ca:0311", name: "AVerMedia Live Gamer Mini (GC311)", quirks: &[CardQuirk::MjpegOnly] },
+    KnownCard { id: "07ca:0553", name: "AVerMedia Live Gamer Ultra (GC553)", quirks: &[CardQuirk::Broken5994] },
+];
+
+pub fn find_known_card(usb_id: &str) -> Option<&'static KnownCard> {
+    KNOWN_CARDS.iter().find(|card| card.id.eq_ignore_ascii_case(usb_id))
+}
@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::process::Command;
+use std::thread::JoinHandle;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::Value;
+
+const NOTIFICATIONS_DEST: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const NOTIFICATIONS_IFACE: &str = "org.freedesktop.Notifications";
+const GNOME_NOTIFICATIONS_SCHEMA: &str = "org.gnome.desktop.notifications";
+
+/// How do-not-disturb was turned on, so it can be undone.
+enum Restore {
+    /// KDE Plasma's inhibition cookie. The server also releases it with the connection.
+    Plasma(u32),
+    /// GNOME's banners were on and got switched off.
+    Gnome,
+    /// Already on, or no supported desktop: nothing to undo.
+    Nothing,
+}
+
+/// Turns on the desktop's do-not-disturb mode so notification popups don't end up in the
+/// capture, and restores the previous state afterwards. Uses the notification inhibition of
+/// KDE Plasma over D-Bus, or GNOME's banner setting. Runs on a worker thread like
+/// `IdleInhibitor`.
+pub struct DoNotDisturb {
+    tx: Option<crossbeam_channel::Sender<bool>>,
+    thread: Option<JoinHandle<()>>,
+    enabled: bool,
+}
+
+impl DoNotDisturb {
+    pub fn start() -> Self {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let thread = std::thread::spawn(move || dnd_thread_main(&rx));
+        Self { tx: Some(tx), thread: Some(thread), enabled: false }
+    }
+
+    /// Turns do-not-disturb on or restores it, doing nothing if that is already the current state.
+    pub fn set(&mut self, enable: bool) {
+        if enable != self.enabled {
+            self.enabled = enable;
+            if let Some(tx) = &self.tx {
+                let _ = tx.send(enable);
+            }
+        }
+    }
+}
+
+impl Drop for DoNotDisturb {
+    /// Waits for the restore, so GNOME's setting isn't left off when the app quits.
+    fn drop(&mut self) {
+        self.tx = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn dnd_thread_main(rx: &crossbeam_channel::Receiver<bool>) {
+    let connection = Connection::session().map_err(|e| tracing::warn!("Failed to connect to the D-Bus session bus: {}", e)).ok();
+    let mut restore: Option<Restore> = None;
+    for enable in rx {
+        match (enable, restore.take()) {
+            (true, None) => restore = Some(enable_dnd(connection.as_ref())),
+            (false, Some(previous)) => restore_dnd(connection.as_ref(), previous),
+            (_, previous) => restore = previous,
+        }
+    }
+    if let Some(previous) = restore {
+        restore_dnd(connection.as_ref(), previous);
+    }
+}
+
+fn notifications_proxy(connection: &Connection) -> Result<Proxy<'_>> {
+    Proxy::new(connection, NOTIFICATIONS_DEST, NOTIFICATIONS_PATH, NOTIFICATIONS_IFACE).context("Failed to create notifications proxy")
+}
+
+fn enable_dnd(connection: Option<&Connection>) -> Restore {
+    // Only Plasma's notification server has Inhibit; GNOME Shell answers with an error.
+    if let Some(proxy) = connection.and_then(|connection| notifications_proxy(connection).ok()) {
+        let hints: HashMap<&str, Value> = HashMap::new();
+        match proxy.call::<_, _, u32>("Inhibit", &("michadame", "Streaming", hints)) {
+            Ok(cookie) => {
+                tracing::info!("Turned on do-not-disturb (Plasma)");
+                return Restore::Plasma(cookie);
+            }
+            Err(e) => tracing::debug!("No Plasma notification inhibition: {}", e),
+        }
+    }
+    match gnome_show_banners() {
+        Ok(true) => match set_gnome_show_banners(false) {
+            Ok(()) => {
+                tracing::info!("Turned on do-not-disturb (GNOME)");
+                return Restore::Gnome;
+            }
+            Err(e) => tracing::warn!("Failed to turn on do-not-disturb: {:#}", e),
+        },
+        Ok(false) => tracing::info!("Do-not-disturb was already on"),
+        Err(e) => tracing::warn!("Do-not-disturb unavailable, no Plasma or GNOME session: {:#}", e),
+    }
+    Restore::Nothing
+}
+
+fn restore_dnd(connection: Option<&Connection>, previous: Restore) {
+    match previous {
+        Restore::Plasma(cookie) => {
+            let result = connection.context("No D-Bus connection").and_then(|connection| {
+                notifications_proxy(connection)?.call::<_, _, ()>("UnInhibit", &(cookie,)).context("UnInhibit failed")
+            });
+            if let Err(e) = result {
+                tracing::warn!("Failed to turn off do-not-disturb: {:#}", e);
+            }
+        }
+        Restore::Gnome => {
+            if let Err(e) = set_gnome_show_banners(true) {
+                tracing::warn!("Failed to turn off do-not-disturb: {:#}", e);
+            }
+        }
+        Restore::Nothing => return,
+    }
+    tracing::info!("Turned off do-not-disturb");
+}
+
+/// GNOME keeps do-not-disturb in GSettings rather than behind a D-Bus method.
+fn gnome_show_banners() -> Result<bool> {
+    let output = Command::new("gsettings").args(["get", GNOME_NOTIFICATIONS_SCHEMA, "show-banners"]).output().context("Failed to run gsettings")?;
+    if !output.status.success() {
+        anyhow::bail!("gsettings: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "true")
+}
+
+fn set_gnome_show_banners(show: bool) -> Result<()> {
+    let status = Command::new("gsettings")
+        .args(["set", GNOME_NOTIFICATIONS_SCHEMA, "show-banners", if show { "true" } else { "false" }])
+        .status()
+        .context("Failed to run gsettings")?;
+    if !status.success() {
+        anyhow::bail!("gsettings exited with {}", status);
+    }
+    Ok(())
+}
@@ -4,13 +4,21 @@ pub enum CrtFilter {
     Off = 0,
     Scanlines = 1,
     Lottes = 2,
+    Xbr = 3,
+    XbrHard = 4,
 }
 
 impl CrtFilter {
+    pub const ALL: [CrtFilter; 5] = [CrtFilter::Off, CrtFilter::Scanlines, CrtFilter::Lottes, CrtFilter::Xbr, CrtFilter::XbrHard];
+    /// Every filter in the hotkey cycle.
+    pub const FULL_CYCLE: u8 = 0b1_1111;
+
     pub fn from_u8(value: u8) -> Self {
         match value {
             1 => CrtFilter::Scanlines,
             2 => CrtFilter::Lottes,
+            3 => CrtFilter::Xbr,
+            4 => CrtFilter::XbrHard,
             _ => CrtFilter::Off,
         }
     }
@@ -19,8 +27,33 @@ impl CrtFilter {
         match self {
             CrtFilter::Off => CrtFilter::Scanlines,
             CrtFilter::Scanlines => CrtFilter::Lottes,
-            CrtFilter::Lottes => CrtFilter::Off,
+            CrtFilter::Lottes => CrtFilter::Xbr,
+            CrtFilter::Xbr => CrtFilter::XbrHard,
+            CrtFilter::XbrHard => CrtFilter::Off,
+        }
+    }
+
+    /// Bit of the filter in a hotkey cycle mask.
+    pub fn cycle_bit(&self) -> u8 {
+        1 << (*self as u8)
+    }
+
+    /// The next filter after this one that's in the hotkey cycle `mask`.
+    /// Returns `None` when the mask has no other filter in it.
+    pub fn next_in_cycle(&self, mask: u8) -> Option<Self> {
+        let mut filter = self.next();
+        while filter != *self {
+            if mask & filter.cycle_bit() != 0 {
+                return Some(filter);
+            }
+            filter = filter.next();
         }
+        None
+    }
+
+    /// Whether the filter is one of the GPU passes that Power Saver skips.
+    pub fn is_gpu_pass(&self) -> bool {
+        matches!(self, CrtFilter::Lottes | CrtFilter::Xbr | CrtFilter::XbrHard)
     }
 
     pub fn to_string(&self) -> &'static str {
@@ -28,6 +61,33 @@ impl CrtFilter {
             CrtFilter::Off => "Off",
             CrtFilter::Scanlines => "Scanlines",
             CrtFilter::Lottes => "Lottes (Advanced)",
+            CrtFilter::Xbr => "xBR Upscaler (Pixel Art)",
+            CrtFilter::XbrHard => "xBR Upscaler, Hard Edges (Pixel Art)",
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BfiMode {
+    Off = 0,
+    BlackFrame = 1,
+    RollingScan = 2,
+}
+
+impl BfiMode {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => BfiMode::BlackFrame,
+            2 => BfiMode::RollingScan,
+            _ => BfiMode::Off,
         }
     }
-}
\ No newline at end of file
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            BfiMode::Off => "Off",
+            BfiMode::BlackFrame => "Black Frame Insertion",
+            BfiMode::RollingScan => "Rolling Scan",
+        }
+    }
+}
@@ -5,6 +5,7 @@ pub fn apply_filter(filter: CrtFilter, frame_data: &mut [u8], width: u32, height
         CrtFilter::Off => {}
         CrtFilter::Scanlines => apply_scanlines_cpu(frame_data, width, height),
         CrtFilter::Lottes => {} // Lottes is now a GPU-only filter
+        CrtFilter::Xbr | CrtFilter::XbrHard => {} // GPU-only as well
     }
 }
 
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use zbus::blocking::{Connection, Proxy};
+
+const SCREENSAVER_DEST: &str = "org.freedesktop.ScreenSaver";
+const SCREENSAVER_PATH: &str = "/org/freedesktop/ScreenSaver";
+const SCREENSAVER_IFACE: &str = "org.freedesktop.ScreenSaver";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum IdleInhibitPolicy {
+    /// Keep the screen on whenever a stream is running.
+    WhileStreaming = 0,
+    /// Only while the video window has focus, so the screen can still blank while e.g.
+    /// recording in the background.
+    WhenFocused = 1,
+    Never = 2,
+}
+
+impl IdleInhibitPolicy {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => IdleInhibitPolicy::WhenFocused,
+            2 => IdleInhibitPolicy::Never,
+            _ => IdleInhibitPolicy::WhileStreaming,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            IdleInhibitPolicy::WhileStreaming => "While streaming",
+            IdleInhibitPolicy::WhenFocused => "While the video window is focused",
+            IdleInhibitPolicy::Never => "Never",
+        }
+    }
+}
+
+/// Keeps the desktop from blanking the screen or locking through the session's
+/// `org.freedesktop.ScreenSaver` service. The D-Bus calls run on a worker thread so a slow
+/// session bus can't stall the UI.
+pub struct IdleInhibitor {
+    tx: crossbeam_channel::Sender<bool>,
+    inhibited: bool,
+}
+
+impl IdleInhibitor {
+    pub fn start() -> Self {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        // Ends when the inhibitor is dropped; the server releases the inhibition with the connection.
+        std::thread::spawn(move || {
+            if let Err(e) = inhibitor_thread_main(&rx) {
+                tracing::warn!("Idle inhibition unavailable: {:#}", e);
+            }
+        });
+        Self { tx, inhibited: false }
+    }
+
+    /// Inhibits or releases, doing nothing if that is already the current state.
+    pub fn set(&mut self, inhibit: bool) {
+        if inhibit != self.inhibited {
+            self.inhibited = inhibit;
+            let _ = self.tx.send(inhibit);
+        }
+    }
+}
+
+fn inhibitor_thread_main(rx: &crossbeam_channel::Receiver<bool>) -> Result<()> {
+    let connection = Connection::session().context("Failed to connect to the D-Bus session bus")?;
+    let screensaver = Proxy::new(&connection, SCREENSAVER_DEST, SCREENSAVER_PATH, SCREENSAVER_IFACE)
+        .context("Failed to create screensaver proxy")?;
+    let mut cookie: Option<u32> = None;
+    for inhibit in rx {
+        match (inhibit, cookie) {
+            (true, None) => match screensaver.call("Inhibit", &("Michadame", "Watching a capture stream")) {
+                Ok(value) => {
+                    tracing::info!("Inhibited screen blanking");
+                    cookie = Some(value);
+                }
+                Err(e) => tracing::warn!("Failed to inhibit screen blanking: {}", e),
+            },
+            (false, Some(value)) => {
+                if let Err(e) = screensaver.call::<_, _, ()>("UnInhibit", &(value,)) {
+                    tracing::warn!("Failed to release the screen blanking inhibition: {}", e);
+                }
+                tracing::info!("Released screen blanking inhibition");
+                cookie = None;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+
+const SAMPLE_RATE: usize = 48000;
+/// Loudness is computed over 100 ms blocks, as in EBU R128.
+const BLOCK_SAMPLES: usize = SAMPLE_RATE / 10;
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+/// Reported while there is no signal, instead of -inf.
+pub const SILENCE_LUFS: f32 = -70.0;
+
+/// A biquad in transposed direct form II.
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The ITU-R BS.1770 K-weighting filter (high shelf + high pass) at 48 kHz.
+fn k_weighting() -> [Biquad; 2] {
+    [
+        Biquad { b: [1.53512485958697, -2.69169618940638, 1.19839281085285], a: [-1.69065929318241, 0.73248077421585], z: [0.0; 2] },
+        Biquad { b: [1.0, -2.0, 1.0], a: [-1.99004745483398, 0.99007225036621], z: [0.0; 2] },
+    ]
+}
+
+fn lufs(mean_square: f64) -> f32 {
+    if mean_square <= 0.0 {
+        return SILENCE_LUFS;
+    }
+    ((-0.691 + 10.0 * mean_square.log10()) as f32).max(SILENCE_LUFS)
+}
+
+/// Measures the loudness of a PulseAudio source by reading it with `parec`.
+pub struct LoudnessMeter {
+    parec: Child,
+    momentary: Arc<AtomicU32>,
+    short_term: Arc<AtomicU32>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl LoudnessMeter {
+    pub fn start(source: &str) -> Result<Self> {
+        let mut parec = Command::new("parec")
+            .arg(format!("--device={}", source))
+            .args(["--format=float32le", "--rate=48000", "--channels=2", "--raw", "--latency-msec=50"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Failed to execute 'parec'. Is pulseaudio-utils installed?")?;
+        let mut stdout = parec.stdout.take().context("Failed to capture parec output")?;
+
+        let momentary = Arc::new(AtomicU32::new(SILENCE_LUFS.to_bits()));
+        let short_term = Arc::new(AtomicU32::new(SILENCE_LUFS.to_bits()));
+        let reader = thread::spawn({
+            let momentary = momentary.clone();
+            let short_term = short_term.clone();
+            move || {
+                let mut filters = [k_weighting(), k_weighting()];
+                let mut blocks: VecDeque<f64> = VecDeque::with_capacity(SHORT_TERM_BLOCKS);
+                let mut block_sum = 0.0;
+                let mut block_len = 0;
+                let mut buf = [0u8; 4096];
+                // parec only exits when killed, which closes the pipe and ends this loop.
+                while let Ok(n) = stdout.read(&mut buf) {
+                    if n == 0 {
+                        break;
+                    }
+                    for frame in buf[..n].chunks_exact(8) {
+                        for (channel, bytes) in frame.chunks_exact(4).enumerate() {
+                            let sample = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64;
+                            let weighted = filters[channel].iter_mut().fold(sample, |x, f| f.process(x));
+                            block_sum += weighted * weighted;
+                        }
+                        block_len += 1;
+                        if block_len == BLOCK_SAMPLES {
+                            if blocks.len() == SHORT_TERM_BLOCKS {
+                                blocks.pop_front();
+                            }
+                            blocks.push_back(block_sum / BLOCK_SAMPLES as f64);
+                            block_sum = 0.0;
+                            block_len = 0;
+                            let mean = |count: usize| blocks.iter().rev().take(count).sum::<f64>() / count.min(blocks.len()) as f64;
+                            momentary.store(lufs(mean(MOMENTARY_BLOCKS)).to_bits(), Ordering::Relaxed);
+                            short_term.store(lufs(mean(SHORT_TERM_BLOCKS)).to_bits(), Ordering::Relaxed);
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Self { parec, momentary, short_term, reader: Some(reader) })
+    }
+
+    /// Loudness over the last 400 ms, in LUFS.
+    pub fn momentary(&self) -> f32 {
+        f32::from_bits(self.momentary.load(Ordering::Relaxed))
+    }
+
+    /// Loudness over the last 3 s, in LUFS.
+    pub fn short_term(&self) -> f32 {
+        f32::from_bits(self.short_term.load(Ordering::Relaxed))
+    }
+}
+
+impl Drop for LoudnessMeter {
+    fn drop(&mut self) {
+        let _ = self.parec.kill();
+        let _ = self.parec.wait();
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use crate::video::modulation::ModParam;
+use anyhow::{Context, Result};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// ALSA raw MIDI ports, as paths with the name of their sound card.
+pub fn midi_devices() -> Vec<(String, String)> {
+    let Ok(paths) = glob::glob("/dev/snd/midiC*D*") else { return Vec::new() };
+    paths
+        .flatten()
+        .map(|path| {
+            let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            let card = file_name.trim_start_matches("midiC").split('D').next().unwrap_or_default().to_string();
+            let label = std::fs::read_to_string(format!("/proc/asound/card{}/id", card))
+                .map(|id| format!("{} ({})", id.trim(), file_name))
+                .unwrap_or_else(|_| file_name.clone());
+            (path.to_string_lossy().into_owned(), label)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum MidiControlKind {
+    /// A knob, fader or button sending control changes.
+    #[default]
+    ControlChange = 0,
+    /// A key or pad sending notes.
+    Note = 1,
+}
+
+/// A knob, fader, key or pad on a controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct MidiControl {
+    pub kind: MidiControlKind,
+    /// 1 to 16.
+    pub channel: u8,
+    /// Controller or note number, 0 to 127.
+    pub number: u8,
+}
+
+impl MidiControl {
+    pub fn label(&self) -> String {
+        match self.kind {
+            MidiControlKind::ControlChange => format!("Ch {} CC {}", self.channel, self.number),
+            MidiControlKind::Note => format!("Ch {} Note {}", self.channel, self.number),
+        }
+    }
+}
+
+/// A control moving, with its new position from 0 to 1. Note offs arrive as notes at 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiEvent {
+    pub control: MidiControl,
+    pub value: f32,
+}
+
+/// Turns the raw MIDI byte stream into events, keeping the running status between reads.
+#[derive(Default)]
+struct MidiParser {
+    status: u8,
+    data: Vec<u8>,
+}
+
+impl MidiParser {
+    fn push(&mut self, byte: u8) -> Option<MidiEvent> {
+        match byte {
+            // Clock and other real-time bytes can arrive in the middle of a message.
+            0xf8..=0xff => None,
+            0x80..=0xf7 => {
+                self.status = byte;
+                self.data.clear();
+                None
+            }
+            // Running status: data bytes keep using the last status byte.
+            _ => {
+                self.data.push(byte);
+                let two_data_bytes = matches!(self.status & 0xf0, 0x80..=0xb0 | 0xe0);
+                let needed = if two_data_bytes { 2 } else { 1 };
+                if self.data.len() < needed {
+                    return None;
+                }
+                let (number, value) = (self.data[0], self.data.get(1).copied().unwrap_or(0));
+                self.data.clear();
+                let channel = (self.status & 0x0f) + 1;
+                let (kind, value) = match self.status & 0xf0 {
+                    0x90 => (MidiControlKind::Note, value),
+                    0x80 => (MidiControlKind::Note, 0),
+                    0xb0 => (MidiControlKind::ControlChange, value),
+                    _ => return None,
+                };
+                Some(MidiEvent { control: MidiControl { kind, channel, number }, value: value as f32 / 127.0 })
+            }
+        }
+    }
+}
+
+/// Reads an ALSA raw MIDI port on its own thread. Knob and key events arrive on the returned
+/// channel.
+pub struct MidiInput {
+    stop_flag: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MidiInput {
+    pub fn start(path: &Path, ctx: &egui::Context) -> Result<(Self, crossbeam_channel::Receiver<MidiEvent>)> {
+        let mut port = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .with_context(|| format!("Failed to open MIDI port '{}'", path.display()))?;
+        tracing::info!(path = %path.display(), "Listening for MIDI");
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let (event_sender, event_receiver) = crossbeam_channel::unbounded();
+        let thread = thread::spawn({
+            let stop_flag = stop_flag.clone();
+            let ctx = ctx.clone();
+            move || {
+                let mut buffer = [0u8; 256];
+                let mut parser = MidiParser::default();
+                while !stop_flag.load(Ordering::Relaxed) {
+                    let len = match port.read(&mut buffer) {
+                        Ok(0) => break,
+                        Ok(len) => len,
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                            thread::sleep(Duration::from_millis(5));
+                            continue;
+                        }
+                        Err(e) => {
+                            tracing::error!("MIDI port read failed: {}", e);
+                            break;
+                        }
+                    };
+                    let events: Vec<MidiEvent> = buffer[..len].iter().filter_map(|&byte| parser.push(byte)).collect();
+                    if events.is_empty() {
+                        continue;
+                    }
+                    for event in events {
+                        let _ = event_sender.send(event);
+                    }
+                    ctx.request_repaint();
+                }
+            }
+        });
+        Ok((Self { stop_flag, thread: Some(thread) }, event_receiver))
+    }
+}
+
+impl Drop for MidiInput {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// What a mapped control does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum MidiTarget {
+    /// Sets `MidiMapping::param` anywhere in its slider's range.
+    #[default]
+    Slider = 0,
+    ToggleStream = 1,
+    StartStream = 2,
+    StopStream = 3,
+    Screenshot = 4,
+    NextFilter = 5,
+    ToggleRecording = 6,
+}
+
+impl MidiTarget {
+    pub const ALL: [MidiTarget; 7] = [
+        MidiTarget::Slider,
+        MidiTarget::ToggleStream,
+        MidiTarget::StartStream,
+        MidiTarget::StopStream,
+        MidiTarget::Screenshot,
+        MidiTarget::NextFilter,
+        MidiTarget::ToggleRecording,
+    ];
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            MidiTarget::Slider => "Slider",
+            MidiTarget::ToggleStream => "Start / stop stream",
+            MidiTarget::StartStream => "Start stream",
+            MidiTarget::StopStream => "Stop stream",
+            MidiTarget::Screenshot => "Screenshot",
+            MidiTarget::NextFilter => "Next filter",
+            MidiTarget::ToggleRecording => "Start / stop recording",
+        }
+    }
+}
+
+/// Binds a control to a filter slider or a command. Commands fire when a button or key is
+/// pressed, i.e. when the control goes from below half to above it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiMapping {
+    pub target: MidiTarget,
+    /// The slider, for `MidiTarget::Slider`.
+    pub param: ModParam,
+    // A table in TOML, so it has to come after the plain values.
+    pub control: MidiControl,
+}
+
+impl Default for MidiMapping {
+    fn default() -> Self {
+        Self {
+            target: MidiTarget::Slider,
+            param: ModParam::WarpX,
+            control: MidiControl { kind: MidiControlKind::ControlChange, channel: 1, number: 1 },
+        }
+    }
+}
@@ -1,10 +1,29 @@
+pub mod alsa;
 pub mod audio;
+pub mod card_db;
+pub mod do_not_disturb;
 pub mod filter_type;
 pub mod filters;
+pub mod idle_inhibit;
+pub mod loudness;
+pub mod midi;
+pub mod pulse_monitor;
+pub mod screencast;
+pub mod sleep_monitor;
+pub mod sys_stats;
 pub mod usb;
 pub mod video;
 use anyhow::Result;
 
-pub type DeviceScanResultData =
-    (Vec<String>, Vec<(String, String)>, Vec<(String, String)>, Vec<(String, String)>);
-pub type DeviceScanResult = Result<DeviceScanResultData>;
\ No newline at end of file
+pub struct DeviceScanResultData {
+    pub video_devices: Vec<String>,
+    pub pulse_sources: Vec<(String, String)>,
+    pub pulse_sinks: Vec<(String, String)>,
+    pub pulse_cards: Vec<audio::PulseCard>,
+    pub usb_devices: Vec<usb::UsbDevice>,
+    /// Set when PulseAudio couldn't be reached; the ALSA lists are only filled in that case.
+    pub pulse_error: Option<String>,
+    pub alsa_captures: Vec<(String, String)>,
+    pub alsa_playbacks: Vec<(String, String)>,
+}
+pub type DeviceScanResult = Result<DeviceScanResultData>;
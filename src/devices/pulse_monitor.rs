@@ -0,0 +1,123 @@
+use eframe::egui;
+use libpulse_binding::context::subscribe::{Facility, InterestMaskSet, Operation};
+use libpulse_binding::context::{Context as PulseContext, FlagSet as PulseContextFlagSet, State as PulseContextState};
+use libpulse_binding::mainloop::standard::{IterateResult, Mainloop};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PulseEvent {
+    /// The connection to the server was lost, e.g. because the daemon exited.
+    Disconnected,
+    /// Connected again after a disconnect. Modules loaded before are gone.
+    Reconnected,
+    /// A module was unloaded, by us or by something else.
+    ModuleRemoved(u32),
+}
+
+/// Keeps a connection to the PulseAudio server to notice restarts and module changes,
+/// reconnecting whenever the connection drops.
+pub struct PulseMonitor {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PulseMonitor {
+    pub fn start(ctx: &egui::Context) -> (Self, crossbeam_channel::Receiver<PulseEvent>) {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            let ctx = ctx.clone();
+            move || monitor_thread_main(&stop, &tx, &ctx)
+        });
+        (Self { stop, thread: Some(thread) }, rx)
+    }
+}
+
+impl Drop for PulseMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn monitor_thread_main(stop: &AtomicBool, tx: &crossbeam_channel::Sender<PulseEvent>, ctx: &egui::Context) {
+    let send = |event| {
+        let _ = tx.send(event);
+        ctx.request_repaint();
+    };
+    let mut connected_before = false;
+    while !stop.load(Ordering::Relaxed) {
+        let Some((mut mainloop, mut context)) = connect(stop) else {
+            thread::sleep(RECONNECT_INTERVAL);
+            continue;
+        };
+        if connected_before {
+            tracing::info!("Reconnected to PulseAudio");
+            send(PulseEvent::Reconnected);
+        }
+        connected_before = true;
+
+        context.set_subscribe_callback(Some(Box::new({
+            let tx = tx.clone();
+            let ctx = ctx.clone();
+            move |facility, operation, index| {
+                if facility == Some(Facility::Module) && operation == Some(Operation::Removed) {
+                    let _ = tx.send(PulseEvent::ModuleRemoved(index));
+                    ctx.request_repaint();
+                }
+            }
+        })));
+        let _subscription = context.subscribe(InterestMaskSet::MODULE, |_| {});
+
+        while !stop.load(Ordering::Relaxed) {
+            match mainloop.iterate(false) {
+                IterateResult::Success(0) => thread::sleep(POLL_INTERVAL),
+                IterateResult::Success(_) => {}
+                IterateResult::Quit(_) | IterateResult::Err(_) => break,
+            }
+            if matches!(context.get_state(), PulseContextState::Failed | PulseContextState::Terminated) {
+                break;
+            }
+        }
+        context.set_subscribe_callback(None);
+        context.disconnect();
+        if !stop.load(Ordering::Relaxed) {
+            tracing::warn!("Lost connection to PulseAudio");
+            send(PulseEvent::Disconnected);
+        }
+    }
+}
+
+fn connect(stop: &AtomicBool) -> Option<(Mainloop, PulseContext)> {
+    let mut mainloop = Mainloop::new()?;
+    let mut context = PulseContext::new(&mainloop, "michadame-monitor")?;
+    context.connect(None, PulseContextFlagSet::empty(), None).ok()?;
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        match context.get_state() {
+            PulseContextState::Ready => return Some((mainloop, context)),
+            PulseContextState::Failed | PulseContextState::Terminated => return None,
+            _ => {}
+        }
+        if stop.load(Ordering::Relaxed) || Instant::now() > deadline {
+            return None;
+        }
+        match mainloop.iterate(false) {
+            IterateResult::Success(0) => thread::sleep(POLL_INTERVAL),
+            IterateResult::Success(_) => {}
+            IterateResult::Quit(_) | IterateResult::Err(_) => return None,
+        }
+    }
+}
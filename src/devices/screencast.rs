@@ -0,0 +1,163 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedFd, OwnedObjectPath, OwnedValue, Value};
+
+const PORTAL_DEST: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SCREENCAST_IFACE: &str = "org.freedesktop.portal.ScreenCast";
+// Source types and cursor modes from the ScreenCast portal spec.
+const SOURCE_MONITOR_AND_WINDOW: u32 = 1 | 2;
+const CURSOR_MODE_HIDDEN: u32 = 1;
+// The PipeWire remote is handed to gstreamer as this fd number.
+const CHILD_PIPEWIRE_FD: RawFd = 3;
+
+/// A running xdg-desktop-portal screencast. The chosen PipeWire stream is converted to
+/// YUV4MPEG by `gst-launch-1.0` so ffmpeg can read it like any other input.
+pub struct ScreencastSession {
+    connection: Connection,
+    session_handle: OwnedObjectPath,
+    gstreamer: Child,
+}
+
+impl ScreencastSession {
+    /// Asks the portal for a window or monitor. Blocks until the user has picked one in the
+    /// desktop's dialog, so this must not be called from the UI thread.
+    pub fn start() -> Result<Self> {
+        let connection = Connection::session().context("Failed to connect to the D-Bus session bus")?;
+        let screencast = Proxy::new(&connection, PORTAL_DEST, PORTAL_PATH, SCREENCAST_IFACE)
+            .context("Failed to create ScreenCast portal proxy")?;
+
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from("michadame_create"));
+        options.insert("session_handle_token", Value::from("michadame"));
+        let results = portal_request(&connection, &screencast, "CreateSession", &(options,), "michadame_create")?;
+        let session_handle = results
+            .get("session_handle")
+            .and_then(|v| match &**v {
+                Value::Str(s) => OwnedObjectPath::try_from(s.as_str().to_string()).ok(),
+                Value::ObjectPath(p) => Some(OwnedObjectPath::from(p.to_owned())),
+                _ => None,
+            })
+            .context("Portal did not return a session handle")?;
+
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from("michadame_select"));
+        options.insert("types", Value::from(SOURCE_MONITOR_AND_WINDOW));
+        options.insert("multiple", Value::from(false));
+        let cursor_modes: u32 = screencast.get_property("AvailableCursorModes").unwrap_or(0);
+        if cursor_modes & CURSOR_MODE_HIDDEN != 0 {
+            options.insert("cursor_mode", Value::from(CURSOR_MODE_HIDDEN));
+        }
+        portal_request(&connection, &screencast, "SelectSources", &(&session_handle, options), "michadame_select")?;
+
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from("michadame_start"));
+        let results = portal_request(&connection, &screencast, "Start", &(&session_handle, "", options), "michadame_start")?;
+        let node_id = results.get("streams").and_then(|v| first_stream_node(v)).context("Portal did not return a stream")?;
+
+        let options: HashMap<&str, Value> = HashMap::new();
+        let pipewire_fd: OwnedFd = screencast
+            .call("OpenPipeWireRemote", &(&session_handle, options))
+            .context("Failed to open PipeWire remote")?;
+        tracing::info!(node_id, "Screencast portal session started");
+
+        let gstreamer = spawn_gstreamer(pipewire_fd.as_raw_fd(), node_id)?;
+        Ok(Self { connection, session_handle, gstreamer })
+    }
+
+    /// Path ffmpeg can open to read the converted stream.
+    pub fn input_path(&self) -> String {
+        let stdout = self.gstreamer.stdout.as_ref().map(|s| s.as_raw_fd()).unwrap_or(-1);
+        format!("/proc/self/fd/{}", stdout)
+    }
+}
+
+impl Drop for ScreencastSession {
+    fn drop(&mut self) {
+        let _ = self.gstreamer.kill();
+        let _ = self.gstreamer.wait();
+        if let Ok(session) = Proxy::new(&self.connection, PORTAL_DEST, self.session_handle.as_ref(), "org.freedesktop.portal.Session") {
+            let _ = session.call_method("Close", &());
+        }
+        tracing::info!("Screencast session closed");
+    }
+}
+
+/// Calls a portal method and waits for the matching `Request.Response` signal.
+fn portal_request<B>(
+    connection: &Connection,
+    proxy: &Proxy,
+    method: &str,
+    body: &B,
+    token: &str,
+) -> Result<HashMap<String, OwnedValue>>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    // The request path is predictable, so subscribe before calling to avoid missing the reply.
+    let sender = connection
+        .unique_name()
+        .context("D-Bus connection has no unique name")?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let request_path = format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+    let request = Proxy::new(connection, PORTAL_DEST, request_path, "org.freedesktop.portal.Request")
+        .context("Failed to create portal request proxy")?;
+    let mut responses = request.receive_signal("Response")?;
+
+    proxy
+        .call_method(method, body)
+        .with_context(|| format!("ScreenCast portal {} call failed. Is xdg-desktop-portal running?", method))?;
+    let message = responses.next().with_context(|| format!("Portal closed the {} request", method))?;
+    let (code, results): (u32, HashMap<String, OwnedValue>) = message.body()?;
+    match code {
+        0 => Ok(results),
+        1 => Err(anyhow!("Screen capture was cancelled")),
+        _ => Err(anyhow!("ScreenCast portal {} request failed", method)),
+    }
+}
+
+/// Extracts the PipeWire node id from the `streams` result, an array of (node_id, properties).
+fn first_stream_node(streams: &Value) -> Option<u32> {
+    let Value::Array(streams) = streams else { return None };
+    match streams.get().first()? {
+        Value::Structure(stream) => match stream.fields().first()? {
+            Value::U32(node_id) => Some(*node_id),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn spawn_gstreamer(pipewire_fd: RawFd, node_id: u32) -> Result<Child> {
+    let fd_arg = format!("fd={}", CHILD_PIPEWIRE_FD);
+    let path_arg = format!("path={}", node_id);
+    let mut command = Command::new("gst-launch-1.0");
+    command
+        .args(["-q", "pipewiresrc", &fd_arg, &path_arg, "do-timestamp=true", "keepalive-time=1000"])
+        // Screencasts only deliver frames on damage; a fixed rate keeps the y4m stream regular.
+        .args(["!", "videorate", "!", "video/x-raw,framerate=60/1"])
+        .args(["!", "videoconvert", "!", "video/x-raw,format=I420"])
+        .args(["!", "y4menc", "!", "fdsink", "fd=1", "sync=false"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped());
+    unsafe {
+        command.pre_exec(move || {
+            // dup2 clears FD_CLOEXEC on the target, except when source and target are equal.
+            let result = if pipewire_fd == CHILD_PIPEWIRE_FD {
+                libc::fcntl(CHILD_PIPEWIRE_FD, libc::F_SETFD, 0)
+            } else {
+                libc::dup2(pipewire_fd, CHILD_PIPEWIRE_FD)
+            };
+            if result < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    command.spawn().context("Failed to execute 'gst-launch-1.0'. Is gstreamer with the pipewire plugin installed?")
+}
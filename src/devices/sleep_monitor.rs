@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use eframe::egui;
+use std::time::Duration;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedFd;
+
+const LOGIND_DEST: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER_IFACE: &str = "org.freedesktop.login1.Manager";
+/// logind itself gives up on delay locks after `InhibitDelayMaxSec` (5 s by default).
+const MAX_SLEEP_DELAY: Duration = Duration::from_secs(4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepEvent {
+    /// The system is about to suspend. Sleep is delayed until `SleepMonitor::ready` is called.
+    Suspending,
+    Resumed,
+}
+
+/// Watches logind's `PrepareForSleep` signal, holding a delay inhibitor so the stream can be
+/// stopped before the capture device goes away.
+pub struct SleepMonitor {
+    ready_tx: crossbeam_channel::Sender<()>,
+}
+
+impl SleepMonitor {
+    pub fn start(ctx: &egui::Context) -> (Self, crossbeam_channel::Receiver<SleepEvent>) {
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let (ready_tx, ready_rx) = crossbeam_channel::bounded(1);
+        let ctx = ctx.clone();
+        // The signal iterator blocks, so the thread lives as long as the application.
+        std::thread::spawn(move || {
+            if let Err(e) = monitor_thread_main(&event_tx, &ready_rx, &ctx) {
+                tracing::warn!("Suspend/resume handling unavailable: {:#}", e);
+            }
+        });
+        (Self { ready_tx }, event_rx)
+    }
+
+    /// Lets the system go to sleep after `SleepEvent::Suspending` was handled.
+    pub fn ready(&self) {
+        let _ = self.ready_tx.try_send(());
+    }
+}
+
+fn monitor_thread_main(
+    event_tx: &crossbeam_channel::Sender<SleepEvent>,
+    ready_rx: &crossbeam_channel::Receiver<()>,
+    ctx: &egui::Context,
+) -> Result<()> {
+    let connection = Connection::system().context("Failed to connect to the D-Bus system bus")?;
+    let manager = Proxy::new(&connection, LOGIND_DEST, LOGIND_PATH, LOGIND_MANAGER_IFACE)
+        .context("Failed to create logind proxy")?;
+    let signals = manager.receive_signal("PrepareForSleep")?;
+    let mut inhibitor = take_inhibitor(&manager);
+    for message in signals {
+        let Ok(going_to_sleep) = message.body::<bool>() else { continue };
+        if going_to_sleep {
+            tracing::info!("System is suspending");
+            while ready_rx.try_recv().is_ok() {}
+            if event_tx.send(SleepEvent::Suspending).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+            let _ = ready_rx.recv_timeout(MAX_SLEEP_DELAY);
+            // Closing the inhibitor fd lets the suspend continue.
+            inhibitor = None;
+        } else {
+            tracing::info!("System resumed");
+            if inhibitor.is_none() {
+                inhibitor = take_inhibitor(&manager);
+            }
+            if event_tx.send(SleepEvent::Resumed).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+    }
+    drop(inhibitor);
+    Ok(())
+}
+
+/// Without the lock the stream is still stopped on suspend, just possibly too late.
+fn take_inhibitor(manager: &Proxy) -> Option<OwnedFd> {
+    match manager.call("Inhibit", &("sleep", "Michadame", "Stopping the capture stream", "delay")) {
+        Ok(fd) => Some(fd),
+        Err(e) => {
+            tracing::warn!("Failed to take a sleep inhibitor lock: {}", e);
+            None
+        }
+    }
+}
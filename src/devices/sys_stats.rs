@@ -0,0 +1,228 @@
+use eframe::egui;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Sampling pauses once nobody has looked at the numbers for this long, e.g. because the
+/// diagnostics section was collapsed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(3);
+/// hwmon drivers reporting the CPU package temperature, most specific first.
+const CPU_SENSORS: &[&str] = &["k10temp", "zenpower", "coretemp", "cpu_thermal", "acpitz"];
+
+#[derive(Debug, Clone, Default)]
+pub struct ThreadUsage {
+    pub name: String,
+    /// Threads sharing the name, e.g. FFmpeg's decoder workers.
+    pub threads: usize,
+    /// In percent of one core.
+    pub cpu_percent: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SystemStats {
+    /// CPU time used by the whole process, in percent of one core.
+    pub cpu_percent: f32,
+    /// Busiest first.
+    pub threads: Vec<ThreadUsage>,
+    pub cpu_temperature: Option<f32>,
+    pub gpu_busy_percent: Option<f32>,
+    pub gpu_temperature: Option<f32>,
+}
+
+struct Shared {
+    stats: Option<SystemStats>,
+    last_read: Instant,
+}
+
+/// Samples the process' CPU usage per thread, and the CPU and GPU load and temperatures where
+/// the system exposes them, once a second on a background thread.
+pub struct SystemSampler {
+    shared: Arc<Mutex<Shared>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SystemSampler {
+    pub fn start(ctx: &egui::Context) -> Self {
+        let shared = Arc::new(Mutex::new(Shared { stats: None, last_read: Instant::now() }));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let shared = shared.clone();
+            let stop = stop.clone();
+            let ctx = ctx.clone();
+            move || sampler_thread_main(&shared, &stop, &ctx)
+        });
+        Self { shared, stop, thread: Some(thread) }
+    }
+
+    /// The latest sample, `None` until two samples were taken. Keeps the sampler running.
+    pub fn snapshot(&self) -> Option<SystemStats> {
+        let mut shared = self.shared.lock().unwrap();
+        shared.last_read = Instant::now();
+        shared.stats.clone()
+    }
+}
+
+impl Drop for SystemSampler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Where the system reports the values we show, looked up once when sampling starts.
+struct Sensors {
+    cpu_temperature: Option<PathBuf>,
+    gpu_busy: Option<PathBuf>,
+    gpu_temperature: Option<PathBuf>,
+    nvidia_smi: bool,
+}
+
+impl Sensors {
+    fn find() -> Self {
+        let hwmon_names: Vec<(String, PathBuf)> = read_dir_paths(Path::new("/sys/class/hwmon"))
+            .into_iter()
+            .filter_map(|dir| Some((read_trimmed(&dir.join("name"))?, dir)))
+            .collect();
+        let cpu_temperature = CPU_SENSORS.iter()
+            .find_map(|sensor| hwmon_names.iter().find(|(name, _)| name == sensor))
+            .map(|(_, dir)| dir.join("temp1_input"));
+
+        // amdgpu reports its load in sysfs. The card's own hwmon directory has its temperature.
+        let amd_device = read_dir_paths(Path::new("/sys/class/drm"))
+            .into_iter()
+            .map(|card| card.join("device"))
+            .find(|device| device.join("gpu_busy_percent").exists());
+        let gpu_temperature = amd_device.as_ref()
+            .and_then(|device| read_dir_paths(&device.join("hwmon")).into_iter().next())
+            .map(|dir| dir.join("temp1_input"));
+        let gpu_busy = amd_device.map(|device| device.join("gpu_busy_percent"));
+        let nvidia_smi = gpu_busy.is_none() && query_nvidia_smi().is_some();
+        Self { cpu_temperature, gpu_busy, gpu_temperature, nvidia_smi }
+    }
+
+    /// Fills in the temperatures and GPU load.
+    fn read(&self, stats: &mut SystemStats) {
+        stats.cpu_temperature = self.cpu_temperature.as_deref().and_then(read_millidegrees);
+        if self.nvidia_smi {
+            if let Some((busy, temperature)) = query_nvidia_smi() {
+                stats.gpu_busy_percent = Some(busy);
+                stats.gpu_temperature = Some(temperature);
+            }
+        } else {
+            stats.gpu_busy_percent = self.gpu_busy.as_deref().and_then(read_trimmed).and_then(|s| s.parse().ok());
+            stats.gpu_temperature = self.gpu_temperature.as_deref().and_then(read_millidegrees);
+        }
+    }
+}
+
+/// CPU ticks used so far, per thread id, with the thread's name.
+type ThreadTicks = HashMap<u32, (String, u64)>;
+
+fn sampler_thread_main(shared: &Mutex<Shared>, stop: &AtomicBool, ctx: &egui::Context) {
+    let sensors = Sensors::find();
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f32;
+    let mut previous: Option<(Instant, u64, ThreadTicks)> = None;
+    let mut next_sample = Instant::now();
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+        if Instant::now() < next_sample {
+            continue;
+        }
+        next_sample += SAMPLE_INTERVAL;
+        if shared.lock().unwrap().last_read.elapsed() > IDLE_TIMEOUT {
+            // Start over when the numbers are wanted again, instead of averaging over the pause.
+            previous = None;
+            next_sample = Instant::now();
+            continue;
+        }
+
+        let now = Instant::now();
+        let Some(process_ticks) = read_stat_ticks(Path::new("/proc/self/stat")).map(|(_, ticks)| ticks) else { break };
+        let thread_ticks = read_thread_ticks();
+        if let Some((then, previous_process, previous_threads)) = &previous {
+            let elapsed_ticks = now.duration_since(*then).as_secs_f32() * ticks_per_second;
+            let percent = |ticks: u64| ticks as f32 / elapsed_ticks * 100.0;
+            let mut by_name: HashMap<&str, ThreadUsage> = HashMap::new();
+            for (tid, (name, ticks)) in &thread_ticks {
+                // Threads started since the last sample count from zero.
+                let before = previous_threads.get(tid).map_or(0, |(_, ticks)| *ticks);
+                let usage = by_name.entry(name).or_insert_with(|| ThreadUsage { name: name.clone(), ..Default::default() });
+                usage.threads += 1;
+                usage.cpu_percent += percent(ticks.saturating_sub(before));
+            }
+            let mut threads: Vec<ThreadUsage> = by_name.into_values().collect();
+            threads.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent).then_with(|| a.name.cmp(&b.name)));
+            let mut stats = SystemStats {
+                cpu_percent: percent(process_ticks.saturating_sub(*previous_process)),
+                threads,
+                ..Default::default()
+            };
+            sensors.read(&mut stats);
+            shared.lock().unwrap().stats = Some(stats);
+            ctx.request_repaint();
+        }
+        previous = Some((now, process_ticks, thread_ticks));
+    }
+}
+
+fn read_thread_ticks() -> ThreadTicks {
+    read_dir_paths(Path::new("/proc/self/task"))
+        .into_iter()
+        .filter_map(|dir| {
+            let tid = dir.file_name()?.to_str()?.parse().ok()?;
+            Some((tid, read_stat_ticks(&dir.join("stat"))?))
+        })
+        .collect()
+}
+
+/// The name and user plus system time from a `/proc/<pid>/stat` style file.
+fn read_stat_ticks(path: &Path) -> Option<(String, u64)> {
+    let stat = std::fs::read_to_string(path).ok()?;
+    // The name is in parentheses and may itself contain spaces or parentheses.
+    let (head, rest) = stat.rsplit_once(')')?;
+    let name = head.split_once('(')?.1.to_string();
+    // `rest` starts at the third field, the state; utime and stime are fields 14 and 15.
+    let mut fields = rest.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some((name, utime + stime))
+}
+
+/// GPU load and temperature of the first NVIDIA card.
+fn query_nvidia_smi() -> Option<(f32, f32)> {
+    let output = Command::new("nvidia-smi")
+        .args(["--query-gpu=utilization.gpu,temperature.gpu", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (busy, temperature) = stdout.lines().next()?.split_once(',')?;
+    Some((busy.trim().parse().ok()?, temperature.trim().parse().ok()?))
+}
+
+fn read_dir_paths(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect())
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn read_millidegrees(path: &Path) -> Option<f32> {
+    read_trimmed(path)?.parse::<f32>().ok().map(|value| value / 1000.0)
+}
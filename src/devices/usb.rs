@@ -1,6 +1,39 @@
 use anyhow::{anyhow, Context, Result};
+use std::path::Path;
 use std::process::Command;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum UsbResetMethod {
+    /// Re-enumerate the device with `usbreset`.
+    UsbReset = 0,
+    /// Switch the hub port's power off and on with `uhubctl`, for cards that need a real power cycle.
+    PowerCycle = 1,
+}
+
+impl UsbResetMethod {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => UsbResetMethod::PowerCycle,
+            _ => UsbResetMethod::UsbReset,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            UsbResetMethod::UsbReset => "Reset (usbreset)",
+            UsbResetMethod::PowerCycle => "Power cycle (uhubctl)",
+        }
+    }
+}
+
+pub fn reset_usb_device_with(device_id: &str, method: UsbResetMethod) -> Result<()> {
+    match method {
+        UsbResetMethod::UsbReset => reset_usb_device(device_id),
+        UsbResetMethod::PowerCycle => power_cycle_usb_device(device_id),
+    }
+}
+
 pub fn reset_usb_device(device_id: &str) -> Result<()> {
     let status = Command::new("pkexec")
         .arg("usbreset")
@@ -17,29 +50,93 @@ pub fn reset_usb_device(device_id: &str) -> Result<()> {
     }
 }
 
-pub fn find_usb_devices() -> Result<Vec<(String, String)>> {
-    let output = Command::new("lsusb")
+/// Power-cycles the hub port the device is plugged into. Only works on hubs with per-port
+/// power switching; uhubctl's error says so otherwise.
+pub fn power_cycle_usb_device(device_id: &str) -> Result<()> {
+    let (hub, port) = find_usb_port(device_id)?;
+    let output = Command::new("pkexec")
+        .args(["uhubctl", "--location", &hub, "--ports", &port, "--action", "cycle", "--delay", "2"])
         .output()
-        .context("Failed to execute 'lsusb'. Is it installed and in your PATH?")?;
+        .context("Failed to execute 'pkexec uhubctl'. Is pkexec installed?")?;
 
-    if !output.status.success() {
+    if output.status.success() {
+        Ok(())
+    } else {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow!("lsusb failed: {}", stderr));
+        let msg = format!("'pkexec uhubctl' failed on hub {} port {}: {}", hub, port, stderr.trim());
+        tracing::error!("{}", msg);
+        Err(anyhow!(msg))
     }
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let devices = stdout.lines()
-        .filter_map(|line| {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() > 6 && parts[4] == "ID" {
-                let id = parts[5].to_string();
-                let name = parts[6..].join(" ");
-                Some((id, name))
-            } else {
-                None
-            }
-        })
-        .collect();
+/// Finds the hub location and port of a `vendor:product` device. A device at `1-4.2` is on
+/// port 2 of hub `1-4`; one at `1-4` is on port 4 of root hub `1`.
+fn find_usb_port(device_id: &str) -> Result<(String, String)> {
+    let device = find_usb_devices()?
+        .into_iter()
+        .find(|d| d.id == device_id)
+        .ok_or_else(|| anyhow!("USB device {} is not connected", device_id))?;
+    let (hub, port) = match device.port_path.rsplit_once('.') {
+        Some(location) => location,
+        None => device.port_path.split_once('-').context("Unexpected sysfs USB device name")?,
+    };
+    Ok((hub.to_string(), port.to_string()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsbDevice {
+    /// `vendor:product`, as saved in the config and passed to usbreset.
+    pub id: String,
+    pub name: String,
+    pub bus: u32,
+    pub device: u32,
+    pub serial: Option<String>,
+    /// The sysfs name, e.g. `1-4.2` for port 2 of the hub on port 4 of bus 1.
+    pub port_path: String,
+}
 
+impl UsbDevice {
+    pub fn label(&self) -> String {
+        let mut label = format!("{} {} (bus {:03}, device {:03}", self.id, self.name, self.bus, self.device);
+        if let Some(serial) = &self.serial {
+            label.push_str(&format!(", serial {}", serial));
+        }
+        label.push(')');
+        label
+    }
+}
+
+/// Lists USB devices from sysfs. Names come from the devices' own descriptor strings.
+pub fn find_usb_devices() -> Result<Vec<UsbDevice>> {
+    let read = |dir: &Path, attr: &str| {
+        std::fs::read_to_string(dir.join(attr)).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+    };
+    let mut devices = Vec::new();
+    for entry in std::fs::read_dir("/sys/bus/usb/devices").context("Failed to read /sys/bus/usb/devices")? {
+        let path = entry?.path();
+        let port_path = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        // Skip interfaces ("1-4:1.0") and root hubs ("usb1").
+        if port_path.contains(':') || port_path.starts_with("usb") {
+            continue;
+        }
+        let (Some(vendor), Some(product)) = (read(&path, "idVendor"), read(&path, "idProduct")) else {
+            continue;
+        };
+        let name = match (read(&path, "manufacturer"), read(&path, "product")) {
+            (Some(manufacturer), Some(product)) => format!("{} {}", manufacturer, product),
+            (None, Some(product)) => product,
+            (Some(manufacturer), None) => manufacturer,
+            (None, None) => "Unknown device".to_string(),
+        };
+        devices.push(UsbDevice {
+            id: format!("{}:{}", vendor, product),
+            name,
+            bus: read(&path, "busnum").and_then(|n| n.parse().ok()).unwrap_or(0),
+            device: read(&path, "devnum").and_then(|n| n.parse().ok()).unwrap_or(0),
+            serial: read(&path, "serial"),
+            port_path,
+        });
+    }
+    devices.sort_by_key(|d| (d.bus, d.device));
     Ok(devices)
-}
\ No newline at end of file
+}
@@ -2,13 +2,25 @@ use crate::video::types::{Resolution, VideoFormat};
 use anyhow::{anyhow, Context, Result};
 use std::process::Command;
 
+// Capability flags from linux/videodev2.h.
+const V4L2_CAP_VIDEO_CAPTURE: u32 = 0x0000_0001;
+const V4L2_CAP_VIDEO_CAPTURE_MPLANE: u32 = 0x0000_1000;
+const V4L2_CAP_VIDEO_M2M_MPLANE: u32 = 0x0000_4000;
+const V4L2_CAP_VIDEO_M2M: u32 = 0x0000_8000;
+
+/// Lists the video nodes that can capture. Capture cards usually also create metadata nodes,
+/// and hardware codecs show up as memory-to-memory nodes; neither delivers video.
 pub fn find_video_devices() -> Result<Vec<String>> {
     let mut devices = Vec::new();
     for entry in glob::glob("/dev/video*").context("Failed to read glob pattern /dev/video*")? {
         match entry {
             Ok(path) => {
                 if let Some(path_str) = path.to_str() {
-                    devices.push(path_str.to_string());
+                    if can_capture(path_str) {
+                        devices.push(path_str.to_string());
+                    } else {
+                        tracing::info!(device = path_str, "Skipping video node that can't capture");
+                    }
                 }
             }
             Err(e) => tracing::error!("Glob error: {:?}", e),
@@ -122,4 +134,78 @@ pub fn find_video_formats(device_path: &str) -> Result<Vec<VideoFormat>> {
         }
     }
     Ok(formats)
-}
\ No newline at end of file
+}
+/// Whether the node captures video, judging by the capabilities `v4l2-ctl --info` reports for it.
+/// Nodes are kept if that can't be determined, e.g. because v4l2-ctl is missing.
+fn can_capture(device_path: &str) -> bool {
+    let Ok(output) = Command::new("v4l2-ctl").arg("--info").arg("-d").arg(device_path).output() else { return true };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let caps_of = |name: &str| {
+        stdout.lines()
+            .map(str::trim)
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.rsplit("0x").next())
+            .and_then(|hex| u32::from_str_radix(hex.trim(), 16).ok())
+    };
+    // Device Caps describe this node; older drivers only report the caps of the whole device.
+    let Some(caps) = caps_of("Device Caps").or_else(|| caps_of("Capabilities")) else { return true };
+    caps & (V4L2_CAP_VIDEO_CAPTURE | V4L2_CAP_VIDEO_CAPTURE_MPLANE) != 0 && caps & (V4L2_CAP_VIDEO_M2M | V4L2_CAP_VIDEO_M2M_MPLANE) == 0
+}
+
+/// Whether the device is a v4l2loopback node, e.g. a copy of the capture card that OBS re-publishes.
+pub fn is_loopback_device(device_path: &str) -> bool {
+    Command::new("v4l2-ctl")
+        .arg("--info")
+        .arg("-d")
+        .arg(device_path)
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout).lines().any(|line| {
+                line.trim_start().starts_with("Driver name") && line.contains("v4l2 loopback")
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// The sysfs directory of the USB device behind a video node, or None if it isn't on USB.
+fn usb_device_dir(device_path: &str) -> Option<std::path::PathBuf> {
+    let node = std::path::Path::new(device_path).file_name()?;
+    let device = std::fs::canonicalize(std::path::Path::new("/sys/class/video4linux").join(node).join("device")).ok()?;
+    // The video node hangs off a USB interface; the device is the first ancestor with ids.
+    device.ancestors().find(|dir| dir.join("idVendor").exists()).map(|dir| dir.to_path_buf())
+}
+
+/// Negotiated USB link speed of a capture device in Mbit/s (e.g. 480 for USB 2.0, 5000 for
+/// USB 3.0), from sysfs. None for devices that aren't on USB.
+pub fn usb_speed_mbps(device_path: &str) -> Option<u32> {
+    let speed = std::fs::read_to_string(usb_device_dir(device_path)?.join("speed")).ok()?;
+    speed.trim().parse::<f32>().ok().map(|mbps| mbps as u32)
+}
+
+/// USB `vendor:product` of a capture device, matching the ids in the USB device list.
+pub fn usb_id(device_path: &str) -> Option<String> {
+    let dir = usb_device_dir(device_path)?;
+    let read = |attr: &str| std::fs::read_to_string(dir.join(attr)).ok().map(|s| s.trim().to_string());
+    Some(format!("{}:{}", read("idVendor")?, read("idProduct")?))
+}
+
+/// Lists other processes holding the device open as (pid, command name), by scanning /proc.
+/// Processes of other users can't be inspected and are silently skipped.
+pub fn find_device_users(device_path: &str) -> Vec<(u32, String)> {
+    let Ok(target) = std::fs::canonicalize(device_path) else { return Vec::new() };
+    let Ok(processes) = std::fs::read_dir("/proc") else { return Vec::new() };
+    let own_pid = std::process::id();
+    let mut users = Vec::new();
+    for entry in processes.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else { continue };
+        if pid == own_pid {
+            continue;
+        }
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else { continue };
+        if fds.flatten().any(|fd| std::fs::read_link(fd.path()).is_ok_and(|link| link == target)) {
+            let name = std::fs::read_to_string(entry.path().join("comm")).map(|n| n.trim().to_string()).unwrap_or_default();
+            users.push((pid, name));
+        }
+    }
+    users
+}
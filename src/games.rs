@@ -0,0 +1,152 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// Fingerprints are the brightness gradients of a 16x16 grid over the frame, one bit each.
+const HASH_SIZE: usize = 16;
+const HASH_WORDS: usize = HASH_SIZE * HASH_SIZE / 64;
+/// A frame matches a fingerprint if at most this many of its 256 bits differ. Compression noise
+/// and small animations flip a few; a different screen flips about half.
+const MAX_DISTANCE: u32 = 24;
+/// Frames whose cells are this close in brightness (out of 255) are too flat to tell apart,
+/// e.g. black between scenes. Their gradients are only noise.
+const MIN_CONTRAST: f32 = 12.0;
+/// Every this many shown frames one is fingerprinted.
+const SAMPLE_INTERVAL: u32 = 30;
+/// A game counts as detected after matching this many sampled frames in a row.
+const CONFIRM_SAMPLES: u32 = 2;
+
+/// Perceptual hash of a frame: set bits where a cell is brighter than its right neighbor.
+/// Survives scaling, brightness changes and compression, unlike comparing pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint([u64; HASH_WORDS]);
+
+impl Fingerprint {
+    /// `None` for frames without enough detail to recognize, see `MIN_CONTRAST`.
+    pub fn of(image: &egui::ColorImage) -> Option<Self> {
+        let [width, height] = image.size;
+        let (columns, rows) = (HASH_SIZE + 1, HASH_SIZE);
+        if width < columns || height < rows {
+            return None;
+        }
+        // Average a few pixels per cell so single pixels of noise don't decide a bit.
+        let cell = |column: usize, row: usize| {
+            let (x0, x1) = (column * width / columns, (column + 1) * width / columns);
+            let (y0, y1) = (row * height / rows, (row + 1) * height / rows);
+            let (mut sum, mut count) = (0.0, 0.0);
+            for y in (y0..y1).step_by(((y1 - y0) / 4).max(1)) {
+                for x in (x0..x1).step_by(((x1 - x0) / 4).max(1)) {
+                    let pixel = image.pixels[y * width + x];
+                    sum += 0.2126 * pixel.r() as f32 + 0.7152 * pixel.g() as f32 + 0.0722 * pixel.b() as f32;
+                    count += 1.0;
+                }
+            }
+            sum / count
+        };
+        let cells: Vec<f32> = (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row))).map(|(column, row)| cell(column, row)).collect();
+        let (min, max) = cells.iter().fold((f32::MAX, f32::MIN), |(min, max), &value| (min.min(value), max.max(value)));
+        if max - min < MIN_CONTRAST {
+            return None;
+        }
+        let mut words = [0u64; HASH_WORDS];
+        for row in 0..rows {
+            for column in 0..HASH_SIZE {
+                if cells[row * columns + column] > cells[row * columns + column + 1] {
+                    let bit = row * HASH_SIZE + column;
+                    words[bit / 64] |= 1 << (bit % 64);
+                }
+            }
+        }
+        Some(Self(words))
+    }
+
+    /// Number of differing bits.
+    pub fn distance(&self, other: &Self) -> u32 {
+        self.0.iter().zip(other.0).map(|(a, b)| (a ^ b).count_ones()).sum()
+    }
+
+    /// As hex, since TOML integers can't hold every `u64`.
+    pub fn to_hex(self) -> String {
+        self.0.iter().map(|word| format!("{:016x}", word)).collect()
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != HASH_WORDS * 16 || !hex.is_ascii() {
+            return None;
+        }
+        let mut words = [0u64; HASH_WORDS];
+        for (index, word) in words.iter_mut().enumerate() {
+            *word = u64::from_str_radix(&hex[index * 16..(index + 1) * 16], 16).ok()?;
+        }
+        Some(Self(words))
+    }
+}
+
+/// A game recognized by its title screen, and the scene to switch to when it shows up.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameProfile {
+    pub name: String,
+    /// Name of the scene holding the game's crop, look and overlays.
+    pub scene: String,
+    /// Hex `Fingerprint`s of the screens it was trained on, e.g. the title screen and the
+    /// publisher logo.
+    pub fingerprints: Vec<String>,
+}
+
+impl GameProfile {
+    /// The closest trained screen's distance to `fingerprint`, if any is close enough.
+    fn distance(&self, fingerprint: &Fingerprint) -> Option<u32> {
+        self.fingerprints
+            .iter()
+            .filter_map(|hex| Fingerprint::from_hex(hex))
+            .map(|trained| trained.distance(fingerprint))
+            .filter(|distance| *distance <= MAX_DISTANCE)
+            .min()
+    }
+}
+
+/// Looks at some of the shown frames for the trained screens.
+#[derive(Default)]
+pub struct GameDetector {
+    frames: u32,
+    /// The game matching the last samples, and how many in a row.
+    candidate: Option<(String, u32)>,
+    detected: Option<String>,
+}
+
+impl GameDetector {
+    /// Returns the name of a game when it's first recognized. It isn't reported again until
+    /// another game was recognized in between, so choosing different settings sticks.
+    pub fn push(&mut self, image: &egui::ColorImage, games: &[GameProfile]) -> Option<String> {
+        self.frames = (self.frames + 1) % SAMPLE_INTERVAL;
+        if self.frames != 0 || games.is_empty() {
+            return None;
+        }
+        let fingerprint = Fingerprint::of(image)?;
+        let Some(game) = games.iter().filter_map(|game| Some((game.distance(&fingerprint)?, game))).min_by_key(|(distance, _)| *distance).map(|(_, game)| game) else {
+            self.candidate = None;
+            return None;
+        };
+        let count = match &self.candidate {
+            Some((name, count)) if *name == game.name => count + 1,
+            _ => 1,
+        };
+        self.candidate = Some((game.name.clone(), count));
+        if count < CONFIRM_SAMPLES || self.detected.as_ref() == Some(&game.name) {
+            return None;
+        }
+        tracing::info!(game = %game.name, "Recognized game");
+        self.detected = Some(game.name.clone());
+        self.detected.clone()
+    }
+
+    /// The game recognized last.
+    pub fn detected(&self) -> Option<&str> {
+        self.detected.as_deref()
+    }
+
+    /// Starts over, so the next game shown is reported even if it's the same one.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
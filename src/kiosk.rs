@@ -0,0 +1,82 @@
+use eframe::egui;
+
+const DEFAULT_PASSPHRASE: &str = "unlock";
+
+/// Options for unattended installations, from the command line:
+/// `--kiosk [--kiosk-at X,Y] [--kiosk-look NAME] [--kiosk-passphrase WORD]`.
+#[derive(Debug, Clone)]
+pub struct KioskOptions {
+    /// Top-left corner of the monitor to go fullscreen on, in desktop coordinates (e.g. `1920,0`).
+    pub position: Option<(f32, f32)>,
+    /// Look to apply before the stream is started.
+    pub look: Option<String>,
+    /// Typed while holding Ctrl+Alt to leave kiosk mode.
+    pub passphrase: String,
+}
+
+/// Returns the kiosk options if `--kiosk` was given.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Option<KioskOptions> {
+    let mut enabled = false;
+    let mut options = KioskOptions { position: None, look: None, passphrase: DEFAULT_PASSPHRASE.to_string() };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--kiosk" => enabled = true,
+            "--kiosk-at" => {
+                let parsed = args.next().and_then(|value| {
+                    let (x, y) = value.split_once(',')?;
+                    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+                });
+                if parsed.is_none() {
+                    tracing::warn!("--kiosk-at expects X,Y, e.g. --kiosk-at 1920,0");
+                }
+                options.position = parsed;
+            }
+            "--kiosk-look" => options.look = args.next(),
+            "--kiosk-passphrase" => match args.next().map(|p| p.to_lowercase()).filter(|p| p.chars().all(|c| c.is_ascii_alphanumeric()) && !p.is_empty()) {
+                Some(passphrase) => options.passphrase = passphrase,
+                None => tracing::warn!("--kiosk-passphrase expects letters and digits only, keeping the default"),
+            },
+            other => tracing::warn!("Ignoring unknown argument '{}'", other),
+        }
+    }
+    enabled.then_some(options)
+}
+
+/// Kiosk mode while running: the UI stays hidden and shortcuts are ignored until unlocked.
+pub struct Kiosk {
+    pub options: KioskOptions,
+    pub unlocked: bool,
+    /// Whether the stream was started automatically already.
+    pub started: bool,
+    typed: String,
+}
+
+impl Kiosk {
+    pub fn new(options: KioskOptions) -> Self {
+        Self { options, unlocked: false, started: false, typed: String::new() }
+    }
+
+    /// Watches for the passphrase typed with Ctrl+Alt held. Returns true once it unlocks.
+    pub fn check_passphrase(&mut self, ctx: &egui::Context) -> bool {
+        let keys: Vec<String> = ctx.input(|i| {
+            i.events.iter().filter_map(|event| match event {
+                egui::Event::Key { key, pressed: true, repeat: false, modifiers, .. } if modifiers.ctrl && modifiers.alt => {
+                    Some(key.name().to_lowercase())
+                }
+                _ => None,
+            }).collect()
+        });
+        for key in keys {
+            self.typed.push_str(&key);
+            // Only the last characters can still complete the passphrase.
+            let excess = self.typed.len().saturating_sub(self.options.passphrase.len());
+            self.typed.drain(..excess);
+            if self.typed == self.options.passphrase {
+                self.unlocked = true;
+                return true;
+            }
+        }
+        false
+    }
+}
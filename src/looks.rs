@@ -0,0 +1,116 @@
+use crate::{app::AppState, config, devices::filter_type::BfiMode, video::filter_settings::{FilterSettings, LegacyFilterFields}};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+pub const LOOK_EXTENSION: &str = "look.toml";
+
+/// The filter stack and its parameters, without anything specific to the user's hardware.
+/// Fields are optional so looks from older or newer versions still import.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct Look {
+    pub name: String,
+    pub crt_filter: Option<u8>,
+    pub pixelate_filter_enabled: Option<bool>,
+    pub pixel_aspect: Option<(u32, u32)>,
+    pub bfi_mode: Option<u8>,
+    pub bfi_cycle: Option<u32>,
+
+    /// Parameters from looks saved by older versions, used when there's no `filter` table.
+    #[serde(flatten, skip_serializing)]
+    pub legacy_filter: LegacyFilterFields,
+    // A table, so it has to come last.
+    pub filter: Option<FilterSettings>,
+}
+
+impl Look {
+    pub fn from_state(state: &AppState, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            crt_filter: Some(state.crt_filter.load(Ordering::Relaxed)),
+            pixelate_filter_enabled: Some(state.pixelate_filter_enabled),
+            pixel_aspect: Some(state.pixel_aspect),
+            bfi_mode: Some(state.bfi_mode as u8),
+            bfi_cycle: Some(state.bfi_cycle),
+            legacy_filter: LegacyFilterFields::default(),
+            filter: Some(state.filter_settings.clone()),
+        }
+    }
+
+    pub fn apply(&self, state: &mut AppState) {
+        if let Some(val) = self.crt_filter {
+            state.crt_filter.store(val, Ordering::Relaxed);
+        }
+        if let Some(val) = self.pixelate_filter_enabled { state.pixelate_filter_enabled = val; }
+        if let Some((num, den)) = self.pixel_aspect {
+            if num > 0 && den > 0 {
+                state.pixel_aspect = (num, den);
+            }
+        }
+        if let Some(val) = self.bfi_mode { state.bfi_mode = BfiMode::from_u8(val); }
+        if let Some(val) = self.bfi_cycle { state.bfi_cycle = val.clamp(2, 4); }
+        if let Some(settings) = self.filter.clone().or_else(|| self.legacy_filter.to_settings()) {
+            state.filter_settings = settings.validated();
+        }
+        state.active_look = Some(self.name.clone());
+        config::mark_dirty(state);
+    }
+}
+
+/// Looks are kept next to the confy config file so they are easy to find and share.
+pub fn looks_dir() -> Result<PathBuf> {
+    let config_path = confy::get_configuration_file_path("michadame", None).context("Failed to locate config directory")?;
+    let dir = config_path.parent().context("Config path has no parent directory")?.join("looks");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    Ok(dir)
+}
+
+pub fn is_look_file(path: &Path) -> bool {
+    path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(LOOK_EXTENSION))
+}
+
+pub fn load_look(path: &Path) -> Result<Look> {
+    let contents = std::fs::read_to_string(path).with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let mut look: Look = toml::from_str(&contents).with_context(|| format!("'{}' is not a valid look file", path.display()))?;
+    if look.name.is_empty() {
+        look.name = path.file_name().and_then(|n| n.to_str()).unwrap_or("Unnamed")
+            .trim_end_matches(LOOK_EXTENSION).trim_end_matches('.').to_string();
+    }
+    Ok(look)
+}
+
+/// Writes the look into the looks directory and returns the file it was saved to.
+pub fn save_look(look: &Look) -> Result<PathBuf> {
+    let file_stem: String = look.name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let path = looks_dir()?.join(format!("{}.{}", file_stem, LOOK_EXTENSION));
+    let contents = toml::to_string_pretty(look).context("Failed to serialize look")?;
+    std::fs::write(&path, contents).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(path)
+}
+
+/// Copies a downloaded look into the looks directory so it shows up in the browser.
+pub fn import_look(path: &Path) -> Result<Look> {
+    let look = load_look(path)?;
+    save_look(&look)?;
+    Ok(look)
+}
+
+/// Lists the looks in the looks directory, sorted by name. Unreadable files are skipped.
+pub fn find_looks() -> Result<Vec<(String, PathBuf)>> {
+    let mut looks: Vec<(String, PathBuf)> = std::fs::read_dir(looks_dir()?)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| is_look_file(path))
+        .filter_map(|path| match load_look(&path) {
+            Ok(look) => Some((look.name, path)),
+            Err(e) => {
+                tracing::warn!("Skipping look: {:#}", e);
+                None
+            }
+        })
+        .collect();
+    looks.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
+    Ok(looks)
+}
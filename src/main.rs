@@ -1,6 +1,17 @@
 mod app;
+mod assets;
 mod config;
 mod devices;
+mod games;
+mod kiosk;
+mod looks;
+mod net;
+mod notifications;
+mod preflight;
+mod scenes;
+mod secrets;
+mod selftest;
+mod support;
 mod ui;
 mod video;
 
@@ -8,7 +19,8 @@ use eframe::egui;
 
 fn main() -> Result<(), eframe::Error> {
     // Setup logging
-    tracing_subscriber::fmt::init();
+    tracing_subscriber::fmt().with_writer(|| support::LogWriter).init();
+    video::ffmpeg_log::install();
 
     // --- Load Icon ---
     let icon = image::load_from_memory(include_bytes!("../assets/logo.png"))
@@ -16,21 +28,44 @@ fn main() -> Result<(), eframe::Error> {
         .to_rgba8();
     let (icon_width, icon_height) = icon.dimensions();
 
+    let kiosk = kiosk::parse_args(std::env::args().skip(1));
+
+    let saved_config = confy::load::<config::MichadameConfig>("michadame", None).ok();
+    // eframe's own window persistence needs its `persistence` feature, so the size lives in our config.
+    let saved_size = saved_config.as_ref()
+        .and_then(|cfg| cfg.main_window_size)
+        .filter(|(w, h)| *w >= 320.0 && *h >= 240.0)
+        .unwrap_or((640.0, 480.0));
+    let present_mode = video::display::PresentMode::from_u8(saved_config.as_ref().and_then(|cfg| cfg.present_mode).unwrap_or(0));
+    let gpu_preference = video::display::GpuPreference::from_u8(saved_config.as_ref().and_then(|cfg| cfg.gpu_preference).unwrap_or(0));
+    // Must happen before the GL context is created.
+    gpu_preference.apply_to_environment();
+
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size([saved_size.0, saved_size.1]) // Default starting size for the video window
+        .with_min_inner_size([320.0, 240.0])
+        .with_icon(egui::IconData {
+            rgba: icon.into_raw(),
+            width: icon_width,
+            height: icon_height,
+        });
+    if let Some(kiosk) = &kiosk {
+        // Fullscreen goes to the monitor the window is on, so place it there first.
+        if let Some(position) = kiosk.position {
+            viewport = viewport.with_position(position);
+        }
+        viewport = viewport.with_decorations(false).with_fullscreen(true);
+    }
+
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([640.0, 480.0]) // Default starting size for the video window
-            .with_min_inner_size([320.0, 240.0])
-            .with_icon(egui::IconData {
-                rgba: icon.into_raw(),
-                width: icon_width,
-                height: icon_height,
-            }),
+        viewport,
         persist_window: true,
+        vsync: present_mode.vsync(),
         ..Default::default()
     };
 
     // Create a closure that will be called once to create the App state.
-    let creator = |cc: &eframe::CreationContext| {
+    let creator = move |cc: &eframe::CreationContext| {
         // --- Embed a local font for 100% robust character support ---
         let mut fonts = egui::FontDefinitions::default();
 
@@ -47,7 +82,7 @@ fn main() -> Result<(), eframe::Error> {
             .extend(vec!["roboto_slab".to_owned(), "noto_sans_jp".to_owned(), "noto_emoji".to_owned()]);
 
         cc.egui_ctx.set_fonts(fonts);
-        Box::new(app::AppState::new(cc)) as Box<dyn eframe::App>
+        Box::new(app::AppState::new(cc, kiosk)) as Box<dyn eframe::App>
     };
 
     eframe::run_native("Michadame Viewer", options, Box::new(creator))
@@ -0,0 +1,157 @@
+use super::osc::{self, OscArg};
+use super::remote::RemoteCommand;
+use crate::devices::filter_type::CrtFilter;
+use anyhow::{Context, Result};
+use eframe::egui;
+use std::net::UdpSocket;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 9001;
+/// OSC addresses are this prefix followed by the command word, e.g. `/michadame/filter`.
+const OSC_PREFIX: &str = "/michadame/";
+
+/// Parses a text command such as `filter lottes` or `profile ps2`. See docs/commands.md.
+pub fn parse_command(text: &str) -> Result<RemoteCommand, String> {
+    let text = text.trim();
+    let (word, argument) = text.split_once(char::is_whitespace).map_or((text, ""), |(word, rest)| (word, rest.trim()));
+    let command = match (word.to_ascii_lowercase().as_str(), argument) {
+        ("start", "") => RemoteCommand::StartStream,
+        ("stop", "") => RemoteCommand::StopStream,
+        ("toggle", "") => RemoteCommand::ToggleStream,
+        ("screenshot", "") => RemoteCommand::Screenshot,
+        ("filter", "next") => RemoteCommand::NextFilter,
+        ("filter", name) => RemoteCommand::SetFilter(parse_filter(name).ok_or_else(|| format!("unknown filter '{}'", name))?),
+        ("profile" | "look", name) if !name.is_empty() => RemoteCommand::ApplyLook(name.to_string()),
+        ("scene", name) if !name.is_empty() => RemoteCommand::SwitchScene(name.to_string()),
+        ("volume", percent) => {
+            let percent: f32 = percent.trim_end_matches('%').parse().map_err(|_| format!("'{}' is not a volume in percent", percent))?;
+            RemoteCommand::SetVolume(percent / 100.0)
+        }
+        ("record", "start") => RemoteCommand::StartRecording,
+        ("record", "stop") => RemoteCommand::StopRecording,
+        ("record", "toggle" | "") => RemoteCommand::ToggleRecording,
+        _ => return Err(format!("unknown command '{}'", text)),
+    };
+    Ok(command)
+}
+
+fn parse_filter(name: &str) -> Option<CrtFilter> {
+    match name.to_ascii_lowercase().as_str() {
+        "off" | "none" => Some(CrtFilter::Off),
+        "scanlines" => Some(CrtFilter::Scanlines),
+        "lottes" | "crt" => Some(CrtFilter::Lottes),
+        "xbr" => Some(CrtFilter::Xbr),
+        "xbr-hard" => Some(CrtFilter::XbrHard),
+        _ => None,
+    }
+}
+
+/// Turns `/michadame/<command> [argument]` into the text form of the command.
+fn osc_to_text(message: &osc::OscMessage) -> Option<String> {
+    let word = message.address.strip_prefix(OSC_PREFIX)?;
+    let argument = match message.args.first() {
+        Some(OscArg::Text(text)) => text.clone(),
+        Some(OscArg::Number(value)) => value.to_string(),
+        Some(OscArg::Integer(value)) => value.to_string(),
+        None => String::new(),
+    };
+    Some(format!("{} {}", word.replace('/', " "), argument))
+}
+
+/// Accepts commands from Stream Deck plugins and scripts on a localhost UDP port, as plain
+/// text (`echo start | nc -u -w0 127.0.0.1 9001`) or as OSC messages. Text commands get a
+/// one-line reply, `ok` or `error: <reason>`.
+pub struct CommandServer {
+    stop_flag: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CommandServer {
+    pub fn start(port: u16, ctx: &egui::Context) -> Result<(Self, crossbeam_channel::Receiver<RemoteCommand>)> {
+        // Only local programs: there's no authentication.
+        let socket = UdpSocket::bind(("127.0.0.1", port)).with_context(|| format!("Failed to listen on UDP port {}", port))?;
+        socket.set_read_timeout(Some(Duration::from_millis(100))).context("Failed to configure command socket")?;
+        tracing::info!(port, "Command port listening");
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let (command_tx, command_rx) = crossbeam_channel::unbounded();
+        let thread = thread::spawn({
+            let stop_flag = stop_flag.clone();
+            let ctx = ctx.clone();
+            move || {
+                let mut buffer = [0u8; 1536];
+                while !stop_flag.load(Ordering::Relaxed) {
+                    let Ok((len, addr)) = socket.recv_from(&mut buffer) else { continue };
+                    let packet = &buffer[..len];
+                    let is_osc = packet.starts_with(b"/") || packet.starts_with(b"#bundle\0");
+                    let texts: Vec<String> = if is_osc {
+                        osc::parse_packet(packet).iter().filter_map(osc_to_text).collect()
+                    } else {
+                        String::from_utf8_lossy(packet).lines().filter(|line| !line.trim().is_empty()).map(str::to_string).collect()
+                    };
+                    for text in texts {
+                        let reply = match parse_command(&text) {
+                            Ok(command) => {
+                                tracing::info!(%addr, ?command, "Command port command");
+                                let _ = command_tx.send(command);
+                                ctx.request_repaint();
+                                "ok".to_string()
+                            }
+                            Err(e) => {
+                                tracing::debug!(%addr, "Rejected command: {}", e);
+                                format!("error: {}", e)
+                            }
+                        };
+                        // OSC senders don't expect text back.
+                        if !is_osc {
+                            let _ = socket.send_to(format!("{}\n", reply).as_bytes(), addr);
+                        }
+                    }
+                }
+            }
+        });
+        Ok((Self { stop_flag, thread: Some(thread) }, command_rx))
+    }
+
+    pub fn stop(mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_command_reads_words_and_arguments() {
+        assert_eq!(parse_command("start"), Ok(RemoteCommand::StartStream));
+        assert_eq!(parse_command("  Filter  Lottes \n"), Ok(RemoteCommand::SetFilter(CrtFilter::Lottes)));
+        assert_eq!(parse_command("volume 80%"), Ok(RemoteCommand::SetVolume(0.8)));
+        assert_eq!(parse_command("record"), Ok(RemoteCommand::ToggleRecording));
+        assert_eq!(parse_command("scene Title screen"), Ok(RemoteCommand::SwitchScene("Title screen".to_string())));
+    }
+
+    #[test]
+    fn parse_command_rejects_unknown_input() {
+        assert!(parse_command("").is_err());
+        assert!(parse_command("start now").is_err());
+        assert!(parse_command("filter sepia").is_err());
+        assert!(parse_command("volume loud").is_err());
+        assert!(parse_command("scene").is_err());
+    }
+
+    #[test]
+    fn osc_messages_become_text_commands() {
+        let message = |address: &str, args| osc::OscMessage { address: address.to_string(), args };
+        assert_eq!(osc_to_text(&message("/michadame/record/start", vec![])).as_deref(), Some("record start "));
+        assert_eq!(osc_to_text(&message("/michadame/volume", vec![OscArg::Integer(80)])).as_deref(), Some("volume 80"));
+        assert_eq!(osc_to_text(&message("/other/start", vec![])), None);
+    }
+}
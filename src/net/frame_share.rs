@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use eframe::egui;
+use std::io::Write;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{fence, AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// First bytes of the shared memory, followed by the layout version.
+pub const MAGIC: &[u8; 8] = b"MCHDFRMS";
+pub const VERSION: u32 = 1;
+pub const SLOT_COUNT: usize = 3;
+const HEADER_SIZE: usize = 64;
+const SLOT_HEADER_SIZE: usize = 64;
+/// Room for a 4K RGBA frame per slot. memfd pages are only allocated once written, so smaller
+/// sources don't use that much memory.
+const MAX_FRAME_BYTES: usize = 3840 * 2160 * 4;
+const SLOT_SIZE: usize = SLOT_HEADER_SIZE + MAX_FRAME_BYTES;
+/// Pixel format code for 8-bit RGBA, alpha always 255.
+pub const FORMAT_RGBA8: u32 = 1;
+
+/// `$XDG_RUNTIME_DIR/michadame-frames.sock`, or in the temp directory without one.
+pub fn default_socket_path() -> PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+    dir.join("michadame-frames.sock")
+}
+
+/// A sealed memfd mapped into our address space, laid out as described in docs/frame_share.md.
+struct FrameRing {
+    fd: OwnedFd,
+    ptr: *mut u8,
+    len: usize,
+    frames: u64,
+}
+
+// The mapping is only written from the writer thread.
+unsafe impl Send for FrameRing {}
+
+impl FrameRing {
+    fn create() -> Result<Self> {
+        let len = HEADER_SIZE + SLOT_COUNT * SLOT_SIZE;
+        let raw = unsafe { libc::memfd_create(c"michadame-frames".as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING) };
+        if raw < 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to create shared memory");
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), len as libc::off_t) } < 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to size shared memory");
+        }
+        let ptr = unsafe { libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, fd.as_raw_fd(), 0) };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error()).context("Failed to map shared memory");
+        }
+        let ring = Self { fd, ptr: ptr as *mut u8, len, frames: 0 };
+        // Clients can then map the whole size without worrying about it shrinking under them. Our
+        // mapping stays writable, but the future write seal refuses writable mappings and writes
+        // through the descriptor the clients get.
+        let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_FUTURE_WRITE | libc::F_SEAL_SEAL;
+        if unsafe { libc::fcntl(ring.fd.as_raw_fd(), libc::F_ADD_SEALS, seals) } < 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to seal shared memory");
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(MAGIC.as_ptr(), ring.ptr, MAGIC.len());
+            std::ptr::write(ring.ptr.add(8) as *mut u32, VERSION);
+            std::ptr::write(ring.ptr.add(12) as *mut u32, SLOT_COUNT as u32);
+            std::ptr::write(ring.ptr.add(16) as *mut u64, SLOT_SIZE as u64);
+        }
+        Ok(ring)
+    }
+
+    fn atomic(&self, offset: usize) -> &AtomicU64 {
+        unsafe { &*(self.ptr.add(offset) as *const AtomicU64) }
+    }
+
+    /// Writes the frame into the next slot and returns its frame number.
+    fn write(&mut self, image: &egui::ColorImage) -> Option<u64> {
+        let bytes: &[u8] = bytemuck::cast_slice(&image.pixels);
+        if bytes.len() > MAX_FRAME_BYTES {
+            return None;
+        }
+        self.frames += 1;
+        let slot = HEADER_SIZE + (self.frames as usize % SLOT_COUNT) * SLOT_SIZE;
+        let sequence = self.atomic(slot);
+        // Odd while writing, so readers know to skip or retry the slot.
+        sequence.store(self.frames * 2 - 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        unsafe {
+            std::ptr::write(self.ptr.add(slot + 8) as *mut u32, image.size[0] as u32);
+            std::ptr::write(self.ptr.add(slot + 12) as *mut u32, image.size[1] as u32);
+            std::ptr::write(self.ptr.add(slot + 16) as *mut u32, image.size[0] as u32 * 4);
+            std::ptr::write(self.ptr.add(slot + 20) as *mut u32, FORMAT_RGBA8);
+            std::ptr::write(self.ptr.add(slot + 24) as *mut u64, monotonic_nanos());
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.add(slot + SLOT_HEADER_SIZE), bytes.len());
+        }
+        sequence.store(self.frames * 2, Ordering::Release);
+        self.atomic(24).store(self.frames, Ordering::Release);
+        Some(self.frames)
+    }
+}
+
+impl Drop for FrameRing {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+/// CLOCK_MONOTONIC, so clients can compare it with their own timestamps.
+fn monotonic_nanos() -> u64 {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// Sends `data` with `fd` attached as SCM_RIGHTS, which std can't do yet.
+fn send_with_fd(stream: &UnixStream, data: &[u8], fd: RawFd) -> std::io::Result<()> {
+    let fd_len = std::mem::size_of::<RawFd>() as u32;
+    let space = unsafe { libc::CMSG_SPACE(fd_len) } as usize;
+    // u64s keep the control buffer aligned for `cmsghdr`.
+    let mut control = vec![0u64; space.div_ceil(8)];
+    let mut iov = libc::iovec { iov_base: data.as_ptr() as *mut libc::c_void, iov_len: data.len() };
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = space as _;
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(fd_len) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+        if libc::sendmsg(stream.as_raw_fd(), &msg, 0) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// A connected client, with the part of a notice its socket buffer had no room for.
+struct Client {
+    stream: UnixStream,
+    unsent: Vec<u8>,
+}
+
+impl Client {
+    /// Sends what's left of the last notice. `WouldBlock` while the socket buffer is still full.
+    fn flush(&mut self) -> std::io::Result<()> {
+        while !self.unsent.is_empty() {
+            match self.stream.write(&self.unsent) {
+                Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.unsent.drain(..written);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends `notice`, or finishes the previous one if that's still going out, so lines never
+    /// tear. Returns false once the client is gone.
+    fn notify(&mut self, notice: &[u8]) -> bool {
+        if self.unsent.is_empty() {
+            self.unsent.extend_from_slice(notice);
+        }
+        match self.flush() {
+            Ok(()) => true,
+            // Its socket buffer is full; it'll see the newer frames in the ring anyway.
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => true,
+            Err(_) => {
+                tracing::info!("Frame sharing client disconnected");
+                false
+            }
+        }
+    }
+}
+
+/// Shares the decoded frames with other programs on this machine through a shared memory
+/// ring, handed out over a Unix socket. Clients are told about each new frame on the socket,
+/// so they can wait for it instead of polling. The protocol is described in
+/// docs/frame_share.md.
+pub struct FrameShare {
+    frame_sender: crossbeam_channel::Sender<Arc<egui::ColorImage>>,
+    stop_flag: Arc<AtomicBool>,
+    clients: Arc<Mutex<Vec<Client>>>,
+    socket_path: PathBuf,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl FrameShare {
+    pub fn start(socket_path: &Path) -> Result<Self> {
+        // A socket file left behind by a crash would make bind fail. Only remove it if nobody answers.
+        if socket_path.exists() && UnixStream::connect(socket_path).is_err() {
+            let _ = std::fs::remove_file(socket_path);
+        }
+        let listener = UnixListener::bind(socket_path).with_context(|| format!("Failed to listen on '{}'", socket_path.display()))?;
+        listener.set_nonblocking(true).context("Failed to configure listener")?;
+        let mut ring = FrameRing::create()?;
+        let ring_fd = ring.fd.try_clone().context("Failed to duplicate the shared memory descriptor")?;
+        tracing::info!(path = %socket_path.display(), "Frame sharing socket listening");
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let clients: Arc<Mutex<Vec<Client>>> = Arc::new(Mutex::new(Vec::new()));
+        let (frame_sender, frame_receiver) = crossbeam_channel::bounded::<Arc<egui::ColorImage>>(1);
+
+        let accept_thread = thread::spawn({
+            let stop_flag = stop_flag.clone();
+            let clients = clients.clone();
+            let hello = format!("MICHADAME-FRAMES {} slots={} slot_size={} header={}\n", VERSION, SLOT_COUNT, SLOT_SIZE, HEADER_SIZE);
+            move || {
+                while !stop_flag.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(e) = send_with_fd(&stream, hello.as_bytes(), ring_fd.as_raw_fd()) {
+                                tracing::warn!("Failed to hand out the frame buffer: {}", e);
+                                continue;
+                            }
+                            // A client that stops reading must not hold up the writer.
+                            let _ = stream.set_nonblocking(true);
+                            tracing::info!("Frame sharing client connected");
+                            clients.lock().unwrap().push(Client { stream, unsent: Vec::new() });
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(50)),
+                        Err(e) => tracing::error!("Frame sharing accept failed: {}", e),
+                    }
+                }
+            }
+        });
+
+        let writer_thread = thread::spawn({
+            let stop_flag = stop_flag.clone();
+            let clients = clients.clone();
+            move || {
+                let mut warned_size = false;
+                while !stop_flag.load(Ordering::Relaxed) {
+                    let Ok(image) = frame_receiver.recv_timeout(Duration::from_millis(100)) else { continue };
+                    if clients.lock().unwrap().is_empty() {
+                        continue;
+                    }
+                    let Some(frame) = ring.write(&image) else {
+                        if !warned_size {
+                            tracing::warn!(size = ?image.size, "Frame too large to share, skipping");
+                            warned_size = true;
+                        }
+                        continue;
+                    };
+                    let notice = format!("frame {}\n", frame);
+                    clients.lock().unwrap().retain_mut(|client| client.notify(notice.as_bytes()));
+                }
+            }
+        });
+
+        Ok(Self {
+            frame_sender,
+            stop_flag,
+            clients,
+            socket_path: socket_path.to_path_buf(),
+            threads: vec![accept_thread, writer_thread],
+        })
+    }
+
+    /// Queues a frame for the clients. Dropped if the previous one is still being copied.
+    pub fn push_frame(&self, image: Arc<egui::ColorImage>) {
+        let _ = self.frame_sender.try_send(image);
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    pub fn stop(mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
@@ -0,0 +1,151 @@
+use eframe::egui;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:16834";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+const IO_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Timer state as reported by LiveSplit, e.g. `time: "1:23.45"`, `phase: "Running"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveSplitStatus {
+    pub time: String,
+    pub phase: String,
+}
+
+/// Commands sent to LiveSplit's timer from the hotkeys, using its server protocol names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveSplitCommand {
+    StartOrSplit,
+    Pause,
+    Resume,
+    Reset,
+}
+
+impl LiveSplitCommand {
+    fn protocol_name(&self) -> &'static str {
+        match self {
+            LiveSplitCommand::StartOrSplit => "startorsplit",
+            LiveSplitCommand::Pause => "pause",
+            LiveSplitCommand::Resume => "resume",
+            LiveSplitCommand::Reset => "reset",
+        }
+    }
+}
+
+/// Polls a LiveSplit Server (Control > Start Server in LiveSplit) for the current time,
+/// reconnecting whenever the connection drops. `None` is sent while disconnected.
+pub struct LiveSplitClient {
+    pub address: String,
+    commands: crossbeam_channel::Sender<LiveSplitCommand>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LiveSplitClient {
+    pub fn start(address: &str, ctx: &egui::Context) -> (Self, crossbeam_channel::Receiver<Option<LiveSplitStatus>>) {
+        let (status_tx, status_rx) = crossbeam_channel::unbounded();
+        let (command_tx, command_rx) = crossbeam_channel::unbounded();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let address = address.to_string();
+            let stop = stop.clone();
+            let ctx = ctx.clone();
+            move || client_thread_main(&address, &stop, &status_tx, &command_rx, &ctx)
+        });
+        (Self { address: address.to_string(), commands: command_tx, stop, thread: Some(thread) }, status_rx)
+    }
+
+    pub fn send(&self, command: LiveSplitCommand) {
+        let _ = self.commands.send(command);
+    }
+}
+
+impl Drop for LiveSplitClient {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn client_thread_main(
+    address: &str,
+    stop: &AtomicBool,
+    status_tx: &crossbeam_channel::Sender<Option<LiveSplitStatus>>,
+    command_rx: &crossbeam_channel::Receiver<LiveSplitCommand>,
+    ctx: &egui::Context,
+) {
+    let mut last_status = None;
+    while !stop.load(Ordering::Relaxed) {
+        let result = connect(address).and_then(|stream| {
+            tracing::info!("Connected to LiveSplit Server at {}", address);
+            poll_connection(stream, stop, command_rx, |status| {
+                if last_status.as_ref() != Some(&status) {
+                    last_status = Some(status.clone());
+                    let _ = status_tx.send(Some(status));
+                    ctx.request_repaint();
+                }
+            })
+        });
+        if let Err(e) = result {
+            tracing::debug!("LiveSplit Server connection to {} failed: {}", address, e);
+        }
+        if last_status.take().is_some() {
+            let _ = status_tx.send(None);
+            ctx.request_repaint();
+        }
+        // Commands issued while disconnected would fire unexpectedly on reconnect.
+        while command_rx.try_recv().is_ok() {}
+        let mut waited = Duration::ZERO;
+        while waited < RECONNECT_INTERVAL && !stop.load(Ordering::Relaxed) {
+            thread::sleep(POLL_INTERVAL);
+            waited += POLL_INTERVAL;
+        }
+    }
+}
+
+fn connect(address: &str) -> std::io::Result<TcpStream> {
+    let addr = address.to_socket_addrs()?.next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Address not found"))?;
+    let stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    Ok(stream)
+}
+
+fn poll_connection(
+    mut stream: TcpStream,
+    stop: &AtomicBool,
+    command_rx: &crossbeam_channel::Receiver<LiveSplitCommand>,
+    mut on_status: impl FnMut(LiveSplitStatus),
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut query = |stream: &mut TcpStream, command: &str| -> std::io::Result<String> {
+        stream.write_all(format!("{}\r\n", command).as_bytes())?;
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(line.trim().to_string())
+    };
+    while !stop.load(Ordering::Relaxed) {
+        for command in command_rx.try_iter() {
+            stream.write_all(format!("{}\r\n", command.protocol_name()).as_bytes())?;
+        }
+        let time = query(&mut stream, "getcurrenttime")?;
+        let phase = query(&mut stream, "getcurrenttimerphase")?;
+        on_status(LiveSplitStatus { time, phase });
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use super::remote::ConnectionSlot;
+use anyhow::{Context, Result};
+use eframe::egui;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Condvar, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct MjpegServerSettings {
+    pub port: u16,
+    /// JPEG quality, 1-100.
+    pub quality: u8,
+    /// Upper bound on frames sent per second, to cap bandwidth.
+    pub max_fps: u32,
+    /// Optional (user, password) for HTTP basic auth. Only with a password is the server
+    /// reachable from other machines.
+    pub credentials: Option<(String, String)>,
+}
+
+impl MjpegServerSettings {
+    pub fn serves_lan(&self) -> bool {
+        self.credentials.as_ref().is_some_and(|(_, password)| !password.is_empty())
+    }
+}
+
+/// The most recent encoded frame, with a sequence number so clients can wait for a new one.
+#[derive(Default)]
+struct LatestFrame {
+    jpeg: Option<Arc<Vec<u8>>>,
+    sequence: u64,
+}
+
+type SharedFrame = Arc<(Mutex<LatestFrame>, Condvar)>;
+
+/// Serves the captured video as MJPEG over HTTP (`multipart/x-mixed-replace`), viewable in
+/// any browser or in VLC/mpv on another machine.
+pub struct MjpegServer {
+    frame_sender: crossbeam_channel::Sender<Arc<egui::ColorImage>>,
+    stop_flag: Arc<AtomicBool>,
+    clients: Arc<AtomicUsize>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl MjpegServer {
+    pub fn start(settings: MjpegServerSettings) -> Result<Self> {
+        // Without a password anyone on the network could watch, so only local clients are let in.
+        let host = if settings.serves_lan() { "0.0.0.0" } else { "127.0.0.1" };
+        let listener = TcpListener::bind((host, settings.port))
+            .with_context(|| format!("Failed to listen on port {}", settings.port))?;
+        listener.set_nonblocking(true).context("Failed to configure listener")?;
+        tracing::info!(host, port = settings.port, "MJPEG preview server listening");
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let clients = Arc::new(AtomicUsize::new(0));
+        let shared: SharedFrame = Arc::new((Mutex::new(LatestFrame::default()), Condvar::new()));
+        let (frame_sender, frame_receiver) = crossbeam_channel::bounded::<Arc<egui::ColorImage>>(1);
+
+        let encoder_thread = thread::spawn({
+            let stop_flag = stop_flag.clone();
+            let shared = shared.clone();
+            let quality = settings.quality.clamp(1, 100);
+            let min_interval = Duration::from_secs_f64(1.0 / settings.max_fps.max(1) as f64);
+            move || {
+                let mut last_sent: Option<Instant> = None;
+                while !stop_flag.load(Ordering::Relaxed) {
+                    let Ok(image) = frame_receiver.recv_timeout(Duration::from_millis(100)) else { continue };
+                    if last_sent.is_some_and(|t| t.elapsed() < min_interval) {
+                        continue;
+                    }
+                    last_sent = Some(Instant::now());
+                    match encode_jpeg(&image, quality) {
+                        Ok(jpeg) => {
+                            let (lock, cvar) = &*shared;
+                            let mut latest = lock.lock().unwrap();
+                            latest.jpeg = Some(Arc::new(jpeg));
+                            latest.sequence += 1;
+                            cvar.notify_all();
+                        }
+                        Err(e) => tracing::error!("Failed to encode preview frame: {:?}", e),
+                    }
+                }
+                // Wake clients so they notice the stop flag.
+                shared.1.notify_all();
+            }
+        });
+
+        let accept_thread = thread::spawn({
+            let stop_flag = stop_flag.clone();
+            let clients = clients.clone();
+            let expected_auth = settings.credentials.as_ref()
+                .map(|(user, pass)| format!("Basic {}", super::base64_encode(format!("{}:{}", user, pass).as_bytes())));
+            move || {
+                while !stop_flag.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, addr)) => {
+                            let Some(slot) = ConnectionSlot::take(&clients) else {
+                                tracing::debug!(%addr, "Too many MJPEG preview clients, dropping one");
+                                continue;
+                            };
+                            tracing::info!(%addr, "MJPEG preview client connected");
+                            let stop_flag = stop_flag.clone();
+                            let shared = shared.clone();
+                            let expected_auth = expected_auth.clone();
+                            thread::spawn(move || {
+                                let _slot = slot;
+                                if let Err(e) = serve_client(stream, &shared, &stop_flag, expected_auth.as_deref()) {
+                                    tracing::debug!(%addr, "MJPEG client disconnected: {}", e);
+                                }
+                            });
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(50)),
+                        Err(e) => tracing::error!("MJPEG accept failed: {}", e),
+                    }
+                }
+            }
+        });
+
+        Ok(Self { frame_sender, stop_flag, clients, threads: vec![encoder_thread, accept_thread] })
+    }
+
+    /// Offers a frame to the server. Dropped if the encoder is still busy with the previous one.
+    pub fn push_frame(&self, image: Arc<egui::ColorImage>) {
+        let _ = self.frame_sender.try_send(image);
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.load(Ordering::Relaxed)
+    }
+
+    pub fn stop(mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn encode_jpeg(image: &egui::ColorImage, quality: u8) -> Result<Vec<u8>> {
+    let rgb: Vec<u8> = image.pixels.iter().flat_map(|p| [p.r(), p.g(), p.b()]).collect();
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality)
+        .encode(&rgb, image.size[0] as u32, image.size[1] as u32, image::ExtendedColorType::Rgb8)
+        .context("JPEG encoding failed")?;
+    Ok(jpeg)
+}
+
+fn serve_client(stream: TcpStream, shared: &SharedFrame, stop_flag: &AtomicBool, expected_auth: Option<&str>) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    // A client that stops reading would otherwise hold its thread and slot forever.
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    // Read the request headers; we serve the same stream for any path.
+    let mut authorized = expected_auth.is_none();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        if let (Some(expected), Some((name, value))) = (expected_auth, line.split_once(':')) {
+            if name.eq_ignore_ascii_case("authorization") && value.trim() == expected {
+                authorized = true;
+            }
+        }
+    }
+
+    if !authorized {
+        stream.write_all(b"HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"Michadame\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")?;
+        return Ok(());
+    }
+
+    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary=frame\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n")?;
+
+    let mut last_sequence = 0;
+    let (lock, cvar) = &**shared;
+    while !stop_flag.load(Ordering::Relaxed) {
+        let jpeg = {
+            let guard = lock.lock().unwrap();
+            let (latest, _) = cvar
+                .wait_timeout_while(guard, Duration::from_millis(500), |f| f.sequence == last_sequence && !stop_flag.load(Ordering::Relaxed))
+                .unwrap();
+            if latest.sequence == last_sequence {
+                continue;
+            }
+            last_sequence = latest.sequence;
+            latest.jpeg.clone()
+        };
+        if let Some(jpeg) = jpeg {
+            write!(stream, "--frame\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n", jpeg.len())?;
+            stream.write_all(&jpeg)?;
+            stream.write_all(b"\r\n")?;
+        }
+    }
+    Ok(())
+}
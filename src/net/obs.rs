@@ -0,0 +1,321 @@
+use anyhow::{anyhow, bail, Context, Result};
+use eframe::egui;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:4455";
+
+const IO_TIMEOUT: Duration = Duration::from_secs(3);
+/// obs-websocket 5.x, the version built into OBS 28 and later.
+const RPC_VERSION: u32 = 1;
+/// Request status codes for starting a running output or stopping a stopped one, which we
+/// don't treat as failures.
+const OUTPUT_RUNNING: u32 = 500;
+const OUTPUT_NOT_RUNNING: u32 = 501;
+
+const OP_HELLO: u32 = 0;
+const OP_IDENTIFY: u32 = 1;
+const OP_IDENTIFIED: u32 = 2;
+const OP_REQUEST: u32 = 6;
+const OP_REQUEST_RESPONSE: u32 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObsEvent {
+    StreamStarted,
+    StreamStopped,
+    /// Only connects and authenticates, to check the settings.
+    Test,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ObsSettings {
+    pub address: String,
+    pub password: String,
+    /// Scene to switch to when the stream starts, none if empty.
+    pub start_scene: String,
+    /// Scene to switch to when the stream stops, none if empty.
+    pub stop_scene: String,
+    /// Start and stop OBS's recording with the stream.
+    pub control_recording: bool,
+}
+
+/// Talks to OBS through obs-websocket. Events are handled one at a time on a worker thread,
+/// each with its own short connection, so OBS doesn't have to be running all the time. The
+/// outcome of each event is sent back as a message for the status line.
+pub struct ObsController {
+    tx: crossbeam_channel::Sender<(ObsSettings, ObsEvent)>,
+}
+
+impl ObsController {
+    pub fn start(ctx: &egui::Context) -> (Self, crossbeam_channel::Receiver<Result<String>>) {
+        let (tx, rx) = crossbeam_channel::unbounded::<(ObsSettings, ObsEvent)>();
+        let (result_tx, result_rx) = crossbeam_channel::unbounded();
+        let ctx = ctx.clone();
+        // Ends when the controller is dropped.
+        std::thread::spawn(move || {
+            for (settings, event) in rx {
+                let result = handle_event(&settings, event).with_context(|| format!("OBS at {}", settings.address));
+                let _ = result_tx.send(result);
+                ctx.request_repaint();
+            }
+        });
+        (Self { tx }, result_rx)
+    }
+
+    pub fn send(&self, settings: ObsSettings, event: ObsEvent) {
+        let _ = self.tx.send((settings, event));
+    }
+}
+
+fn handle_event(settings: &ObsSettings, event: ObsEvent) -> Result<String> {
+    let mut socket = WebSocket::connect(settings.address.trim())?;
+    let version = identify(&mut socket, &settings.password)?;
+    let mut done = Vec::new();
+    match event {
+        ObsEvent::Test => return Ok(format!("Connected to obs-websocket {}.", version)),
+        ObsEvent::StreamStarted => {
+            if let Some(scene) = Some(settings.start_scene.trim()).filter(|s| !s.is_empty()) {
+                request(&mut socket, "SetCurrentProgramScene", &format!(r#"{{"sceneName":"{}"}}"#, super::json_escape(scene)))?;
+                done.push(format!("switched to '{}'", scene));
+            }
+            if settings.control_recording {
+                request(&mut socket, "StartRecord", "{}")?;
+                done.push("recording started".to_string());
+            }
+        }
+        ObsEvent::StreamStopped => {
+            // Stop first, so the end of the recording doesn't show the stop scene.
+            if settings.control_recording {
+                request(&mut socket, "StopRecord", "{}")?;
+                done.push("recording stopped".to_string());
+            }
+            if let Some(scene) = Some(settings.stop_scene.trim()).filter(|s| !s.is_empty()) {
+                request(&mut socket, "SetCurrentProgramScene", &format!(r#"{{"sceneName":"{}"}}"#, super::json_escape(scene)))?;
+                done.push(format!("switched to '{}'", scene));
+            }
+        }
+    }
+    let _ = socket.close();
+    Ok(if done.is_empty() { "OBS: nothing to do.".to_string() } else { format!("OBS: {}.", done.join(", ")) })
+}
+
+/// Runs the Hello/Identify handshake and returns the obs-websocket version.
+fn identify(socket: &mut WebSocket, password: &str) -> Result<String> {
+    let hello = socket.read_message()?;
+    if json_field(&hello, "op").as_deref() != Some(&OP_HELLO.to_string()) {
+        bail!("Unexpected greeting, is this obs-websocket?");
+    }
+    let version = json_field(&hello, "obsWebSocketVersion").unwrap_or_default();
+    let authentication = match (json_field(&hello, "challenge"), json_field(&hello, "salt")) {
+        (Some(challenge), Some(salt)) => {
+            if password.is_empty() {
+                bail!("OBS requires a password");
+            }
+            format!(r#","authentication":"{}""#, authentication_string(password, &salt, &challenge))
+        }
+        _ => String::new(),
+    };
+    socket.send_text(&format!(r#"{{"op":{},"d":{{"rpcVersion":{},"eventSubscriptions":0{}}}}}"#, OP_IDENTIFY, RPC_VERSION, authentication))?;
+    // OBS closes the connection instead of answering when the password is wrong.
+    let identified = socket.read_message().map_err(|e| anyhow!("Authentication failed ({})", e))?;
+    if json_field(&identified, "op").as_deref() != Some(&OP_IDENTIFIED.to_string()) {
+        bail!("Unexpected reply to identification");
+    }
+    Ok(version)
+}
+
+/// base64(sha256(base64(sha256(password + salt)) + challenge)), as obs-websocket expects it.
+fn authentication_string(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = super::base64_encode(&super::sha256(format!("{}{}", password, salt).as_bytes()));
+    super::base64_encode(&super::sha256(format!("{}{}", secret, challenge).as_bytes()))
+}
+
+fn request(socket: &mut WebSocket, request_type: &str, data: &str) -> Result<()> {
+    socket.send_text(&format!(
+        r#"{{"op":{},"d":{{"requestType":"{}","requestId":"{}","requestData":{}}}}}"#,
+        OP_REQUEST, request_type, request_type, data
+    ))?;
+    // Skip anything else OBS sends until the response arrives.
+    loop {
+        let message = socket.read_message()?;
+        if json_field(&message, "op").as_deref() != Some(&OP_REQUEST_RESPONSE.to_string())
+            || json_field(&message, "requestId").as_deref() != Some(request_type) {
+            continue;
+        }
+        let code: u32 = json_field(&message, "code").and_then(|c| c.parse().ok()).unwrap_or(0);
+        if json_field(&message, "result").as_deref() == Some("true") || code == OUTPUT_RUNNING || code == OUTPUT_NOT_RUNNING {
+            return Ok(());
+        }
+        let comment = json_field(&message, "comment").unwrap_or_else(|| format!("code {}", code));
+        bail!("{} failed: {}", request_type, comment);
+    }
+}
+
+/// The value of the first `"key":` anywhere in a JSON text, unquoted if it's a string. Enough
+/// for the few flat fields of the obs-websocket messages we look at.
+fn json_field(json: &str, key: &str) -> Option<String> {
+    let start = json.find(&format!("\"{}\"", key))? + key.len() + 2;
+    let rest = json[start..].trim_start().strip_prefix(':')?.trim_start();
+    let Some(string) = rest.strip_prefix('"') else {
+        let end = rest.find([',', '}', ']']).unwrap_or(rest.len());
+        return Some(rest[..end].trim().to_string());
+    };
+    let mut value = String::new();
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    value.push(u32::from_str_radix(&code, 16).ok().and_then(char::from_u32).unwrap_or('?'));
+                }
+                other => value.push(other),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Just enough of a WebSocket client (RFC 6455) for obs-websocket: text messages, no
+/// extensions, and pings answered while waiting for a message.
+struct WebSocket {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl WebSocket {
+    fn connect(address: &str) -> Result<Self> {
+        let addr = address.to_socket_addrs()?.next().ok_or_else(|| anyhow!("Address not found"))?;
+        let stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT).context("Failed to connect, is obs-websocket enabled?")?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let mut writer = stream.try_clone()?;
+        let key = super::base64_encode(&[random_u64().to_ne_bytes(), random_u64().to_ne_bytes()].concat());
+        write!(
+            writer,
+            "GET / HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Protocol: obswebsocket.json\r\n\r\n",
+            address, key
+        )?;
+        let mut reader = BufReader::new(stream);
+        let mut status = String::new();
+        reader.read_line(&mut status)?;
+        if status.split_whitespace().nth(1) != Some("101") {
+            bail!("Not a WebSocket server: {}", status.trim());
+        }
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 {
+                bail!("Connection closed during the handshake");
+            }
+            if header.trim().is_empty() {
+                break;
+            }
+        }
+        Ok(Self { reader, writer })
+    }
+
+    fn send_text(&mut self, text: &str) -> Result<()> {
+        self.send_frame(0x1, text.as_bytes())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.send_frame(0x8, &1000u16.to_be_bytes())
+    }
+
+    /// Client frames have to be masked.
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len @ 126..=0xffff => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        let mask = (random_u64() as u32).to_ne_bytes();
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(byte, mask)| byte ^ mask));
+        self.writer.write_all(&frame)?;
+        Ok(())
+    }
+
+    /// The next text message, put together from its fragments.
+    fn read_message(&mut self) -> Result<String> {
+        let mut message = Vec::new();
+        loop {
+            let mut header = [0u8; 2];
+            self.reader.read_exact(&mut header)?;
+            let fin = header[0] & 0x80 != 0;
+            let opcode = header[0] & 0x0f;
+            let len = match header[1] & 0x7f {
+                126 => {
+                    let mut len = [0u8; 2];
+                    self.reader.read_exact(&mut len)?;
+                    u16::from_be_bytes(len) as u64
+                }
+                127 => {
+                    let mut len = [0u8; 8];
+                    self.reader.read_exact(&mut len)?;
+                    u64::from_be_bytes(len)
+                }
+                len => len as u64,
+            };
+            // Servers don't mask their frames.
+            let mut payload = vec![0u8; len as usize];
+            self.reader.read_exact(&mut payload)?;
+            match opcode {
+                0x8 => {
+                    let reason = payload.get(2..).map(String::from_utf8_lossy).unwrap_or_default();
+                    bail!("Connection closed by OBS{}", if reason.is_empty() { String::new() } else { format!(": {}", reason) });
+                }
+                0x9 => self.send_frame(0xa, &payload)?,
+                0x0..=0x2 => {
+                    message.extend_from_slice(&payload);
+                    if fin {
+                        return Ok(String::from_utf8_lossy(&message).into_owned());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Unpredictable enough for WebSocket keys and masks, without pulling in a random number crate.
+fn random_u64() -> u64 {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos());
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authentication_string_matches_protocol_example() {
+        // The example from the obs-websocket protocol documentation.
+        let auth = authentication_string("supersecretpassword", "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=", "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY=");
+        assert_eq!(auth, "1Ct943GAT+6YQUUX47Ia/ncufilbe6+oD6lY+5kaCu4=");
+    }
+
+    #[test]
+    fn json_field_reads_numbers_and_strings() {
+        let hello = r#"{"op":0,"d":{"obsWebSocketVersion":"5.1.0","authentication":{"challenge":"a\"b","salt":"cA"}}}"#;
+        assert_eq!(json_field(hello, "op").as_deref(), Some("0"));
+        assert_eq!(json_field(hello, "obsWebSocketVersion").as_deref(), Some("5.1.0"));
+        assert_eq!(json_field(hello, "challenge").as_deref(), Some("a\"b"));
+        assert_eq!(json_field(hello, "salt").as_deref(), Some("cA"));
+        assert_eq!(json_field(hello, "missing"), None);
+    }
+}
@@ -0,0 +1,150 @@
+/// An argument of an OSC message. Floats and doubles come out as `Number`, 32 and 64 bit
+/// integers as `Integer`; blobs, time tags and the argument-less types are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Number(f32),
+    Integer(i64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    /// The first number as a level from 0 to 1. Integers are taken to go from 0 to 127, like
+    /// the MIDI values controllers sending integers use.
+    pub fn first_level(&self) -> Option<f32> {
+        self.args.iter().find_map(|arg| match arg {
+            OscArg::Number(value) if value.is_finite() => Some(value.clamp(0.0, 1.0)),
+            OscArg::Integer(value) => Some((*value).clamp(0, 127) as f32 / 127.0),
+            _ => None,
+        })
+    }
+}
+
+/// Reads OSC strings, which are null-terminated and padded to four bytes.
+fn osc_string(data: &[u8], offset: &mut usize) -> Option<String> {
+    let rest = data.get(*offset..)?;
+    let end = rest.iter().position(|&b| b == 0)?;
+    let text = std::str::from_utf8(&rest[..end]).ok()?.to_string();
+    *offset += (end + 4) & !3;
+    Some(text)
+}
+
+/// The messages in an OSC packet, unwrapping bundles. Broken messages are left out.
+pub fn parse_packet(data: &[u8]) -> Vec<OscMessage> {
+    let mut messages = Vec::new();
+    parse_into(data, &mut messages);
+    messages
+}
+
+fn parse_into(data: &[u8], messages: &mut Vec<OscMessage>) {
+    if let Some(mut elements) = data.strip_prefix(b"#bundle\0") {
+        // Skip the time tag; messages are applied as soon as they arrive.
+        elements = elements.get(8..).unwrap_or_default();
+        while elements.len() >= 4 {
+            let size = u32::from_be_bytes([elements[0], elements[1], elements[2], elements[3]]) as usize;
+            let Some(element) = elements.get(4..4 + size) else { return };
+            parse_into(element, messages);
+            elements = &elements[4 + size..];
+        }
+        return;
+    }
+    let mut offset = 0;
+    let Some(address) = osc_string(data, &mut offset).filter(|address| address.starts_with('/')) else { return };
+    // Some senders leave out the type tags of messages without arguments.
+    let types = osc_string(data, &mut offset).unwrap_or_default();
+    let mut args = Vec::new();
+    for tag in types.chars().skip(1) {
+        let size = match tag {
+            'f' | 'i' => 4,
+            'd' | 'h' | 't' => 8,
+            's' | 'S' => {
+                let Some(text) = osc_string(data, &mut offset) else { return };
+                args.push(OscArg::Text(text));
+                continue;
+            }
+            'b' => {
+                let Some(len) = data.get(offset..offset + 4) else { return };
+                offset += 4 + ((u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize + 3) & !3);
+                continue;
+            }
+            _ => 0,
+        };
+        let Some(bytes) = data.get(offset..offset + size) else { return };
+        offset += size;
+        let arg = match tag {
+            'f' => OscArg::Number(f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            'i' => OscArg::Integer(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64),
+            'd' => OscArg::Number(f64::from_be_bytes(bytes.try_into().unwrap()) as f32),
+            'h' => OscArg::Integer(i64::from_be_bytes(bytes.try_into().unwrap())),
+            _ => continue,
+        };
+        args.push(arg);
+    }
+    messages.push(OscMessage { address, args });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An OSC string: null-terminated and padded to four bytes.
+    fn padded(text: &str) -> Vec<u8> {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.resize((text.len() + 4) & !3, 0);
+        bytes
+    }
+
+    fn message(address: &str, types: &str, args: &[&[u8]]) -> Vec<u8> {
+        [padded(address), padded(types), args.concat()].concat()
+    }
+
+    #[test]
+    fn parse_packet_reads_arguments() {
+        let packet = message("/michadame/1", ",fisd", &[&0.5f32.to_be_bytes(), &64i32.to_be_bytes(), &padded("knob"), &2.0f64.to_be_bytes()]);
+        let messages = parse_packet(&packet);
+        assert_eq!(
+            messages,
+            vec![OscMessage {
+                address: "/michadame/1".to_string(),
+                args: vec![OscArg::Number(0.5), OscArg::Integer(64), OscArg::Text("knob".to_string()), OscArg::Number(2.0)],
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_packet_unwraps_bundles() {
+        let first = message("/a", ",i", &[&1i32.to_be_bytes()]);
+        let second = message("/b", ",", &[]);
+        let mut packet = [padded("#bundle"), vec![0; 8]].concat();
+        for element in [&first, &second] {
+            packet.extend_from_slice(&(element.len() as u32).to_be_bytes());
+            packet.extend_from_slice(element);
+        }
+        let addresses: Vec<_> = parse_packet(&packet).into_iter().map(|message| message.address).collect();
+        assert_eq!(addresses, ["/a", "/b"]);
+    }
+
+    #[test]
+    fn parse_packet_drops_broken_messages() {
+        assert!(parse_packet(b"").is_empty());
+        assert!(parse_packet(&padded("no-slash")).is_empty());
+        // Announces a float but ends before it.
+        assert!(parse_packet(&message("/a", ",f", &[&[0, 0]])).is_empty());
+    }
+
+    #[test]
+    fn first_level_scales_integers() {
+        let level = |args| OscMessage { address: "/a".to_string(), args }.first_level();
+        assert_eq!(level(vec![OscArg::Number(0.25)]), Some(0.25));
+        assert_eq!(level(vec![OscArg::Number(2.0)]), Some(1.0));
+        assert_eq!(level(vec![OscArg::Integer(127)]), Some(1.0));
+        assert_eq!(level(vec![OscArg::Integer(-5)]), Some(0.0));
+        assert_eq!(level(vec![OscArg::Text("x".to_string()), OscArg::Integer(0)]), Some(0.0));
+        assert_eq!(level(vec![OscArg::Number(f32::NAN)]), None);
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::{Context, Result};
+use eframe::egui;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 8081;
+
+const PAGE: &str = include_str!("../../assets/remote.html");
+/// Connections served at once, here and by the MJPEG preview. A phone or two polling the status,
+/// or a few viewers, never get near this; more connections are dropped rather than each getting a thread.
+pub(crate) const MAX_CONNECTIONS: usize = 16;
+
+/// Commands from the remote page and the command port (`super::command`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteCommand {
+    StartStream,
+    StopStream,
+    ToggleStream,
+    /// Applies the look with this name, ignoring case, or does nothing for an empty name.
+    ApplyLook(String),
+    /// Loopback volume, 1.0 being 100%.
+    SetVolume(f32),
+    Screenshot,
+    NextFilter,
+    SetFilter(crate::devices::filter_type::CrtFilter),
+    /// Switches to the scene with this name, ignoring case, or this number counting from 1.
+    SwitchScene(String),
+    StartRecording,
+    StopRecording,
+    ToggleRecording,
+}
+
+/// What the remote page shows, kept up to date by the app.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RemoteStatus {
+    pub streaming: bool,
+    pub look: Option<String>,
+    pub looks: Vec<String>,
+    pub volume: f32,
+    pub message: String,
+}
+
+impl RemoteStatus {
+    fn to_json(&self) -> String {
+        let looks: Vec<String> = self.looks.iter().map(|look| format!("\"{}\"", super::json_escape(look))).collect();
+        format!(
+            r#"{{"streaming":{},"look":{},"looks":[{}],"volume":{},"message":"{}"}}"#,
+            self.streaming,
+            self.look.as_ref().map_or("null".to_string(), |look| format!("\"{}\"", super::json_escape(look))),
+            looks.join(","),
+            self.volume,
+            super::json_escape(&self.message),
+        )
+    }
+}
+
+/// Only with a password is the server reachable from other machines; without one anybody on
+/// the network could stop the stream.
+pub fn serves_lan(credentials: Option<&(String, String)>) -> bool {
+    credentials.is_some_and(|(_, password)| !password.is_empty())
+}
+
+/// Counts a connection for as long as it's being served.
+pub(crate) struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    pub(crate) fn take(connections: &Arc<AtomicUsize>) -> Option<Self> {
+        connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| (count < MAX_CONNECTIONS).then_some(count + 1))
+            .ok()
+            .map(|_| Self(connections.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A small web page for controlling the stream from a phone or another computer on the LAN.
+/// Button presses arrive as `RemoteCommand`s on the returned channel.
+pub struct RemoteServer {
+    status: Arc<Mutex<RemoteStatus>>,
+    stop_flag: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RemoteServer {
+    pub fn start(port: u16, credentials: Option<(String, String)>, ctx: &egui::Context) -> Result<(Self, crossbeam_channel::Receiver<RemoteCommand>)> {
+        let local_only = !serves_lan(credentials.as_ref());
+        let host = if local_only { "127.0.0.1" } else { "0.0.0.0" };
+        let listener = TcpListener::bind((host, port)).with_context(|| format!("Failed to listen on port {}", port))?;
+        listener.set_nonblocking(true).context("Failed to configure listener")?;
+        tracing::info!(host, port, "Remote control server listening");
+
+        let status = Arc::new(Mutex::new(RemoteStatus::default()));
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let (command_tx, command_rx) = crossbeam_channel::unbounded();
+        let thread = thread::spawn({
+            let status = status.clone();
+            let stop_flag = stop_flag.clone();
+            let ctx = ctx.clone();
+            let expected_auth = credentials.map(|(user, pass)| format!("Basic {}", super::base64_encode(format!("{}:{}", user, pass).as_bytes())));
+            let connections = Arc::new(AtomicUsize::new(0));
+            move || {
+                while !stop_flag.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, addr)) => {
+                            let Some(slot) = ConnectionSlot::take(&connections) else {
+                                tracing::debug!(%addr, "Too many remote control connections, dropping one");
+                                continue;
+                            };
+                            let status = status.clone();
+                            let command_tx = command_tx.clone();
+                            let ctx = ctx.clone();
+                            let expected_auth = expected_auth.clone();
+                            thread::spawn(move || {
+                                let _slot = slot;
+                                if let Err(e) = serve_request(stream, &status, expected_auth.as_deref(), local_only, |command| {
+                                    tracing::info!(%addr, ?command, "Remote control command");
+                                    let _ = command_tx.send(command);
+                                    ctx.request_repaint();
+                                }) {
+                                    tracing::debug!(%addr, "Remote control request failed: {}", e);
+                                }
+                            });
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(50)),
+                        Err(e) => tracing::error!("Remote control accept failed: {}", e),
+                    }
+                }
+            }
+        });
+        Ok((Self { status, stop_flag, thread: Some(thread) }, command_rx))
+    }
+
+    pub fn set_status(&self, status: RemoteStatus) {
+        *self.status.lock().unwrap() = status;
+    }
+
+    pub fn stop(mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn serve_request(
+    stream: TcpStream,
+    status: &Mutex<RemoteStatus>,
+    expected_auth: Option<&str>,
+    local_only: bool,
+    on_command: impl FnOnce(RemoteCommand),
+) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut authorized = expected_auth.is_none();
+    let (mut host, mut origin) = (None, None);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("authorization") && expected_auth == Some(value) {
+            authorized = true;
+        } else if name.eq_ignore_ascii_case("host") {
+            host = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("origin") {
+            origin = Some(value.to_string());
+        }
+    }
+    // A page on another site can't read the answers, but it could still send commands, and
+    // through DNS rebinding reach a server that only listens locally.
+    if local_only && !host.as_deref().is_some_and(is_local_host) {
+        return respond(&mut stream, "403 Forbidden", "text/plain", "Forbidden");
+    }
+    if !authorized {
+        stream.write_all(b"HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"Michadame\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")?;
+        return Ok(());
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or("/"));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let param = |key: &str| {
+        query.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| percent_decode(value))
+    };
+    if method == "POST" && !is_same_origin(origin.as_deref(), host.as_deref()) {
+        return respond(&mut stream, "403 Forbidden", "text/plain", "Cross-origin requests are not allowed");
+    }
+    let command = match (method, path) {
+        ("GET", "/") => return respond(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE),
+        ("GET", "/api/status") => {
+            let json = status.lock().unwrap().to_json();
+            return respond(&mut stream, "200 OK", "application/json", &json);
+        }
+        ("POST", "/api/start") => Some(RemoteCommand::StartStream),
+        ("POST", "/api/stop") => Some(RemoteCommand::StopStream),
+        ("POST", "/api/screenshot") => Some(RemoteCommand::Screenshot),
+        ("POST", "/api/filter") => Some(RemoteCommand::NextFilter),
+        ("POST", "/api/look") => param("name").map(RemoteCommand::ApplyLook),
+        ("POST", "/api/volume") => param("value").and_then(|v| v.parse().ok()).map(RemoteCommand::SetVolume),
+        _ => return respond(&mut stream, "404 Not Found", "text/plain", "Not found"),
+    };
+    match command {
+        Some(command) => {
+            on_command(command);
+            respond(&mut stream, "200 OK", "text/plain", "OK")
+        }
+        None => respond(&mut stream, "400 Bad Request", "text/plain", "Missing or invalid parameter"),
+    }
+}
+
+/// Whether a `Host` header names this machine, with or without a port.
+fn is_local_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or(""),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    matches!(name.to_ascii_lowercase().as_str(), "localhost" | "127.0.0.1" | "::1")
+}
+
+/// Browsers send `Origin` with every POST, so one that doesn't match the host the request went
+/// to comes from another site. Requests without it come from tools like curl, not pages.
+fn is_same_origin(origin: Option<&str>, host: Option<&str>) -> bool {
+    let Some(origin) = origin else { return true };
+    let origin_host = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+    host.is_some_and(|host| origin_host.eq_ignore_ascii_case(host))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    )?;
+    Ok(())
+}
+
+/// Decodes `%XX` escapes and `+` in a query string value.
+fn percent_decode(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut input = value.bytes();
+    while let Some(byte) = input.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = input.by_ref().take(2).collect();
+                match std::str::from_utf8(&hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(decoded) => bytes.push(decoded),
+                    None => {
+                        bytes.push(b'%');
+                        bytes.extend_from_slice(&hex);
+                    }
+                }
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cross_origin_posts_are_rejected() {
+        assert!(is_same_origin(Some("http://192.168.1.5:8081"), Some("192.168.1.5:8081")));
+        assert!(is_same_origin(None, Some("localhost:8081")));
+        assert!(!is_same_origin(Some("https://evil.example"), Some("localhost:8081")));
+        assert!(!is_same_origin(Some("http://localhost:8081"), None));
+    }
+
+    #[test]
+    fn local_hosts() {
+        assert!(is_local_host("localhost:8081"));
+        assert!(is_local_host("127.0.0.1"));
+        assert!(is_local_host("[::1]:8081"));
+        assert!(!is_local_host("rebind.example:8081"));
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("Super+Metroid%20%28NTSC%29"), "Super Metroid (NTSC)");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
@@ -0,0 +1,120 @@
+use eframe::egui;
+use std::time::{Duration, Instant};
+
+/// Most toasts shown at once; older ones make room for new ones.
+pub const MAX_TOASTS: usize = 4;
+
+/// Ordered by severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NotificationLevel {
+    /// Progress and confirmations of what was asked for. Only shown in the status line.
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl NotificationLevel {
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            NotificationLevel::Info => egui::Color32::GRAY,
+            NotificationLevel::Success => egui::Color32::LIGHT_GREEN,
+            NotificationLevel::Warning => egui::Color32::YELLOW,
+            NotificationLevel::Error => egui::Color32::LIGHT_RED,
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            NotificationLevel::Info => "ℹ",
+            NotificationLevel::Success => "✔",
+            NotificationLevel::Warning => "⚠",
+            NotificationLevel::Error => "✖",
+        }
+    }
+
+    /// How long the toast stays up. Errors stay longer so they aren't missed.
+    fn toast_duration(&self) -> Duration {
+        match self {
+            NotificationLevel::Info | NotificationLevel::Success => Duration::from_secs(4),
+            NotificationLevel::Warning => Duration::from_secs(8),
+            NotificationLevel::Error => Duration::from_secs(15),
+        }
+    }
+}
+
+/// Something the user can do straight from a notification, usually trying again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationAction {
+    StartStream,
+    RescanDevices,
+    ScanFormats,
+    StartRecording,
+    StartPreviewServer,
+    MeasureLatency,
+}
+
+impl NotificationAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            NotificationAction::RescanDevices => "Rescan",
+            _ => "Retry",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub message: String,
+    pub action: Option<NotificationAction>,
+    pub created: Instant,
+}
+
+impl Notification {
+    pub fn new(level: NotificationLevel, message: impl Into<String>) -> Self {
+        Self { level, message: message.into(), action: None, created: Instant::now() }
+    }
+
+    pub fn info(message: impl Into<String>) -> Self {
+        Self::new(NotificationLevel::Info, message)
+    }
+
+    pub fn success(message: impl Into<String>) -> Self {
+        Self::new(NotificationLevel::Success, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(NotificationLevel::Warning, message)
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(NotificationLevel::Error, message)
+    }
+
+    pub fn with_action(mut self, action: NotificationAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    pub fn log(&self) {
+        match self.level {
+            NotificationLevel::Error => tracing::error!("{}", self.message),
+            NotificationLevel::Warning => tracing::warn!("{}", self.message),
+            NotificationLevel::Info | NotificationLevel::Success => tracing::info!("{}", self.message),
+        }
+    }
+
+    pub fn is_toast(&self) -> bool {
+        self.level != NotificationLevel::Info
+    }
+
+    pub fn expired(&self) -> bool {
+        self.created.elapsed() > self.level.toast_duration()
+    }
+
+    /// Time until the toast expires, for scheduling the repaint that removes it.
+    pub fn remaining(&self) -> Duration {
+        self.level.toast_duration().saturating_sub(self.created.elapsed())
+    }
+}
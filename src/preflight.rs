@@ -0,0 +1,171 @@
+use crate::{app::AppState, config, devices, devices::audio::AudioRouting, notifications::Notification, video::types::SourceKind};
+use eframe::egui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreflightFix {
+    Rescan,
+    PickDefaultFormat,
+    PickDefaultAudio,
+}
+
+impl PreflightFix {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            PreflightFix::Rescan => "Rescan Devices",
+            PreflightFix::PickDefaultFormat => "Pick Default Format",
+            PreflightFix::PickDefaultAudio => "Pick Default Audio",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreflightIssue {
+    pub message: String,
+    pub fix: Option<PreflightFix>,
+    /// Blocking issues would make the stream fail; the others only deserve a warning.
+    pub blocking: bool,
+}
+
+impl PreflightIssue {
+    fn blocking(message: impl Into<String>, fix: Option<PreflightFix>) -> Self {
+        Self { message: message.into(), fix, blocking: true }
+    }
+
+    fn warning(message: impl Into<String>, fix: Option<PreflightFix>) -> Self {
+        Self { message: message.into(), fix, blocking: false }
+    }
+}
+
+/// Validates everything `start_stream` needs, so problems can be fixed before anything is set up.
+pub fn run_checks(state: &AppState) -> Vec<PreflightIssue> {
+    let mut issues = Vec::new();
+    if state.device_scan_receiver.is_some() {
+        issues.push(PreflightIssue::blocking("Devices are still being scanned.", None));
+        return issues;
+    }
+
+    match state.source_kind {
+        SourceKind::Capture => check_capture_device(state, &mut issues),
+        SourceKind::Network => {
+            if state.network_source_url.trim().is_empty() {
+                issues.push(PreflightIssue::blocking("No stream URL entered.", None));
+            }
+        }
+        SourceKind::File => {
+            if !std::path::Path::new(&state.file_source_path).is_file() {
+                issues.push(PreflightIssue::blocking(format!("File '{}' not found.", state.file_source_path), None));
+            }
+        }
+        SourceKind::Screen => {}
+    }
+
+    check_audio_devices(state, &mut issues);
+    issues
+}
+
+fn check_capture_device(state: &AppState, issues: &mut Vec<PreflightIssue>) {
+    let device = &state.selected_video_device;
+    if device.is_empty() || !std::path::Path::new(device).exists() {
+        issues.push(PreflightIssue::blocking("Selected video device doesn't exist (was it unplugged?).", Some(PreflightFix::Rescan)));
+        return;
+    }
+    if let Err(e) = std::fs::File::open(device) {
+        let message = if e.kind() == std::io::ErrorKind::PermissionDenied {
+            format!("No permission to open {}. Add your user to the 'video' group and log in again.", device)
+        } else {
+            format!("Can't open {}: {}", device, e)
+        };
+        issues.push(PreflightIssue::blocking(message, None));
+        return;
+    }
+    let users = devices::video::find_device_users(device);
+    if !users.is_empty() {
+        let names: Vec<String> = users.iter().map(|(pid, name)| format!("{} ({})", name, pid)).collect();
+        issues.push(PreflightIssue::warning(format!("{} is already open in: {}. Streaming will likely fail.", device, names.join(", ")), None));
+    }
+
+    let format_valid = state.supported_formats.get(state.selected_format_index).is_some_and(|format| {
+        format.resolutions.iter().any(|r| (r.width, r.height) == state.selected_resolution)
+    });
+    if !format_valid {
+        issues.push(PreflightIssue::blocking("No video format/resolution selected.", Some(PreflightFix::PickDefaultFormat)));
+        return;
+    }
+
+    let format = &state.supported_formats[state.selected_format_index];
+    let needed = crate::video::types::estimated_bandwidth_mbps(&format.fourcc, state.selected_resolution, state.selected_framerate);
+    if let (Some(needed), Some(speed)) = (needed, state.video_usb_speed) {
+        let usable = crate::video::types::usable_usb_bandwidth_mbps(speed);
+        if needed > usable {
+            issues.push(PreflightIssue::warning(
+                format!("The selected format needs ~{:.0} Mbit/s, more than {} can carry (~{:.0} Mbit/s).", needed, crate::video::types::usb_speed_name(speed), usable),
+                None,
+            ));
+        }
+    }
+}
+
+fn check_audio_devices(state: &AppState, issues: &mut Vec<PreflightIssue>) {
+    if state.pulse_available {
+        let source_valid = state.selected_pulse_source_name.as_ref()
+            .is_some_and(|name| state.pulse_sources.iter().any(|(_, n)| n == name));
+        if !source_valid {
+            issues.push(PreflightIssue::blocking("No valid PulseAudio source selected.", Some(PreflightFix::PickDefaultAudio)));
+        }
+        if state.audio_routing == AudioRouting::Loopback {
+            let sink_valid = state.selected_pulse_sink_name.as_ref()
+                .is_some_and(|name| state.pulse_sinks.iter().any(|(_, n)| n == name));
+            if !sink_valid {
+                issues.push(PreflightIssue::blocking("No valid PulseAudio sink selected.", Some(PreflightFix::PickDefaultAudio)));
+            }
+        }
+    } else if state.alsa_enabled {
+        let capture_valid = state.selected_alsa_capture.as_ref()
+            .is_some_and(|dev| state.alsa_captures.iter().any(|(_, d)| d == dev));
+        let playback_valid = state.selected_alsa_playback.as_ref()
+            .is_some_and(|dev| state.alsa_playbacks.iter().any(|(_, d)| d == dev));
+        if !capture_valid || !playback_valid {
+            issues.push(PreflightIssue::blocking("ALSA capture or playback device not selected.", Some(PreflightFix::PickDefaultAudio)));
+        }
+    }
+}
+
+pub fn apply_fix(state: &mut AppState, fix: PreflightFix, ctx: &egui::Context) {
+    match fix {
+        PreflightFix::Rescan => {
+            state.start_device_scan(ctx);
+            state.notify(Notification::info("Re-scanning devices..."));
+        }
+        PreflightFix::PickDefaultFormat => {
+            if state.selected_video_device.is_empty() {
+                return;
+            }
+            // The scan runs on a worker; `handle_format_scan_result` picks the defaults.
+            state.pick_default_format_pending = true;
+            let device = state.selected_video_device.clone();
+            state.select_video_device(&device, ctx);
+        }
+        PreflightFix::PickDefaultAudio => {
+            if state.pulse_available {
+                let source_valid = state.selected_pulse_source_name.as_ref()
+                    .is_some_and(|name| state.pulse_sources.iter().any(|(_, n)| n == name));
+                if !source_valid {
+                    state.selected_pulse_source_name = state.pulse_sources.first().map(|(_, name)| name.clone());
+                }
+                let sink_valid = state.selected_pulse_sink_name.as_ref()
+                    .is_some_and(|name| state.pulse_sinks.iter().any(|(_, n)| n == name));
+                if !sink_valid {
+                    state.selected_pulse_sink_name = state.pulse_sinks.first().map(|(_, name)| name.clone());
+                }
+            } else {
+                if state.selected_alsa_capture.is_none() {
+                    state.selected_alsa_capture = state.alsa_captures.first().map(|(_, dev)| dev.clone());
+                }
+                if state.selected_alsa_playback.is_none() {
+                    state.selected_alsa_playback = state.alsa_playbacks.first().map(|(_, dev)| dev.clone());
+                }
+            }
+            config::mark_dirty(state);
+        }
+    }
+}
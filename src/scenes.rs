@@ -0,0 +1,41 @@
+use crate::{app::AppState, video::recorder::CropRect, video::types::SourceKind};
+use serde::{Deserialize, Serialize};
+
+/// Scenes bound to the number keys 1-9.
+pub const MAX_HOTKEY_SCENES: usize = 9;
+
+/// A named view of a capture device: which part of the frame to show, the look to apply and
+/// which overlays are on. Switching between scenes of the same device keeps the capture running.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Scene {
+    pub name: String,
+    /// The capture device, or `None` to keep the current source.
+    pub device: Option<String>,
+    /// Part of the source frame to show, in source pixels.
+    pub crop: Option<CropRect>,
+    /// Name of a saved look, or `None` to keep the current filters.
+    pub look: Option<String>,
+    /// The bezel overlay image, empty for none, or `None` to keep the current one.
+    pub overlay: Option<String>,
+    pub overlay_enabled: bool,
+    pub text_overlay_enabled: bool,
+}
+
+impl Scene {
+    pub fn from_state(state: &AppState, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            device: (state.source_kind == SourceKind::Capture).then(|| state.selected_video_device.clone()),
+            crop: state.display_crop_enabled.then_some(state.display_crop),
+            look: state.active_look.clone(),
+            overlay: Some(state.overlay_path.clone()),
+            overlay_enabled: state.overlay_enabled,
+            text_overlay_enabled: state.text_overlay_enabled,
+        }
+    }
+
+    /// True if switching to this scene means restarting the capture on another device.
+    pub fn needs_restart(&self, state: &AppState) -> bool {
+        self.device.as_ref().is_some_and(|device| state.source_kind != SourceKind::Capture || *device != state.selected_video_device)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::config::MichadameConfig;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::PathBuf;
+
+/// Passwords, kept out of the config file in a file only the user can read, so they don't end
+/// up in shared configs, dotfile repositories or screenshots of the config.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Secrets {
+    pub preview_password: Option<String>,
+    pub remote_password: Option<String>,
+    pub obs_password: Option<String>,
+}
+
+impl Secrets {
+    /// The passwords in `cfg`, which carries them in memory but never writes them out.
+    pub fn from_config(cfg: &MichadameConfig) -> Self {
+        let set = |secret: &Option<String>| secret.clone().filter(|secret| !secret.is_empty());
+        Self { preview_password: set(&cfg.preview_password), remote_password: set(&cfg.remote_password), obs_password: set(&cfg.obs_password) }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Next to the confy config file, like looks and assets.
+fn secrets_path() -> Result<PathBuf> {
+    let config_path = confy::get_configuration_file_path("michadame", None).context("Failed to locate config directory")?;
+    Ok(config_path.parent().context("Config path has no parent directory")?.join("secrets.toml"))
+}
+
+/// The saved passwords, or none if there's no secrets file yet.
+pub fn load() -> Secrets {
+    let Ok(path) = secrets_path() else { return Secrets::default() };
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+            tracing::error!("'{}' is not a valid secrets file: {}", path.display(), e);
+            Secrets::default()
+        }),
+        Err(_) => Secrets::default(),
+    }
+}
+
+/// Writes the passwords, readable only by the user. Without any password and no file yet,
+/// nothing is written.
+pub fn store(secrets: &Secrets) -> Result<()> {
+    let path = secrets_path()?;
+    if secrets.is_empty() && !path.exists() {
+        return Ok(());
+    }
+    let contents = toml::to_string(secrets).context("Failed to serialize secrets")?;
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)
+        .with_context(|| format!("Failed to open '{}'", path.display()))?;
+    // `mode` only applies to new files.
+    file.set_permissions(std::fs::Permissions::from_mode(0o600)).with_context(|| format!("Failed to restrict '{}'", path.display()))?;
+    file.write_all(contents.as_bytes()).with_context(|| format!("Failed to write '{}'", path.display()))
+}
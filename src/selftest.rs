@@ -0,0 +1,145 @@
+use crate::devices;
+use eframe::glow::{self, HasContext};
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+}
+
+impl CheckStatus {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            CheckStatus::Ok => "OK",
+            CheckStatus::Warning => "WARN",
+            CheckStatus::Failed => "FAIL",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SystemCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl SystemCheck {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self { name, status, detail: detail.into() }
+    }
+}
+
+/// Describes the GL context. Must run on the thread that owns it, so it's gathered at startup.
+/// The filter shaders are compiled before this runs, so getting here means they compiled.
+pub fn describe_gl(gl: &glow::Context) -> String {
+    unsafe {
+        format!(
+            "{} on {} ({}), GLSL {}; filter shaders compiled",
+            gl.get_parameter_string(glow::VERSION),
+            gl.get_parameter_string(glow::RENDERER),
+            gl.get_parameter_string(glow::VENDOR),
+            gl.get_parameter_string(glow::SHADING_LANGUAGE_VERSION),
+        )
+    }
+}
+
+/// Runs every check. Blocks on PulseAudio and external tools, so call it off the UI thread.
+pub fn run_system_check(gl_info: Option<String>) -> Vec<SystemCheck> {
+    let mut checks = Vec::new();
+    check_video_devices(&mut checks);
+    checks.push(match devices::audio::find_pulse_devices() {
+        Ok((sources, sinks)) => SystemCheck::new("PulseAudio", CheckStatus::Ok, format!("{} source(s), {} sink(s)", sources.len(), sinks.len())),
+        Err(e) => SystemCheck::new("PulseAudio", CheckStatus::Warning, format!("Not reachable ({}), only ALSA is available", e)),
+    });
+    checks.push(match gl_info {
+        Some(info) => SystemCheck::new("OpenGL", CheckStatus::Ok, info),
+        None => SystemCheck::new("OpenGL", CheckStatus::Failed, "No OpenGL context, GPU filters are unavailable"),
+    });
+    check_ffmpeg_libraries(&mut checks);
+
+    for (tool, args, purpose, status) in [
+        ("v4l2-ctl", &["--version"][..], "format detection", CheckStatus::Failed),
+        ("pkexec", &["--version"][..], "USB reset", CheckStatus::Warning),
+        ("usbreset", &["--help"][..], "USB reset", CheckStatus::Warning),
+        ("uhubctl", &["-v"][..], "USB power cycling", CheckStatus::Warning),
+        ("ffmpeg", &["-version"][..], "recording", CheckStatus::Warning),
+        ("parec", &["--version"][..], "latency measurement and the loudness meter", CheckStatus::Warning),
+        ("gst-launch-1.0", &["--version"][..], "screen capture", CheckStatus::Warning),
+    ] {
+        checks.push(check_tool(tool, args, purpose, status));
+    }
+    checks
+}
+
+fn check_video_devices(checks: &mut Vec<SystemCheck>) {
+    let devices = match devices::video::find_video_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            checks.push(SystemCheck::new("Video devices", CheckStatus::Failed, format!("{:#}", e)));
+            return;
+        }
+    };
+    if devices.is_empty() {
+        checks.push(SystemCheck::new("Video devices", CheckStatus::Warning, "No /dev/video* devices found"));
+        return;
+    }
+    for device in devices {
+        let check = match std::fs::File::open(&device) {
+            Ok(_) => SystemCheck::new("Video device", CheckStatus::Ok, format!("{} is readable", device)),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => SystemCheck::new(
+                "Video device",
+                CheckStatus::Failed,
+                format!("{}: permission denied, add your user to the 'video' group", device),
+            ),
+            Err(e) => SystemCheck::new("Video device", CheckStatus::Warning, format!("{}: {}", device, e)),
+        };
+        checks.push(check);
+    }
+}
+
+fn check_ffmpeg_libraries(checks: &mut Vec<SystemCheck>) {
+    if let Err(e) = ffmpeg_next::init() {
+        checks.push(SystemCheck::new("FFmpeg libraries", CheckStatus::Failed, format!("Failed to initialize: {}", e)));
+        return;
+    }
+    use ffmpeg_next::codec::Id;
+    let missing: Vec<&str> = [(Id::MJPEG, "mjpeg"), (Id::H264, "h264"), (Id::RAWVIDEO, "rawvideo")]
+        .into_iter()
+        .filter(|(id, _)| ffmpeg_next::decoder::find(*id).is_none())
+        .map(|(_, name)| name)
+        .collect();
+    checks.push(if missing.is_empty() {
+        SystemCheck::new("FFmpeg decoders", CheckStatus::Ok, "mjpeg, h264 and rawvideo available")
+    } else {
+        SystemCheck::new("FFmpeg decoders", CheckStatus::Failed, format!("Missing: {}", missing.join(", ")))
+    });
+}
+
+fn check_tool(tool: &'static str, args: &[&str], purpose: &str, missing_status: CheckStatus) -> SystemCheck {
+    // Only whether it runs matters; some tools exit non-zero for --help/--version.
+    match Command::new(tool).args(args).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status() {
+        Ok(_) => SystemCheck::new(tool, CheckStatus::Ok, "installed"),
+        Err(_) => SystemCheck::new(tool, missing_status, format!("not found, needed for {}", purpose)),
+    }
+}
+
+/// Plain-text report to paste into a bug report.
+pub fn format_report(checks: &[SystemCheck]) -> String {
+    let mut report = format!("Michadame {} system check\n", env!("CARGO_PKG_VERSION"));
+    if let Ok(release) = std::fs::read_to_string("/etc/os-release") {
+        if let Some(name) = release.lines().find_map(|l| l.strip_prefix("PRETTY_NAME=")) {
+            report.push_str(&format!("OS: {}\n", name.trim_matches('"')));
+        }
+    }
+    if let Ok(kernel) = std::fs::read_to_string("/proc/sys/kernel/osrelease") {
+        report.push_str(&format!("Kernel: {}\n", kernel.trim()));
+    }
+    report.push('\n');
+    for check in checks {
+        report.push_str(&format!("[{:>4}] {}: {}\n", check.status.to_string(), check.name, check.detail));
+    }
+    report
+}
@@ -0,0 +1,234 @@
+use crate::{config::MichadameConfig, selftest, video::ffmpeg_log};
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Log lines kept for support bundles.
+const MAX_LOG_LINES: usize = 2000;
+
+static LOG_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Writes log output to stdout and keeps the most recent lines for support bundles.
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = strip_ansi(&String::from_utf8_lossy(buf));
+        let mut lines = LOG_LINES.lock().unwrap();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            if lines.len() == MAX_LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+        drop(lines);
+        std::io::stdout().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
+
+/// Removes the terminal color codes of the log formatter.
+fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI sequences end with a letter, e.g. `ESC[2m`.
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Masks what identifies a particular device in its name, e.g. serial numbers in PulseAudio
+/// and udev names, keeping the model so the report still says which card it was.
+fn sanitize_device(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for part in name.split_inclusive(['-', '_', '.', ':', '/', ' ']) {
+        let token = part.trim_end_matches(['-', '_', '.', ':', '/', ' ']);
+        let digits = token.chars().filter(char::is_ascii_digit).count();
+        let hex = token.chars().all(|c| c.is_ascii_hexdigit());
+        if token.len() >= 6 && digits >= 2 && (hex || digits * 2 >= token.len()) {
+            result.push_str("XXXX");
+            result.push_str(&part[token.len()..]);
+        } else {
+            result.push_str(part);
+        }
+    }
+    result
+}
+
+fn hide(secret: &mut Option<String>) {
+    if secret.as_ref().is_some_and(|secret| !secret.is_empty()) {
+        *secret = Some("<hidden>".to_string());
+    }
+}
+
+/// The config as TOML, without passwords or device serial numbers, and with the home
+/// directory, which usually holds the user name, shortened to `~`.
+pub fn sanitized_config(mut cfg: MichadameConfig) -> Result<String> {
+    for device in [
+        &mut cfg.video_device,
+        &mut cfg.usb_device,
+        &mut cfg.pulse_source,
+        &mut cfg.pulse_sink,
+        &mut cfg.monitor_output_sink,
+        &mut cfg.alsa_capture_device,
+        &mut cfg.alsa_playback_device,
+        &mut cfg.midi_device,
+        &mut cfg.record_mic_source,
+    ] {
+        *device = device.as_deref().map(sanitize_device);
+    }
+    for scene in cfg.scenes.iter_mut().flatten() {
+        scene.device = scene.device.as_deref().map(sanitize_device);
+    }
+    // Stream URLs can carry credentials.
+    for secret in [&mut cfg.preview_password, &mut cfg.remote_password, &mut cfg.obs_password, &mut cfg.network_source_url] {
+        hide(secret);
+    }
+    let text = toml::to_string(&cfg).context("Failed to serialize the config")?;
+    Ok(match std::env::var("HOME") {
+        Ok(home) if !home.is_empty() => text.replace(&home, "~"),
+        _ => text,
+    })
+}
+
+/// CRC-32 as used by zip.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Current local time in the MS-DOS format of zip headers, as (time, date).
+fn dos_date_time() -> (u16, u16) {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    let time = ((tm.tm_hour << 11) | (tm.tm_min << 5) | (tm.tm_sec / 2)) as u16;
+    let date = (((tm.tm_year - 80).max(0) << 9) | ((tm.tm_mon + 1) << 5) | tm.tm_mday) as u16;
+    (time, date)
+}
+
+/// Writes `files` into an uncompressed zip archive. The bundle is a few hundred KB of text,
+/// so compression isn't worth a dependency.
+fn write_zip(path: &Path, files: &[(&str, Vec<u8>)]) -> Result<()> {
+    let (time, date) = dos_date_time();
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in files {
+        let offset = archive.len() as u32;
+        let (crc, size, name_len) = (crc32(data), data.len() as u32, name.len() as u16);
+        // Version 2.0, UTF-8 names, stored.
+        let common = [&20u16.to_le_bytes()[..], &0x0800u16.to_le_bytes(), &0u16.to_le_bytes(), &time.to_le_bytes(), &date.to_le_bytes(), &crc.to_le_bytes(), &size.to_le_bytes(), &size.to_le_bytes(), &name_len.to_le_bytes(), &0u16.to_le_bytes()].concat();
+        archive.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        archive.extend_from_slice(&common);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(data);
+
+        directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&common);
+        // Comment length, disk number, internal and external attributes.
+        directory.extend_from_slice(&[0u8; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let (directory_offset, directory_size, count) = (archive.len() as u32, directory.len() as u32, files.len() as u16);
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    archive.extend_from_slice(&[0u8; 4]);
+    archive.extend_from_slice(&count.to_le_bytes());
+    archive.extend_from_slice(&count.to_le_bytes());
+    archive.extend_from_slice(&directory_size.to_le_bytes());
+    archive.extend_from_slice(&directory_offset.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+    std::fs::write(path, archive).with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// Zips the recent log, the FFmpeg log, the sanitized config, a fresh system check and
+/// `diagnostics` (see `AppState::diagnostics_report`) into `dir`, for attaching to a bug
+/// report. A config that couldn't be serialized is replaced by a note with the error, since
+/// that may well be the bug. Runs the system check, so it belongs on a worker thread.
+pub fn create_bundle(dir: &Path, config: Result<String>, diagnostics: String, gl_info: Option<String>) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    let system_check = selftest::format_report(&selftest::run_system_check(gl_info));
+    let log = LOG_LINES.lock().unwrap().iter().map(|line| format!("{}\n", line)).collect::<String>();
+    let ffmpeg_log = ffmpeg_log::recent_lines().iter().map(|(level, line)| format!("[{}] {}\n", level.to_string(), line.trim_end())).collect::<String>();
+    let config = match config {
+        Ok(config) => ("config.toml", config.into_bytes()),
+        Err(e) => ("config-error.txt", format!("The config could not be included: {:#}\n", e).into_bytes()),
+    };
+    let files = [
+        ("michadame.log", log.into_bytes()),
+        ("ffmpeg.log", ffmpeg_log.into_bytes()),
+        config,
+        ("system-check.txt", system_check.into_bytes()),
+        ("diagnostics.txt", diagnostics.into_bytes()),
+    ];
+    let (date, time) = crate::video::recorder::local_date_time();
+    let path = crate::video::recorder::unique_output_path(dir, &format!("michadame-support-{}_{}", date, time), "zip");
+    write_zip(&path, &files)?;
+    tracing::info!(path = %path.display(), "Created support bundle");
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn write_zip_layout() {
+        let path = std::env::temp_dir().join(format!("michadame-test-{}.zip", std::process::id()));
+        write_zip(&path, &[("a.txt", b"hello".to_vec()), ("b.txt", Vec::new())]).unwrap();
+        let zip = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // First local header, its name and its data.
+        assert_eq!(u32_at(&zip, 0), 0x0403_4b50);
+        assert_eq!(u32_at(&zip, 14), crc32(b"hello"));
+        assert_eq!(u32_at(&zip, 18), 5);
+        assert_eq!(&zip[30..35], b"a.txt");
+        assert_eq!(&zip[35..40], b"hello");
+
+        // The end record points at a central directory with both files.
+        let end = zip.len() - 22;
+        assert_eq!(u32_at(&zip, end), 0x0605_4b50);
+        assert_eq!(u16_at(&zip, end + 10), 2);
+        let directory = u32_at(&zip, end + 16) as usize;
+        assert_eq!(directory + u32_at(&zip, end + 12) as usize, end);
+        assert_eq!(u32_at(&zip, directory), 0x0201_4b50);
+        assert_eq!(u32_at(&zip, directory + 42), 0);
+        let second = directory + 46 + 5;
+        assert_eq!(u32_at(&zip, second), 0x0201_4b50);
+        assert_eq!(&zip[second + 46..second + 51], b"b.txt");
+        assert_eq!(u32_at(&zip, u32_at(&zip, second + 42) as usize), 0x0403_4b50);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{app::AppState, config, devices, devices::filter_type::CrtFilter};
+use crate::{app::AppState, assets::AssetKind, config, devices, devices::audio::AudioRouting, devices::filter_type::{BfiMode, CrtFilter}, devices::idle_inhibit::IdleInhibitPolicy, devices::sys_stats::SystemSampler, notifications::Notification, video::autocrop::AutoCropMode, video::display::{GpuPreference, PresentMode}, video::filter_settings::FilterSettings, video::clips, video::ffmpeg_log::{self, FfmpegLogLevel}, video::overlay::TextOverlayPosition, video::timer::TimerSource, video::interpolate::InterpolationMode, video::pacing::PacingMode, video::recorder::{RecordQuality, TimelapseMode, VideoEncoder}, video::types::{SourceKind, StreamInfo, ThreadPriority}};
 use eframe::egui;
 
 pub fn layout_top_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
@@ -8,6 +8,41 @@ pub fn layout_top_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
             ui.add(egui::Image::new(logo).max_height(160.0));
         }
         ui.heading("Michadame Viewer");
+        if ui.add_enabled(state.system_check_receiver.is_none(), egui::Button::new("🩺 System Check"))
+            .on_hover_text("Checks devices, PulseAudio, OpenGL, FFmpeg and the external tools, with a report to attach to bug reports.")
+            .clicked()
+        {
+            state.start_system_check(ui.ctx());
+        }
+        if ui.add_enabled(state.undo_history.can_undo() || state.config_save.is_pending(), egui::Button::new("↶ Undo"))
+            .on_hover_text("Undo the last settings change (Ctrl+Z).")
+            .clicked()
+        {
+            state.undo_config_change(ui.ctx());
+            changed = true;
+        }
+        let mut auto_save = !state.manual_save;
+        if ui.checkbox(&mut auto_save, "Auto-save")
+            .on_hover_text("Write every settings change to the config file right away. Turn off to experiment freely, then Save or Revert.")
+            .changed()
+        {
+            state.manual_save = !auto_save;
+            config::save_config(state);
+            changed = true;
+        }
+        if state.manual_save {
+            let dirty = config::has_unsaved_changes(state);
+            if ui.add_enabled(dirty, egui::Button::new("💾 Save")).on_hover_text("Write the settings to the config file (Ctrl+S).").clicked() {
+                config::save_config(state);
+            }
+            if ui.add_enabled(dirty, egui::Button::new("⟲ Revert")).on_hover_text("Go back to the settings last saved.").clicked() {
+                state.revert_config_changes(ui.ctx());
+                changed = true;
+            }
+            if dirty {
+                ui.label(egui::RichText::new("● Unsaved changes").color(egui::Color32::YELLOW));
+            }
+        }
     });
     ui.separator();
 
@@ -15,32 +50,45 @@ pub fn layout_top_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
         ui.label("USB Device to Reset:");
         let selected_text = state.selected_usb_device.as_ref()
             .and_then(|selected_id| {
-                state.usb_devices.iter().find(|(id, _)| id == selected_id)
-                    .map(|(id, name)| format!("{} {}", id, name))
+                state.usb_devices.iter().find(|d| &d.id == selected_id)
+                    .map(|d| d.label())
             })
             .unwrap_or_else(|| "None".to_string());
         egui::ComboBox::from_id_source("usb_device_selector")
             .selected_text(selected_text)
             .show_ui(ui, |ui| {
                 let mut combo_changed = ui.selectable_value(&mut state.selected_usb_device, None, "None").changed();
-                for (id, name) in &state.usb_devices {
-                    combo_changed |= ui.selectable_value(&mut state.selected_usb_device, Some(id.clone()), format!("{} {}", id, name)).changed();
+                for device in &state.usb_devices {
+                    combo_changed |= ui.selectable_value(&mut state.selected_usb_device, Some(device.id.clone()), device.label()).changed();
                 }
                 if combo_changed {
-                    config::save_config(state);
+                    config::mark_dirty(state);
                     changed = true;
                 }
             });
 
-        if let Some(selected_device) = &state.selected_usb_device {
-            if ui.button("Reset USB Device").clicked() {
-                state.status_message = match devices::usb::reset_usb_device(selected_device) {
-                    Ok(_) => "USB device reset successfully.".to_string(),
-                    Err(e) => format!("Failed to reset USB: {}", e),
-                };
+        if state.selected_usb_device.is_some() {
+            egui::ComboBox::from_id_source("usb_reset_method_selector")
+                .selected_text(state.usb_reset_method.to_string())
+                .show_ui(ui, |ui| {
+                    let mut combo_changed = false;
+                    for method in [devices::usb::UsbResetMethod::UsbReset, devices::usb::UsbResetMethod::PowerCycle] {
+                        combo_changed |= ui.selectable_value(&mut state.usb_reset_method, method, method.to_string()).changed();
+                    }
+                    if combo_changed {
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                })
+                .response
+                .on_hover_text("A power cycle switches the hub port off and on, for cards that only recover from a real power loss. Needs uhubctl and a hub with per-port power switching.");
+            if state.usb_reset_running() {
+                ui.spinner();
+            } else if ui.button("Reset USB Device").clicked() {
+                state.reset_usb_device(false);
             }
-            if ui.checkbox(&mut state.reset_usb_on_startup, "Reset on startup").on_hover_text("Requires pkexec to be configured for usbreset without a password prompt for automatic startup reset.").changed() {
-                config::save_config(state);
+            if ui.checkbox(&mut state.reset_usb_on_startup, "Reset on startup").on_hover_text("Requires pkexec to be configured for usbreset (or uhubctl) without a password prompt for automatic startup reset.").changed() {
+                config::mark_dirty(state);
                 changed = true;
             }
         }
@@ -49,147 +97,404 @@ pub fn layout_top_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
     ui.separator();
 
     ui.horizontal(|ui| {
-        ui.label("Video Device:");
-        let _combo_box = egui::ComboBox::from_id_source("video_device_selector")
-            .selected_text(state.selected_video_device.as_str())
+        ui.label("Source:");
+        egui::ComboBox::from_id_source("source_kind_selector")
+            .selected_text(state.source_kind.to_string())
             .show_ui(ui, |ui| {
                 let mut combo_changed = false;
-                for device in &state.video_devices {
-                    combo_changed |= ui.selectable_value(&mut state.selected_video_device, device.clone(), device.as_str()).changed();
-                }
-                if combo_changed && !state.selected_video_device.is_empty() {
-                    config::save_config(state);
-                    state.supported_formats.clear();
-                    state.selected_format_index = 0;
-                    state.selected_resolution = (0, 0);
-
-                    match devices::video::find_video_formats(&state.selected_video_device) {
-                        Ok(formats) => {
-                            state.status_message = format!("Found {} formats for {}.", formats.len(), state.selected_video_device);
-                            state.supported_formats = formats;
-                            if let Some(res) = state.supported_formats.first().and_then(|f| f.resolutions.first()) {
-                                state.selected_resolution = (res.width, res.height);
-                                state.selected_framerate = res.framerates.first().cloned().unwrap_or(0);
-                            }
-                            // After loading formats, try to apply the saved config for them.
-                            if let Ok(cfg) = confy::load::<config::MichadameConfig>("michadame", None) {
-                                crate::video::types::apply_saved_format_config(state, &cfg);
-                            }
-                        }
-                        Err(e) => {
-                            state.status_message = format!("Failed to scan formats: {}", e);
-                        }
-                    }
+                for kind in [SourceKind::Capture, SourceKind::Network, SourceKind::File, SourceKind::Screen] {
+                    combo_changed |= ui.selectable_value(&mut state.source_kind, kind, kind.to_string()).changed();
+                }
+                if combo_changed {
+                    config::mark_dirty(state);
                     changed = true;
                 }
             });
     });
 
-    if !state.supported_formats.is_empty() {
+    if state.source_kind == SourceKind::Network {
         ui.horizontal(|ui| {
-            let selected_format_description = state.supported_formats[state.selected_format_index].description.clone();
-            let resolutions = state.supported_formats[state.selected_format_index].resolutions.clone();
+            ui.label("Stream URL:");
+            let response = ui.add(egui::TextEdit::singleline(&mut state.network_source_url)
+                .hint_text("rtsp://camera.local/stream")
+                .desired_width(360.0))
+                .on_hover_text("Anything FFmpeg can open: rtsp://, rtp://, srt://, or an http:// MJPEG stream.");
+            if response.lost_focus() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+    }
+
+    if state.source_kind == SourceKind::File {
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            let response = ui.add(egui::TextEdit::singleline(&mut state.file_source_path)
+                .hint_text("Drop a video or image onto the window")
+                .desired_width(360.0));
+            if response.lost_focus() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+            if ui.checkbox(&mut state.file_source_loop, "Loop").changed() {
+                state.playback_control.looping.store(state.file_source_loop, std::sync::atomic::Ordering::Relaxed);
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+        if state.video_thread.is_some() {
+            changed |= layout_playback_controls(ui, state);
+        }
+    }
+
+    if state.source_kind == SourceKind::Screen && state.video_thread.is_none() {
+        ui.label("The desktop will ask which window or monitor to capture when the stream starts.");
+    }
 
-            ui.label("Format:");
-            egui::ComboBox::from_id_source("format_selector")
-                .selected_text(selected_format_description)
+    if state.source_kind == SourceKind::Capture {
+        ui.horizontal(|ui| {
+            ui.label("Video Device:");
+            let _combo_box = egui::ComboBox::from_id_source("video_device_selector")
+                .selected_text(state.selected_video_device.as_str())
                 .show_ui(ui, |ui| {
-                    for (i, format) in state.supported_formats.iter().enumerate() {
-                        if ui.selectable_value(&mut state.selected_format_index, i, &format.description).changed() {
-                            if let Some(res) = state.supported_formats[i].resolutions.first() {
-                                state.selected_resolution = (res.width, res.height);
-                                state.selected_framerate = res.framerates.first().cloned().unwrap_or(0);
-                            }
-                            config::save_config(state);
-                            changed = true;
-                        }
+                    let mut combo_changed = false;
+                    for device in &state.video_devices {
+                        combo_changed |= ui.selectable_value(&mut state.selected_video_device, device.clone(), device.as_str()).changed();
+                    }
+                    if combo_changed && !state.selected_video_device.is_empty() {
+                        let device = state.selected_video_device.clone();
+                        state.select_video_device(&device, ui.ctx());
+                        changed = true;
                     }
                 });
+            if state.formats_loading() {
+                ui.spinner();
+            }
+            if ui.add_enabled(state.video_thread.is_none() && state.probe_receiver.is_none() && !state.supported_formats.is_empty(), egui::Button::new("🔍 Probe"))
+                .on_hover_text("Briefly opens every advertised format, resolution and framerate and reports which ones actually deliver frames. Takes about two seconds per mode.")
+                .clicked()
+            {
+                state.start_device_probe();
+                changed = true;
+            }
+            if let Some(card) = state.known_card {
+                let quirks: Vec<&str> = card.quirks.iter().map(|q| q.to_string()).collect();
+                ui.label(format!("ℹ {}", card.name))
+                    .on_hover_text(format!("Known card: {}. Presets were applied when it was first detected.", quirks.join(", ")));
+            }
+        });
 
-            ui.label("Resolution:");
-            egui::ComboBox::from_id_source("resolution_selector")
-                .selected_text(format!("{}x{}", state.selected_resolution.0, state.selected_resolution.1))
-                .show_ui(ui, |ui| {
-                    for res in &resolutions {
-                        if ui.selectable_value(&mut state.selected_resolution, (res.width, res.height), format!("{}x{}", res.width, res.height)).changed() {
-                            state.selected_framerate = res.framerates.first().cloned().unwrap_or(0);
-                            config::save_config(state);
-                            changed = true;
-                        }
+        if !state.supported_formats.is_empty() {
+            ui.horizontal(|ui| {
+                let selected_format_description = state.supported_formats[state.selected_format_index].description.clone();
+                let resolutions = state.supported_formats[state.selected_format_index].resolutions.clone();
+
+                if ui.checkbox(&mut state.auto_format, "Auto").on_hover_text("Picks the highest resolution that reaches 60 fps, using raw formats at low resolutions and MJPEG at high ones, unless a format priority is set.").changed() {
+                    if state.auto_format {
+                        crate::video::types::apply_auto_format(state);
                     }
-                });
+                    config::mark_dirty(state);
+                    changed = true;
+                }
+                ui.add_enabled_ui(!state.auto_format, |ui| {
+                    ui.label("Format:");
+                    egui::ComboBox::from_id_source("format_selector")
+                        .selected_text(selected_format_description)
+                        .show_ui(ui, |ui| {
+                            for i in 0..state.supported_formats.len() {
+                                let description = state.supported_formats[i].description.clone();
+                                if ui.selectable_value(&mut state.selected_format_index, i, description).changed() {
+                                    if let Some(res) = state.supported_formats[i].resolutions.first() {
+                                        state.selected_resolution = (res.width, res.height);
+                                        state.selected_framerate = res.framerates.first().cloned().unwrap_or(0);
+                                    }
+                                    config::mark_dirty(state);
+                                    changed = true;
+                                }
+                            }
+                        });
 
-            if let Some(res_info) = resolutions.iter().find(|r| r.width == state.selected_resolution.0 && r.height == state.selected_resolution.1) {
-                if !res_info.framerates.is_empty() {
-                    ui.label("Framerate:");
-                    egui::ComboBox::from_id_source("framerate_selector")
-                        .selected_text(format!("{} fps", state.selected_framerate))
+                    ui.label("Resolution:");
+                    egui::ComboBox::from_id_source("resolution_selector")
+                        .selected_text(format!("{}x{}", state.selected_resolution.0, state.selected_resolution.1))
                         .show_ui(ui, |ui| {
-                            for &fps in &res_info.framerates {
-                                if ui.selectable_value(&mut state.selected_framerate, fps, format!("{} fps", fps)).changed() {
-                                    config::save_config(state);
+                            for res in &resolutions {
+                                if ui.selectable_value(&mut state.selected_resolution, (res.width, res.height), format!("{}x{}", res.width, res.height)).changed() {
+                                    state.selected_framerate = res.framerates.first().cloned().unwrap_or(0);
+                                    config::mark_dirty(state);
                                     changed = true;
                                 }
                             }
                         });
+
+                    if let Some(res_info) = resolutions.iter().find(|r| r.width == state.selected_resolution.0 && r.height == state.selected_resolution.1) {
+                        if !res_info.framerates.is_empty() {
+                            ui.label("Framerate:");
+                            egui::ComboBox::from_id_source("framerate_selector")
+                                .selected_text(format!("{} fps", state.selected_framerate))
+                                .show_ui(ui, |ui| {
+                                    for &fps in &res_info.framerates {
+                                        if ui.selectable_value(&mut state.selected_framerate, fps, format!("{} fps", fps)).changed() {
+                                            config::mark_dirty(state);
+                                            changed = true;
+                                        }
+                                    }
+                                });
+                            }
                     }
-            }
-        });
+                });
+            });
+            layout_usb_bandwidth_ui(ui, state);
+            changed |= persistent_collapsing(ui, state, "Advanced format", layout_format_override_ui).unwrap_or(false);
+        }
     }
     ui.separator();
 
-    ui.group(|ui| {
-        ui.horizontal(|ui| {
-            ui.label("PulseAudio Configuration:");
-            if ui.button("🔄 Refresh").clicked() {
-                state.status_message = "Refresh clicked. Please restart the app to re-scan devices.".to_string();
-                changed = true;
+    if state.pulse_available {
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label("PulseAudio Configuration:");
+                if ui.add_enabled(state.device_scan_receiver.is_none(), egui::Button::new("🔄 Refresh")).clicked() {
+                    state.start_device_scan(ui.ctx());
+                    state.notify(Notification::info("Re-scanning devices..."));
+                    changed = true;
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Routing:");
+                egui::ComboBox::from_id_source("audio_routing_selector")
+                    .selected_text(state.audio_routing.to_string())
+                    .show_ui(ui, |ui| {
+                        let mut combo_changed = false;
+                        for routing in [AudioRouting::Loopback, AudioRouting::MonitorOnly, AudioRouting::DefaultSource] {
+                            combo_changed |= ui.selectable_value(&mut state.audio_routing, routing, routing.to_string()).changed();
+                        }
+                        if combo_changed {
+                            config::mark_dirty(state);
+                            changed = true;
+                        }
+                    });
+            });
+
+            if !state.pulse_cards.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label("Card:");
+                    let selected_card_desc = state.pulse_cards.iter()
+                        .find(|card| Some(&card.name) == state.selected_pulse_card.as_ref())
+                        .map(|card| card.description.clone())
+                        .unwrap_or_else(|| "Select a Card".to_string());
+                    egui::ComboBox::from_id_source("pulse_card_selector")
+                        .selected_text(selected_card_desc)
+                        .show_ui(ui, |ui| {
+                            let mut combo_changed = false;
+                            for card in &state.pulse_cards {
+                                combo_changed |= ui.selectable_value(&mut state.selected_pulse_card, Some(card.name.clone()), &card.description).changed();
+                            }
+                            if combo_changed {
+                                config::mark_dirty(state);
+                                changed = true;
+                            }
+                        });
+
+                    let selected_card = state.pulse_cards.iter()
+                        .find(|card| Some(&card.name) == state.selected_pulse_card.as_ref())
+                        .cloned();
+                    if let Some(card) = selected_card {
+                        ui.label("Profile:");
+                        let active_desc = card.profiles.iter()
+                            .find(|(_, name)| Some(name) == card.active_profile.as_ref())
+                            .map(|(desc, _)| desc.as_str())
+                            .unwrap_or("Off");
+                        let mut requested_profile = None;
+                        let switching = state.card_profile_receiver.is_some();
+                        ui.add_enabled_ui(!switching, |ui| {
+                            egui::ComboBox::from_id_source("pulse_profile_selector")
+                                .selected_text(active_desc)
+                                .show_ui(ui, |ui| {
+                                    for (desc, name) in &card.profiles {
+                                        if ui.selectable_label(Some(name) == card.active_profile.as_ref(), desc).clicked() {
+                                            requested_profile = Some(name.clone());
+                                        }
+                                    }
+                                });
+                        });
+                        if switching {
+                            ui.spinner();
+                        }
+                        if let Some(profile) = requested_profile {
+                            state.switch_card_profile(ui.ctx(), &card.name, &profile);
+                            changed = true;
+                        }
+                    }
+                });
+                if let Some(card) = state.pulse_cards.iter().find(|card| Some(&card.name) == state.selected_pulse_card.as_ref()) {
+                    if !card.ports.is_empty() {
+                        ui.label(egui::RichText::new(format!("Ports: {}", card.ports.join(", "))).small().weak());
+                    }
+                }
             }
-        });
 
-        let selected_source_desc = state.pulse_sources.iter()
-            .find(|(_, name)| Some(name) == state.selected_pulse_source_name.as_ref())
-            .map(|(desc, _)| desc.as_str())
-            .unwrap_or("Select an Input");
+            let selected_source_desc = state.pulse_sources.iter()
+                .find(|(_, name)| Some(name) == state.selected_pulse_source_name.as_ref())
+                .map(|(desc, _)| desc.as_str())
+                .unwrap_or("Select an Input");
 
-        egui::ComboBox::from_label("Input (Source)")
-            .selected_text(selected_source_desc)
-            .show_ui(ui, |ui| {
-                let mut combo_changed = false;
-                for (desc, name) in &state.pulse_sources {
-                    combo_changed |= ui.selectable_value(&mut state.selected_pulse_source_name, Some(name.clone()), desc).changed();
+            egui::ComboBox::from_label("Input (Source)")
+                .selected_text(selected_source_desc)
+                .show_ui(ui, |ui| {
+                    let mut combo_changed = false;
+                    for (desc, name) in &state.pulse_sources {
+                        combo_changed |= ui.selectable_value(&mut state.selected_pulse_source_name, Some(name.clone()), desc).changed();
+                    }
+                    if combo_changed {
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                });
+
+            let selected_sink_desc = state.pulse_sinks.iter()
+                .find(|(_, name)| Some(name) == state.selected_pulse_sink_name.as_ref())
+                .map(|(desc, _)| desc.as_str())
+                .unwrap_or("Select an Output");
+
+            if state.audio_routing == AudioRouting::Loopback {
+                egui::ComboBox::from_label("Output (Sink)")
+                    .selected_text(selected_sink_desc)
+                    .show_ui(ui, |ui| {
+                        let mut combo_changed = false;
+                        for (desc, name) in &state.pulse_sinks {
+                            combo_changed |= ui.selectable_value(&mut state.selected_pulse_sink_name, Some(name.clone()), desc).changed();
+                        }
+                        if combo_changed {
+                            config::mark_dirty(state);
+                            changed = true;
+                        }
+                    });
+            }
+
+            ui.horizontal(|ui| {
+                let mut custom_latency = state.loopback_latency_msec.is_some();
+                if ui.checkbox(&mut custom_latency, "Loopback latency (ms):").changed() {
+                    state.loopback_latency_msec = if custom_latency { Some(state.measured_latency_msec.unwrap_or(200)) } else { None };
+                    config::mark_dirty(state);
+                    changed = true;
                 }
-                if combo_changed {
-                    config::save_config(state);
+                if let Some(latency) = &mut state.loopback_latency_msec {
+                    if ui.add(egui::DragValue::new(latency).clamp_range(1..=2000)).changed() {
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                }
+                let measuring = state.latency_measure_receiver.is_some();
+                if ui.add_enabled(!measuring && state.video_thread.is_none(), egui::Button::new("⏱ Measure Latency"))
+                    .on_hover_text("Plays a click through the output and listens for it on the input. Point the input at the output's monitor (or a mic near the speakers).")
+                    .clicked()
+                {
+                    state.start_latency_measurement(ui.ctx());
                     changed = true;
                 }
+                if let Some(measured) = state.measured_latency_msec {
+                    ui.label(format!("Measured: {} ms", measured));
+                    if ui.button("Use").clicked() {
+                        state.loopback_latency_msec = Some(measured);
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                }
             });
 
-        let selected_sink_desc = state.pulse_sinks.iter()
-            .find(|(_, name)| Some(name) == state.selected_pulse_sink_name.as_ref())
-            .map(|(desc, _)| desc.as_str())
-            .unwrap_or("Select an Output");
+            if state.audio_routing == AudioRouting::Loopback {
+                ui.horizontal(|ui| {
+                    ui.label("Loopback volume:");
+                    let response = ui.add(egui::Slider::new(&mut state.loopback_volume, 0.0..=1.5).custom_formatter(|v, _| format!("{:.0}%", v * 100.0)));
+                    if response.changed() {
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                    // Every PulseAudio call takes a round trip, so only apply where the slider was let go.
+                    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                        state.apply_loopback_volume();
+                    }
+                });
+            }
 
-        egui::ComboBox::from_label("Output (Sink)")
-            .selected_text(selected_sink_desc)
-            .show_ui(ui, |ui| {
-                let mut combo_changed = false;
-                for (desc, name) in &state.pulse_sinks {
-                    combo_changed |= ui.selectable_value(&mut state.selected_pulse_sink_name, Some(name.clone()), desc).changed();
+            let monitor_desc = state.monitor_output_sink.as_ref()
+                .map(|sink| state.pulse_sinks.iter().find(|(_, name)| name == sink).map_or(sink.as_str(), |(desc, _)| desc.as_str()))
+                .unwrap_or("Off");
+            let mut monitor_changed = false;
+            egui::ComboBox::from_label("Monitor output")
+                .selected_text(monitor_desc)
+                .show_ui(ui, |ui| {
+                    monitor_changed |= ui.selectable_value(&mut state.monitor_output_sink, None, "Off").changed();
+                    for (desc, name) in &state.pulse_sinks {
+                        monitor_changed |= ui.selectable_value(&mut state.monitor_output_sink, Some(name.clone()), desc).changed();
+                    }
+                })
+                .response
+                .on_hover_text("Also plays the capture through this output, e.g. headphones, with its own volume. Works with every routing mode.");
+            if monitor_changed {
+                state.apply_monitor_output();
+                config::mark_dirty(state);
+                changed = true;
+            }
+            if state.monitor_output_sink.is_some() {
+                ui.horizontal(|ui| {
+                    ui.label("Monitor volume:");
+                    let response = ui.add(egui::Slider::new(&mut state.monitor_output_volume, 0.0..=1.5).custom_formatter(|v, _| format!("{:.0}%", v * 100.0)));
+                    if response.changed() {
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                        state.apply_monitor_output_volume();
+                    }
+                });
+                if state.monitor_output_sink == state.selected_pulse_sink_name && state.audio_routing == AudioRouting::Loopback {
+                    ui.label(egui::RichText::new("⚠ The monitor output is the same as the main output, so the audio plays twice").color(egui::Color32::YELLOW));
                 }
-                if combo_changed {
-                    config::save_config(state);
+            }
+
+            ui.horizontal(|ui| {
+                let mut settings_changed = false;
+                settings_changed |= ui.checkbox(&mut state.limiter_enabled, "Limiter")
+                    .on_hover_text("Routes the loopback through a lookahead limiter so consoles booting at full volume don't blast your ears. Needs the swh LADSPA plugins. Applies on the next stream start.")
+                    .changed();
+                if state.limiter_enabled {
+                    settings_changed |= ui.add(egui::Slider::new(&mut state.limiter_limit_db, -24.0..=0.0).suffix(" dB")).changed();
+                }
+                settings_changed |= ui.checkbox(&mut state.loudness_meter_enabled, "Loudness meter").changed();
+                if settings_changed {
+                    config::mark_dirty(state);
                     changed = true;
                 }
             });
-    });
+            if state.audio_routing == AudioRouting::Loopback {
+                changed |= persistent_collapsing(ui, state, "Advanced audio", layout_loopback_options_ui).unwrap_or(false);
+            }
+            if let Some(meter) = &state.loudness_meter {
+                let momentary = meter.momentary();
+                // Map -60..0 LUFS onto the bar; consoles usually sit around -23 to -14.
+                let fraction = ((momentary + 60.0) / 60.0).clamp(0.0, 1.0);
+                let color = if momentary > -9.0 { egui::Color32::RED } else if momentary > -18.0 { egui::Color32::YELLOW } else { egui::Color32::GREEN };
+                ui.horizontal(|ui| {
+                    ui.add(egui::ProgressBar::new(fraction).fill(color).desired_width(200.0)
+                        .text(format!("M {:.1} LUFS", momentary)));
+                    ui.label(format!("S {:.1} LUFS", meter.short_term()));
+                });
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+            }
+        });
+    } else {
+        changed |= layout_alsa_ui(ui, state);
+    }
     ui.separator();
 
     ui.horizontal(|ui| {
         let is_running = state.video_thread.is_some();
-        let start_button = ui.add_enabled(!is_running && state.selected_resolution.0 > 0, egui::Button::new("▶ Start Stream"));
+        let start_button = ui.add_enabled(!is_running && !state.stream_stopping(), egui::Button::new("▶ Start Stream"));
         if start_button.clicked() {
-            state.start_stream(ui.ctx());
+            state.request_start_stream(ui.ctx());
             changed = true;
         }
         let stop_button = ui.add_enabled(is_running, egui::Button::new("⏹ Stop Stream"));
@@ -197,86 +502,2122 @@ pub fn layout_top_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
             state.stop_stream(ui.ctx());
             changed = true;
         }
+        if state.stream_stopping() {
+            ui.spinner();
+            ui.label("Stopping…");
+        }
+        if let Some(info) = &state.stream_info {
+            ui.menu_button("ℹ Stream Info", |ui| {
+                egui::Grid::new("stream_info_grid").num_columns(2).striped(true).show(ui, |ui| {
+                    ui.label("Input format:"); ui.label(&info.container); ui.end_row();
+                    ui.label("Codec:"); ui.label(&info.codec); ui.end_row();
+                    ui.label("Resolution:"); ui.label(format!("{}x{}", info.resolution.0, info.resolution.1)); ui.end_row();
+                    ui.label("Pixel format:"); ui.label(&info.pixel_format); ui.end_row();
+                    ui.label("Color range:"); ui.label(&info.color_range); ui.end_row();
+                    ui.label("Color space:"); ui.label(&info.color_space); ui.end_row();
+                    ui.label("Frame rate:"); ui.label(&info.frame_rate); ui.end_row();
+                    ui.label("Time base:"); ui.label(&info.time_base); ui.end_row();
+                });
+            });
+            let measured = state.frame_pacer.source_rate();
+            let text = frame_rate_summary(info, measured);
+            let card_short = matches!((info.requested_fps, info.device_fps), (Some(requested), Some(device)) if device < requested as f64 * 0.98);
+            let app_short = info.device_fps.is_some_and(|device| (measured as f64) < device * 0.95);
+            let label = if card_short || app_short {
+                egui::RichText::new(format!("⚠ {}", text)).color(egui::Color32::YELLOW)
+            } else {
+                egui::RichText::new(text)
+            };
+            ui.label(label).on_hover_text("What the card was asked for, what it reports delivering, and how many frames per second actually reach the viewer. If the card delivers less than requested, it's the card or the format; if the measured rate is lower than the card's, frames are lost in the app.");
+            let buffer_age_us = state.buffer_age_us.load(std::sync::atomic::Ordering::Relaxed);
+            if let (true, Some(fps)) = (buffer_age_us >= 0, info.device_fps.or(info.requested_fps.map(f64::from))) {
+                let age_ms = buffer_age_us as f64 / 1000.0;
+                // The buffer being read counts as one; whatever else fits in its age was filled after it.
+                let queued = 1 + (age_ms * fps / 1000.0) as u32;
+                let text = format!("Capture queue: {} buffer(s), {:.1} ms", queued, age_ms);
+                let label = if queued > 2 { egui::RichText::new(format!("⚠ {}", text)).color(egui::Color32::YELLOW) } else { egui::RichText::new(text) };
+                ui.label(label).on_hover_text("Filled V4L2 buffers waiting to be read, estimated from how long the buffer just read has been waiting. Buffers are read oldest first. More than one or two adds a frame of latency each, usually because decoding can't keep up. ffmpeg picks the number of buffers, so it can't be changed here.");
+            }
+        }
     });
 
     let current_filter = CrtFilter::from_u8(state.crt_filter.load(std::sync::atomic::Ordering::Relaxed));
 
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Filter cycle (C key):").on_hover_text("Filters the C key switches between, in this order.");
+        for filter in CrtFilter::ALL {
+            let mut in_cycle = state.filter_cycle & filter.cycle_bit() != 0;
+            if ui.checkbox(&mut in_cycle, filter.to_string()).changed() {
+                state.filter_cycle ^= filter.cycle_bit();
+                config::mark_dirty(state);
+                changed = true;
+            }
+        }
+    });
     ui.horizontal(|ui| {
         if ui.checkbox(&mut state.pixelate_filter_enabled, "Enable 480p Pixelate Filter (GPU)").on_hover_text("This is a GPU-based pre-filter that runs before other effects.").changed() {
-            config::save_config(state);
+            config::mark_dirty(state);
+            changed = true;
+        }
+        let response = ui.add_enabled(state.pixelate_filter_enabled, egui::Checkbox::new(&mut state.filter_settings.pixelate_low_res, "Low-res cache"))
+            .on_hover_text("Downscales once into a 480p buffer that the later filters work on, instead of keeping the full resolution. Saves GPU bandwidth with 4K sources and windows; the CRT shader then treats the image as a 480p source.");
+        if response.changed() {
+            config::mark_dirty(state);
             changed = true;
         }
     });
-    if current_filter == CrtFilter::Lottes {
-        ui.group(|ui| {
-            ui.label("Lottes Filter Settings");
-            ui.collapsing("Geometry", |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("Warp X:");
-                    if ui.add(egui::Slider::new(&mut state.crt_warp_x, 0.0..=0.125)).changed() { config::save_config(state); changed = true; }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("Warp Y:");
-                    if ui.add(egui::Slider::new(&mut state.crt_warp_y, 0.0..=0.125)).changed() { config::save_config(state); changed = true; }
-                });
-            });
-            ui.collapsing("Scanlines & Pixels", |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("Scanline Hardness:");
-                    if ui.add(egui::Slider::new(&mut state.crt_hard_scan, -20.0..=-1.0)).changed() { config::save_config(state); changed = true; }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("Pixel Hardness:");
-                    if ui.add(egui::Slider::new(&mut state.crt_hard_pix, -20.0..=0.0)).changed() { config::save_config(state); changed = true; }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("Filter Shape:");
-                    if ui.add(egui::Slider::new(&mut state.crt_shape, 0.0..=10.0)).changed() { config::save_config(state); changed = true; }
-                });
-            });
-            ui.collapsing("Bloom", |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("Bloom Amount:");
-                    if ui.add(egui::Slider::new(&mut state.crt_bloom_amount, 0.0..=1.0)).changed() { config::save_config(state); changed = true; }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("Bloom X Softness:");
-                    if ui.add(egui::Slider::new(&mut state.crt_hard_bloom_pix, -4.0..=-0.5)).changed() { config::save_config(state); changed = true; }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("Bloom Y Softness:");
-                    if ui.add(egui::Slider::new(&mut state.crt_hard_bloom_scan, -4.0..=-1.0)).changed() { config::save_config(state); changed = true; }
-                });
-            });
-            ui.collapsing("Mask & Color", |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("Shadow Mask Type:");
-                    if ui.add(egui::Slider::new(&mut state.crt_shadow_mask, 0.0..=4.0).step_by(1.0)).changed() { config::save_config(state); changed = true; }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("Brightness:");
-                    if ui.add(egui::Slider::new(&mut state.crt_brightboost, 0.0..=2.0)).changed() { config::save_config(state); changed = true; }
-                });
-                if ui.button("Reset to Defaults").clicked() {
-                    let defaults = crate::video::gpu_filter::ShaderParams::default();
-                    state.crt_hard_scan = defaults.hard_scan;
-                    state.crt_warp_x = defaults.warp_x;
-                    state.crt_warp_y = defaults.warp_y; state.crt_shadow_mask = defaults.shadow_mask;
-                    state.crt_brightboost = defaults.brightboost;
-                    state.crt_hard_bloom_pix = defaults.hard_bloom_pix;
-                    state.crt_hard_bloom_scan = defaults.hard_bloom_scan;
-                    state.crt_bloom_amount = defaults.bloom_amount;
-                    state.crt_shape = defaults.shape;
-                    state.crt_hard_pix = defaults.hard_pix;
-                    config::save_config(state);
+    ui.horizontal(|ui| {
+        if ui.checkbox(&mut state.filter_settings.sharpen_enabled, "Adaptive Sharpening (CAS)").on_hover_text("Contrast adaptive sharpening for soft, upscaled sources. Runs after the pixelate filter and before the CRT shader.").changed() {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if state.filter_settings.sharpen_enabled && ui.add(egui::Slider::new(&mut state.filter_settings.sharpen_strength, FilterSettings::SHARPEN_STRENGTH).text("Strength")).changed() {
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        if ui.checkbox(&mut state.filter_settings.phosphor_enabled, "Phosphor Persistence").on_hover_text("Lets the previous frames fade out instead of disappearing, leaving the motion trails of a CRT's phosphors.").changed() {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if state.filter_settings.phosphor_enabled && ui.add(egui::Slider::new(&mut state.filter_settings.phosphor_persistence, FilterSettings::PHOSPHOR_PERSISTENCE).text("Persistence")).changed() {
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        let mut power_saver = state.power_saver.load(std::sync::atomic::Ordering::Relaxed);
+        if ui.checkbox(&mut power_saver, "Power Saver").on_hover_text("Caps the UI at 30 FPS, skips the Lottes and upscaling shaders and black frame insertion, and uses cheaper software scaling (downscaling sources wider than 1280px).").changed() {
+            state.power_saver.store(power_saver, std::sync::atomic::Ordering::Relaxed);
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("GPU memory cap (MB):");
+        if ui.add(egui::DragValue::new(&mut state.gpu_memory_cap_mb).clamp_range(128..=16384).speed(16))
+            .on_hover_text("Video memory the filters' render targets may use. Larger sources first lower the upscale factor, then turn the upscaler and the other GPU filters off. Lower it on graphics cards with 2 GB or less.")
+            .changed()
+        {
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        let mut auto_downscale = state.auto_downscale.load(std::sync::atomic::Ordering::Relaxed);
+        if ui.checkbox(&mut auto_downscale, "Downscale large sources to the window").on_hover_text("Halves sources that are at least twice the size of the video area (e.g. 4K in a small window) before upload and filtering.").changed() {
+            state.auto_downscale.store(auto_downscale, std::sync::atomic::Ordering::Relaxed);
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Frame Pacing:");
+        egui::ComboBox::from_id_source("pacing_mode_selector")
+            .selected_text(state.pacing_mode.to_string())
+            .show_ui(ui, |ui| {
+                let mut combo_changed = false;
+                for mode in [PacingMode::Immediate, PacingMode::VsyncLocked, PacingMode::HalfRate] {
+                    combo_changed |= ui.selectable_value(&mut state.pacing_mode, mode, mode.to_string()).changed();
+                }
+                if combo_changed {
+                    config::mark_dirty(state);
                     changed = true;
                 }
-            });
-        });
+            })
+            .response
+            .on_hover_text("Immediate shows frames as they arrive. VSync Locked spreads them evenly over the display's refreshes to avoid judder. Half Refresh Rate presents on every other refresh, for 30 fps sources on 60 Hz or 60 fps on 120 Hz.");
+        if state.frame_receiver.is_some() {
+            ui.label(format!("{:.0} fps on ~{:.0} Hz", state.frame_pacer.source_rate(), state.frame_pacer.refresh_rate()));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Interpolation:");
+        egui::ComboBox::from_id_source("interpolation_mode_selector")
+            .selected_text(state.interpolation_mode.to_string())
+            .show_ui(ui, |ui| {
+                let mut combo_changed = false;
+                for mode in InterpolationMode::ALL {
+                    combo_changed |= ui.selectable_value(&mut state.interpolation_mode, mode, mode.to_string()).changed();
+                }
+                if combo_changed {
+                    config::mark_dirty(state);
+                    changed = true;
+                }
+            })
+            .response
+            .on_hover_text("Shows an in-between frame after each frame of sources below 45 fps, for 30 fps sources on 60 Hz displays. Blend averages the two frames. Motion compensated estimates motion with FFmpeg's minterpolate on a 480 pixel wide copy, which costs more CPU and shows softer in-between frames. Only the display is interpolated, not recordings.");
+    });
+    match state.interpolation_mode {
+        InterpolationMode::Off => {}
+        InterpolationMode::Blend => {
+            ui.label(egui::RichText::new("⚠ Interpolation adds latency: each frame is held back by half a source frame (~17 ms at 30 fps).").color(egui::Color32::YELLOW));
+        }
+        InterpolationMode::MotionCompensated => {
+            ui.label(egui::RichText::new("⚠ Interpolation adds latency: minterpolate looks ahead, holding each frame back by two or more source frames (~70 ms or more at 30 fps).").color(egui::Color32::YELLOW));
+        }
     }
-
-
-    ui.separator();
-    ui.label(&state.status_message);
-    changed
-}
\ No newline at end of file
+    ui.horizontal(|ui| {
+        ui.label("Video thread priority:");
+        egui::ComboBox::from_id_source("thread_priority_selector")
+            .selected_text(state.thread_priority.to_string())
+            .show_ui(ui, |ui| {
+                let mut combo_changed = false;
+                for priority in [ThreadPriority::Normal, ThreadPriority::High, ThreadPriority::Realtime] {
+                    combo_changed |= ui.selectable_value(&mut state.thread_priority, priority, priority.to_string()).changed();
+                }
+                if combo_changed {
+                    config::mark_dirty(state);
+                    changed = true;
+                }
+            })
+            .response
+            .on_hover_text("Runs decoding ahead of other programs, to reduce dropped frames while an encoder or a game loads the system. Needs permission to raise priorities (e.g. CAP_SYS_NICE or limits.conf); otherwise the threads keep their normal priority. Applies on the next stream start.");
+    });
+    ui.horizontal(|ui| {
+        if ui.checkbox(&mut state.throttle_when_minimized, "Pause display while minimized").on_hover_text("Skips texture uploads and most repaints while the video window is minimized. The network preview keeps receiving every frame.").changed() {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if ui.checkbox(&mut state.restart_after_resume, "Restart stream after resume").on_hover_text("The stream is always stopped before the system suspends. With this enabled it's started again a few seconds after resuming.").changed() {
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        if ui.checkbox(&mut state.direct_upload_enabled, "Direct GL upload (expert)")
+            .on_hover_text("Copies each frame into the filter renderer's texture while drawing it, instead of handing it to egui, which shows it one UI frame later. Not used with the display wall, the clean feed or burned-in recording. Compare both paths under Diagnostics.")
+            .changed()
+        {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if state.direct_upload_enabled && !state.direct_upload_active() {
+            ui.label(egui::RichText::new("⚠ Inactive with the wall, clean feed or burned-in recording").color(egui::Color32::YELLOW));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Keep screen on:");
+        egui::ComboBox::from_id_source("idle_inhibit_policy")
+            .selected_text(state.idle_inhibit_policy.to_string())
+            .show_ui(ui, |ui| {
+                let mut combo_changed = false;
+                for policy in [IdleInhibitPolicy::WhileStreaming, IdleInhibitPolicy::WhenFocused, IdleInhibitPolicy::Never] {
+                    combo_changed |= ui.selectable_value(&mut state.idle_inhibit_policy, policy, policy.to_string()).changed();
+                }
+                if combo_changed {
+                    config::mark_dirty(state);
+                    changed = true;
+                }
+            })
+            .response
+            .on_hover_text("Stops the desktop from blanking the screen or locking while a stream runs. Choose Never to let the monitor sleep during long audio-only or recording sessions.");
+    });
+    if ui.checkbox(&mut state.do_not_disturb_enabled, "Do not disturb while streaming or recording")
+        .on_hover_text("Turns on the desktop's do-not-disturb mode so notification popups don't show up over the capture, and restores it afterwards. Works on KDE Plasma and GNOME.")
+        .changed()
+    {
+        config::mark_dirty(state);
+        changed = true;
+    }
+    ui.horizontal(|ui| {
+        ui.label("Presentation:");
+        egui::ComboBox::from_id_source("present_mode")
+            .selected_text(state.present_mode.to_string())
+            .show_ui(ui, |ui| {
+                for mode in [PresentMode::Fifo, PresentMode::Immediate] {
+                    if ui.selectable_value(&mut state.present_mode, mode, mode.to_string()).changed() {
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Immediate lowers latency at the cost of tearing. Mailbox presentation isn't available with the OpenGL renderer.");
+        ui.label("GPU:");
+        egui::ComboBox::from_id_source("gpu_preference")
+            .selected_text(state.gpu_preference.to_string())
+            .show_ui(ui, |ui| {
+                for gpu in [GpuPreference::Default, GpuPreference::Discrete] {
+                    if ui.selectable_value(&mut state.gpu_preference, gpu, gpu.to_string()).changed() {
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                }
+            })
+            .response
+            .on_hover_text(format!("On hybrid laptops, renders on the discrete GPU using PRIME render offload.\nCurrently: {}", state.gl_info.as_deref().unwrap_or("unknown")));
+        ui.label("(applies after restart)");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Window Size:");
+        for scale in 1..=4 {
+            let response = ui.selectable_value(&mut state.window_scale, scale, format!("{}x", scale))
+                .on_hover_text("Sizes the video window to a whole multiple of the video in device pixels, independent of the desktop's scale factor.");
+            if response.clicked() {
+                if let Some(texture) = state.video_texture.as_ref().filter(|_| state.video_thread.is_some()) {
+                    let size = texture.region(state.display_crop_enabled.then_some(state.display_crop)).size();
+                    state.resize_to_video_scale(ui.ctx(), size);
+                }
+                config::mark_dirty(state);
+                changed = true;
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Pixel Aspect Ratio:");
+        let preset_label = crate::video::types::PAR_PRESETS.iter()
+            .find(|(_, num, den)| (*num, *den) == state.pixel_aspect)
+            .map(|(label, _, _)| label.to_string())
+            .unwrap_or_else(|| "Custom".to_string());
+        egui::ComboBox::from_id_source("par_selector")
+            .selected_text(preset_label)
+            .show_ui(ui, |ui| {
+                let mut combo_changed = false;
+                for (label, num, den) in crate::video::types::PAR_PRESETS {
+                    combo_changed |= ui.selectable_value(&mut state.pixel_aspect, (*num, *den), *label).changed();
+                }
+                if combo_changed {
+                    config::mark_dirty(state);
+                    changed = true;
+                }
+            });
+        let mut custom_changed = ui.add(egui::DragValue::new(&mut state.pixel_aspect.0).clamp_range(1..=64)).changed();
+        ui.label(":");
+        custom_changed |= ui.add(egui::DragValue::new(&mut state.pixel_aspect.1).clamp_range(1..=64)).changed();
+        if custom_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    if matches!(current_filter, CrtFilter::Xbr | CrtFilter::XbrHard) {
+        ui.group(|ui| {
+            ui.label("Upscaler Settings");
+            ui.horizontal(|ui| {
+                ui.label("Scale Factor:");
+                for factor in FilterSettings::UPSCALE_FACTOR {
+                    if ui.selectable_value(&mut state.filter_settings.upscale_factor, factor, format!("{}x", factor)).changed() {
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                }
+            }).response.on_hover_text("How many screen pixels each source pixel is split into before the image is scaled to the window. Higher factors give smoother edges at a higher GPU cost.");
+        });
+    }
+    if current_filter == CrtFilter::Lottes {
+        ui.group(|ui| {
+            ui.label("Lottes Filter Settings");
+            persistent_collapsing(ui, state, "Geometry", |ui, state| {
+                ui.horizontal(|ui| {
+                    ui.label("Warp X:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.warp_x, FilterSettings::WARP)).changed() { config::mark_dirty(state); changed = true; }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Warp Y:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.warp_y, FilterSettings::WARP)).changed() { config::mark_dirty(state); changed = true; }
+                });
+            });
+            persistent_collapsing(ui, state, "Scanlines & Pixels", |ui, state| {
+                ui.horizontal(|ui| {
+                    ui.label("Scanline Hardness:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.hard_scan, FilterSettings::HARD_SCAN)).changed() { config::mark_dirty(state); changed = true; }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Pixel Hardness:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.hard_pix, FilterSettings::HARD_PIX)).changed() { config::mark_dirty(state); changed = true; }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Filter Shape:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.shape, FilterSettings::SHAPE)).changed() { config::mark_dirty(state); changed = true; }
+                });
+            });
+            persistent_collapsing(ui, state, "Bloom", |ui, state| {
+                ui.horizontal(|ui| {
+                    ui.label("Bloom Amount:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.bloom_amount, FilterSettings::BLOOM_AMOUNT)).changed() { config::mark_dirty(state); changed = true; }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Bloom X Softness:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.hard_bloom_pix, FilterSettings::HARD_BLOOM_PIX)).changed() { config::mark_dirty(state); changed = true; }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Bloom Y Softness:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.hard_bloom_scan, FilterSettings::HARD_BLOOM_SCAN)).changed() { config::mark_dirty(state); changed = true; }
+                });
+            });
+            persistent_collapsing(ui, state, "Glass Glare", |ui, state| {
+                if ui.checkbox(&mut state.filter_settings.glare_enabled, "Reflect room light on the glass").on_hover_text("Adds the reflection and highlight of a lit room on the curved screen glass.").changed() {
+                    config::mark_dirty(state);
+                    changed = true;
+                }
+                ui.add_enabled_ui(state.filter_settings.glare_enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Intensity:");
+                        if ui.add(egui::Slider::new(&mut state.filter_settings.glare_intensity, FilterSettings::GLARE_INTENSITY)).changed() { config::mark_dirty(state); changed = true; }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Ambient Light:");
+                        if ui.color_edit_button_rgb(&mut state.filter_settings.glare_ambient_color).changed() { config::mark_dirty(state); changed = true; }
+                    });
+                });
+            });
+            persistent_collapsing(ui, state, "Mask & Color", |ui, state| {
+                ui.horizontal(|ui| {
+                    ui.label("Shadow Mask Type:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.shadow_mask, FilterSettings::SHADOW_MASK).step_by(1.0)).changed() { config::mark_dirty(state); changed = true; }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Brightness:");
+                    if ui.add(egui::Slider::new(&mut state.filter_settings.brightboost, FilterSettings::BRIGHTBOOST)).changed() { config::mark_dirty(state); changed = true; }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Reset to Defaults").clicked() {
+                        state.filter_settings.copy_lottes(&state.filter_defaults);
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                    let saved = state.filter_settings.lottes_equals(&state.filter_defaults);
+                    if ui.add_enabled(!saved, egui::Button::new("Save as Defaults"))
+                        .on_hover_text("Make the current CRT values what \"Reset to Defaults\" goes back to")
+                        .clicked()
+                    {
+                        state.filter_defaults.copy_lottes(&state.filter_settings);
+                        config::mark_dirty(state);
+                    }
+                    let built_in = FilterSettings::default();
+                    if ui.add_enabled(!state.filter_defaults.lottes_equals(&built_in), egui::Button::new("Restore Built-in"))
+                        .on_hover_text("Forget the saved defaults and reset to the values Michadame ships with")
+                        .clicked()
+                    {
+                        state.filter_defaults.copy_lottes(&built_in);
+                        state.filter_settings.copy_lottes(&built_in);
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                });
+            });
+        });
+    }
+
+    changed |= layout_color_correction_ui(ui, state);
+    changed |= layout_modulation_ui(ui, state);
+    changed |= layout_midi_ui(ui, state);
+    changed |= layout_looks_ui(ui, state);
+    layout_assets_ui(ui, state);
+    changed |= layout_scenes_ui(ui, state);
+    changed |= layout_games_ui(ui, state);
+
+    ui.group(|ui| {
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut state.overlay_enabled, "Show Bezel Overlay").changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+            ui.label("Opacity:");
+            if ui.add(egui::Slider::new(&mut state.overlay_opacity, 0.0..=1.0)).changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Overlay PNG:");
+            ui.text_edit_singleline(&mut state.overlay_path_input)
+                .on_hover_text("Saved with the settings and with each scene, so scenes can use different bezels.");
+            if ui.button("Apply").clicked() {
+                state.overlay_path = state.overlay_path_input.trim().to_string();
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+        let bezels: Vec<_> = state.assets.iter().filter(|asset| asset.kind == AssetKind::Bezel && asset.is_valid()).cloned().collect();
+        if !bezels.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label("From assets:");
+                let current = state.current_overlay_path().cloned();
+                let selected = bezels.iter().find(|asset| current.as_deref() == asset.path.to_str()).map_or("Choose...", |asset| asset.name.as_str());
+                egui::ComboBox::from_id_source("bezel_asset_selector")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        for asset in &bezels {
+                            if ui.selectable_label(current.as_deref() == asset.path.to_str(), &asset.name).clicked() {
+                                let path = asset.path.to_string_lossy().into_owned();
+                                state.overlay_path_input = path.clone();
+                                state.overlay_path = path;
+                                config::mark_dirty(state);
+                                changed = true;
+                            }
+                        }
+                    });
+            });
+        }
+    });
+
+    persistent_collapsing(ui, state, "Text Overlay", |ui, state| {
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut state.text_overlay_enabled, "Show text over the video").changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+            egui::ComboBox::from_id_source("text_overlay_position")
+                .selected_text(state.text_overlay_position.to_string())
+                .show_ui(ui, |ui| {
+                    for position in [TextOverlayPosition::Top, TextOverlayPosition::Bottom] {
+                        if ui.selectable_value(&mut state.text_overlay_position, position, position.to_string()).changed() {
+                            config::mark_dirty(state);
+                            changed = true;
+                        }
+                    }
+                });
+            ui.label("Size:");
+            if ui.add(egui::Slider::new(&mut state.text_overlay_size, 8.0..=96.0)).changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Text:");
+            if ui.add_enabled(state.text_overlay_file.trim().is_empty(), egui::TextEdit::singleline(&mut state.text_overlay_text)).changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Text file:");
+            if ui.text_edit_singleline(&mut state.text_overlay_file)
+                .on_hover_text("Shows the contents of this file instead of the text above, updated whenever another program rewrites it (e.g. \"Now playing: ...\").")
+                .changed()
+            {
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+    });
+
+    persistent_collapsing(ui, state, "Timer Overlay", |ui, state| {
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut state.timer_enabled, "Show timer")
+                .on_hover_text("T starts and pauses the timer, R resets it. The keys work in the video window.")
+                .changed()
+            {
+                config::mark_dirty(state);
+                changed = true;
+            }
+            egui::ComboBox::from_id_source("timer_source")
+                .selected_text(state.timer_source.to_string())
+                .show_ui(ui, |ui| {
+                    for source in [TimerSource::Builtin, TimerSource::LiveSplit] {
+                        if ui.selectable_value(&mut state.timer_source, source, source.to_string()).changed() {
+                            config::mark_dirty(state);
+                            changed = true;
+                        }
+                    }
+                });
+            ui.label("Size:");
+            if ui.add(egui::Slider::new(&mut state.timer_size, 8.0..=128.0)).changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+        match state.timer_source {
+            TimerSource::Builtin => {
+                ui.horizontal(|ui| {
+                    let label = if state.stopwatch.is_running() { "⏸ Pause" } else { "▶ Start" };
+                    if ui.button(label).clicked() {
+                        state.toggle_timer();
+                    }
+                    if ui.button("⟲ Reset").clicked() {
+                        state.reset_timer();
+                    }
+                    ui.label(crate::video::timer::format_time(state.stopwatch.elapsed()));
+                });
+            }
+            TimerSource::LiveSplit => {
+                ui.horizontal(|ui| {
+                    ui.label("Server:");
+                    if ui.text_edit_singleline(&mut state.livesplit_address)
+                        .on_hover_text("Start the server in LiveSplit with Control > Start Server.")
+                        .changed()
+                    {
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                    match &state.livesplit_status {
+                        Some(status) => ui.label(format!("✔ {} ({})", status.time, status.phase)),
+                        None if state.timer_enabled => ui.label(egui::RichText::new("⚠ Not connected").color(egui::Color32::YELLOW)),
+                        None => ui.label(""),
+                    };
+                });
+            }
+        }
+    });
+
+    changed |= layout_autosplit_ui(ui, state);
+
+    if ui.checkbox(&mut state.clean_feed_enabled, "Show clean feed window")
+        .on_hover_text("Opens a second window with the raw video, without CRT filters, overlay or black frame insertion, e.g. for a capture or monitor screen.")
+        .changed()
+    {
+        config::mark_dirty(state);
+        changed = true;
+    }
+
+    persistent_collapsing(ui, state, "Display Wall", |ui, state| {
+        if ui.checkbox(&mut state.wall_enabled, "Span video across multiple windows")
+            .on_hover_text("Splits the video into tiles, one window each. Move each window to its monitor and press F there to make it fullscreen. CRT filters, the overlay and black frame insertion are not applied.")
+            .changed()
+        {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Columns:");
+            if ui.add(egui::DragValue::new(&mut state.wall_layout.columns).clamp_range(1..=4)).changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+            ui.label("Rows:");
+            if ui.add(egui::DragValue::new(&mut state.wall_layout.rows).clamp_range(1..=4)).changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+            ui.label("Bezel:");
+            if ui.add(egui::Slider::new(&mut state.wall_layout.bezel_percent, 0.0..=20.0).suffix("%"))
+                .on_hover_text("Hides the part of the picture behind the monitor bezels, as a percentage of one screen's size.")
+                .changed()
+            {
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+    });
+
+    persistent_collapsing(ui, state, "Network Preview", |ui, state| {
+        ui.horizontal(|ui| {
+            let toggle = ui.checkbox(&mut state.preview_enabled, "Serve MJPEG preview over LAN")
+                .on_hover_text("Open http://<this-machine>:<port>/ in a browser or VLC on another machine.");
+            if toggle.changed() {
+                state.apply_preview_server();
+                config::mark_dirty(state);
+                changed = true;
+            }
+            if let Some(server) = &state.preview_server {
+                ui.label(format!("{} viewer(s)", server.client_count()));
+            }
+        });
+        let mut settings_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Port:");
+            settings_changed |= ui.add(egui::DragValue::new(&mut state.preview_port).clamp_range(1024..=65535)).changed();
+            ui.label("Quality:");
+            settings_changed |= ui.add(egui::Slider::new(&mut state.preview_quality, 10..=100)).changed();
+            ui.label("Max FPS:");
+            settings_changed |= ui.add(egui::Slider::new(&mut state.preview_max_fps, 1..=60)).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("User:");
+            settings_changed |= ui.add(egui::TextEdit::singleline(&mut state.preview_username).desired_width(100.0)).changed();
+            ui.label("Password:");
+            settings_changed |= ui.add(egui::TextEdit::singleline(&mut state.preview_password).password(true).desired_width(100.0)).changed();
+        }).response.on_hover_text("Leave the user empty to disable authentication. Without a password, only this machine can connect. The password is kept in secrets.toml next to the config, readable only by you.");
+        if settings_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if state.preview_enabled && ui.button("Restart server with new settings").clicked() {
+            state.apply_preview_server();
+            changed = true;
+        }
+    });
+
+    persistent_collapsing(ui, state, "Remote Control", |ui, state| {
+        ui.horizontal(|ui| {
+            let toggle = ui.checkbox(&mut state.remote_enabled, "Serve a remote control page over LAN")
+                .on_hover_text("Open http://<this-machine>:<port>/ on a phone to start/stop the stream, switch looks, change the volume and take screenshots.");
+            if toggle.changed() {
+                state.apply_remote_server(ui.ctx());
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+        let mut settings_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Port:");
+            settings_changed |= ui.add(egui::DragValue::new(&mut state.remote_port).clamp_range(1024..=65535)).changed();
+            ui.label("User:");
+            settings_changed |= ui.add(egui::TextEdit::singleline(&mut state.remote_username).desired_width(100.0)).changed();
+            ui.label("Password:");
+            settings_changed |= ui.add(egui::TextEdit::singleline(&mut state.remote_password).password(true).desired_width(100.0)).changed();
+        }).response.on_hover_text("Leave the user empty to disable authentication. Without a password, only this machine can connect. The password is kept in secrets.toml next to the config, readable only by you.");
+        if settings_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if state.remote_enabled && ui.button("Restart server with new settings").clicked() {
+            state.apply_remote_server(ui.ctx());
+            changed = true;
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            let toggle = ui.checkbox(&mut state.command_port_enabled, "Accept commands on local UDP port")
+                .on_hover_text("For Stream Deck plugins and scripts on this machine. Send text like \"start\", \"stop\", \"filter lottes\" or \"profile ps2\", or the same as OSC messages to /michadame/<command>. See docs/commands.md.");
+            if toggle.changed() {
+                state.apply_command_server(ui.ctx());
+                config::mark_dirty(state);
+                changed = true;
+            }
+            if ui.add(egui::DragValue::new(&mut state.command_port).clamp_range(1024..=65535)).changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+            if state.command_server.is_some() && ui.button("Restart").on_hover_text("Listen on the new port.").clicked() {
+                state.apply_command_server(ui.ctx());
+            }
+        });
+    });
+
+    persistent_collapsing(ui, state, "Frame Sharing", |ui, state| {
+        ui.horizontal(|ui| {
+            let toggle = ui.checkbox(&mut state.frame_share_enabled, "Share frames with local programs")
+                .on_hover_text("Other programs on this machine can read the decoded frames from shared memory without opening the capture device. See docs/frame_share.md for the protocol.");
+            if toggle.changed() {
+                state.apply_frame_share();
+                config::mark_dirty(state);
+                changed = true;
+            }
+            if let Some(share) = &state.frame_share {
+                ui.label(format!("{} client(s)", share.client_count()));
+            }
+        });
+        if let Some(share) = &state.frame_share {
+            ui.label(egui::RichText::new(format!("Socket: {}", share.socket_path().display())).weak());
+        }
+    });
+
+    changed |= layout_obs_ui(ui, state);
+    changed |= layout_recording_ui(ui, state);
+    layout_recordings_browser_ui(ui, state);
+    changed |= layout_ffmpeg_log_ui(ui, state);
+    changed |= layout_latency_calibration_ui(ui, state);
+    layout_diagnostics_ui(ui, state);
+
+    ui.group(|ui| {
+        ui.horizontal(|ui| {
+            ui.label("Motion Clarity:");
+            egui::ComboBox::from_id_source("bfi_mode_selector")
+                .selected_text(state.bfi_mode.to_string())
+                .show_ui(ui, |ui| {
+                    let mut combo_changed = false;
+                    for mode in [BfiMode::Off, BfiMode::BlackFrame, BfiMode::RollingScan] {
+                        combo_changed |= ui.selectable_value(&mut state.bfi_mode, mode, mode.to_string()).changed();
+                    }
+                    if combo_changed {
+                        state.bfi_refresh_counter = 0;
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                });
+            if state.bfi_mode != BfiMode::Off {
+                ui.label("Refreshes per frame:");
+                if ui.add(egui::Slider::new(&mut state.bfi_cycle, 2..=4))
+                    .on_hover_text("2 for a 120 Hz display showing 60 fps, 4 for 240 Hz.")
+                    .changed()
+                {
+                    config::mark_dirty(state);
+                    changed = true;
+                }
+            }
+        });
+        if state.bfi_mode != BfiMode::Off {
+            ui.label(egui::RichText::new("⚠ Black frame insertion causes visible flicker, especially below 120 Hz. Do not use it if you are sensitive to flashing lights.").color(egui::Color32::YELLOW));
+        }
+    });
+
+    ui.separator();
+    ui.label(egui::RichText::new(&state.status.message).color(state.status.level.color()));
+    changed
+}
+
+fn layout_alsa_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut changed = false;
+    ui.group(|ui| {
+        ui.label("ALSA Fallback (PulseAudio is not available):");
+        if ui.checkbox(&mut state.alsa_enabled, "Enable in-app ALSA loopback").changed() {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if !state.alsa_enabled {
+            return;
+        }
+
+        let selected_capture_desc = state.alsa_captures.iter()
+            .find(|(_, dev)| Some(dev) == state.selected_alsa_capture.as_ref())
+            .map(|(desc, _)| desc.as_str())
+            .unwrap_or("Select a Capture Device");
+        egui::ComboBox::from_label("Capture")
+            .selected_text(selected_capture_desc)
+            .show_ui(ui, |ui| {
+                let mut combo_changed = false;
+                for (desc, dev) in &state.alsa_captures {
+                    combo_changed |= ui.selectable_value(&mut state.selected_alsa_capture, Some(dev.clone()), desc).changed();
+                }
+                if combo_changed {
+                    config::mark_dirty(state);
+                    changed = true;
+                }
+            });
+
+        let selected_playback_desc = state.alsa_playbacks.iter()
+            .find(|(_, dev)| Some(dev) == state.selected_alsa_playback.as_ref())
+            .map(|(desc, _)| desc.as_str())
+            .unwrap_or("Select a Playback Device");
+        egui::ComboBox::from_label("Playback")
+            .selected_text(selected_playback_desc)
+            .show_ui(ui, |ui| {
+                let mut combo_changed = false;
+                for (desc, dev) in &state.alsa_playbacks {
+                    combo_changed |= ui.selectable_value(&mut state.selected_alsa_playback, Some(dev.clone()), desc).changed();
+                }
+                if combo_changed {
+                    config::mark_dirty(state);
+                    changed = true;
+                }
+            });
+    });
+    changed
+}
+
+fn layout_playback_controls(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    use std::sync::atomic::Ordering;
+    let mut changed = false;
+    let playback = state.playback_control.clone();
+    ui.horizontal(|ui| {
+        let paused = playback.paused.load(Ordering::Relaxed);
+        if ui.button(if paused { "▶ Play" } else { "⏸ Pause" }).clicked() {
+            playback.paused.store(!paused, Ordering::Relaxed);
+            changed = true;
+        }
+        let duration_ms = playback.duration_ms.load(Ordering::Relaxed);
+        let mut position_secs = playback.position_ms.load(Ordering::Relaxed) as f64 / 1000.0;
+        let duration_secs = duration_ms as f64 / 1000.0;
+        if duration_ms > 0 {
+            let label = format!("{:.1} / {:.1} s", position_secs, duration_secs);
+            let slider = egui::Slider::new(&mut position_secs, 0.0..=duration_secs)
+                .show_value(false)
+                .text(label);
+            if ui.add(slider).changed() {
+                playback.request_seek((position_secs * 1000.0) as u64);
+                changed = true;
+            }
+        }
+    });
+    // Keep the position readout moving.
+    ui.ctx().request_repaint();
+    changed
+}
+
+/// Brightness, contrast and gamma, and the test card calibration that suggests them.
+fn layout_color_correction_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut changed = false;
+    persistent_collapsing(ui, state, "Color Correction", |ui, state| {
+        let settings = &mut state.filter_settings;
+        let mut settings_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Brightness:");
+            settings_changed |= ui.add(egui::Slider::new(&mut settings.brightness, FilterSettings::BRIGHTNESS)).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Contrast:");
+            settings_changed |= ui.add(egui::Slider::new(&mut settings.contrast, FilterSettings::CONTRAST)).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Gamma:");
+            settings_changed |= ui.add(egui::Slider::new(&mut settings.gamma, FilterSettings::GAMMA)).changed();
+            if ui.button("Reset").on_hover_text("Go back to the saved defaults").clicked() {
+                settings.set_color_adjust(state.filter_defaults.color_adjust());
+                settings_changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            let current = settings.color_adjust();
+            if ui.add_enabled(current != state.filter_defaults.color_adjust(), egui::Button::new("Save as Defaults"))
+                .on_hover_text("Make these values what \"Reset\" goes back to")
+                .clicked()
+            {
+                state.filter_defaults.set_color_adjust(current);
+                settings_changed = true;
+            }
+            if ui.add_enabled(!state.filter_defaults.color_adjust().is_neutral(), egui::Button::new("Restore Built-in"))
+                .on_hover_text("Forget the saved defaults and turn the correction off")
+                .clicked()
+            {
+                state.filter_defaults.set_color_adjust(Default::default());
+                settings.set_color_adjust(Default::default());
+                settings_changed = true;
+            }
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("White point:");
+            settings_changed |= ui.add(egui::Slider::new(&mut settings.white_point, FilterSettings::WHITE_POINT).suffix(" K").step_by(100.0))
+                .on_hover_text("Color temperature of the simulated screen's white")
+                .changed();
+        });
+        ui.horizontal(|ui| {
+            for (preset, name, hover) in [
+                (crate::video::color::D65, "D65", "Neutral: the colors as captured"),
+                (crate::video::color::D93, "D93", "The bluish white of Japanese CRTs, which a lot of games were made on"),
+            ] {
+                if ui.selectable_label(settings.white_point == preset, name).on_hover_text(hover).clicked() {
+                    settings.white_point = preset;
+                    settings_changed = true;
+                }
+            }
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            let running = state.color_calibration_running();
+            if ui.add_enabled(!running, egui::Button::new("Calibrate from test card"))
+                .on_hover_text("Show a full-screen gray ramp on the console, e.g. the 240p Test Suite's \"Gray ramp\", then press this. The ramp's darkest part should come out black, its brightest white and its middle halfway. With \"Show only a region\" on (Scenes), only that region is measured.")
+                .clicked()
+            {
+                state.start_color_calibration();
+            }
+            if running {
+                ui.spinner();
+                ui.label("Analyzing...");
+            }
+        });
+        if let Some(calibration) = state.calibration_result {
+            let recommended = calibration.recommended;
+            ui.label(format!(
+                "Measured black {:.0}%, middle {:.0}%, white {:.0}%",
+                calibration.black * 100.0, calibration.middle * 100.0, calibration.white * 100.0
+            ));
+            ui.horizontal(|ui| {
+                ui.label(format!("Recommended: brightness {:.2}, contrast {:.2}, gamma {:.2}", recommended.brightness, recommended.contrast, recommended.gamma));
+                if ui.button("Apply").clicked() {
+                    state.filter_settings.set_color_adjust(recommended);
+                    state.calibration_result = None;
+                    settings_changed = true;
+                }
+            });
+        }
+        if settings_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    changed
+}
+
+/// Browser for shareable filter "looks" (see `looks.rs`).
+fn layout_looks_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut changed = false;
+    persistent_collapsing(ui, state, "Looks", |ui, state| {
+        let mut apply = None;
+        for (name, path) in &state.available_looks {
+            ui.horizontal(|ui| {
+                if ui.button("Apply").clicked() {
+                    apply = Some(path.clone());
+                }
+                ui.label(name).on_hover_text(path.display().to_string());
+            });
+        }
+        if state.available_looks.is_empty() {
+            ui.label("No looks saved yet.");
+        }
+        if let Some(path) = apply {
+            match crate::looks::load_look(&path) {
+                Ok(look) => {
+                    look.apply(state);
+                    state.notify(Notification::success(format!("Applied look '{}'", look.name)));
+                    changed = true;
+                }
+                Err(e) => state.notify(Notification::error(format!("Failed to load look: {:#}", e))),
+            }
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.look_name_input).hint_text("Look name").desired_width(200.0));
+            let name = state.look_name_input.trim().to_string();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new("Save Current Look")).clicked() {
+                match crate::looks::save_look(&crate::looks::Look::from_state(state, &name)) {
+                    Ok(path) => state.notify(Notification::success(format!("Saved look to {}", path.display()))),
+                    Err(e) => state.notify(Notification::error(format!("Failed to save look: {:#}", e))),
+                }
+                state.refresh_looks();
+                changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.look_import_path)
+                .hint_text("Path to a .look.toml, or drop it on the window")
+                .desired_width(300.0));
+            if ui.add_enabled(!state.look_import_path.trim().is_empty(), egui::Button::new("Import")).clicked() {
+                let path = std::path::PathBuf::from(state.look_import_path.trim());
+                state.import_look(&path);
+                changed = true;
+            }
+            if ui.button("Refresh").clicked() {
+                state.refresh_looks();
+            }
+        });
+    });
+    changed
+}
+
+/// LFOs and OSC/MIDI inputs that animate filter parameters.
+fn layout_modulation_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    use crate::video::modulation::{ModParam, ModSource, Modulation};
+    let mut changed = false;
+    persistent_collapsing(ui, state, "Modulation", |ui, state| {
+        if ui.checkbox(&mut state.modulation_enabled, "Animate filter parameters")
+            .on_hover_text("Swing filter parameters around their slider values with LFOs, or drive them from OSC or a MIDI controller. Parameters never leave their slider's range.")
+            .changed()
+        {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut state.modulation_osc_enabled, "OSC on UDP port")
+                .on_hover_text("Listens on localhost only, for OSC apps and bridges on this computer.")
+                .changed()
+            {
+                state.apply_osc_input(ui.ctx());
+                config::mark_dirty(state);
+                changed = true;
+            }
+            if ui.add(egui::DragValue::new(&mut state.modulation_osc_port).clamp_range(1024..=65535)).changed() {
+                config::mark_dirty(state);
+                changed = true;
+            }
+            if state.osc_input.is_some() && ui.button("Restart").on_hover_text("Listen on the new port.").clicked() {
+                state.apply_osc_input(ui.ctx());
+            }
+        });
+        if let Some(last) = state.osc_input.as_ref().and_then(|input| input.values().last_received) {
+            ui.label(egui::RichText::new(format!("Last received: {}", last)).weak());
+        }
+
+        let live = state.modulated_filter_settings();
+        let mut remove = None;
+        let mut modulations_changed = false;
+        for (index, modulation) in state.modulations.iter_mut().enumerate() {
+            ui.separator();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source(("modulation_param", index))
+                    .selected_text(modulation.param.to_string())
+                    .show_ui(ui, |ui| {
+                        for param in ModParam::ALL {
+                            modulations_changed |= ui.selectable_value(&mut modulation.param, param, param.to_string()).changed();
+                        }
+                    });
+                ui.label("from");
+                egui::ComboBox::from_id_source(("modulation_source", index))
+                    .selected_text(modulation.source.to_string())
+                    .show_ui(ui, |ui| {
+                        for source in ModSource::ALL {
+                            modulations_changed |= ui.selectable_value(&mut modulation.source, source, source.to_string()).changed();
+                        }
+                    });
+                if ui.small_button("🗑").on_hover_text("Delete this modulation").clicked() {
+                    remove = Some(index);
+                }
+            });
+            ui.horizontal(|ui| {
+                match modulation.source {
+                    source if source.is_lfo() => {
+                        ui.label("Rate:");
+                        modulations_changed |= ui.add(egui::Slider::new(&mut modulation.rate_hz, 0.01..=10.0).logarithmic(true).suffix(" Hz")).changed();
+                    }
+                    ModSource::Osc => {
+                        ui.label("Address:");
+                        modulations_changed |= ui.add(egui::TextEdit::singleline(&mut modulation.osc_address).desired_width(140.0)).changed();
+                    }
+                    _ => {
+                        ui.label("Channel:");
+                        modulations_changed |= ui.add(egui::DragValue::new(&mut modulation.midi_channel).clamp_range(1..=16)).changed();
+                        ui.label("CC:");
+                        modulations_changed |= ui.add(egui::DragValue::new(&mut modulation.midi_controller).clamp_range(0..=127)).changed();
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Depth:");
+                modulations_changed |= ui.add(egui::Slider::new(&mut modulation.depth, 0.0..=1.0))
+                    .on_hover_text("How far the parameter swings either way, as a share of its slider's range.")
+                    .changed();
+                if state.modulation_enabled {
+                    ui.label(format!("Now: {:.3}", modulation.param.value(&live)));
+                }
+            });
+        }
+        if let Some(index) = remove {
+            state.modulations.remove(index);
+            modulations_changed = true;
+        }
+        ui.separator();
+        if ui.button("➕ Add modulation").clicked() {
+            state.modulations.push(Modulation::default());
+            modulations_changed = true;
+        }
+        if modulations_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if state.modulation_enabled && state.modulations.iter().any(|modulation| modulation.source == ModSource::Osc) && !state.modulation_osc_enabled {
+            ui.label(egui::RichText::new("⚠ OSC modulations need the OSC port turned on").color(egui::Color32::YELLOW));
+        }
+        if state.modulation_enabled && state.modulations.iter().any(|modulation| modulation.source == ModSource::Midi) && state.midi_device.is_none() {
+            ui.label(egui::RichText::new("⚠ MIDI modulations need a port chosen in MIDI Controller").color(egui::Color32::YELLOW));
+        }
+    });
+    changed
+}
+
+/// A MIDI controller's knobs on filter sliders and its buttons on stream commands.
+fn layout_midi_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    use crate::devices::midi::{self, MidiMapping, MidiTarget};
+    use crate::video::modulation::ModParam;
+    let mut changed = false;
+    persistent_collapsing(ui, state, "MIDI Controller", |ui, state| {
+        ui.horizontal(|ui| {
+            ui.label("MIDI port:");
+            let devices = midi::midi_devices();
+            let selected = state.midi_device.as_ref().map(|path| {
+                devices.iter().find(|(device, _)| device == path).map_or(path.clone(), |(_, label)| label.clone())
+            });
+            let mut device_changed = false;
+            egui::ComboBox::from_id_source("midi_device_selector")
+                .selected_text(selected.unwrap_or_else(|| "None".to_string()))
+                .show_ui(ui, |ui| {
+                    device_changed |= ui.selectable_value(&mut state.midi_device, None, "None").changed();
+                    for (path, label) in devices {
+                        device_changed |= ui.selectable_value(&mut state.midi_device, Some(path), label).changed();
+                    }
+                });
+            if device_changed {
+                state.apply_midi_input(ui.ctx());
+                config::mark_dirty(state);
+                changed = true;
+            }
+        });
+        if let Some(event) = state.midi_last_event {
+            ui.label(egui::RichText::new(format!("Last received: {} = {:.2}", event.control.label(), event.value)).weak());
+        }
+
+        let (mut learn, mut remove) = (None, None);
+        let mut mappings_changed = false;
+        for (index, mapping) in state.midi_mappings.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let learning = state.midi_learn == Some(index);
+                let label = if learning { "Move a control...".to_string() } else { mapping.control.label() };
+                if ui.selectable_label(learning, label).on_hover_text("Click, then move a knob or press a button to bind it.").clicked() {
+                    learn = Some(if learning { None } else { Some(index) });
+                }
+                ui.label("→");
+                egui::ComboBox::from_id_source(("midi_target", index))
+                    .selected_text(mapping.target.to_string())
+                    .show_ui(ui, |ui| {
+                        for target in MidiTarget::ALL {
+                            mappings_changed |= ui.selectable_value(&mut mapping.target, target, target.to_string()).changed();
+                        }
+                    });
+                if mapping.target == MidiTarget::Slider {
+                    egui::ComboBox::from_id_source(("midi_param", index))
+                        .selected_text(mapping.param.to_string())
+                        .show_ui(ui, |ui| {
+                            for param in ModParam::ALL {
+                                mappings_changed |= ui.selectable_value(&mut mapping.param, param, param.to_string()).changed();
+                            }
+                        });
+                }
+                if ui.small_button("🗑").on_hover_text("Delete this mapping").clicked() {
+                    remove = Some(index);
+                }
+            });
+        }
+        if let Some(learn) = learn {
+            state.midi_learn = learn;
+        }
+        if let Some(index) = remove {
+            state.midi_mappings.remove(index);
+            state.midi_learn = None;
+            mappings_changed = true;
+        }
+        if ui.button("➕ Add mapping").on_hover_text("Adds a mapping and waits for the control to bind.").clicked() {
+            state.midi_mappings.push(MidiMapping::default());
+            state.midi_learn = Some(state.midi_mappings.len() - 1);
+            mappings_changed = true;
+        }
+        if mappings_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if state.midi_device.is_none() && !state.midi_mappings.is_empty() {
+            ui.label(egui::RichText::new("⚠ Choose a MIDI port to use the mappings").color(egui::Color32::YELLOW));
+        }
+    });
+    changed
+}
+
+/// The user's shaders, masks, bezels and LUTs, with what was found when checking them. Only
+/// bezels have a picker; the rest are listed so problems show up before the filters can use them.
+fn layout_assets_ui(ui: &mut egui::Ui, state: &mut AppState) {
+    persistent_collapsing(ui, state, "Assets", |ui, state| {
+        if let Ok(dir) = crate::assets::assets_dir() {
+            ui.label(egui::RichText::new(format!("Folder: {}", dir.display())).weak())
+                .on_hover_text("Drop files into the shaders, masks, bezels and luts subfolders. New and changed files are picked up within a second.");
+        }
+        for kind in AssetKind::ALL {
+            let assets: Vec<_> = state.assets.iter().filter(|asset| asset.kind == kind).collect();
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(format!("{} ({})", kind.to_string(), assets.len())).strong());
+                let usage = if kind.has_picker() { "pick one under Overlay" } else { "checked only, the filters can't use these yet" };
+                ui.label(egui::RichText::new(usage).weak());
+            });
+            if assets.is_empty() {
+                ui.label(egui::RichText::new(format!("None yet, add them to {}/", kind.dir_name())).weak());
+            }
+            for asset in assets {
+                ui.horizontal(|ui| {
+                    match &asset.check {
+                        Ok(detail) => ui.label(format!("✔ {} ({})", asset.name, detail)),
+                        Err(problem) => ui.label(egui::RichText::new(format!("⚠ {}: {}", asset.name, problem)).color(egui::Color32::YELLOW)),
+                    }
+                    .on_hover_text(asset.path.display().to_string());
+                });
+            }
+        }
+    });
+}
+
+fn layout_autosplit_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    use crate::video::autosplit::{SplitAction, SplitTrigger};
+    let mut changed = false;
+    persistent_collapsing(ui, state, "Auto Splitter", |ui, state| {
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut state.autosplit_enabled, "Split when the video matches a reference image")
+                .on_hover_text("Compares a region of each frame with a picture, e.g. a \"Stage clear\" banner, and sends the trigger's command to LiveSplit Server when they match.")
+                .changed()
+            {
+                state.apply_autosplitter(ui.ctx());
+                config::mark_dirty(state);
+                changed = true;
+            }
+            if state.autosplit_enabled && ui.button("Reload triggers").on_hover_text("Apply changed triggers and reference images.").clicked() {
+                state.apply_autosplitter(ui.ctx());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("LiveSplit Server:");
+            if ui.text_edit_singleline(&mut state.livesplit_address)
+                .on_hover_text("Start the server in LiveSplit with Control > Start Server.")
+                .changed()
+            {
+                config::mark_dirty(state);
+                changed = true;
+            }
+            match &state.livesplit_status {
+                Some(status) => ui.label(format!("✔ {} ({})", status.time, status.phase)),
+                None if state.autosplit_enabled => ui.label(egui::RichText::new("⚠ Not connected").color(egui::Color32::YELLOW)),
+                None => ui.label(""),
+            };
+        });
+        if let Some((trigger, at)) = &state.autosplit_last_event {
+            ui.label(format!("Last match: {} ({}s ago)", trigger, at.elapsed().as_secs()));
+        }
+
+        let similarities = state.autosplitter.as_ref().map(|(splitter, _)| splitter.similarities()).unwrap_or_default();
+        let (mut capture, mut remove) = (None, None);
+        let mut triggers_changed = false;
+        for (index, trigger) in state.autosplit_triggers.iter_mut().enumerate() {
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                triggers_changed |= ui.add(egui::TextEdit::singleline(&mut trigger.name).desired_width(120.0)).changed();
+                egui::ComboBox::from_id_source(("autosplit_action", index))
+                    .selected_text(trigger.action.to_string())
+                    .show_ui(ui, |ui| {
+                        for action in SplitAction::ALL {
+                            triggers_changed |= ui.selectable_value(&mut trigger.action, action, action.to_string()).changed();
+                        }
+                    });
+                match similarities.get(index).copied().flatten() {
+                    Some(similarity) => ui.label(format!("Match: {:.0}%", similarity * 100.0)),
+                    None if state.autosplit_enabled => ui.label(egui::RichText::new("⚠ Region outside the frame").color(egui::Color32::YELLOW)),
+                    None => ui.label(""),
+                };
+                if ui.small_button("🗑").on_hover_text("Delete this trigger").clicked() {
+                    remove = Some(index);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Image:");
+                triggers_changed |= ui.add(egui::TextEdit::singleline(&mut trigger.image_path).desired_width(220.0)).changed();
+                if ui.button("Capture").on_hover_text("Save the region of the current frame as this trigger's image.").clicked() {
+                    capture = Some(index);
+                }
+            });
+            let (x, y, w, h) = &mut trigger.region;
+            ui.horizontal(|ui| {
+                ui.label("X:");
+                triggers_changed |= ui.add(egui::DragValue::new(x)).changed();
+                ui.label("Y:");
+                triggers_changed |= ui.add(egui::DragValue::new(y)).changed();
+                ui.label("Width:");
+                triggers_changed |= ui.add(egui::DragValue::new(w).clamp_range(1..=7680)).changed();
+                ui.label("Height:");
+                triggers_changed |= ui.add(egui::DragValue::new(h).clamp_range(1..=4320)).changed();
+            }).response.on_hover_text("The part of the source frame to compare, in source pixels.");
+            ui.horizontal(|ui| {
+                ui.label("Threshold:");
+                triggers_changed |= ui.add(egui::Slider::new(&mut trigger.threshold, 0.5..=1.0).fixed_decimals(2)).changed();
+                ui.label("Cooldown:");
+                triggers_changed |= ui.add(egui::Slider::new(&mut trigger.cooldown_secs, 0.0..=60.0).suffix(" s")).changed();
+            });
+        }
+        if let Some(index) = remove {
+            state.autosplit_triggers.remove(index);
+            triggers_changed = true;
+        }
+        if let Some(index) = capture {
+            triggers_changed |= state.capture_autosplit_reference(index);
+        }
+        ui.separator();
+        if ui.button("➕ Add trigger").clicked() {
+            let name = format!("Split {}", state.autosplit_triggers.len() + 1);
+            state.autosplit_triggers.push(SplitTrigger { name, ..Default::default() });
+            triggers_changed = true;
+        }
+        if triggers_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if state.autosplitter.as_ref().is_some_and(|(splitter, _)| splitter.triggers() != state.autosplit_triggers.as_slice()) {
+            ui.label(egui::RichText::new("⚠ Press \"Reload triggers\" to use the changes").color(egui::Color32::YELLOW));
+        }
+    });
+    changed
+}
+
+fn layout_scenes_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut changed = false;
+    persistent_collapsing(ui, state, "Scenes", |ui, state| {
+        let mut crop_changed = false;
+        ui.horizontal(|ui| {
+            crop_changed |= ui.checkbox(&mut state.display_crop_enabled, "Show only a region")
+                .on_hover_text("Zooms in on this rectangle of the source frame, e.g. a game's HUD. Recordings and the network preview still get the whole frame.")
+                .changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Auto-crop:");
+            egui::ComboBox::from_id_source("auto_crop_mode")
+                .selected_text(state.auto_crop_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in AutoCropMode::ALL {
+                        crop_changed |= ui.selectable_value(&mut state.auto_crop_mode, mode, mode.to_string()).changed();
+                    }
+                })
+                .response
+                .on_hover_text("Looks for black borders around the picture, e.g. a 4:3 game in a 16:9 signal. \"Automatic\" follows them as they change.");
+        });
+        if state.auto_crop_mode == AutoCropMode::Suggest {
+            if let Some((x, y, w, h)) = state.auto_crop_suggestion {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Black borders found, the picture is {}x{} at {}, {}", w, h, x, y));
+                    if ui.button("Crop").clicked() {
+                        state.accept_auto_crop();
+                        changed = true;
+                    }
+                });
+            }
+        }
+        if state.display_crop_enabled {
+            let (x, y, w, h) = &mut state.display_crop;
+            ui.horizontal(|ui| {
+                ui.label("X:");
+                crop_changed |= ui.add(egui::DragValue::new(x)).changed();
+                ui.label("Y:");
+                crop_changed |= ui.add(egui::DragValue::new(y)).changed();
+                ui.label("Width:");
+                crop_changed |= ui.add(egui::DragValue::new(w).clamp_range(1..=7680)).changed();
+                ui.label("Height:");
+                crop_changed |= ui.add(egui::DragValue::new(h).clamp_range(1..=4320)).changed();
+            });
+        }
+        if crop_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        ui.separator();
+        let (mut switch, mut remove) = (None, None);
+        for (index, scene) in state.scenes.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.button("Switch").clicked() {
+                    switch = Some(index);
+                }
+                let label = if index < crate::scenes::MAX_HOTKEY_SCENES { format!("{}: {}", index + 1, scene.name) } else { scene.name.clone() };
+                let text = egui::RichText::new(label);
+                let text = if state.active_scene.as_ref() == Some(&scene.name) { text.strong() } else { text };
+                let mut details = vec![scene.device.clone().unwrap_or_else(|| "Current source".to_string())];
+                if let Some((x, y, w, h)) = scene.crop {
+                    details.push(format!("{}x{} at {},{}", w, h, x, y));
+                }
+                if let Some(look) = &scene.look {
+                    details.push(format!("look '{}'", look));
+                }
+                ui.label(text).on_hover_text(details.join(", "));
+                if ui.small_button("🗑").on_hover_text("Delete this scene").clicked() {
+                    remove = Some(index);
+                }
+            });
+        }
+        if state.scenes.is_empty() {
+            ui.label("No scenes yet. Set up the crop, look and overlays, then save them as a scene.");
+        }
+        if let Some(index) = switch {
+            state.switch_scene(index, ui.ctx());
+            changed = true;
+        }
+        if let Some(index) = remove {
+            let scene = state.scenes.remove(index);
+            if state.active_scene.as_ref() == Some(&scene.name) {
+                state.active_scene = None;
+            }
+            config::mark_dirty(state);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.scene_name_input).hint_text("Scene name").desired_width(200.0));
+            let name = state.scene_name_input.trim().to_string();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new("Save Current Scene"))
+                .on_hover_text("Saves the capture device, region, applied look and overlays. Scenes on the same device switch without restarting the capture.")
+                .clicked()
+            {
+                state.save_scene(&name);
+                state.notify(Notification::success(format!("Saved scene '{}'", name)));
+                changed = true;
+            }
+        });
+        ui.label(egui::RichText::new("Keys 1-9 switch to the first nine scenes.").weak());
+    });
+    changed
+}
+
+/// Game detection: title screens recognized by `games::Fingerprint`, each switching to a scene.
+fn layout_games_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut changed = false;
+    persistent_collapsing(ui, state, "Games", |ui, state| {
+        if ui.checkbox(&mut state.game_detection_enabled, "Switch settings when a known game starts")
+            .on_hover_text("Watches for the title screens below and switches to the game's scene when one shows up")
+            .changed()
+        {
+            state.game_detector.reset();
+            config::mark_dirty(state);
+            changed = true;
+        }
+        let mut remove = None;
+        for (index, game) in state.games.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let text = egui::RichText::new(&game.name);
+                let text = if state.game_detector.detected() == Some(game.name.as_str()) { text.strong() } else { text };
+                ui.label(text);
+                ui.label(egui::RichText::new(format!("{} screen{}, scene '{}'", game.fingerprints.len(), if game.fingerprints.len() == 1 { "" } else { "s" }, game.scene)).weak());
+                if ui.small_button("🗑").on_hover_text("Forget this game. Its scene stays.").clicked() {
+                    remove = Some(index);
+                }
+            });
+            if !state.scenes.iter().any(|scene| scene.name == game.scene) {
+                ui.label(egui::RichText::new(format!("⚠ The scene '{}' no longer exists", game.scene)).color(egui::Color32::YELLOW));
+            }
+        }
+        if state.games.is_empty() {
+            ui.label("No games yet. Show a game's title screen, set up the crop and look, then remember it.");
+        }
+        if let Some(index) = remove {
+            state.games.remove(index);
+            config::mark_dirty(state);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.game_name_input).hint_text("Game name").desired_width(200.0));
+            let name = state.game_name_input.trim().to_string();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new("Remember This Screen"))
+                .on_hover_text("Learns the picture on screen as one of the game's screens, and saves the current setup as a scene named after the game. Do it again on other screens, e.g. the publisher logo, to recognize the game more reliably.")
+                .clicked()
+            {
+                state.remember_game(&name);
+                changed = true;
+            }
+        });
+    });
+    changed
+}
+
+fn layout_obs_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut changed = false;
+    persistent_collapsing(ui, state, "OBS", |ui, state| {
+        let mut settings_changed = false;
+        ui.horizontal(|ui| {
+            settings_changed |= ui.checkbox(&mut state.obs_enabled, "Control OBS when the stream starts and stops")
+                .on_hover_text("Needs obs-websocket, built into OBS 28 and later (Tools > WebSocket Server Settings).")
+                .changed();
+            if ui.add_enabled(state.obs_enabled, egui::Button::new("Test connection")).clicked() {
+                state.send_obs_event(crate::net::obs::ObsEvent::Test, ui.ctx());
+            }
+        });
+        ui.add_enabled_ui(state.obs_enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Address:");
+                settings_changed |= ui.add(egui::TextEdit::singleline(&mut state.obs_settings.address).hint_text(crate::net::obs::DEFAULT_ADDRESS).desired_width(140.0)).changed();
+                ui.label("Password:");
+                settings_changed |= ui.add(egui::TextEdit::singleline(&mut state.obs_settings.password).password(true).desired_width(100.0)).changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Scene on start:");
+                settings_changed |= ui.add(egui::TextEdit::singleline(&mut state.obs_settings.start_scene).desired_width(120.0)).changed();
+                ui.label("on stop:");
+                settings_changed |= ui.add(egui::TextEdit::singleline(&mut state.obs_settings.stop_scene).desired_width(120.0)).changed();
+            }).response.on_hover_text("Leave empty to keep the current scene.");
+            settings_changed |= ui.checkbox(&mut state.obs_settings.control_recording, "Start and stop OBS recording with the stream").changed();
+        });
+        if settings_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    changed
+}
+
+fn layout_recording_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut changed = false;
+    persistent_collapsing(ui, state, "Recording", |ui, state| {
+        if let Some(path) = &state.unfinished_recording {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            ui.label(egui::RichText::new(format!("⚠ The last recording ({}) was interrupted.", name)).color(egui::Color32::YELLOW));
+            ui.horizontal(|ui| {
+                if ui.button("Recover last recording").on_hover_text("Saves what was recorded up to the interruption to a new file next to it.").clicked() {
+                    state.start_recording_recovery(ui.ctx());
+                    changed = true;
+                }
+                if ui.button("Dismiss").clicked() {
+                    state.dismiss_unfinished_recording();
+                    changed = true;
+                }
+            });
+        } else if state.recovery_receiver.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Recovering recording…");
+            });
+        }
+        ui.horizontal(|ui| {
+            if let Some(recorder) = &state.recorder {
+                if ui.button("⏹ Stop Recording").clicked() {
+                    state.stop_recording();
+                    changed = true;
+                } else {
+                    let secs = recorder.elapsed().as_secs();
+                    ui.label(egui::RichText::new(format!("● REC {:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)).color(egui::Color32::RED));
+                    ui.label(format!("{} frames", recorder.frames_written()));
+                    let chapters = recorder.chapter_count();
+                    if ui.button("🔖 Marker (K)").on_hover_text("Starts a new chapter in the recording, e.g. for each attempt.").clicked() {
+                        state.add_chapter_marker();
+                        changed = true;
+                    }
+                    if chapters > 0 {
+                        ui.label(format!("{} marker(s)", chapters));
+                    }
+                }
+            } else if ui.add_enabled(state.frame_receiver.is_some(), egui::Button::new("⏺ Start Recording"))
+                .on_disabled_hover_text("Start the stream first.")
+                .clicked()
+            {
+                state.start_recording();
+                changed = true;
+            }
+            if ui.add_enabled(state.frame_receiver.is_some(), egui::Button::new("📷 Screenshot (S)"))
+                .on_hover_text("Saves the current frame as a PNG in the recordings folder.")
+                .clicked()
+            {
+                state.save_screenshot(ui.ctx());
+                changed = true;
+            }
+        });
+        let mut settings_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Folder:");
+            settings_changed |= ui.text_edit_singleline(&mut state.recording_dir).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("File name:");
+            settings_changed |= ui.text_edit_singleline(&mut state.recording_file_template)
+                .on_hover_text("Placeholders: {date}, {time}, {profile} (the applied look), {res} and {source}. A number is appended if the file already exists.")
+                .changed();
+            if ui.small_button("Reset").clicked() {
+                state.recording_file_template = crate::video::recorder::DEFAULT_FILE_TEMPLATE.to_string();
+                settings_changed = true;
+            }
+        });
+        if let Some(name) = state.next_recording_path().file_name() {
+            ui.label(egui::RichText::new(format!("Next: {}", name.to_string_lossy())).weak());
+        }
+        let encoders = state.available_encoders(ui.ctx()).cloned();
+        ui.horizontal(|ui| {
+            ui.label("Encoder:");
+            egui::ComboBox::from_id_source("record_encoder_selector")
+                .selected_text(state.record_encoder.to_string())
+                .show_ui(ui, |ui| {
+                    for encoder in VideoEncoder::ALL {
+                        let label = if encoders.as_ref().is_none_or(|encoders| encoders.contains(&encoder)) {
+                            encoder.to_string().to_string()
+                        } else {
+                            format!("{} (not in ffmpeg)", encoder.to_string())
+                        };
+                        settings_changed |= ui.selectable_value(&mut state.record_encoder, encoder, label).changed();
+                    }
+                })
+                .response
+                .on_hover_text("Hardware encoders leave the CPU free for decoding. If recording stops right away, the GPU or driver doesn't support the selected one.");
+            if encoders.is_none() {
+                ui.spinner();
+            }
+            ui.label("Quality:");
+            egui::ComboBox::from_id_source("record_quality_selector")
+                .selected_text(state.record_quality.to_string())
+                .show_ui(ui, |ui| {
+                    for quality in [RecordQuality::High, RecordQuality::Balanced, RecordQuality::Small] {
+                        settings_changed |= ui.selectable_value(&mut state.record_quality, quality, quality.to_string()).changed();
+                    }
+                });
+        });
+        if state.record_encoder == VideoEncoder::Vaapi {
+            ui.horizontal(|ui| {
+                ui.label("Render node:");
+                settings_changed |= ui.add(egui::TextEdit::singleline(&mut state.vaapi_device).desired_width(160.0)).changed();
+            }).response.on_hover_text("The GPU VA-API encodes on. With several GPUs, each has its own /dev/dri/renderD* node.");
+        }
+        ui.add_enabled_ui(state.pulse_available, |ui| {
+            ui.horizontal(|ui| {
+                settings_changed |= ui.checkbox(&mut state.record_audio, "Capture audio track").changed();
+                settings_changed |= ui.add(egui::Slider::new(&mut state.record_audio_volume, 0.0..=2.0).text("Gain")).changed();
+            });
+            ui.horizontal(|ui| {
+                settings_changed |= ui.checkbox(&mut state.record_mic_enabled, "Microphone track").changed();
+                egui::ComboBox::from_id_source("record_mic_selector")
+                    .selected_text(state.record_mic_source.as_ref()
+                        .and_then(|name| state.pulse_sources.iter().find(|(_, n)| n == name).map(|(desc, _)| desc.clone()))
+                        .unwrap_or_else(|| "Select a source".to_string()))
+                    .show_ui(ui, |ui| {
+                        for (desc, name) in &state.pulse_sources {
+                            settings_changed |= ui.selectable_value(&mut state.record_mic_source, Some(name.clone()), desc).changed();
+                        }
+                    });
+                settings_changed |= ui.add(egui::Slider::new(&mut state.record_mic_volume, 0.0..=2.0).text("Gain")).changed();
+            }).response.on_hover_text("Each source is recorded as a separate audio track, so commentary can be remixed later.");
+            if state.record_mic_enabled {
+                ui.horizontal(|ui| {
+                    settings_changed |= ui.checkbox(&mut state.mic_highpass_enabled, "High-pass")
+                        .on_hover_text("Cuts rumble and desk thumps, and suppresses steady noise like fans. Also removes what the default output plays from the microphone.")
+                        .changed();
+                    settings_changed |= ui.checkbox(&mut state.mic_gate_enabled, "Noise gate")
+                        .on_hover_text("Mutes the microphone while you aren't talking. Needs the swh LADSPA plugins.")
+                        .changed();
+                    if state.mic_gate_enabled {
+                        settings_changed |= ui.add(egui::Slider::new(&mut state.mic_gate_threshold_db, -70.0..=0.0).suffix(" dB").text("Threshold"))
+                            .on_hover_text("Sounds quieter than this are muted. Raise it until background noise is gone between sentences.")
+                            .changed();
+                    }
+                });
+                if state.recorder.is_some() && (state.mic_highpass_enabled || state.mic_gate_enabled) {
+                    ui.label(egui::RichText::new("Filter changes apply to the next recording.").weak());
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            settings_changed |= ui.checkbox(&mut state.record_burn_in, "Record with filters burned in")
+                .on_hover_text("Records what the GPU filters produce at the source resolution instead of the raw capture. The bezel overlay and black frame insertion are not included. Only frames shown while the video window is visible are recorded.")
+                .changed();
+        });
+        if state.record_burn_in {
+            ui.label(egui::RichText::new("⚠ Reading frames back from the GPU is slow and may cause dropped frames at high resolutions.").color(egui::Color32::YELLOW));
+        }
+        ui.horizontal(|ui| {
+            ui.label("Time-lapse:");
+            egui::ComboBox::from_id_source("timelapse_mode_selector")
+                .selected_text(state.timelapse_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in [TimelapseMode::Off, TimelapseMode::EverySeconds, TimelapseMode::EveryFrames] {
+                        settings_changed |= ui.selectable_value(&mut state.timelapse_mode, mode, mode.to_string()).changed();
+                    }
+                });
+            if state.timelapse_mode != TimelapseMode::Off {
+                ui.label("N:");
+                settings_changed |= ui.add(egui::DragValue::new(&mut state.timelapse_interval).clamp_range(1..=3600))
+                    .on_hover_text("Kept frames play back at the capture frame rate. Audio is not recorded in time-lapse mode.")
+                    .changed();
+            }
+        });
+        ui.add_enabled_ui(state.pulse_available, |ui| {
+            ui.horizontal(|ui| {
+                settings_changed |= ui.checkbox(&mut state.highlight_clips_enabled, "Save highlights on loud moments")
+                    .on_hover_text("While recording, cuts a clip out of the recording whenever the capture audio passes the threshold, e.g. a crowd cheer or a boss kill. Uses the loudness meter, which starts with the stream.")
+                    .changed();
+            });
+            if state.highlight_clips_enabled {
+                ui.horizontal(|ui| {
+                    ui.label("Threshold:");
+                    settings_changed |= ui.add(egui::Slider::new(&mut state.highlight_threshold_lufs, -40.0..=0.0).suffix(" LUFS")).changed();
+                    ui.label("Before peak:");
+                    settings_changed |= ui.add(egui::DragValue::new(&mut state.highlight_lead_secs).clamp_range(5..=120).suffix(" s")).changed();
+                });
+                if state.video_thread.is_some() && state.loudness_meter.is_none() {
+                    ui.label(egui::RichText::new("⚠ Restart the stream to start listening for loud moments.").color(egui::Color32::YELLOW));
+                } else if state.timelapse_mode != TimelapseMode::Off {
+                    ui.label(egui::RichText::new("⚠ Highlights aren't saved in time-lapse mode.").color(egui::Color32::YELLOW));
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            settings_changed |= ui.checkbox(&mut state.record_crop_enabled, "Record only a region")
+                .on_hover_text("Crops the recording to this rectangle of the source frame, whatever the viewer is showing. Sizes are rounded down to even numbers.")
+                .changed();
+        });
+        if state.record_crop_enabled {
+            let (x, y, w, h) = &mut state.record_crop;
+            ui.horizontal(|ui| {
+                ui.label("X:");
+                settings_changed |= ui.add(egui::DragValue::new(x)).changed();
+                ui.label("Y:");
+                settings_changed |= ui.add(egui::DragValue::new(y)).changed();
+                ui.label("Width:");
+                settings_changed |= ui.add(egui::DragValue::new(w).clamp_range(2..=7680)).changed();
+                ui.label("Height:");
+                settings_changed |= ui.add(egui::DragValue::new(h).clamp_range(2..=4320)).changed();
+            });
+        }
+        if settings_changed {
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if state.recorder.is_some() {
+            ui.label("Changes apply to the next recording.");
+        }
+    });
+    changed
+}
+
+/// Recent recordings, and a trim editor cutting clips out of them without re-encoding.
+fn layout_recordings_browser_ui(ui: &mut egui::Ui, state: &mut AppState) {
+    persistent_collapsing(ui, state, "Recordings", |ui, state| {
+        if ui.button("🔄 Refresh").clicked() {
+            state.recording_list = None;
+        }
+        let recordings = state.recording_list
+            .get_or_insert_with(|| clips::recent_recordings(std::path::Path::new(&state.recording_dir), 20))
+            .clone();
+        if recordings.is_empty() {
+            ui.label("No recordings in the folder yet.");
+        }
+        let recording_path = state.recorder.as_ref().map(|recorder| recorder.output_path().to_path_buf());
+        egui::ScrollArea::vertical().id_source("recordings_list").max_height(150.0).show(ui, |ui| {
+            for recording in &recordings {
+                ui.horizontal(|ui| {
+                    let in_progress = recording_path.as_ref() == Some(&recording.path);
+                    let busy = state.trim_preview_receiver.is_some() || state.trim_receiver.is_some();
+                    if ui.add_enabled(!in_progress && !busy, egui::Button::new("✂ Trim"))
+                        .on_disabled_hover_text("Wait for the recording or the current trim to finish.")
+                        .clicked()
+                    {
+                        state.open_trim(recording.path.clone(), ui.ctx());
+                    }
+                    ui.label(recording.name());
+                    ui.label(egui::RichText::new(format!("{:.1} MB", recording.size as f64 / (1024.0 * 1024.0))).weak());
+                });
+            }
+        });
+
+        if state.trim_preview_receiver.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Loading thumbnails…");
+            });
+        }
+        let trimming = state.trim_receiver.is_some();
+        let (mut save, mut close) = (false, false);
+        if let Some(session) = &mut state.trim_session {
+            ui.separator();
+            ui.label(egui::RichText::new(session.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()).strong());
+            egui::ScrollArea::horizontal().id_source("trim_thumbnails").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for (time, texture) in &session.thumbnails {
+                        let tint = if (session.start..=session.end).contains(time) { egui::Color32::WHITE } else { egui::Color32::from_gray(70) };
+                        let response = ui.add(egui::Image::new(texture).fit_to_exact_size(egui::vec2(96.0, 54.0)).tint(tint).sense(egui::Sense::click()))
+                            .on_hover_text(format!("{}\nClick to start here, right-click to end here.", clips::format_timestamp(*time)));
+                        if response.clicked() {
+                            session.start = time.min(session.end);
+                        }
+                        if response.secondary_clicked() {
+                            session.end = time.max(session.start);
+                        }
+                    }
+                });
+            });
+            let duration = session.duration;
+            ui.add(egui::Slider::new(&mut session.start, 0.0..=duration).text("Start").custom_formatter(|v, _| clips::format_timestamp(v)));
+            ui.add(egui::Slider::new(&mut session.end, 0.0..=duration).text("End").custom_formatter(|v, _| clips::format_timestamp(v)));
+            session.end = session.end.max(session.start);
+            ui.label(format!("Clip length: {}", clips::format_timestamp(session.end - session.start)));
+            ui.horizontal(|ui| {
+                save = ui.add_enabled(!trimming && session.end - session.start > 0.1, egui::Button::new("💾 Save trimmed copy"))
+                    .on_hover_text("Copies the streams without re-encoding, so the clip starts at the keyframe before the start point. The original is kept.")
+                    .clicked();
+                if trimming {
+                    ui.spinner();
+                }
+                close = ui.button("Close").clicked();
+            });
+        }
+        if save {
+            state.start_trim(ui.ctx());
+        }
+        if close {
+            state.trim_session = None;
+        }
+    });
+}
+
+fn layout_ffmpeg_log_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut changed = false;
+    persistent_collapsing(ui, state, "FFmpeg Log", |ui, state| {
+        ui.horizontal(|ui| {
+            ui.label("Level:");
+            egui::ComboBox::from_id_source("ffmpeg_log_level_selector")
+                .selected_text(state.ffmpeg_log_level.to_string())
+                .show_ui(ui, |ui| {
+                    let mut combo_changed = false;
+                    for level in [FfmpegLogLevel::Error, FfmpegLogLevel::Warning, FfmpegLogLevel::Info, FfmpegLogLevel::Verbose, FfmpegLogLevel::Debug] {
+                        combo_changed |= ui.selectable_value(&mut state.ffmpeg_log_level, level, level.to_string()).changed();
+                    }
+                    if combo_changed {
+                        ffmpeg_log::set_level(state.ffmpeg_log_level);
+                        config::mark_dirty(state);
+                        changed = true;
+                    }
+                });
+            if ui.button("Clear").clicked() {
+                ffmpeg_log::clear();
+            }
+        });
+        let lines = ffmpeg_log::recent_lines();
+        egui::ScrollArea::vertical().max_height(150.0).stick_to_bottom(true).show(ui, |ui| {
+            if lines.is_empty() {
+                ui.label("No messages from FFmpeg.");
+            }
+            for (level, line) in &lines {
+                let color = match level {
+                    FfmpegLogLevel::Error => egui::Color32::RED,
+                    FfmpegLogLevel::Warning => egui::Color32::YELLOW,
+                    _ => ui.visuals().text_color(),
+                };
+                ui.label(egui::RichText::new(line).monospace().color(color));
+            }
+        });
+    });
+    changed
+}
+
+/// Wizard measuring the end-to-end video latency by filming a flashing pattern next to the
+/// viewer showing it.
+fn layout_latency_calibration_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut changed = false;
+    persistent_collapsing(ui, state, "Latency Calibration", |ui, state| {
+        ui.label("1. Open the pattern window on a screen whose output also goes into the capture card, e.g. a mirrored HDMI output, and start the stream.");
+        let pattern_open = state.latency_pattern_started.is_some();
+        if ui.button(if pattern_open { "Close pattern window" } else { "Open pattern window" }).clicked() {
+            state.latency_pattern_started = if pattern_open { None } else { Some(std::time::Instant::now()) };
+        }
+        ui.label("2. Film the pattern window and the viewer side by side with a phone for about 10 seconds, at 60 fps or more and not in slow motion.");
+        ui.label("3. Copy the video to this computer and analyze it:");
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.latency_video_path).hint_text("Path to the phone video").desired_width(300.0));
+            let analyzing = state.photon_latency_receiver.is_some();
+            if ui.add_enabled(!analyzing && !state.latency_video_path.trim().is_empty(), egui::Button::new("Analyze")).clicked() {
+                state.start_photon_latency_analysis(ui.ctx());
+            }
+            if analyzing {
+                ui.spinner();
+            }
+        });
+        ui.label(egui::RichText::new(format!("The result is saved for the current profile, '{}'.", state.latency_profile())).weak());
+        let mut remove = None;
+        let mut profiles: Vec<(&String, &u32)> = state.photon_latency_msec.iter().collect();
+        profiles.sort();
+        for (profile, latency) in profiles {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}: {} ms", profile, latency));
+                if ui.small_button("🗑").on_hover_text("Forget this measurement").clicked() {
+                    remove = Some(profile.clone());
+                }
+            });
+        }
+        if let Some(profile) = remove {
+            state.photon_latency_msec.remove(&profile);
+            config::mark_dirty(state);
+            changed = true;
+        }
+        if ui.checkbox(&mut state.latency_overlay_enabled, "Show latency on the video")
+            .on_hover_text("Shows the estimated time frames wait in the display queue and the measured end-to-end latency.")
+            .changed()
+        {
+            config::mark_dirty(state);
+            changed = true;
+        }
+    });
+    changed
+}
+
+fn layout_diagnostics_ui(ui: &mut egui::Ui, state: &mut AppState) {
+    persistent_collapsing(ui, state, "Diagnostics", |ui, state| {
+        let creating = state.support_bundle_receiver.is_some();
+        if ui.add_enabled(!creating, egui::Button::new(if creating { "Creating support bundle..." } else { "Create support bundle" }))
+            .on_hover_text("Zips the recent logs, the config without passwords or device serial numbers, a system check and these diagnostics into the recording folder, for attaching to bug reports.")
+            .clicked()
+        {
+            state.create_support_bundle(ui.ctx());
+        }
+        layout_signal_log_ui(ui, state);
+        let sampler = state.system_sampler.get_or_insert_with(|| SystemSampler::start(ui.ctx()));
+        let Some(stats) = sampler.snapshot() else {
+            ui.label("Sampling...");
+            return;
+        };
+        ui.label(format!("CPU: {:.0}% of one core", stats.cpu_percent));
+        if let Some(texture) = &state.video_texture {
+            let upload = *texture.stats.lock().unwrap();
+            let path = if texture.direct_frame().is_some() { "direct GL" } else { "egui textures" };
+            let format_ms = |ms: Option<f32>| ms.map_or("-".to_string(), |ms| format!("{:.1} ms", ms));
+            ui.label(format!("Frame upload: {}", path));
+            ui.label(format!("Upload to screen: egui {}, direct {} (of which {} copying)", format_ms(upload.egui_ms), format_ms(upload.direct_ms), format_ms(upload.direct_upload_ms)))
+                .on_hover_text("Average time from a frame leaving the pacer until a paint callback draws it. The egui path shows frames one UI frame after they're uploaded.");
+        }
+        let mut readings = Vec::new();
+        if let Some(temperature) = stats.cpu_temperature {
+            readings.push(format!("CPU {:.0} °C", temperature));
+        }
+        if let Some(busy) = stats.gpu_busy_percent {
+            readings.push(format!("GPU {:.0}% busy", busy));
+        }
+        if let Some(temperature) = stats.gpu_temperature {
+            readings.push(format!("GPU {:.0} °C", temperature));
+        }
+        if !readings.is_empty() {
+            ui.label(readings.join(", "));
+        }
+        egui::Grid::new("diagnostics_threads_grid").num_columns(3).striped(true).show(ui, |ui| {
+            ui.strong("Thread");
+            ui.strong("CPU");
+            ui.strong("Share");
+            ui.end_row();
+            for usage in &stats.threads {
+                let name = if usage.threads > 1 { format!("{} ×{}", usage.name, usage.threads) } else { usage.name.clone() };
+                ui.label(name);
+                ui.label(format!("{:.1}%", usage.cpu_percent));
+                let share = if stats.cpu_percent > 0.0 { usage.cpu_percent / stats.cpu_percent } else { 0.0 };
+                ui.add(egui::ProgressBar::new(share.clamp(0.0, 1.0)).desired_width(80.0).text(format!("{:.0}%", share * 100.0)));
+                ui.end_row();
+            }
+        });
+    });
+}
+
+/// Black frames and signal drops of the capture, newest first.
+fn layout_signal_log_ui(ui: &mut egui::Ui, state: &mut AppState) {
+    let count = state.signal_log.events().len();
+    ui.horizontal(|ui| {
+        ui.label(format!("Signal events: {}", count))
+            .on_hover_text("Black frames, gaps in the frames from the capture card and stream failures since the app started, with their time. Many short ones over a long session point to a flaky HDMI cable or handshake. Included in the support bundle.");
+        if count > 0 && ui.button("Clear").clicked() {
+            state.signal_log.clear();
+        }
+    });
+    let events = state.signal_log.events();
+    if events.is_empty() {
+        return;
+    }
+    egui::ScrollArea::vertical().id_source("signal_log_scroll").max_height(120.0).show(ui, |ui| {
+        for event in events.iter().rev() {
+            ui.label(egui::RichText::new(event.describe()).monospace());
+        }
+    });
+}
+
+/// Shows the USB link speed and whether the selected uncompressed format fits through it.
+fn layout_usb_bandwidth_ui(ui: &mut egui::Ui, state: &AppState) {
+    let Some(speed) = state.video_usb_speed else { return };
+    let Some(format) = state.supported_formats.get(state.selected_format_index) else { return };
+    let usable = crate::video::types::usable_usb_bandwidth_mbps(speed);
+    let link = format!("{} ({} Mbit/s)", crate::video::types::usb_speed_name(speed), speed);
+    match crate::video::types::estimated_bandwidth_mbps(&format.fourcc, state.selected_resolution, state.selected_framerate) {
+        Some(needed) if needed > usable => {
+            ui.label(egui::RichText::new(format!(
+                "⚠ {}: this format needs ~{:.0} Mbit/s but the link carries ~{:.0} Mbit/s of video. The card will drop frames or refuse to stream; pick MJPEG or a lower resolution/framerate.",
+                link, needed, usable
+            )).color(egui::Color32::YELLOW));
+        }
+        Some(needed) => {
+            ui.label(format!("{}: needs ~{:.0} of ~{:.0} Mbit/s.", link, needed, usable));
+        }
+        None => {
+            ui.label(format!("{}: compressed format, bandwidth depends on the picture.", link));
+        }
+    }
+}
+
+/// Format priority for Auto, and raw strings for ffmpeg's v4l2 input. Applied on the next stream start.
+fn layout_format_override_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut settings_changed = false;
+    egui::Grid::new("format_override_grid").num_columns(2).show(ui, |ui| {
+        ui.label("Auto format priority:").on_hover_text("Fourccs Auto prefers at the same resolution, most wanted first, e.g. NV12, YUYV, MJPG.");
+        if ui.text_edit_singleline(&mut state.format_priority).changed() {
+            if state.auto_format {
+                crate::video::types::apply_auto_format(state);
+            }
+            settings_changed = true;
+        }
+        ui.end_row();
+
+        let options = &mut state.format_override;
+        for (label, value, hint) in [
+            ("input_format:", &mut options.input_format, "Replaces the format name derived from the fourcc, e.g. mjpeg or yuyv422."),
+            ("pixel_format:", &mut options.pixel_format, "Replaces the pixel format passed to ffmpeg, e.g. nv12."),
+        ] {
+            let mut set = value.is_some();
+            if ui.checkbox(&mut set, label).on_hover_text(hint).changed() {
+                *value = set.then(String::new);
+                settings_changed = true;
+            }
+            if let Some(text) = value {
+                settings_changed |= ui.text_edit_singleline(text).changed();
+            }
+            ui.end_row();
+        }
+    });
+    if state.format_override != Default::default() {
+        ui.label(egui::RichText::new("⚠ Overrides are passed to ffmpeg as-is, for every format. Clear them if the stream stops opening.").color(egui::Color32::YELLOW));
+    }
+    if settings_changed {
+        config::mark_dirty(state);
+    }
+    settings_changed
+}
+
+/// module-loopback parameters beyond the latency. Applied on the next stream start.
+fn layout_loopback_options_ui(ui: &mut egui::Ui, state: &mut AppState) -> bool {
+    let mut settings_changed = false;
+    let options = &mut state.loopback_options;
+    egui::Grid::new("loopback_options_grid").num_columns(2).show(ui, |ui| {
+        let mut set = options.adjust_time.is_some();
+        if ui.checkbox(&mut set, "Adjust time (s):")
+            .on_hover_text("How often the loopback corrects clock drift between the capture card and the output. 0 disables the correction.")
+            .changed()
+        {
+            options.adjust_time = set.then_some(10);
+            settings_changed = true;
+        }
+        if let Some(adjust_time) = &mut options.adjust_time {
+            settings_changed |= ui.add(egui::DragValue::new(adjust_time).clamp_range(0..=60)).changed();
+        }
+        ui.end_row();
+
+        let mut set = options.max_latency_msec.is_some();
+        if ui.checkbox(&mut set, "Max latency (ms):")
+            .on_hover_text("Latency at which the loopback drops audio to catch up.")
+            .changed()
+        {
+            options.max_latency_msec = set.then_some(state.loopback_latency_msec.unwrap_or(200) * 2);
+            settings_changed = true;
+        }
+        if let Some(max_latency) = &mut options.max_latency_msec {
+            settings_changed |= ui.add(egui::DragValue::new(max_latency).clamp_range(1..=5000)).changed();
+        }
+        ui.end_row();
+
+        let mut set = options.channels.is_some();
+        if ui.checkbox(&mut set, "Channels:").changed() {
+            options.channels = set.then_some(2);
+            settings_changed = true;
+        }
+        if let Some(channels) = &mut options.channels {
+            settings_changed |= ui.add(egui::DragValue::new(channels).clamp_range(1..=8)).changed();
+        }
+        ui.end_row();
+
+        ui.label("Extra arguments:").on_hover_text("Passed to module-loopback as-is, e.g. resample_method=soxr-vhq");
+        settings_changed |= ui.text_edit_singleline(&mut options.extra_args).changed();
+        ui.end_row();
+
+        settings_changed |= ui.checkbox(&mut state.drift_check_enabled, "Drift warning (ms):")
+            .on_hover_text("Checks the loopback's latency every 30 seconds and warns when it has moved this far from where it started. Some cards slowly desync over long sessions.")
+            .changed();
+        if state.drift_check_enabled {
+            settings_changed |= ui.add(egui::DragValue::new(&mut state.drift_threshold_msec).clamp_range(5..=1000)).changed();
+        }
+        ui.end_row();
+
+        if state.drift_check_enabled {
+            settings_changed |= ui.checkbox(&mut state.drift_auto_reload, "Reload loopback on drift")
+                .on_hover_text("Reloads the loopback instead of only warning, which causes a short audio dropout.")
+                .changed();
+            if let Some(drift) = state.audio_drift_msec {
+                let text = format!("Drift: {:+} ms", drift);
+                if drift.unsigned_abs() > state.drift_threshold_msec {
+                    ui.label(egui::RichText::new(format!("⚠ {}", text)).color(egui::Color32::YELLOW));
+                } else {
+                    ui.label(text);
+                }
+            }
+            ui.end_row();
+        }
+    });
+    if settings_changed {
+        config::mark_dirty(state);
+    }
+    settings_changed
+}
+
+/// A collapsing section whose open state is remembered in the config across runs.
+fn persistent_collapsing<R>(
+    ui: &mut egui::Ui,
+    state: &mut AppState,
+    title: &str,
+    add_contents: impl FnOnce(&mut egui::Ui, &mut AppState) -> R,
+) -> Option<R> {
+    // Same id CollapsingHeader derives from its title, so we can read back the toggled state.
+    let id = ui.make_persistent_id(egui::Id::new(title));
+    let default_open = state.open_sections.get(title).copied().unwrap_or(false);
+    let response = egui::CollapsingHeader::new(title)
+        .default_open(default_open)
+        .show(ui, |ui| add_contents(ui, state));
+    if response.header_response.clicked() {
+        if let Some(collapsing) = egui::collapsing_header::CollapsingState::load(ui.ctx(), id) {
+            state.open_sections.insert(title.to_string(), collapsing.is_open());
+            config::mark_dirty(state);
+        }
+    }
+    response.body_returned
+}
+
+/// E.g. "requested 60, device delivering 59.94 (measured 59.2)".
+fn frame_rate_summary(info: &StreamInfo, measured: f32) -> String {
+    let device = match info.device_fps {
+        Some(fps) => format!("device delivering {:.2}", fps),
+        None => "device rate unknown".to_string(),
+    };
+    match info.requested_fps {
+        Some(requested) => format!("requested {}, {} (measured {:.1})", requested, device, measured),
+        None => format!("{} (measured {:.1})", device, measured),
+    }
+}
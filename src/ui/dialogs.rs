@@ -1,4 +1,4 @@
-use crate::{app::AppState, config};
+use crate::{app::AppState, config, notifications::Notification, preflight, selftest, selftest::CheckStatus, video::probe::ProbeOutcome};
 use eframe::egui;
 
 pub fn show_first_run_dialog(state: &mut AppState, ctx: &egui::Context, ui: &mut egui::Ui) -> bool {
@@ -23,7 +23,7 @@ pub fn show_first_run_dialog(state: &mut AppState, ctx: &egui::Context, ui: &mut
             ui.vertical_centered(|ui| {
                 if ui.button("I Understand").clicked() {
                     state.show_first_run_dialog = false;
-                    config::save_config(state);
+                    config::mark_dirty(state);
                     true
                 } else {
                     false
@@ -38,15 +38,29 @@ pub fn show_quit_dialog(state: &mut AppState, ctx: &egui::Context, ui: &mut egui
     let screen_rect = ctx.screen_rect();
     ui.painter().rect_filled(screen_rect, 0.0, egui::Color32::from_rgba_unmultiplied(0, 0, 0, 128));
 
+    let unsaved = state.manual_save && config::has_unsaved_changes(state);
     egui::Window::new("Quit?")
         .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
         .collapsible(false)
         .resizable(false)
         .show(ctx, |ui| {
-            ui.label("A video stream is active. Are you sure you want to quit the application?");
+            if state.video_window_open {
+                ui.label("A video stream is active. Are you sure you want to quit the application?");
+            }
+            if unsaved {
+                ui.label("The settings have unsaved changes.");
+            }
             ui.add_space(15.0);
             ui.horizontal(|ui| {
-                if ui.button("Yes, quit").clicked() {
+                if unsaved {
+                    if ui.button("Save and quit").clicked() {
+                        config::save_config(state);
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                    if ui.button("Quit without saving").clicked() {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                } else if ui.button("Yes, quit").clicked() {
                     ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                 }
                 if ui.button("Cancel").clicked() {
@@ -77,4 +91,231 @@ pub fn show_stop_stream_dialog(state: &mut AppState, ctx: &egui::Context, ui: &m
                 }
             });
         });
-}
\ No newline at end of file
+}
+
+pub fn show_video_failure_dialog(state: &mut AppState, ctx: &egui::Context, ui: &mut egui::Ui) {
+    let Some(failure) = state.video_thread_failure.clone() else { return };
+    let screen_rect = ctx.screen_rect();
+    ui.painter().rect_filled(screen_rect, 0.0, egui::Color32::from_rgba_unmultiplied(0, 0, 0, 128));
+
+    egui::Window::new("Stream Stopped")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("The video stream stopped unexpectedly and audio routing was cleaned up.");
+            ui.label(egui::RichText::new(&failure.message).monospace().color(egui::Color32::LIGHT_RED));
+
+            if let Some(busy) = &failure.busy {
+                ui.add_space(10.0);
+                if !busy.users.is_empty() {
+                    let names: Vec<String> = busy.users.iter().map(|(pid, name)| format!("{} ({})", name, pid)).collect();
+                    ui.label(format!("Currently used by: {}", names.join(", ")));
+                }
+                for (device, loopback) in &busy.alternatives {
+                    let label = if *loopback {
+                        format!("Use {} (v4l2loopback copy, read-only preview)", device)
+                    } else {
+                        format!("Use {} instead", device)
+                    };
+                    if ui.button(label).clicked() {
+                        state.video_thread_failure = None;
+                        state.busy_retry_at = None;
+                        state.select_video_device(device, ctx);
+                        state.start_stream(ctx);
+                    }
+                }
+                if let Some(retry_at) = state.busy_retry_at {
+                    let remaining = retry_at.saturating_duration_since(std::time::Instant::now()).as_secs_f32();
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(format!("Waiting for the device to be released, retrying in {:.0}s...", remaining.ceil()));
+                        if ui.button("Stop Waiting").clicked() {
+                            state.busy_retry_at = None;
+                        }
+                    });
+                } else if ui.button("Wait and Retry").on_hover_text("Keep retrying every few seconds until the other application releases the device.").clicked() {
+                    state.busy_retry_at = Some(std::time::Instant::now());
+                }
+            }
+
+            ui.add_space(15.0);
+            ui.horizontal(|ui| {
+                if ui.button("Restart Stream").clicked() {
+                    state.video_thread_failure = None;
+                    state.start_stream(ctx);
+                }
+                if ui.button("Close").clicked() {
+                    state.video_thread_failure = None;
+                    state.busy_retry_at = None;
+                    state.control_window_open = true;
+                }
+            });
+        });
+}
+
+pub fn show_preflight_dialog(state: &mut AppState, ctx: &egui::Context, ui: &mut egui::Ui) {
+    // Fixes like a rescan finish asynchronously, so wait for them before checking again.
+    if state.preflight_dirty && state.device_scan_receiver.is_none() && !state.formats_loading() {
+        state.preflight_issues = Some(preflight::run_checks(state));
+        state.preflight_dirty = false;
+    }
+    let Some(issues) = state.preflight_issues.clone() else { return };
+
+    let screen_rect = ctx.screen_rect();
+    ui.painter().rect_filled(screen_rect, 0.0, egui::Color32::from_rgba_unmultiplied(0, 0, 0, 128));
+
+    egui::Window::new("Before Starting")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut fix_clicked = None;
+            if state.preflight_dirty {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Checking again...");
+                });
+            } else if issues.is_empty() {
+                ui.label("All checks passed.");
+            }
+            for issue in &issues {
+                ui.horizontal(|ui| {
+                    let (icon, color) = if issue.blocking { ("❌", egui::Color32::LIGHT_RED) } else { ("⚠", egui::Color32::YELLOW) };
+                    ui.label(egui::RichText::new(icon).color(color));
+                    ui.label(&issue.message);
+                    if let Some(fix) = issue.fix {
+                        if ui.add_enabled(!state.preflight_dirty, egui::Button::new(fix.to_string())).clicked() {
+                            fix_clicked = Some(fix);
+                        }
+                    }
+                });
+            }
+            ui.add_space(15.0);
+            ui.horizontal(|ui| {
+                let can_start = !state.preflight_dirty && issues.iter().all(|issue| !issue.blocking);
+                let label = if issues.is_empty() { "Start Stream" } else { "Start Anyway" };
+                if ui.add_enabled(can_start, egui::Button::new(label)).clicked() {
+                    state.preflight_issues = None;
+                    state.start_stream(ctx);
+                }
+                if ui.add_enabled(!state.preflight_dirty, egui::Button::new("Check Again")).clicked() {
+                    state.preflight_dirty = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    state.preflight_issues = None;
+                    state.preflight_dirty = false;
+                }
+            });
+            if let Some(fix) = fix_clicked {
+                preflight::apply_fix(state, fix, ctx);
+                state.preflight_dirty = true;
+            }
+        });
+}
+
+pub fn show_probe_dialog(state: &mut AppState, ctx: &egui::Context) {
+    let Some(results) = &state.probe_results else { return };
+    let mut open = true;
+    let running = state.probe_receiver.is_some();
+    let mut cancel = false;
+    egui::Window::new("Device Probe")
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(500.0)
+        .show(ctx, |ui| {
+            if running {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Probing mode {} of {}...", results.len() + 1, state.probe_total));
+                    cancel = ui.button("Cancel").clicked();
+                });
+            }
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("probe_results_grid").num_columns(4).striped(true).show(ui, |ui| {
+                    for result in results {
+                        let (icon, color, detail) = match &result.outcome {
+                            ProbeOutcome::Ok { measured_fps } => ("✔", egui::Color32::GREEN, format!("{:.1} fps", measured_fps)),
+                            ProbeOutcome::Slow { measured_fps } => ("⚠", egui::Color32::YELLOW, format!("only {:.1} fps", measured_fps)),
+                            ProbeOutcome::NoFrames => ("❌", egui::Color32::LIGHT_RED, "no frames".to_string()),
+                            ProbeOutcome::Failed(e) => ("❌", egui::Color32::LIGHT_RED, e.clone()),
+                        };
+                        ui.label(egui::RichText::new(icon).color(color));
+                        ui.label(&result.format);
+                        ui.label(format!("{}x{} @ {} fps", result.resolution.0, result.resolution.1, result.fps));
+                        ui.label(detail);
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+    if cancel || !open {
+        state.stop_device_probe();
+    }
+    if !open {
+        state.probe_results = None;
+    }
+}
+
+pub fn show_system_check_dialog(state: &mut AppState, ctx: &egui::Context) {
+    let Some(results) = state.system_check_results.clone() else { return };
+    let mut open = true;
+    egui::Window::new("System Check")
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(500.0)
+        .show(ctx, |ui| {
+            if state.system_check_receiver.is_some() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Checking...");
+                });
+                return;
+            }
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for check in &results {
+                    ui.horizontal(|ui| {
+                        let (icon, color) = match check.status {
+                            CheckStatus::Ok => ("✔", egui::Color32::GREEN),
+                            CheckStatus::Warning => ("⚠", egui::Color32::YELLOW),
+                            CheckStatus::Failed => ("❌", egui::Color32::LIGHT_RED),
+                        };
+                        ui.label(egui::RichText::new(icon).color(color));
+                        ui.label(egui::RichText::new(check.name).strong());
+                        ui.label(&check.detail);
+                    });
+                }
+            });
+            ui.add_space(10.0);
+            if ui.button("📋 Copy Report").clicked() {
+                let report = selftest::format_report(&results);
+                ui.output_mut(|o| o.copied_text = report);
+                state.notify(Notification::success("System check report copied to the clipboard."));
+            }
+        });
+    if !open {
+        state.system_check_results = None;
+    }
+}
+
+pub fn show_memory_warning_dialog(state: &mut AppState, ctx: &egui::Context) {
+    let Some(message) = state.memory_warning.clone() else { return };
+    let mut open = true;
+    egui::Window::new("⚠ Not Enough Video Memory")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .default_width(400.0)
+        .show(ctx, |ui| {
+            ui.label(message);
+            ui.add_space(5.0);
+            ui.label("Enable \"Downscale large sources to the window\", pick a lower capture resolution, or raise the GPU memory cap if your graphics card has memory to spare.");
+            ui.add_space(10.0);
+            if ui.button("OK").clicked() {
+                state.memory_warning = None;
+            }
+        });
+    if !open {
+        state.memory_warning = None;
+    }
+}
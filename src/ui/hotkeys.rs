@@ -0,0 +1,64 @@
+use crate::{app::AppState, config, notifications::Notification};
+use eframe::egui;
+
+/// Keyboard shortcuts for the stream, handled in the video window and, while a stream runs, in the
+/// controls window too. Window commands always go to the video window, whichever has focus.
+pub fn handle_shortcuts(state: &mut AppState, ctx: &egui::Context) {
+    let video = egui::ViewportId::ROOT;
+    if ctx.input(|i| i.key_pressed(egui::Key::F)) {
+        let is_fullscreen = !ctx.input_for(video, |i| i.viewport().fullscreen.unwrap_or(false));
+        ctx.send_viewport_cmd_to(video, egui::ViewportCommand::Fullscreen(is_fullscreen));
+    }
+    if ctx.input(|i| i.key_pressed(egui::Key::C)) {
+        state.cycle_crt_filter();
+    }
+    if ctx.input(|i| i.key_pressed(egui::Key::G)) {
+        state.pixelate_filter_enabled = !state.pixelate_filter_enabled;
+        let status = if state.pixelate_filter_enabled { "enabled" } else { "disabled" };
+        state.notify(Notification::info(format!("480p Pixelate filter {}.", status)));
+        config::mark_dirty(state);
+    }
+    if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        // Allow Esc to exit fullscreen on the video window
+        ctx.send_viewport_cmd_to(video, egui::ViewportCommand::Fullscreen(false));
+    }
+    if ctx.input(|i| i.key_pressed(egui::Key::Q)) && state.video_window_open && !state.show_stop_stream_dialog {
+        // The dialog is drawn in the video window, so bring it up when Q was pressed in the controls.
+        state.show_stop_stream_dialog = true;
+        ctx.send_viewport_cmd_to(video, egui::ViewportCommand::Focus);
+    }
+    if ctx.input(|i| i.key_pressed(egui::Key::M)) {
+        state.control_window_open = !state.control_window_open;
+    }
+    if ctx.input(|i| i.key_pressed(egui::Key::S) && i.modifiers.is_none()) {
+        state.save_screenshot(ctx);
+    }
+    if ctx.input(|i| i.key_pressed(egui::Key::K)) {
+        state.add_chapter_marker();
+    }
+    let scene_keys = [
+        egui::Key::Num1, egui::Key::Num2, egui::Key::Num3, egui::Key::Num4, egui::Key::Num5,
+        egui::Key::Num6, egui::Key::Num7, egui::Key::Num8, egui::Key::Num9,
+    ];
+    if let Some(index) = scene_keys.iter().take(state.scenes.len()).position(|key| ctx.input(|i| i.key_pressed(*key))) {
+        state.switch_scene(index, ctx);
+    }
+    if state.timer_enabled && ctx.input(|i| i.key_pressed(egui::Key::T)) {
+        state.toggle_timer();
+    }
+    if state.timer_enabled && ctx.input(|i| i.key_pressed(egui::Key::R)) {
+        state.reset_timer();
+    }
+}
+
+/// Undo and Save for the settings, handled in both windows whether or not a stream runs. Called
+/// before `handle_shortcuts`, so Ctrl+S doesn't also take a screenshot.
+pub fn handle_config_shortcuts(state: &mut AppState, ctx: &egui::Context) {
+    // Text fields have their own Ctrl+Z.
+    if !ctx.wants_keyboard_input() && ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z)) {
+        state.undo_config_change(ctx);
+    }
+    if state.manual_save && ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::S)) {
+        config::save_config(state);
+    }
+}
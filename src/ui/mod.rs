@@ -1,11 +1,14 @@
 use crate::app::AppState;
 use eframe::egui;
-use eframe::egui_glow;
-use crate::devices::filter_type::CrtFilter;
+use eframe::{egui_glow, glow};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::video::gpu_filter::{CrtFilterRenderer, GlContextStatus, Upscaler};
+use crate::devices::filter_type::{BfiMode, CrtFilter};
 use crate::video;
 
 pub mod controls;
 pub mod dialogs;
+pub mod hotkeys;
 
 pub fn draw_main_ui(state: &mut AppState, ctx: &egui::Context) -> bool {
     let panel_frame = if state.is_fullscreen {
@@ -24,35 +27,290 @@ pub fn draw_main_ui(state: &mut AppState, ctx: &egui::Context) -> bool {
 
             repaint_requested |= controls::layout_top_ui(ui, state);
 
+            if state.system_check_results.is_some() {
+                dialogs::show_system_check_dialog(state, ctx);
+                repaint_requested = true;
+            }
+
+            if state.probe_results.is_some() {
+                dialogs::show_probe_dialog(state, ctx);
+                repaint_requested = true;
+            }
+
+            if state.preflight_issues.is_some() {
+                dialogs::show_preflight_dialog(state, ctx, ui);
+                repaint_requested = true;
+            }
+
+            if state.memory_warning.is_some() {
+                dialogs::show_memory_warning_dialog(state, ctx);
+                repaint_requested = true;
+            }
+
             repaint_requested
         })
         .inner
 }
 
+/// Draws the toasts in the bottom right corner, newest at the bottom, and runs the action of
+/// a clicked button.
+pub fn draw_toasts(state: &mut AppState, ctx: &egui::Context) {
+    state.toasts.retain(|toast| !toast.expired());
+    let Some(next_expiry) = state.toasts.iter().map(|toast| toast.remaining()).min() else { return };
+    ctx.request_repaint_after(next_expiry);
+    let mut clicked = None;
+    let mut dismissed = None;
+    egui::Area::new(egui::Id::new("toasts"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            ui.set_max_width(360.0);
+            for (index, toast) in state.toasts.iter().enumerate() {
+                egui::Frame::popup(ui.style()).stroke(egui::Stroke::new(1.0, toast.level.color())).show(ui, |ui| {
+                    let text = format!("{} {}", toast.level.icon(), toast.message);
+                    ui.add(egui::Label::new(egui::RichText::new(text).color(toast.level.color())).wrap(true));
+                    ui.horizontal(|ui| {
+                        if let Some(action) = toast.action {
+                            if ui.button(action.label()).clicked() {
+                                clicked = Some((index, action));
+                            }
+                        }
+                        if ui.small_button("Dismiss").clicked() {
+                            dismissed = Some(index);
+                        }
+                    });
+                });
+            }
+        });
+    if let Some((index, action)) = clicked {
+        state.toasts.remove(index);
+        state.run_notification_action(action, ctx);
+    } else if let Some(index) = dismissed {
+        state.toasts.remove(index);
+    }
+}
+
+/// Draws the text overlay as a banner at the top or bottom of the video.
+fn draw_text_overlay(state: &mut AppState, ui: &egui::Ui, rect: egui::Rect) {
+    let text = if state.text_overlay_file.trim().is_empty() {
+        state.text_overlay_text.clone()
+    } else {
+        let path = state.text_overlay_file.trim().to_string();
+        state.text_overlay_source.text(&path).to_string()
+    };
+    if text.is_empty() {
+        return;
+    }
+    let margin = state.text_overlay_size * 0.4;
+    let galley = ui.painter().layout(
+        text,
+        egui::FontId::proportional(state.text_overlay_size),
+        egui::Color32::WHITE,
+        (rect.width() - 2.0 * margin).max(0.0),
+    );
+    let banner_height = galley.size().y + 2.0 * margin;
+    let banner = match state.text_overlay_position {
+        video::overlay::TextOverlayPosition::Top => egui::Rect::from_min_size(rect.min, egui::vec2(rect.width(), banner_height)),
+        video::overlay::TextOverlayPosition::Bottom => egui::Rect::from_min_max(egui::pos2(rect.min.x, rect.max.y - banner_height), rect.max),
+    };
+    ui.painter().rect_filled(banner, 0.0, egui::Color32::from_black_alpha(160));
+    ui.painter().galley(banner.min + egui::vec2(margin, margin), galley, egui::Color32::WHITE);
+}
+
+/// Draws the speedrun timer in the top right corner of the video.
+fn draw_timer_overlay(state: &AppState, ui: &egui::Ui, rect: egui::Rect) {
+    let Some(text) = state.timer_text() else { return };
+    let margin = state.timer_size * 0.25;
+    let galley = ui.painter().layout_no_wrap(text, egui::FontId::monospace(state.timer_size), egui::Color32::WHITE);
+    let size = galley.size() + egui::vec2(2.0 * margin, 2.0 * margin);
+    let background = egui::Rect::from_min_size(egui::pos2(rect.max.x - size.x, rect.min.y), size);
+    ui.painter().rect_filled(background, 0.0, egui::Color32::from_black_alpha(160));
+    ui.painter().galley(background.min + egui::vec2(margin, margin), galley, egui::Color32::WHITE);
+}
+
+/// The estimated display queue latency next to the measured end-to-end latency, bottom left.
+fn draw_latency_overlay(state: &AppState, ui: &egui::Ui, rect: egui::Rect) {
+    let queue = state.frame_pacer.queue_latency().as_millis();
+    let measured = match state.photon_latency_msec.get(&state.latency_profile()) {
+        Some(latency) => format!("{} ms", latency),
+        None => "not calibrated".to_string(),
+    };
+    let text = format!("Queue ~{} ms | Measured {}", queue, measured);
+    let margin = 6.0;
+    let galley = ui.painter().layout_no_wrap(text, egui::FontId::monospace(14.0), egui::Color32::WHITE);
+    let size = galley.size() + egui::vec2(2.0 * margin, 2.0 * margin);
+    let background = egui::Rect::from_min_size(egui::pos2(rect.min.x, rect.max.y - size.y), size);
+    ui.painter().rect_filled(background, 0.0, egui::Color32::from_black_alpha(160));
+    ui.painter().galley(background.min + egui::vec2(margin, margin), galley, egui::Color32::WHITE);
+}
+
+/// Short-lived message centered at the top of the video, fading out at the end.
+fn draw_osd(state: &mut AppState, ui: &egui::Ui, rect: egui::Rect) {
+    let Some((text, shown_at)) = &state.osd_message else { return };
+    let elapsed = shown_at.elapsed();
+    if elapsed >= crate::app::OSD_DURATION {
+        state.osd_message = None;
+        return;
+    }
+    let remaining = (crate::app::OSD_DURATION - elapsed).as_secs_f32();
+    let alpha = (remaining / 0.3).min(1.0);
+    let font = egui::FontId::proportional((rect.height() / 20.0).clamp(16.0, 48.0));
+    let galley = ui.painter().layout_no_wrap(text.clone(), font, egui::Color32::WHITE.gamma_multiply(alpha));
+    let margin = galley.size().y * 0.4;
+    let size = galley.size() + egui::vec2(2.0 * margin, 2.0 * margin);
+    let background = egui::Rect::from_min_size(egui::pos2(rect.center().x - size.x / 2.0, rect.min.y + margin), size);
+    ui.painter().rect_filled(background, 4.0, egui::Color32::from_black_alpha((160.0 * alpha) as u8));
+    ui.painter().galley(background.min + egui::vec2(margin, margin), galley, egui::Color32::WHITE);
+    ui.ctx().request_repaint();
+}
+
+/// Size of a paint callback's area in device pixels, which is what GL works in.
+/// The rect is in points, which differ from pixels under fractional scaling.
+fn physical_size(info: egui::PaintCallbackInfo) -> (f32, f32) {
+    let viewport = info.viewport_in_pixels();
+    (viewport.width_px as f32, viewport.height_px as f32)
+}
+
+/// Checks the GL context before drawing with the renderer. A rebuilt renderer is flagged so the
+/// video texture gets allocated again too. Returns false while there's no usable context.
+fn renderer_ready(renderer: &mut CrtFilterRenderer, gl: &glow::Context, context_reset: &AtomicBool) -> bool {
+    match renderer.check_context(gl) {
+        GlContextStatus::Ok => true,
+        GlContextStatus::Lost => false,
+        GlContextStatus::Rebuilt => {
+            context_reset.store(true, Ordering::Relaxed);
+            true
+        }
+    }
+}
+
+/// Uploads the frame waiting on the direct path, if there is one, and records how long it took
+/// from `VideoTexture::upload` to here.
+fn upload_direct_frame(renderer: &mut CrtFilterRenderer, gl: &glow::Context, direct: &video::texture::DirectFrame, stats: &std::sync::Mutex<video::texture::UploadStats>) {
+    let Some((image, handed_over)) = direct.lock().unwrap().take() else { return };
+    let started = std::time::Instant::now();
+    renderer.upload_direct(gl, &image);
+    stats.lock().unwrap().record_direct(handed_over.elapsed(), started.elapsed());
+}
+
+/// Draws the raw video without any filters or overlay, e.g. for a capture or monitoring screen.
+pub fn draw_clean_feed(state: &mut AppState, ui: &mut egui::Ui, ctx: &egui::Context) {
+    let response = ui.allocate_response(ui.available_size(), egui::Sense::click());
+    let video_texture = state.video_texture.as_ref().unwrap().texture();
+    let video_texture_id = video_texture.id();
+    let source = video::texture::SourceRegion::new(video_texture.size(), state.display_crop_enabled.then_some(state.display_crop));
+    if let Some(renderer_arc) = &state.crt_renderer {
+        let renderer_clone = renderer_arc.clone();
+        let pixel_aspect = state.pixel_aspect_ratio();
+        let rect = response.rect;
+        let context_reset = state.gl_context_reset.clone();
+        let callback = egui::PaintCallback { rect, callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |info, painter| {
+            let mut renderer = renderer_clone.lock().unwrap();
+            let Some(texture) = painter.texture(video_texture_id).filter(|_| renderer_ready(&mut renderer, painter.gl(), &context_reset)) else { return };
+            renderer.draw_passthrough(painter.gl(), texture, source, physical_size(info), pixel_aspect, crate::video::color::D65);
+        }))};
+        ui.painter().add(callback);
+    }
+    if response.double_clicked() {
+        let is_fullscreen = !ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(is_fullscreen));
+    }
+}
+
+/// Draws one tile of the display wall, stretched over the whole window.
+/// The GPU filters, overlay and black frame insertion only run in the single-window mode.
+pub fn draw_wall_tile(state: &mut AppState, ui: &mut egui::Ui, ctx: &egui::Context, index: usize) {
+    let response = ui.allocate_response(ui.available_size(), egui::Sense::click());
+    if index == 0 {
+        // Downscaling should consider the size of the whole wall, not a single tile.
+        let pixels_per_point = ctx.pixels_per_point();
+        let display_size = (
+            (response.rect.width() * pixels_per_point) as u32 * state.wall_layout.columns.max(1),
+            (response.rect.height() * pixels_per_point) as u32 * state.wall_layout.rows.max(1),
+        );
+        state.display_size.store(video::types::pack_size(display_size), std::sync::atomic::Ordering::Relaxed);
+    }
+    let video_texture = state.video_texture.as_ref().unwrap().texture();
+    let source = video::texture::SourceRegion::new(video_texture.size(), state.display_crop_enabled.then_some(state.display_crop)).uv();
+    let tile = state.wall_layout.tile_uv(index);
+    let uv = egui::Rect::from_min_max(source.lerp_inside(tile.min.to_vec2()), source.lerp_inside(tile.max.to_vec2()));
+    ui.painter().image(video_texture.id(), response.rect, uv, egui::Color32::WHITE);
+    if response.double_clicked() {
+        let is_fullscreen = !ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(is_fullscreen));
+    }
+}
+
 pub fn draw_video_player(state: &mut AppState, ui: &mut egui::Ui, ctx: &egui::Context) {
-    if state.video_window_open {
+    if state.video_window_open && state.wall_enabled {
+        draw_wall_tile(state, ui, ctx, 0);
+    } else if state.video_window_open {
         let response = ui.allocate_response(ui.available_size(), egui::Sense::click());
-        let video_texture = state.video_texture.as_ref().unwrap();
-        let video_texture_id = video_texture.id();
-        let texture_size = video_texture.size_vec2();
+        let pixels_per_point = ctx.pixels_per_point();
+        let display_size = (
+            (response.rect.width() * pixels_per_point) as u32,
+            (response.rect.height() * pixels_per_point) as u32,
+        );
+        state.display_size.store(video::types::pack_size(display_size), std::sync::atomic::Ordering::Relaxed);
+        let video_texture = state.video_texture.as_mut().unwrap();
+        let direct = video_texture.direct_frame().cloned();
+        let upload_stats = video_texture.stats.clone();
+        // Drawn for the first time this frame, so it goes into the egui path's statistics.
+        let uploaded_at = video_texture.take_upload_time();
+        let video_texture_id = video_texture.texture().id();
+        let source = video_texture.region(state.display_crop_enabled.then_some(state.display_crop));
 
         let filter = CrtFilter::from_u8(state.crt_filter.load(std::sync::atomic::Ordering::Relaxed));
+        let power_saver = state.power_saver.load(std::sync::atomic::Ordering::Relaxed);
+        let run_lottes = filter == CrtFilter::Lottes && !power_saver;
+        let upscaler = match filter {
+            CrtFilter::Xbr => Some(Upscaler::Xbr),
+            CrtFilter::XbrHard => Some(Upscaler::XbrHard),
+            _ => None,
+        }.filter(|_| !power_saver);
+        let gpu_filters = state.pixelate_filter_enabled || state.filter_settings.sharpen_enabled || state.filter_settings.phosphor_enabled || run_lottes || upscaler.is_some();
+        let memory_fit = gpu_filters.then(|| {
+            state.fit_gpu_memory(source.size(), upscaler.map(|_| state.filter_settings.upscale_factor))
+        });
+        let upscaler = upscaler.filter(|_| memory_fit.is_some_and(|fit| fit.upscale_factor.is_some()));
 
         // All GPU filtering is handled within a single paint callback to ensure correct state.
-        if state.pixelate_filter_enabled || filter == CrtFilter::Lottes {
+        if let Some(memory_fit) = memory_fit.filter(|fit| fit.filters) {
             if let Some(renderer_arc) = &state.crt_renderer {
                 let renderer_clone = renderer_arc.clone();
-                let params = video::gpu_filter::ShaderParams::from_state(state);
+                let mut params = video::gpu_filter::ShaderParams::from_state(state);
+                if let Some(factor) = memory_fit.upscale_factor {
+                    params.settings.upscale_factor = factor;
+                }
                 let pixelate = state.pixelate_filter_enabled;
-                let run_lottes = filter == CrtFilter::Lottes;
-                let rect = response.rect;
+                // Only read back once per uploaded frame, not on every repaint.
+                let burn_in_sender = (state.burn_in_readback_pending && state.burn_in_active())
+                    .then(|| state.filtered_frame_sender.clone());
+                state.burn_in_readback_pending = false;
     
+                let context_reset = state.gl_context_reset.clone();
                 let callback = egui::PaintCallback {
                     rect: response.rect,
-                    callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |_info, painter| {
+                    callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |info, painter| {
                         let mut renderer = renderer_clone.lock().unwrap();
-                        let output_size = (rect.width(), rect.height()); // The size of the viewport area to draw in
-                        renderer.paint(painter, video_texture_id, (texture_size.x as u32, texture_size.y as u32), output_size, &params, pixelate, run_lottes)
+                        if !renderer_ready(&mut renderer, painter.gl(), &context_reset) {
+                            return;
+                        }
+                        let output_size = physical_size(info);
+                        if let Some(direct) = &direct {
+                            upload_direct_frame(&mut renderer, painter.gl(), direct, &upload_stats);
+                            renderer.paint_direct(painter.gl(), source, output_size, &params, pixelate, run_lottes, upscaler);
+                            return;
+                        }
+                        if let Some(uploaded_at) = uploaded_at {
+                            upload_stats.lock().unwrap().record_egui(uploaded_at.elapsed());
+                        }
+                        renderer.paint(painter, video_texture_id, source, output_size, &params, pixelate, run_lottes, upscaler);
+                        if let Some(sender) = &burn_in_sender {
+                            if let Some(image) = renderer.render_to_image(painter, video_texture_id, source, &params, pixelate, run_lottes, upscaler) {
+                                let _ = sender.try_send(std::sync::Arc::new(image));
+                            }
+                        }
                     })),
                 };
                 ui.painter().add(callback);
@@ -60,12 +318,75 @@ pub fn draw_video_player(state: &mut AppState, ui: &mut egui::Ui, ctx: &egui::Co
         } else {
             // Fallback to a simple passthrough shader if no other GPU filters are active.
             let renderer_clone = state.crt_renderer.as_ref().unwrap().clone();
+            let pixel_aspect = state.pixel_aspect_ratio();
+            let white_point = state.filter_settings.white_point;
             let rect = response.rect;
-            let callback = egui::PaintCallback { rect, callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |_info, painter| {
-                renderer_clone.lock().unwrap().draw_passthrough(painter.gl(), painter.texture(video_texture_id).unwrap(), (texture_size.x as u32, texture_size.y as u32), (rect.width(), rect.height()));
+            let context_reset = state.gl_context_reset.clone();
+            let callback = egui::PaintCallback { rect, callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |info, painter| {
+                let mut renderer = renderer_clone.lock().unwrap();
+                if !renderer_ready(&mut renderer, painter.gl(), &context_reset) {
+                    return;
+                }
+                let texture = match &direct {
+                    Some(direct) => {
+                        upload_direct_frame(&mut renderer, painter.gl(), direct, &upload_stats);
+                        renderer.direct_texture()
+                    }
+                    None => {
+                        if let Some(uploaded_at) = uploaded_at {
+                            upload_stats.lock().unwrap().record_egui(uploaded_at.elapsed());
+                        }
+                        painter.texture(video_texture_id)
+                    }
+                };
+                let Some(texture) = texture else { return };
+                renderer.draw_passthrough(painter.gl(), texture, source, physical_size(info), pixel_aspect, white_point);
             }))};
             ui.painter().add(callback);
         }
+
+        if state.overlay_enabled {
+            state.ensure_overlay_texture(ctx);
+            if let Some(overlay) = &state.overlay_texture {
+                let tint = egui::Color32::from_white_alpha((state.overlay_opacity.clamp(0.0, 1.0) * 255.0) as u8);
+                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                ui.painter().image(overlay.id(), response.rect, uv, tint);
+            }
+        }
+
+        if state.text_overlay_enabled {
+            draw_text_overlay(state, ui, response.rect);
+        }
+        if state.timer_enabled {
+            draw_timer_overlay(state, ui, response.rect);
+        }
+        if state.latency_overlay_enabled {
+            draw_latency_overlay(state, ui, response.rect);
+        }
+        draw_osd(state, ui, response.rect);
+
+        // Black frame insertion runs last, over whatever the filters produced.
+        // Skipped by the power saver since it forces a repaint on every display refresh.
+        if state.bfi_mode != BfiMode::Off && !power_saver {
+            if let Some(renderer_arc) = &state.crt_renderer {
+                let renderer_clone = renderer_arc.clone();
+                let mode = state.bfi_mode;
+                let cycle = state.bfi_cycle.max(2);
+                let step = (state.bfi_refresh_counter % cycle as u64) as u32;
+                state.bfi_refresh_counter = state.bfi_refresh_counter.wrapping_add(1);
+                let rect = response.rect;
+                let context_reset = state.gl_context_reset.clone();
+                let callback = egui::PaintCallback { rect, callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |info, painter| {
+                    let mut renderer = renderer_clone.lock().unwrap();
+                    if renderer_ready(&mut renderer, painter.gl(), &context_reset) {
+                        renderer.draw_bfi(painter.gl(), mode, step, cycle, physical_size(info));
+                    }
+                }))};
+                ui.painter().add(callback);
+            }
+            // BFI needs a fresh frame on every display refresh, not only when video arrives.
+            ctx.request_repaint();
+        }
         if response.double_clicked() {
             let is_fullscreen = !ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
             ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(is_fullscreen));
@@ -0,0 +1,129 @@
+use super::recorder::CropRect;
+use eframe::egui;
+use std::collections::VecDeque;
+
+/// Pixels at or below this luma (out of 255) count as black. Capture cards rarely output a
+/// clean 0 for the borders.
+const BLACK_LEVEL: f32 = 24.0;
+/// A row or column may have this share of brighter pixels and still be border, for noise.
+const MAX_BRIGHT_SHARE: f32 = 0.02;
+/// Only every this many pixels along a row or column are looked at.
+const SAMPLE_STEP: usize = 4;
+/// Every this many shown frames one is analyzed.
+const SAMPLE_INTERVAL: u32 = 15;
+/// Analyzed frames whose content is combined before a crop is proposed, about 5 s at 60 fps.
+/// Dark scenes come and go, the borders stay.
+const WINDOW: usize = 20;
+/// Borders thinner than this share of the frame are left alone, e.g. a few dark columns of
+/// overscan, so the picture isn't rescaled for them.
+const MIN_BORDER_SHARE: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AutoCropMode {
+    Off = 0,
+    /// Detect black borders and offer to crop them.
+    Suggest = 1,
+    /// Crop to whatever the borders currently are.
+    Automatic = 2,
+}
+
+impl AutoCropMode {
+    pub const ALL: [AutoCropMode; 3] = [AutoCropMode::Off, AutoCropMode::Suggest, AutoCropMode::Automatic];
+
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => AutoCropMode::Suggest,
+            2 => AutoCropMode::Automatic,
+            _ => AutoCropMode::Off,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            AutoCropMode::Off => "Off",
+            AutoCropMode::Suggest => "Suggest",
+            AutoCropMode::Automatic => "Automatic",
+        }
+    }
+}
+
+fn is_black(pixel: egui::Color32) -> bool {
+    0.2126 * pixel.r() as f32 + 0.7152 * pixel.g() as f32 + 0.0722 * pixel.b() as f32 <= BLACK_LEVEL
+}
+
+fn is_border(pixels: impl Iterator<Item = egui::Color32>) -> bool {
+    let (mut total, mut bright) = (0usize, 0usize);
+    for pixel in pixels.step_by(SAMPLE_STEP) {
+        total += 1;
+        bright += usize::from(!is_black(pixel));
+    }
+    bright as f32 <= total as f32 * MAX_BRIGHT_SHARE
+}
+
+/// The part of the frame inside its black borders, as (left, top, right, bottom) edges.
+/// `None` for a frame that is black all over, e.g. during a fade.
+fn content_bounds(image: &egui::ColorImage) -> Option<(usize, usize, usize, usize)> {
+    let [width, height] = image.size;
+    let row = |y: usize| image.pixels[y * width..(y + 1) * width].iter().copied();
+    let column = |x: usize| image.pixels[x..].iter().step_by(width).copied();
+    // Walking in from each edge only touches the borders, not the whole frame.
+    let top = (0..height).find(|&y| !is_border(row(y)))?;
+    let bottom = (top..height).rev().find(|&y| !is_border(row(y)))? + 1;
+    let left = (0..width).find(|&x| !is_border(column(x)))?;
+    let right = (left..width).rev().find(|&x| !is_border(column(x)))? + 1;
+    Some((left, top, right, bottom))
+}
+
+/// Watches the shown frames for black borders, e.g. a 4:3 game pillarboxed in a 16:9 signal.
+#[derive(Default)]
+pub struct BorderDetector {
+    frames: u32,
+    window: VecDeque<(usize, usize, usize, usize)>,
+    frame_size: [usize; 2],
+}
+
+impl BorderDetector {
+    /// Looks at some of the frames. Returns the crop that removes the borders seen over the
+    /// last few seconds, `None` for the whole frame, once it has seen enough frames; until
+    /// then nothing.
+    pub fn push(&mut self, image: &egui::ColorImage) -> Option<Option<CropRect>> {
+        self.frames = (self.frames + 1) % SAMPLE_INTERVAL;
+        if self.frames != 0 || image.width() == 0 || image.height() == 0 {
+            return None;
+        }
+        if image.size != self.frame_size {
+            self.reset();
+            self.frame_size = image.size;
+        }
+        let bounds = content_bounds(image)?;
+        if self.window.len() == WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(bounds);
+        if self.window.len() < WINDOW {
+            return None;
+        }
+        let left = self.window.iter().map(|b| b.0).min().unwrap_or_default();
+        let top = self.window.iter().map(|b| b.1).min().unwrap_or_default();
+        let right = self.window.iter().map(|b| b.2).max().unwrap_or_default();
+        let bottom = self.window.iter().map(|b| b.3).max().unwrap_or_default();
+        let [width, height] = image.size;
+        let min_x = (width as f32 * MIN_BORDER_SHARE) as usize;
+        let min_y = (height as f32 * MIN_BORDER_SHARE) as usize;
+        let crop_x = left > min_x || width - right > min_x;
+        let crop_y = top > min_y || height - bottom > min_y;
+        if !crop_x && !crop_y {
+            return Some(None);
+        }
+        let (left, right) = if crop_x { (left, right) } else { (0, width) };
+        let (top, bottom) = if crop_y { (top, bottom) } else { (0, height) };
+        Some(Some((left as u32, top as u32, (right - left) as u32, (bottom - top) as u32)))
+    }
+
+    /// Starts over, e.g. after the source changed.
+    pub fn reset(&mut self) {
+        self.frames = 0;
+        self.window.clear();
+    }
+}
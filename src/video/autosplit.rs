@@ -0,0 +1,215 @@
+use super::recorder::CropRect;
+use anyhow::{Context, Result};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Reference images and frame regions are both compared at this many sample points.
+const SAMPLE_GRID: (usize, usize) = (32, 32);
+/// After firing, a trigger only re-arms once the match drops this far below its threshold, so
+/// a screen that hovers around the threshold doesn't split twice.
+const REARM_MARGIN: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum SplitAction {
+    /// Starts the timer, or splits if it's running.
+    #[default]
+    StartOrSplit = 0,
+    Reset = 1,
+    Pause = 2,
+    Resume = 3,
+}
+
+impl SplitAction {
+    pub const ALL: [SplitAction; 4] = [SplitAction::StartOrSplit, SplitAction::Reset, SplitAction::Pause, SplitAction::Resume];
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            SplitAction::StartOrSplit => "Start / split",
+            SplitAction::Reset => "Reset",
+            SplitAction::Pause => "Pause",
+            SplitAction::Resume => "Resume",
+        }
+    }
+}
+
+/// Fires `action` when `region` of the frame looks like the reference image.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitTrigger {
+    pub name: String,
+    /// PNG of what the region shows at the split, e.g. a "Stage clear" banner.
+    pub image_path: String,
+    /// Part of the source frame to compare, in source pixels.
+    pub region: CropRect,
+    /// How alike the region and the image have to be, from 0 to 1.
+    pub threshold: f32,
+    pub action: SplitAction,
+    /// Minimum time between two firings of this trigger.
+    pub cooldown_secs: f32,
+}
+
+impl Default for SplitTrigger {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            image_path: String::new(),
+            region: (0, 0, 320, 240),
+            threshold: 0.9,
+            action: SplitAction::StartOrSplit,
+            cooldown_secs: 3.0,
+        }
+    }
+}
+
+/// A trigger that matched.
+#[derive(Debug, Clone)]
+pub struct SplitEvent {
+    pub trigger: String,
+    pub action: SplitAction,
+}
+
+/// Reference images live next to the confy config file, like looks.
+pub fn references_dir() -> Result<PathBuf> {
+    let config_path = confy::get_configuration_file_path("michadame", None).context("Failed to locate config directory")?;
+    let dir = config_path.parent().context("Config path has no parent directory")?.join("autosplit");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    Ok(dir)
+}
+
+/// Saves `region` of `image` as the reference image for a trigger named `name`.
+pub fn capture_reference(image: &egui::ColorImage, region: CropRect, name: &str) -> Result<PathBuf> {
+    let cropped = super::texture::crop_image(image, region).context("The region is outside the video frame")?;
+    let file_name: String = name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+    let path = references_dir()?.join(format!("{}.png", if file_name.is_empty() { "trigger" } else { &file_name }));
+    let rgba: Vec<u8> = cropped.pixels.iter().flat_map(|p| p.to_array()).collect();
+    image::save_buffer(&path, &rgba, cropped.size[0] as u32, cropped.size[1] as u32, image::ExtendedColorType::Rgba8)
+        .with_context(|| format!("Failed to save '{}'", path.display()))?;
+    Ok(path)
+}
+
+/// RGB values of `SAMPLE_GRID` points spread evenly over the `(x, y, w, h)` part of an image.
+fn sample(width: usize, height: usize, region: CropRect, pixel: impl Fn(usize, usize) -> [u8; 3]) -> Option<Vec<[u8; 3]>> {
+    let (x, y, w, h) = (region.0 as usize, region.1 as usize, region.2 as usize, region.3 as usize);
+    if w == 0 || h == 0 || x + w > width || y + h > height {
+        return None;
+    }
+    let (columns, rows) = SAMPLE_GRID;
+    Some(
+        (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|(column, row)| pixel(x + (column * 2 + 1) * w / (columns * 2), y + (row * 2 + 1) * h / (rows * 2)))
+            .collect(),
+    )
+}
+
+/// 1 for identical samples, 0 for black against white everywhere.
+fn similarity(a: &[[u8; 3]], b: &[[u8; 3]]) -> f32 {
+    let difference: u32 = a.iter().zip(b).flat_map(|(p, q)| (0..3).map(move |c| p[c].abs_diff(q[c]) as u32)).sum();
+    1.0 - difference as f32 / (a.len() * 3 * 255) as f32
+}
+
+struct LoadedTrigger {
+    trigger: SplitTrigger,
+    reference: Vec<[u8; 3]>,
+    armed: bool,
+    last_fired: Option<Instant>,
+}
+
+fn load_trigger(trigger: &SplitTrigger) -> Result<LoadedTrigger> {
+    let image = image::open(&trigger.image_path)
+        .with_context(|| format!("Failed to open the image of trigger '{}'", trigger.name))?
+        .to_rgb8();
+    let reference = sample(image.width() as usize, image.height() as usize, (0, 0, image.width(), image.height()), |x, y| {
+        image.get_pixel(x as u32, y as u32).0
+    })
+    .with_context(|| format!("The image of trigger '{}' is empty", trigger.name))?;
+    Ok(LoadedTrigger { trigger: trigger.clone(), reference, armed: true, last_fired: None })
+}
+
+/// Compares the live frames against the triggers' reference images on its own thread and
+/// reports the triggers that match.
+pub struct AutoSplitter {
+    frame_sender: crossbeam_channel::Sender<Arc<egui::ColorImage>>,
+    stop_flag: Arc<AtomicBool>,
+    /// Latest match of each trigger, in trigger order, for tuning the thresholds.
+    similarities: Arc<Mutex<Vec<Option<f32>>>>,
+    triggers: Vec<SplitTrigger>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AutoSplitter {
+    pub fn start(triggers: &[SplitTrigger], ctx: &egui::Context) -> Result<(Self, crossbeam_channel::Receiver<SplitEvent>)> {
+        let mut loaded = triggers.iter().map(load_trigger).collect::<Result<Vec<_>>>()?;
+        let (frame_sender, frame_receiver) = crossbeam_channel::bounded::<Arc<egui::ColorImage>>(1);
+        let (event_sender, event_receiver) = crossbeam_channel::unbounded();
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let similarities = Arc::new(Mutex::new(vec![None; loaded.len()]));
+        let thread = thread::spawn({
+            let stop_flag = stop_flag.clone();
+            let similarities = similarities.clone();
+            let ctx = ctx.clone();
+            move || {
+                while !stop_flag.load(Ordering::Relaxed) {
+                    let Ok(image) = frame_receiver.recv_timeout(Duration::from_millis(100)) else { continue };
+                    let [width, height] = image.size;
+                    let mut current = Vec::with_capacity(loaded.len());
+                    for entry in &mut loaded {
+                        let samples = sample(width, height, entry.trigger.region, |x, y| {
+                            let p = image.pixels[y * width + x];
+                            [p.r(), p.g(), p.b()]
+                        });
+                        let Some(samples) = samples else {
+                            current.push(None);
+                            continue;
+                        };
+                        let score = similarity(&samples, &entry.reference);
+                        current.push(Some(score));
+                        if score < entry.trigger.threshold - REARM_MARGIN {
+                            entry.armed = true;
+                        }
+                        let cooled_down = entry.last_fired.is_none_or(|at| at.elapsed().as_secs_f32() >= entry.trigger.cooldown_secs);
+                        if entry.armed && cooled_down && score >= entry.trigger.threshold {
+                            entry.armed = false;
+                            entry.last_fired = Some(Instant::now());
+                            tracing::info!(trigger = %entry.trigger.name, similarity = score, "Auto-split trigger matched");
+                            let _ = event_sender.send(SplitEvent { trigger: entry.trigger.name.clone(), action: entry.trigger.action });
+                            ctx.request_repaint();
+                        }
+                    }
+                    *similarities.lock().unwrap() = current;
+                }
+            }
+        });
+        Ok((Self { frame_sender, stop_flag, similarities, triggers: triggers.to_vec(), thread: Some(thread) }, event_receiver))
+    }
+
+    /// Queues a frame for matching. Dropped if the previous one is still being compared.
+    pub fn push_frame(&self, image: Arc<egui::ColorImage>) {
+        let _ = self.frame_sender.try_send(image);
+    }
+
+    pub fn similarities(&self) -> Vec<Option<f32>> {
+        self.similarities.lock().unwrap().clone()
+    }
+
+    /// The triggers as they were when started.
+    pub fn triggers(&self) -> &[SplitTrigger] {
+        &self.triggers
+    }
+}
+
+impl Drop for AutoSplitter {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
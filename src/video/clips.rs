@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use eframe::egui;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+/// Frames shown on the trim strip.
+pub const THUMBNAIL_COUNT: usize = 8;
+const THUMBNAIL_SIZE: (usize, usize) = (160, 90);
+/// File types the browser lists, besides screenshots and chapter sidecars.
+const VIDEO_EXTENSIONS: &[&str] = &["mkv", "mp4", "webm", "mov"];
+
+#[derive(Debug, Clone)]
+pub struct RecordingFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+impl RecordingFile {
+    pub fn name(&self) -> String {
+        self.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+}
+
+/// The `limit` most recently modified videos in `dir`, newest first.
+pub fn recent_recordings(dir: &Path, limit: usize) -> Vec<RecordingFile> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+    let mut files: Vec<RecordingFile> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+            if !VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+                return None;
+            }
+            let metadata = std::fs::metadata(&path).ok().filter(|m| m.is_file())?;
+            Some(RecordingFile { path, size: metadata.len(), modified: metadata.modified().ok()? })
+        })
+        .collect();
+    files.sort_by_key(|file| std::cmp::Reverse(file.modified));
+    files.truncate(limit);
+    files
+}
+
+/// A recording's length and evenly spaced frames from it, as (seconds, image).
+pub struct ClipPreview {
+    pub duration: f64,
+    pub thumbnails: Vec<(f64, egui::ColorImage)>,
+}
+
+/// Reads the duration with FFmpeg and grabs the thumbnails with the `ffmpeg` tool, seeking to
+/// each one so long recordings don't have to be decoded in full.
+pub fn load_preview(path: &Path) -> Result<ClipPreview> {
+    ffmpeg_next::init().context("Failed to initialize FFmpeg")?;
+    let ictx = ffmpeg_next::format::input(path).with_context(|| format!("Failed to open '{}'", path.display()))?;
+    let duration = ictx.duration().max(0) as f64 / 1_000_000.0;
+    if duration <= 0.0 {
+        anyhow::bail!("'{}' has no duration, it may still be recording", path.display());
+    }
+    let thumbnails = (0..THUMBNAIL_COUNT)
+        .map(|i| duration * (i as f64 + 0.5) / THUMBNAIL_COUNT as f64)
+        .filter_map(|time| match grab_frame(path, time) {
+            Ok(image) => Some((time, image)),
+            Err(e) => {
+                tracing::warn!("Failed to grab thumbnail at {:.1}s: {:#}", time, e);
+                None
+            }
+        })
+        .collect();
+    Ok(ClipPreview { duration, thumbnails })
+}
+
+fn grab_frame(path: &Path, time: f64) -> Result<egui::ColorImage> {
+    let (width, height) = THUMBNAIL_SIZE;
+    let mut child = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-ss", &format!("{:.3}", time), "-i"])
+        .arg(path)
+        .args(["-frames:v", "1", "-vf", &format!("scale={}:{}", width, height), "-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to execute 'ffmpeg'")?;
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    child.stdout.take().context("ffmpeg has no stdout")?.read_to_end(&mut rgba)?;
+    child.wait()?;
+    if rgba.len() < width * height * 4 {
+        anyhow::bail!("ffmpeg returned no frame");
+    }
+    Ok(egui::ColorImage::from_rgba_unmultiplied([width, height], &rgba[..width * height * 4]))
+}
+
+/// The recording open in the trim editor, with the cut points in seconds.
+pub struct TrimSession {
+    pub path: PathBuf,
+    pub duration: f64,
+    pub start: f64,
+    pub end: f64,
+    pub thumbnails: Vec<(f64, egui::TextureHandle)>,
+}
+
+/// Copies `start..end` seconds of `path` into a new file next to it, named with `suffix`,
+/// without re-encoding. Stream copy can only cut at keyframes, so the clip starts at the
+/// keyframe before `start`.
+pub fn trim_recording(path: &Path, start: f64, end: f64, suffix: &str) -> Result<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "recording".to_string());
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_else(|| super::recorder::FILE_EXTENSION.to_string());
+    let trimmed = super::recorder::unique_output_path(dir, &format!("{}_{}", stem, suffix), &extension);
+    let status = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-ss", &format!("{:.3}", start), "-i"])
+        .arg(path)
+        .args(["-t", &format!("{:.3}", end - start), "-map", "0", "-c", "copy", "-avoid_negative_ts", "make_zero"])
+        .arg(&trimmed)
+        .status()
+        .context("Failed to execute 'ffmpeg' to trim the recording")?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&trimmed);
+        anyhow::bail!("ffmpeg failed to trim '{}' ({})", path.display(), status);
+    }
+    tracing::info!(path = %trimmed.display(), start, end, "Trimmed recording");
+    Ok(trimmed)
+}
+
+/// `H:MM:SS.s`, or `M:SS.s` under an hour.
+pub fn format_timestamp(seconds: f64) -> String {
+    let tenths = (seconds.max(0.0) * 10.0).round() as u64;
+    let (hours, minutes, secs) = (tenths / 36000, tenths / 600 % 60, tenths % 600);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}.{}", hours, minutes, secs / 10, secs % 10)
+    } else {
+        format!("{}:{:02}.{}", minutes, secs / 10, secs % 10)
+    }
+}
@@ -0,0 +1,165 @@
+use super::recorder::CropRect;
+use anyhow::Result;
+use eframe::egui;
+use std::sync::Arc;
+
+/// Frames averaged for a test card calibration.
+pub const CALIBRATION_FRAMES: usize = 30;
+/// Frames dropped at the start of a calibration, which may still have the old adjustment.
+const CALIBRATION_SKIP: usize = 10;
+/// A ramp's black and white have to be at least this far apart, out of 1.
+const MIN_RAMP_RANGE: f32 = 0.3;
+
+/// Brightness, contrast and gamma applied to every frame on the CPU, so all outputs get them.
+/// Values are on a 0-1 scale: `out = (in * contrast + brightness) ^ (1 / gamma)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorAdjust {
+    pub brightness: f32,
+    pub contrast: f32,
+    pub gamma: f32,
+}
+
+impl Default for ColorAdjust {
+    fn default() -> Self {
+        Self { brightness: 0.0, contrast: 1.0, gamma: 1.0 }
+    }
+}
+
+impl ColorAdjust {
+    pub fn is_neutral(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The adjustment as a table from input to output channel values.
+    pub fn lut(&self) -> [u8; 256] {
+        let mut lut = [0u8; 256];
+        for (value, out) in lut.iter_mut().enumerate() {
+            let level = (value as f32 / 255.0 * self.contrast + self.brightness).clamp(0.0, 1.0);
+            *out = (level.powf(1.0 / self.gamma.max(0.01)) * 255.0).round() as u8;
+        }
+        lut
+    }
+
+    /// Applies the adjustment to packed 8-bit channels.
+    pub fn apply(&self, data: &mut [u8]) {
+        if self.is_neutral() {
+            return;
+        }
+        let lut = self.lut();
+        for value in data {
+            *value = lut[*value as usize];
+        }
+    }
+}
+
+/// Color temperatures of the white point presets, in kelvin. sRGB monitors are D65; Japanese
+/// TVs and monitors were usually set to D93, which gives a bluer white.
+pub const D65: f32 = 6500.0;
+pub const D93: f32 = 9300.0;
+
+/// CIE daylight chromaticity for a color temperature from 4000 to 25000 K.
+fn daylight_chromaticity(kelvin: f64) -> (f64, f64) {
+    let t = kelvin.clamp(4000.0, 25000.0);
+    let x = if t <= 7000.0 {
+        -4.6070e9 / t.powi(3) + 2.9678e6 / t.powi(2) + 0.09911e3 / t + 0.244063
+    } else {
+        -2.0064e9 / t.powi(3) + 1.9018e6 / t.powi(2) + 0.24748e3 / t + 0.237040
+    };
+    (x, -3.0 * x * x + 2.870 * x - 0.275)
+}
+
+/// Linear sRGB levels that make a white of that chromaticity.
+fn white_rgb(kelvin: f64) -> [f64; 3] {
+    let (x, y) = daylight_chromaticity(kelvin);
+    let (big_x, big_z) = (x / y, (1.0 - x - y) / y);
+    [
+        3.2406 * big_x - 1.5372 - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 + 1.0570 * big_z,
+    ]
+}
+
+/// Column-major 3x3 matrix for linear RGB that moves white from D65 to `kelvin`, the way a CRT's
+/// gun gains set its color temperature. Scaled so the strongest channel stays at full level,
+/// which keeps white from clipping; D93 dims red instead of boosting blue.
+pub fn white_point_matrix(kelvin: f32) -> [f32; 9] {
+    let (target, d65) = (white_rgb(kelvin as f64), white_rgb(D65 as f64));
+    let gains = [target[0] / d65[0], target[1] / d65[1], target[2] / d65[2]];
+    let max = gains.iter().copied().fold(f64::MIN, f64::max);
+    let [r, g, b] = gains.map(|gain| (gain / max) as f32);
+    [r, 0.0, 0.0, 0.0, g, 0.0, 0.0, 0.0, b]
+}
+
+/// What a test card analysis measured, and the adjustment that corrects it.
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    /// Levels of the ramp's darkest, middle and brightest parts, out of 1.
+    pub black: f32,
+    pub middle: f32,
+    pub white: f32,
+    pub recommended: ColorAdjust,
+}
+
+/// Measures a full-screen gray ramp, e.g. the 240p Test Suite's "Gray ramp", which has as many
+/// pixels at each level from black to white. Its darkest pixels should be black, its brightest
+/// white and its median halfway. The recommendation stretches black and white into place, then
+/// moves the median back to the middle with gamma.
+pub fn analyze_test_card(frames: &[Arc<egui::ColorImage>], region: Option<CropRect>) -> Result<Calibration> {
+    let mut histogram = [0u64; 256];
+    for frame in frames.iter().skip(CALIBRATION_SKIP) {
+        let cropped = region.and_then(|region| super::texture::crop_image(frame, region));
+        let image = cropped.as_ref().unwrap_or(frame);
+        for pixel in &image.pixels {
+            let luma = 0.2126 * pixel.r() as f32 + 0.7152 * pixel.g() as f32 + 0.0722 * pixel.b() as f32;
+            histogram[luma.round() as usize] += 1;
+        }
+    }
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        anyhow::bail!("No video frames to analyze");
+    }
+    let percentile = |share: f64| {
+        let target = (total as f64 * share) as u64;
+        let mut seen = 0;
+        for (level, count) in histogram.iter().enumerate() {
+            seen += count;
+            if seen > target {
+                return level as f32 / 255.0;
+            }
+        }
+        1.0
+    };
+    // Ignore the odd stray pixel at either end, e.g. noise or a bright frame edge.
+    let (black, middle, white) = (percentile(0.01), percentile(0.5), percentile(0.99));
+    if white - black < MIN_RAMP_RANGE {
+        anyhow::bail!("The picture doesn't go from dark to bright. Show a full-screen gray ramp and try again");
+    }
+    let contrast = 1.0 / (white - black);
+    let brightness = -black * contrast;
+    let stretched_middle = ((middle - black) * contrast).clamp(0.05, 0.95);
+    let gamma = stretched_middle.ln() / 0.5f32.ln();
+    tracing::info!(black, middle, white, contrast, brightness, gamma, "Analyzed test card");
+    Ok(Calibration { black, middle, white, recommended: ColorAdjust { brightness, contrast, gamma } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_point_matrix_keeps_d65() {
+        let matrix = white_point_matrix(D65);
+        let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        assert!(matrix.iter().zip(identity).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", matrix);
+    }
+
+    #[test]
+    fn white_point_matrix_d93_dims_red() {
+        let matrix = white_point_matrix(D93);
+        let (r, g, b) = (matrix[0], matrix[4], matrix[8]);
+        assert_eq!(b, 1.0);
+        assert!(r < g && g < b, "{:?}", matrix);
+        // Only gains, no mixing of channels.
+        assert!([1, 2, 3, 5, 6, 7].iter().all(|&i| matrix[i] == 0.0));
+    }
+}
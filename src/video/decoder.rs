@@ -1,14 +1,15 @@
-use crate::devices::{filter_type::CrtFilter, filters};
-use crate::video::types::VideoFormat;
-use anyhow::{Context, Result};
+use crate::devices::{filter_type::CrtFilter, filters, screencast::ScreencastSession};
+use crate::video::types::{CaptureSettings, SourceKind, StreamInfo, ThreadPriority, VideoFormat};
+use anyhow::{anyhow, Context, Result};
 use eframe::egui;
 use ffmpeg_next::format::Pixel;
 use std::sync::{
-    atomic::{AtomicBool, AtomicU8, Ordering},
+    atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicU8, Ordering},
     Arc,
 };
 use std::thread;
-fn setup_ffmpeg_options(
+use std::time::{Duration, Instant};
+pub(super) fn setup_ffmpeg_options(
     format: &VideoFormat,
     resolution: (u32, u32),
     framerate: u32,
@@ -31,21 +32,76 @@ fn setup_ffmpeg_options(
     }
     (pixel_format_str, ffmpeg_options)
 }
+fn setup_network_options(url: &str) -> ffmpeg_next::Dictionary<'static> {
+    let mut ffmpeg_options = ffmpeg_next::Dictionary::new();
+    ffmpeg_options.set("fflags", "nobuffer+discardcorrupt");
+    ffmpeg_options.set("flags", "low_delay");
+    // Give up on unresponsive servers instead of hanging the video thread (microseconds).
+    ffmpeg_options.set("rw_timeout", "5000000");
+    if url.starts_with("rtsp://") {
+        // UDP loses too many packets on Wi-Fi to be usable for a viewer.
+        ffmpeg_options.set("rtsp_transport", "tcp");
+    }
+    ffmpeg_options
+}
+
+/// The capture device is already streaming to another program (e.g. OBS), which V4L2 doesn't allow.
+#[derive(Debug)]
+pub struct DeviceBusyError {
+    pub device: String,
+}
+
+impl std::fmt::Display for DeviceBusyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is busy, it is probably in use by another application", self.device)
+    }
+}
+
+impl std::error::Error for DeviceBusyError {}
+
 pub fn video_thread_main(
     frame_sender: crossbeam_channel::Sender<Arc<egui::ColorImage>>,
     stop_flag: Arc<AtomicBool>,
-    device: String,
-    format: VideoFormat,
-    resolution: (u32, u32),
-    framerate: u32,
+    settings: CaptureSettings,
     crt_filter: Arc<AtomicU8>,
 ) -> Result<()> {
     ffmpeg_next::init().context("Failed to initialize FFmpeg")?;
-    let (_pixel_format, ffmpeg_options) = setup_ffmpeg_options(&format, resolution, framerate);
+    set_thread_priority(settings.thread_priority);
+    settings.buffer_age_us.store(-1, Ordering::Relaxed);
+    let mut device = settings.input.clone();
+    // Kept alive until the thread exits; dropping it ends the screencast.
+    let mut screencast = None;
+    let ffmpeg_options = match settings.source_kind {
+        SourceKind::Capture => {
+            let (_, mut ffmpeg_options) = setup_ffmpeg_options(&settings.format, settings.resolution, settings.framerate);
+            let format_override = &settings.format_override;
+            if let Some(input_format) = format_override.input_format.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+                ffmpeg_options.set("input_format", input_format);
+            }
+            if let Some(pixel_format) = format_override.pixel_format.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+                ffmpeg_options.set("pixel_format", pixel_format);
+            }
+            ffmpeg_options
+        }
+        SourceKind::Network => setup_network_options(&device),
+        SourceKind::File => return file_playback_main(frame_sender, stop_flag, &settings, crt_filter),
+        SourceKind::Screen => {
+            let session = screencast.insert(ScreencastSession::start().context("Failed to start screen capture")?);
+            device = session.input_path();
+            let mut ffmpeg_options = ffmpeg_next::Dictionary::new();
+            ffmpeg_options.set("fflags", "nobuffer");
+            ffmpeg_options
+        }
+    };
 
     tracing::info!(device = %device, options = ?ffmpeg_options, "Starting FFmpeg with options");
-    let ictx = ffmpeg_next::format::input_with_dictionary(&device, ffmpeg_options)
-        .context("Failed to open input device with ffmpeg")?;
+    let ictx = match ffmpeg_next::format::input_with_dictionary(&device, ffmpeg_options) {
+        Ok(ictx) => ictx,
+        Err(ffmpeg_next::Error::Other { errno }) if errno == libc::EBUSY && settings.source_kind == SourceKind::Capture => {
+            return Err(DeviceBusyError { device }.into());
+        }
+        Err(e) => return Err(e).context("Failed to open input device with ffmpeg"),
+    };
 
     let input = ictx.streams().best(ffmpeg_next::media::Type::Video).context("Could not find best video stream")?;
     let video_stream_index = input.index();
@@ -55,55 +111,367 @@ pub fn video_thread_main(
         .context("Failed to create software video decoder")?;
 
     decoder.set_threading(ffmpeg_next::codec::threading::Config::default());
+    let mut pending_info = Some(describe_stream(&ictx, &input, &decoder, settings.framerate));
+    let time_base = input.time_base();
     let (packet_tx, packet_rx) = crossbeam_channel::bounded(1);
     let reader_stop_flag = stop_flag.clone();
-    let _reader_thread = thread::spawn(move || {
+    let thread_priority = settings.thread_priority;
+    let buffer_age = settings.buffer_age_us.clone();
+    let is_capture = settings.source_kind == SourceKind::Capture;
+    let _reader_thread = thread::Builder::new().name("video-reader".to_string()).spawn(move || {
+        set_thread_priority(thread_priority);
         let mut ictx = ictx;
         for (stream, packet) in ictx.packets() {
             if reader_stop_flag.load(Ordering::Relaxed) { break; }
             if stream.index() == video_stream_index {
+                if is_capture {
+                    buffer_age.store(buffer_age_us(packet.pts(), time_base).unwrap_or(-1), Ordering::Relaxed);
+                }
                 let _ = packet_tx.try_send(packet);
             }
         }
         tracing::info!("Packet reader thread finished.");
-    });
+    }).context("Failed to start the packet reader thread")?;
+
+    let mut scaler = None;
+    while !stop_flag.load(Ordering::Relaxed) {
+        let Ok(packet) = packet_rx.recv() else {
+            if stop_flag.load(Ordering::Relaxed) {
+                break;
+            }
+            // The reader thread only exits on end of stream or a read error (e.g. a network drop).
+            return Err(anyhow!("Input stream ended"));
+        };
+        decoder.send_packet(&packet).context("Failed to send packet to decoder")?;
+        let mut decoded = ffmpeg_next::frame::Video::empty();
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            report_stream_info(&mut pending_info, &decoded, &settings.info_sender);
+            let image = frame_to_image(&mut scaler, &decoded, &crt_filter, &settings)?;
+            if frame_sender.try_send(image).is_err() {
+                break;
+            }
+        }
+    }
+    tracing::info!("Video thread finished.");
+    Ok(())
+}
+
+/// How long ago the driver filled the buffer of a packet from ffmpeg's v4l2 input, which
+/// stamps packets with the buffer's kernel timestamp converted to wall clock time. With frames
+/// read as soon as they're filled this stays below a frame interval; more means filled buffers
+/// are queueing up. `None` if the timestamp isn't wall clock time (some drivers).
+///
+/// ffmpeg's v4l2 input doesn't expose its buffer queue, and always requests its own fixed number
+/// of buffers from the driver, so the queue depth can only be inferred like this, not set.
+fn buffer_age_us(pts: Option<i64>, time_base: ffmpeg_next::Rational) -> Option<i64> {
+    let pts_us = pts? as i128 * 1_000_000 * time_base.numerator() as i128 / time_base.denominator().max(1) as i128;
+    let now_us = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok()?.as_micros() as i128;
+    let age = now_us - pts_us;
+    // Anything else is a timestamp on another clock.
+    (0..10_000_000).contains(&age).then_some(age as i64)
+}
+
+/// Nice value of threads at `ThreadPriority::High`.
+const HIGH_PRIORITY_NICE: libc::c_int = -10;
+/// SCHED_RR priority of threads at `ThreadPriority::Realtime`. Low, so it only wins against
+/// normal threads and doesn't starve audio servers that also run realtime.
+const REALTIME_PRIORITY: libc::c_int = 10;
+
+/// Raises the calling thread's scheduling priority. Unprivileged users usually may not, in
+/// which case the thread stays as it is and a warning is logged.
+fn set_thread_priority(priority: ThreadPriority) {
+    if priority == ThreadPriority::Realtime {
+        let param = libc::sched_param { sched_priority: REALTIME_PRIORITY };
+        // Linux applies the policy to the calling thread for pid 0.
+        if unsafe { libc::sched_setscheduler(0, libc::SCHED_RR, &param) } == 0 {
+            tracing::info!("Thread running with SCHED_RR");
+            return;
+        }
+        tracing::warn!("SCHED_RR not permitted ({}), trying a higher nice value instead", std::io::Error::last_os_error());
+    }
+    if priority != ThreadPriority::Normal {
+        let tid = unsafe { libc::gettid() } as libc::id_t;
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, HIGH_PRIORITY_NICE) } == 0 {
+            tracing::info!(nice = HIGH_PRIORITY_NICE, "Raised thread priority");
+        } else {
+            tracing::warn!("Failed to raise thread priority: {}", std::io::Error::last_os_error());
+        }
+    }
+}
+
+fn describe_stream(
+    ictx: &ffmpeg_next::format::context::Input,
+    stream: &ffmpeg_next::format::stream::Stream,
+    decoder: &ffmpeg_next::decoder::Video,
+    requested_fps: u32,
+) -> StreamInfo {
+    let rate = stream.avg_frame_rate();
+    let device_fps = (rate.numerator() > 0 && rate.denominator() > 0).then(|| f64::from(rate));
+    let frame_rate = match device_fps {
+        Some(fps) => format!("{} ({:.2} FPS)", rate, fps),
+        None => "Unknown".to_string(),
+    };
+    StreamInfo {
+        container: ictx.format().name().to_string(),
+        codec: decoder.codec().map(|c| c.name().to_string()).unwrap_or_else(|| format!("{:?}", decoder.id())),
+        frame_rate,
+        time_base: stream.time_base().to_string(),
+        requested_fps: (requested_fps > 0).then_some(requested_fps),
+        device_fps,
+        ..Default::default()
+    }
+}
+
+/// Fills in the per-frame details from the first decoded frame and hands the info to the UI once.
+fn report_stream_info(
+    pending_info: &mut Option<StreamInfo>,
+    decoded: &ffmpeg_next::frame::Video,
+    info_sender: &crossbeam_channel::Sender<StreamInfo>,
+) {
+    let Some(mut info) = pending_info.take() else { return };
+    info.resolution = (decoded.width(), decoded.height());
+    info.pixel_format = format!("{:?}", decoded.format());
+    info.color_range = format!("{:?}", decoded.color_range());
+    info.color_space = format!("{:?}", decoded.color_space());
+    let _ = info_sender.try_send(info);
+}
+
+/// Widest frame produced while power saving; larger sources are downscaled to save CPU and upload bandwidth.
+const POWER_SAVER_MAX_WIDTH: u32 = 1280;
+
+/// Picks the size to convert a frame to. With auto downscale on, sources much larger than the
+/// video area are halved ("mip levels") as long as they still cover it, so a 4K source in a
+/// 960x540 window isn't converted, uploaded and filtered at full size every frame.
+fn output_size(source: (u32, u32), display: (u32, u32), low_power: bool) -> (u32, u32) {
+    let (mut width, mut height) = source;
+    if display.0 > 0 && display.1 > 0 {
+        while width / 2 >= display.0 && height / 2 >= display.1 {
+            width /= 2;
+            height /= 2;
+        }
+    }
+    if low_power && width > POWER_SAVER_MAX_WIDTH {
+        height = height * POWER_SAVER_MAX_WIDTH / width;
+        width = POWER_SAVER_MAX_WIDTH;
+    }
+    if (width, height) == source {
+        return source;
+    }
+    // Keep scaled sizes even for chroma-subsampled formats.
+    ((width & !1).max(2), (height & !1).max(2))
+}
+
+/// Converts a decoded frame to RGB and applies the CPU filters.
+/// The scaler is tagged with the (size, power saver) it was built for and rebuilt when that changes.
+fn frame_to_image(
+    scaler: &mut Option<((u32, u32, bool), ffmpeg_next::software::scaling::context::Context)>,
+    decoded: &ffmpeg_next::frame::Video,
+    crt_filter: &AtomicU8,
+    settings: &CaptureSettings,
+) -> Result<Arc<egui::ColorImage>> {
+    let low_power = settings.power_saver.load(Ordering::Relaxed);
+    let display = if settings.auto_downscale.load(Ordering::Relaxed) {
+        crate::video::types::unpack_size(settings.display_size.load(Ordering::Relaxed))
+    } else {
+        (0, 0)
+    };
+    let (width, height) = output_size((decoded.width(), decoded.height()), display, low_power);
+    let key = (width, height, low_power);
+    if scaler.as_ref().is_some_and(|(built_for, _)| *built_for != key) {
+        *scaler = None;
+    }
+    let (_, scaler) = match scaler {
+        Some(scaler) => scaler,
+        None => {
+            let flags = if low_power {
+                ffmpeg_next::software::scaling::flag::Flags::POINT
+            } else if (width, height) != (decoded.width(), decoded.height()) {
+                // Area averaging avoids aliasing when shrinking by 2x or more.
+                ffmpeg_next::software::scaling::flag::Flags::AREA
+            } else {
+                ffmpeg_next::software::scaling::flag::Flags::FAST_BILINEAR
+            };
+            scaler.insert((
+                key,
+                ffmpeg_next::software::scaling::context::Context::get(
+                    decoded.format(),
+                    decoded.width(),
+                    decoded.height(),
+                    Pixel::RGB24, width, height,
+                    flags,
+                ).context("Failed to create scaler")?,
+            ))
+        }
+    };
+    let mut rgb_frame = ffmpeg_next::frame::Video::empty();
+    scaler.run(decoded, &mut rgb_frame).context("Scaler failed")?;
+
+    let width = rgb_frame.width();
+    let height = rgb_frame.height();
+    let image_data = rgb_frame.data_mut(0);
+    let color_adjust = *settings.color_adjust.lock().unwrap();
+    color_adjust.apply(image_data);
+    let filter_type = CrtFilter::from_u8(crt_filter.load(Ordering::Relaxed));
+    if filter_type != CrtFilter::Off {
+        filters::apply_filter(filter_type, image_data, width, height);
+    }
+
+    Ok(Arc::new(egui::ColorImage::from_rgb([width as usize, height as usize], rgb_frame.data(0))))
+}
+
+/// Shared between the UI and the file playback thread for transport controls.
+#[derive(Debug, Default)]
+pub struct PlaybackControl {
+    pub paused: AtomicBool,
+    pub looping: AtomicBool,
+    /// Requested seek position in milliseconds, or -1 for none.
+    pub seek_request_ms: AtomicI64,
+    pub position_ms: AtomicU64,
+    pub duration_ms: AtomicU64,
+}
+
+impl PlaybackControl {
+    pub fn new() -> Self {
+        Self { seek_request_ms: AtomicI64::new(-1), looping: AtomicBool::new(true), ..Default::default() }
+    }
+
+    pub fn request_seek(&self, position_ms: u64) {
+        self.seek_request_ms.store(position_ms as i64, Ordering::Relaxed);
+    }
+}
+
+/// How often a looping still image is sent again, so recordings and the preview keep getting frames.
+const STILL_IMAGE_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Plays a local video or image file in real time through the same conversion as live capture.
+fn file_playback_main(
+    frame_sender: crossbeam_channel::Sender<Arc<egui::ColorImage>>,
+    stop_flag: Arc<AtomicBool>,
+    settings: &CaptureSettings,
+    crt_filter: Arc<AtomicU8>,
+) -> Result<()> {
+    let path = settings.input.as_str();
+    let playback = settings.playback.as_ref();
+    let mut ictx = ffmpeg_next::format::input(path).with_context(|| format!("Failed to open file '{}'", path))?;
+    let input = ictx.streams().best(ffmpeg_next::media::Type::Video).context("File has no video stream")?;
+    let video_stream_index = input.index();
+    let time_base = input.time_base();
+    let mut decoder = ffmpeg_next::codec::context::Context::from_parameters(input.parameters())
+        .and_then(|c| c.decoder().video())
+        .context("Failed to create video decoder")?;
+    let mut pending_info = Some(describe_stream(&ictx, &input, &decoder, settings.framerate));
+
+    // The container duration is in AV_TIME_BASE (microseconds).
+    playback.duration_ms.store((ictx.duration().max(0) / 1000) as u64, Ordering::Relaxed);
+    playback.position_ms.store(0, Ordering::Relaxed);
 
+    let pts_to_ms = |pts: i64| pts * 1000 * time_base.numerator() as i64 / time_base.denominator().max(1) as i64;
     let mut scaler = None;
+    // Wall-clock time corresponding to `clock_base_ms` of media time.
+    let mut clock_start = Instant::now();
+    let mut clock_base_ms: Option<i64> = None;
+    // The first frame since the last seek, kept in case the file turns out to be a still image.
+    let mut first_frame: Option<ffmpeg_next::frame::Video> = None;
+    let mut frames_since_seek = 0u32;
+    let mut decoded_from_start = true;
+    // A looping still image is decoded once and converted again for every frame sent.
+    let mut still_image: Option<ffmpeg_next::frame::Video> = None;
+
     while !stop_flag.load(Ordering::Relaxed) {
-        if let Ok(packet) = packet_rx.recv() {
-            decoder.send_packet(&packet).context("Failed to send packet to decoder")?;
-            let mut decoded = ffmpeg_next::frame::Video::empty();
-            while decoder.receive_frame(&mut decoded).is_ok() {
-                let frame_to_process = &decoded;
-
-                let scaler = scaler.get_or_insert_with(|| {
-                    ffmpeg_next::software::scaling::context::Context::get(
-                        frame_to_process.format(), 
-                        frame_to_process.width(), 
-                        frame_to_process.height(),
-                        Pixel::RGB24, decoded.width(), decoded.height(),
-                        ffmpeg_next::software::scaling::flag::Flags::FAST_BILINEAR,
-                    ).unwrap()
+        let seek_ms = playback.seek_request_ms.swap(-1, Ordering::Relaxed);
+        if seek_ms >= 0 && still_image.is_none() {
+            let target = seek_ms * 1000;
+            if let Err(e) = ictx.seek(target, ..=target) {
+                tracing::warn!("Seek failed: {}", e);
+            }
+            decoder.flush();
+            clock_base_ms = None;
+            frames_since_seek = 0;
+            decoded_from_start = seek_ms == 0;
+        }
+        if playback.paused.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(20));
+            clock_base_ms = None;
+            continue;
+        }
+
+        if let Some(frame) = &still_image {
+            thread::sleep(STILL_IMAGE_INTERVAL);
+            let image = frame_to_image(&mut scaler, frame, &crt_filter, settings)?;
+            if !send_file_frame(&frame_sender, image, &stop_flag) {
+                return Ok(());
+            }
+            continue;
+        }
+
+        let at_end = match ictx.packets().next() {
+            Some((stream, packet)) => {
+                if stream.index() != video_stream_index {
+                    continue;
+                }
+                decoder.send_packet(&packet).context("Failed to send packet to decoder")?;
+                false
+            }
+            None => {
+                // Without this the decoder keeps the last frames of the file to itself.
+                let _ = decoder.send_eof();
+                true
+            }
+        };
+
+        let mut decoded = ffmpeg_next::frame::Video::empty();
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            frames_since_seek += 1;
+            if frames_since_seek == 1 {
+                first_frame = Some(decoded.clone());
+            }
+            if let Some(pts_ms) = decoded.timestamp().map(pts_to_ms) {
+                let base = *clock_base_ms.get_or_insert_with(|| {
+                    clock_start = Instant::now();
+                    pts_ms
                 });
-                let mut rgb_frame = ffmpeg_next::frame::Video::empty();
-                scaler.run(frame_to_process, &mut rgb_frame).context("Scaler failed")?;
-                
-                let width = rgb_frame.width();
-                let height = rgb_frame.height();
-                let image_data = rgb_frame.data_mut(0);
-                let filter_type = CrtFilter::from_u8(crt_filter.load(Ordering::Relaxed));
-                if filter_type != CrtFilter::Off {
-                    filters::apply_filter(filter_type, image_data, width, height);
+                let due = clock_start + Duration::from_millis((pts_ms - base).max(0) as u64);
+                if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                    thread::sleep(wait);
                 }
+                playback.position_ms.store(pts_ms.max(0) as u64, Ordering::Relaxed);
+            }
+            report_stream_info(&mut pending_info, &decoded, &settings.info_sender);
+            let image = frame_to_image(&mut scaler, &decoded, &crt_filter, settings)?;
+            if !send_file_frame(&frame_sender, image, &stop_flag) {
+                return Ok(());
+            }
+        }
 
-                let image = Arc::new(egui::ColorImage::from_rgb([width as usize, height as usize], rgb_frame.data(0)));
+        if at_end {
+            if !playback.looping.load(Ordering::Relaxed) {
+                // Hold the last frame until the user stops or seeks.
+                playback.paused.store(true, Ordering::Relaxed);
+            } else if decoded_from_start && frames_since_seek == 1 {
+                still_image = first_frame.take();
+            } else {
+                playback.request_seek(0);
+            }
+        }
+    }
+    tracing::info!("File playback thread finished.");
+    Ok(())
+}
 
-                if frame_sender.try_send(image).is_err() {
-                    break;
+/// Unlike live capture, files must not drop frames, so this waits until the UI takes the frame
+/// (but keeps an eye on the stop flag, since the UI stops receiving while it joins us).
+/// Returns false once playback should end.
+fn send_file_frame(frame_sender: &crossbeam_channel::Sender<Arc<egui::ColorImage>>, mut image: Arc<egui::ColorImage>, stop_flag: &AtomicBool) -> bool {
+    loop {
+        match frame_sender.send_timeout(image, Duration::from_millis(50)) {
+            Ok(()) => return true,
+            Err(crossbeam_channel::SendTimeoutError::Timeout(pending)) => {
+                if stop_flag.load(Ordering::Relaxed) {
+                    return false;
                 }
+                image = pending;
             }
+            Err(crossbeam_channel::SendTimeoutError::Disconnected(_)) => return false,
         }
     }
-    tracing::info!("Video thread finished.");
-    Ok(())
-}
\ No newline at end of file
+}
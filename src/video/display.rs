@@ -0,0 +1,70 @@
+/// Both settings are read once at startup, before the GL context exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Waits for vsync: no tearing, up to a frame of extra latency.
+    Fifo,
+    /// Presents right away: lowest latency, may tear.
+    Immediate,
+}
+
+impl PresentMode {
+    pub fn from_u8(val: u8) -> Self {
+        match val {
+            1 => PresentMode::Immediate,
+            _ => PresentMode::Fifo,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            PresentMode::Fifo => "VSync (FIFO)",
+            PresentMode::Immediate => "Immediate (may tear)",
+        }
+    }
+
+    pub fn vsync(&self) -> bool {
+        *self == PresentMode::Fifo
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuPreference {
+    /// Whatever the desktop renders on, usually the integrated GPU on hybrid laptops.
+    Default,
+    /// The discrete GPU through PRIME render offload.
+    Discrete,
+}
+
+impl GpuPreference {
+    pub fn from_u8(val: u8) -> Self {
+        match val {
+            1 => GpuPreference::Discrete,
+            _ => GpuPreference::Default,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            GpuPreference::Default => "Default",
+            GpuPreference::Discrete => "Discrete (PRIME offload)",
+        }
+    }
+
+    /// GL has no API to pick a GPU, so this sets the PRIME offload variables Mesa and the
+    /// NVIDIA driver read when the context is created. Variables set by the user take priority.
+    pub fn apply_to_environment(&self) {
+        if *self != GpuPreference::Discrete {
+            return;
+        }
+        let vars = [("DRI_PRIME", "1"), ("__NV_PRIME_RENDER_OFFLOAD", "1")];
+        for (name, value) in vars {
+            if std::env::var_os(name).is_none() {
+                std::env::set_var(name, value);
+            }
+        }
+        // Only the NVIDIA driver provides this GLX vendor; selecting it elsewhere breaks GLX.
+        if std::path::Path::new("/proc/driver/nvidia").exists() && std::env::var_os("__GLX_VENDOR_LIBRARY_NAME").is_none() {
+            std::env::set_var("__GLX_VENDOR_LIBRARY_NAME", "nvidia");
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use ffmpeg_next::ffi;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+
+/// Lines kept for the in-app log panel.
+const MAX_LINES: usize = 500;
+
+// av_log levels, from libavutil/log.h.
+const AV_LOG_ERROR: c_int = 16;
+const AV_LOG_WARNING: c_int = 24;
+const AV_LOG_INFO: c_int = 32;
+const AV_LOG_VERBOSE: c_int = 40;
+const AV_LOG_DEBUG: c_int = 48;
+
+#[cfg(target_arch = "x86_64")]
+type VaList = *mut ffi::__va_list_tag;
+#[cfg(not(target_arch = "x86_64"))]
+type VaList = ffi::va_list;
+
+static MAX_LEVEL: AtomicI32 = AtomicI32::new(AV_LOG_WARNING);
+static LINES: Mutex<VecDeque<(FfmpegLogLevel, String)>> = Mutex::new(VecDeque::new());
+
+thread_local! {
+    // av_log_format_line2 uses this to only prefix the first part of a line split over several calls.
+    static PRINT_PREFIX: Cell<c_int> = const { Cell::new(1) };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum FfmpegLogLevel {
+    Error = 0,
+    Warning = 1,
+    Info = 2,
+    Verbose = 3,
+    Debug = 4,
+}
+
+impl FfmpegLogLevel {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => FfmpegLogLevel::Error,
+            2 => FfmpegLogLevel::Info,
+            3 => FfmpegLogLevel::Verbose,
+            4 => FfmpegLogLevel::Debug,
+            _ => FfmpegLogLevel::Warning,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            FfmpegLogLevel::Error => "Error",
+            FfmpegLogLevel::Warning => "Warning",
+            FfmpegLogLevel::Info => "Info",
+            FfmpegLogLevel::Verbose => "Verbose",
+            FfmpegLogLevel::Debug => "Debug",
+        }
+    }
+
+    fn av_level(&self) -> c_int {
+        match self {
+            FfmpegLogLevel::Error => AV_LOG_ERROR,
+            FfmpegLogLevel::Warning => AV_LOG_WARNING,
+            FfmpegLogLevel::Info => AV_LOG_INFO,
+            FfmpegLogLevel::Verbose => AV_LOG_VERBOSE,
+            FfmpegLogLevel::Debug => AV_LOG_DEBUG,
+        }
+    }
+
+    fn from_av_level(level: c_int) -> Self {
+        match level {
+            l if l <= AV_LOG_ERROR => FfmpegLogLevel::Error,
+            l if l <= AV_LOG_WARNING => FfmpegLogLevel::Warning,
+            l if l <= AV_LOG_INFO => FfmpegLogLevel::Info,
+            l if l <= AV_LOG_VERBOSE => FfmpegLogLevel::Verbose,
+            _ => FfmpegLogLevel::Debug,
+        }
+    }
+}
+
+/// Replaces ffmpeg's stderr logging with the tracing log and the in-app log panel.
+pub fn install() {
+    unsafe { ffi::av_log_set_callback(Some(log_callback)) };
+}
+
+pub fn set_level(level: FfmpegLogLevel) {
+    MAX_LEVEL.store(level.av_level(), Ordering::Relaxed);
+}
+
+/// A copy of the most recent lines, oldest first.
+pub fn recent_lines() -> Vec<(FfmpegLogLevel, String)> {
+    LINES.lock().unwrap().iter().cloned().collect()
+}
+
+pub fn clear() {
+    LINES.lock().unwrap().clear();
+}
+
+unsafe extern "C" fn log_callback(avcl: *mut c_void, level: c_int, fmt: *const c_char, vl: VaList) {
+    if level > MAX_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut buf = [0 as c_char; 1024];
+    let mut print_prefix = PRINT_PREFIX.with(|p| p.get());
+    ffi::av_log_format_line2(avcl, level, fmt, vl, buf.as_mut_ptr(), buf.len() as c_int, &mut print_prefix);
+    PRINT_PREFIX.with(|p| p.set(print_prefix));
+
+    let line = std::ffi::CStr::from_ptr(buf.as_ptr()).to_string_lossy();
+    let line = line.trim_end();
+    if line.is_empty() {
+        return;
+    }
+    let level = FfmpegLogLevel::from_av_level(level);
+    match level {
+        FfmpegLogLevel::Error => tracing::error!(target: "ffmpeg", "{}", line),
+        FfmpegLogLevel::Warning => tracing::warn!(target: "ffmpeg", "{}", line),
+        FfmpegLogLevel::Info => tracing::info!(target: "ffmpeg", "{}", line),
+        _ => tracing::debug!(target: "ffmpeg", "{}", line),
+    }
+    // Never block or panic inside a C callback.
+    if let Ok(mut lines) = LINES.try_lock() {
+        if lines.len() == MAX_LINES {
+            lines.pop_front();
+        }
+        lines.push_back((level, line.to_string()));
+    }
+}
@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// Parameters of the filter chain, shared by the controls, the config file, looks and the
+/// renderer. Missing fields take their defaults, so files from older versions still load.
+/// Stored as a table, so it has to come after all plain values in the structs containing it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    // Lottes
+    pub hard_scan: f32,
+    pub warp_x: f32,
+    pub warp_y: f32,
+    /// Mask type, 0 (none) to 4.
+    pub shadow_mask: f32,
+    pub brightboost: f32,
+    pub hard_bloom_pix: f32,
+    pub hard_bloom_scan: f32,
+    pub bloom_amount: f32,
+    pub shape: f32,
+    pub hard_pix: f32,
+    pub glare_enabled: bool,
+    pub glare_intensity: f32,
+    /// sRGB color of the room light reflected by the screen glass.
+    pub glare_ambient_color: [f32; 3],
+
+    /// Pixelate into a framebuffer the size of the pixel grid, so the later passes work on
+    /// far fewer pixels. They then see the image as the low resolution source it imitates.
+    pub pixelate_low_res: bool,
+
+    // Contrast adaptive sharpening
+    pub sharpen_enabled: bool,
+    pub sharpen_strength: f32,
+
+    /// Output pixels per source pixel for the xBR upscalers.
+    pub upscale_factor: u32,
+
+    pub phosphor_enabled: bool,
+    /// Share of the previous frame still lit after 1/60 s.
+    pub phosphor_persistence: f32,
+
+    /// Color correction, applied to the decoded frames before everything else. See `ColorAdjust`.
+    pub brightness: f32,
+    pub contrast: f32,
+    pub gamma: f32,
+    /// Color temperature of the simulated tube's white, in kelvin. 6500 (D65) leaves colors
+    /// alone; Japanese sets were usually set to 9300 (D93). See `color::white_point_matrix`.
+    pub white_point: f32,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            hard_scan: -8.0,
+            warp_x: 0.031,
+            warp_y: 0.041,
+            shadow_mask: 3.0,
+            brightboost: 1.0,
+            hard_bloom_pix: -1.5,
+            hard_bloom_scan: -2.0,
+            bloom_amount: 0.15,
+            shape: 2.0,
+            hard_pix: -3.0,
+            glare_enabled: false,
+            glare_intensity: 0.3,
+            glare_ambient_color: [1.0, 0.9, 0.75],
+            pixelate_low_res: false,
+            sharpen_enabled: false,
+            sharpen_strength: 0.5,
+            upscale_factor: 2,
+            phosphor_enabled: false,
+            phosphor_persistence: 0.5,
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            white_point: super::color::D65,
+        }
+    }
+}
+
+impl FilterSettings {
+    pub const HARD_SCAN: RangeInclusive<f32> = -20.0..=-1.0;
+    pub const WARP: RangeInclusive<f32> = 0.0..=0.125;
+    pub const SHADOW_MASK: RangeInclusive<f32> = 0.0..=4.0;
+    pub const BRIGHTBOOST: RangeInclusive<f32> = 0.0..=2.0;
+    pub const HARD_BLOOM_PIX: RangeInclusive<f32> = -4.0..=-0.5;
+    pub const HARD_BLOOM_SCAN: RangeInclusive<f32> = -4.0..=-1.0;
+    pub const BLOOM_AMOUNT: RangeInclusive<f32> = 0.0..=1.0;
+    pub const SHAPE: RangeInclusive<f32> = 0.0..=10.0;
+    pub const HARD_PIX: RangeInclusive<f32> = -20.0..=0.0;
+    pub const GLARE_INTENSITY: RangeInclusive<f32> = 0.0..=1.0;
+    pub const SHARPEN_STRENGTH: RangeInclusive<f32> = 0.0..=1.0;
+    pub const UPSCALE_FACTOR: RangeInclusive<u32> = 2..=4;
+    pub const PHOSPHOR_PERSISTENCE: RangeInclusive<f32> = 0.0..=0.95;
+    pub const BRIGHTNESS: RangeInclusive<f32> = -0.5..=0.5;
+    pub const CONTRAST: RangeInclusive<f32> = 0.5..=2.0;
+    pub const GAMMA: RangeInclusive<f32> = 0.4..=2.5;
+    pub const WHITE_POINT: RangeInclusive<f32> = 5000.0..=11000.0;
+
+    /// Brings every value into the range its control allows, e.g. after loading a hand-edited
+    /// file. Values that aren't numbers at all go back to their defaults.
+    pub fn validated(mut self) -> Self {
+        let defaults = Self::default();
+        self.hard_scan = clamp_or(self.hard_scan, Self::HARD_SCAN, defaults.hard_scan);
+        self.warp_x = clamp_or(self.warp_x, Self::WARP, defaults.warp_x);
+        self.warp_y = clamp_or(self.warp_y, Self::WARP, defaults.warp_y);
+        self.shadow_mask = clamp_or(self.shadow_mask, Self::SHADOW_MASK, defaults.shadow_mask).round();
+        self.brightboost = clamp_or(self.brightboost, Self::BRIGHTBOOST, defaults.brightboost);
+        self.hard_bloom_pix = clamp_or(self.hard_bloom_pix, Self::HARD_BLOOM_PIX, defaults.hard_bloom_pix);
+        self.hard_bloom_scan = clamp_or(self.hard_bloom_scan, Self::HARD_BLOOM_SCAN, defaults.hard_bloom_scan);
+        self.bloom_amount = clamp_or(self.bloom_amount, Self::BLOOM_AMOUNT, defaults.bloom_amount);
+        self.shape = clamp_or(self.shape, Self::SHAPE, defaults.shape);
+        self.hard_pix = clamp_or(self.hard_pix, Self::HARD_PIX, defaults.hard_pix);
+        self.glare_intensity = clamp_or(self.glare_intensity, Self::GLARE_INTENSITY, defaults.glare_intensity);
+        for (channel, default) in self.glare_ambient_color.iter_mut().zip(defaults.glare_ambient_color) {
+            *channel = clamp_or(*channel, 0.0..=1.0, default);
+        }
+        self.sharpen_strength = clamp_or(self.sharpen_strength, Self::SHARPEN_STRENGTH, defaults.sharpen_strength);
+        self.upscale_factor = self.upscale_factor.clamp(*Self::UPSCALE_FACTOR.start(), *Self::UPSCALE_FACTOR.end());
+        self.phosphor_persistence = clamp_or(self.phosphor_persistence, Self::PHOSPHOR_PERSISTENCE, defaults.phosphor_persistence);
+        self.brightness = clamp_or(self.brightness, Self::BRIGHTNESS, defaults.brightness);
+        self.contrast = clamp_or(self.contrast, Self::CONTRAST, defaults.contrast);
+        self.gamma = clamp_or(self.gamma, Self::GAMMA, defaults.gamma);
+        self.white_point = clamp_or(self.white_point, Self::WHITE_POINT, defaults.white_point);
+        self
+    }
+
+    pub fn color_adjust(&self) -> super::color::ColorAdjust {
+        super::color::ColorAdjust { brightness: self.brightness, contrast: self.contrast, gamma: self.gamma }
+    }
+
+    /// Takes over a calibration's recommendation, limited to what the controls allow.
+    pub fn set_color_adjust(&mut self, adjust: super::color::ColorAdjust) {
+        self.brightness = adjust.brightness.clamp(*Self::BRIGHTNESS.start(), *Self::BRIGHTNESS.end());
+        self.contrast = adjust.contrast.clamp(*Self::CONTRAST.start(), *Self::CONTRAST.end());
+        self.gamma = adjust.gamma.clamp(*Self::GAMMA.start(), *Self::GAMMA.end());
+    }
+
+    /// Takes over the Lottes parameters of `other`, leaving the other filters alone. Resets
+    /// them when `other` holds the user's defaults, and saves them as defaults the other way round.
+    pub fn copy_lottes(&mut self, other: &Self) {
+        self.hard_scan = other.hard_scan;
+        self.warp_x = other.warp_x;
+        self.warp_y = other.warp_y;
+        self.shadow_mask = other.shadow_mask;
+        self.brightboost = other.brightboost;
+        self.hard_bloom_pix = other.hard_bloom_pix;
+        self.hard_bloom_scan = other.hard_bloom_scan;
+        self.bloom_amount = other.bloom_amount;
+        self.shape = other.shape;
+        self.hard_pix = other.hard_pix;
+    }
+
+    pub fn lottes_equals(&self, other: &Self) -> bool {
+        let mut copy = self.clone();
+        copy.copy_lottes(other);
+        copy == *self
+    }
+}
+
+fn clamp_or(value: f32, range: RangeInclusive<f32>, default: f32) -> f32 {
+    if value.is_finite() { value.clamp(*range.start(), *range.end()) } else { default }
+}
+
+/// The Lottes parameters as separate `crt_*` keys, the way config files and looks stored them
+/// before `FilterSettings`. Only read, to carry those values over.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct LegacyFilterFields {
+    crt_hard_scan: Option<f32>,
+    crt_warp_x: Option<f32>,
+    crt_warp_y: Option<f32>,
+    crt_shadow_mask: Option<f32>,
+    crt_brightboost: Option<f32>,
+    crt_hard_bloom_pix: Option<f32>,
+    crt_hard_bloom_scan: Option<f32>,
+    crt_bloom_amount: Option<f32>,
+    crt_shape: Option<f32>,
+    crt_hard_pix: Option<f32>,
+}
+
+impl LegacyFilterFields {
+    /// The settings the old keys describe, or `None` if there weren't any.
+    pub fn to_settings(&self) -> Option<FilterSettings> {
+        if *self == Self::default() {
+            return None;
+        }
+        let mut settings = FilterSettings::default();
+        let fields = [
+            (self.crt_hard_scan, &mut settings.hard_scan),
+            (self.crt_warp_x, &mut settings.warp_x),
+            (self.crt_warp_y, &mut settings.warp_y),
+            (self.crt_shadow_mask, &mut settings.shadow_mask),
+            (self.crt_brightboost, &mut settings.brightboost),
+            (self.crt_hard_bloom_pix, &mut settings.hard_bloom_pix),
+            (self.crt_hard_bloom_scan, &mut settings.hard_bloom_scan),
+            (self.crt_bloom_amount, &mut settings.bloom_amount),
+            (self.crt_shape, &mut settings.shape),
+            (self.crt_hard_pix, &mut settings.hard_pix),
+        ];
+        for (value, field) in fields {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        Some(settings.validated())
+    }
+}
@@ -1,7 +1,27 @@
 use eframe::glow::{self, HasContext};
 use eframe::{egui, egui_glow};
 
+use crate::devices::filter_type::BfiMode;
+use crate::video::color::white_point_matrix;
+use crate::video::filter_settings::FilterSettings;
+use crate::video::texture::SourceRegion;
 use std::num::NonZero;
+use std::time::Instant;
+
+/// Index of the framebuffer the filter chain renders into for burned-in recording.
+const CAPTURE_TARGET: usize = 5;
+/// Index of the framebuffer the sharpening pass renders into.
+const SHARPEN_TARGET: usize = 6;
+/// Indices of the two framebuffers the phosphor pass alternates between, one holding the
+/// previous output while the other is drawn into.
+const PHOSPHOR_TARGETS: [usize; 2] = [7, 8];
+/// Longest gap between frames the phosphor decay accounts for, so a stall doesn't leave a
+/// frozen trail and the first frame after it clears the history.
+const MAX_PHOSPHOR_STEP: f32 = 0.25;
+/// The pixelate filter simulates 16:9 480p.
+const PIXELATE_GRID: (f32, f32) = (854.0, 480.0);
+/// Framebuffers allocated at the source resolution, see `setup_framebuffers`.
+const PASS_TARGETS: usize = 9;
 
 const VS_SRC: &str = r#"#version 330 core
     layout(location = 0) in vec2 a_pos;
@@ -34,6 +54,158 @@ const FS_PIXELATE: &str = r#"#version 330 core
         out_color = texture(video_texture, pixelated_uv);
     }"#;
 
+// Copies the display crop out of the video texture, so the filters only ever see the cropped
+// frame. Flipped like the pixelate pass, so the result keeps the video texture's orientation.
+const FS_CROP: &str = r#"#version 330 core
+    in vec2 v_tc;
+    out vec4 out_color;
+    uniform sampler2D video_texture;
+    uniform vec4 sourceRect; // Offset and size of the crop in texture coordinates
+
+    void main() {
+        vec2 flipped_tc = vec2(v_tc.x, 1.0 - v_tc.y);
+        out_color = texture(video_texture, sourceRect.xy + flipped_tc * sourceRect.zw);
+    }"#;
+
+// Contrast adaptive sharpening, after AMD FidelityFX CAS (without its scaling).
+// Sharpens less where the neighbourhood already has high contrast, so edges don't ring.
+const FS_SHARPEN: &str = r#"#version 330 core
+    in vec2 v_tc;
+    out vec4 out_color;
+    uniform sampler2D video_texture;
+    uniform float sharpness; // 0.0 (subtle) to 1.0 (strong)
+
+    vec3 Tap(ivec2 pos, ivec2 offset, ivec2 max_pos) {
+        return texelFetch(video_texture, clamp(pos + offset, ivec2(0), max_pos), 0).rgb;
+    }
+
+    void main() {
+        ivec2 size = textureSize(video_texture, 0);
+        // Flipped like the pixelate pass, so the result keeps the video texture's orientation.
+        ivec2 pos = ivec2(vec2(v_tc.x, 1.0 - v_tc.y) * vec2(size));
+        ivec2 max_pos = size - 1;
+        // a b c
+        // d e f
+        // g h i
+        vec3 a = Tap(pos, ivec2(-1, -1), max_pos);
+        vec3 b = Tap(pos, ivec2( 0, -1), max_pos);
+        vec3 c = Tap(pos, ivec2( 1, -1), max_pos);
+        vec3 d = Tap(pos, ivec2(-1,  0), max_pos);
+        vec3 e = Tap(pos, ivec2( 0,  0), max_pos);
+        vec3 f = Tap(pos, ivec2( 1,  0), max_pos);
+        vec3 g = Tap(pos, ivec2(-1,  1), max_pos);
+        vec3 h = Tap(pos, ivec2( 0,  1), max_pos);
+        vec3 i = Tap(pos, ivec2( 1,  1), max_pos);
+
+        // Soft minimum and maximum: the cross plus the whole 3x3 block.
+        vec3 mn = min(min(min(d, e), min(f, b)), h);
+        mn += min(mn, min(min(a, c), min(g, i)));
+        vec3 mx = max(max(max(d, e), max(f, b)), h);
+        mx += max(mx, max(max(a, c), max(g, i)));
+
+        vec3 amp = sqrt(clamp(min(mn, 2.0 - mx) / max(mx, vec3(1e-5)), 0.0, 1.0));
+        float peak = -1.0 / mix(8.0, 5.0, sharpness);
+        vec3 w = amp * peak;
+        vec3 color = (b * w + d * w + f * w + h * w + e) / (1.0 + 4.0 * w);
+        out_color = vec4(clamp(color, 0.0, 1.0), 1.0);
+    }"#;
+
+// Edge-directed pixel art upscaling in the style of xBR level 2. Each output pixel looks at the
+// corner of its source texel it's closest to, and cuts that corner off along a 45 degree, shallow
+// or steep edge when the 5x5 neighbourhood says one runs through it. `hardEdges` picks whole
+// source colors instead of blending them, for a crisper result.
+const FS_UPSCALE: &str = r#"#version 330 core
+    in vec2 v_tc;
+    out vec4 out_color;
+    uniform sampler2D video_texture;
+    uniform float scale; // Output pixels per source pixel
+    uniform float hardEdges; // 1.0 = no blending across the cut
+
+    ivec2 pos;
+    ivec2 dir;
+    ivec2 max_pos;
+
+    // Neighbour at (x, y), mirrored so the corner being looked at is always bottom-right.
+    vec3 Tap(int x, int y) {
+        return texelFetch(video_texture, clamp(pos + ivec2(x, y) * dir, ivec2(0), max_pos), 0).rgb;
+    }
+
+    float Dist(vec3 a, vec3 b) {
+        return dot(abs(a - b), vec3(0.299, 0.587, 0.114));
+    }
+
+    bool Same(vec3 a, vec3 b) {
+        return Dist(a, b) < 0.02;
+    }
+
+    float Cut(float distance) {
+        // About one output pixel wide, or a hard step.
+        float aa = hardEdges > 0.5 ? 0.0 : 0.5 / scale;
+        return aa > 0.0 ? smoothstep(-aa, aa, distance) : step(0.0, distance);
+    }
+
+    void main() {
+        ivec2 size = textureSize(video_texture, 0);
+        max_pos = size - 1;
+        // Flipped like the pixelate pass, so the result keeps the video texture's orientation.
+        vec2 src = vec2(v_tc.x, 1.0 - v_tc.y) * vec2(size);
+        pos = ivec2(src);
+        vec2 fp = fract(src);
+        dir = ivec2(fp.x < 0.5 ? -1 : 1, fp.y < 0.5 ? -1 : 1);
+        // Position within the texel, with the corner being looked at at (1, 1).
+        vec2 p = mix(1.0 - fp, fp, vec2(greaterThan(dir, ivec2(0))));
+
+        //    A1 B1 C1
+        // A0 A  B  C  C4
+        // D0 D  E  F  F4
+        // G0 G  H  I  I4
+        //    G5 H5 I5
+        vec3 B = Tap(0, -1), C = Tap(1, -1), D = Tap(-1, 0), E = Tap(0, 0), F = Tap(1, 0);
+        vec3 G = Tap(-1, 1), H = Tap(0, 1), I = Tap(1, 1);
+        vec3 F4 = Tap(2, 0), I4 = Tap(2, 1), H5 = Tap(0, 2), I5 = Tap(1, 2);
+
+        // Edge running along H-F (across the E-I diagonal) versus along E-I.
+        float e = Dist(E, C) + Dist(E, G) + Dist(I, F4) + Dist(I, H5) + 4.0 * Dist(H, F);
+        float i = Dist(H, D) + Dist(H, I5) + Dist(F, I4) + Dist(F, B) + 4.0 * Dist(E, I);
+
+        vec3 color = E;
+        if (e < i && !Same(E, F) && !Same(E, H)) {
+            vec3 px = Dist(E, F) <= Dist(E, H) ? F : H;
+            float ke = Dist(F, G);
+            float ki = Dist(H, C);
+            float coverage;
+            if (2.0 * ke <= ki && !Same(E, G) && !Same(D, G)) {
+                // Shallow edge continuing through G: x + 2y = 2
+                coverage = Cut((p.x + 2.0 * p.y - 2.0) / sqrt(5.0));
+            } else if (ke >= 2.0 * ki && !Same(E, C) && !Same(B, C)) {
+                // Steep edge continuing through C: 2x + y = 2
+                coverage = Cut((2.0 * p.x + p.y - 2.0) / sqrt(5.0));
+            } else {
+                // 45 degree edge: x + y = 1.5
+                coverage = Cut((p.x + p.y - 1.5) / sqrt(2.0));
+            }
+            color = mix(E, px, coverage);
+        }
+        out_color = vec4(color, 1.0);
+    }"#;
+
+// Phosphor persistence: the previous output fades instead of disappearing, leaving trails
+// behind moving bright objects like a CRT's phosphors do.
+const FS_PHOSPHOR: &str = r#"#version 330 core
+    in vec2 v_tc;
+    out vec4 out_color;
+    uniform sampler2D video_texture;
+    uniform sampler2D history_texture;
+    uniform float decay; // Share of the previous output still lit
+
+    void main() {
+        // Flipped like the pixelate pass, so the result keeps the video texture's orientation.
+        ivec2 pos = ivec2(vec2(v_tc.x, 1.0 - v_tc.y) * vec2(textureSize(video_texture, 0)));
+        vec3 current = texelFetch(video_texture, pos, 0).rgb;
+        vec3 previous = texelFetch(history_texture, pos, 0).rgb;
+        out_color = vec4(max(current, previous * decay), 1.0);
+    }"#;
+
 // Simple passthrough shader for drawing a texture to the screen
 const FS_PASSTHROUGH: &str = r#"#version 330 core
     in vec2 v_tc;
@@ -41,6 +213,9 @@ const FS_PASSTHROUGH: &str = r#"#version 330 core
     uniform sampler2D video_texture;
     uniform vec2 videoResolution;
     uniform vec2 outputResolution;
+    uniform float pixelAspect; // Width of a source pixel relative to its height
+    uniform mat3 whitePoint; // Color temperature, applied in linear light
+    uniform vec4 sourceRect; // Shown part of the texture: offset and size in texture coordinates
     
     // Convert from linear to sRGB color space
     float ToSrgb1(float c) {
@@ -51,7 +226,7 @@ const FS_PASSTHROUGH: &str = r#"#version 330 core
     }
 
     void main() {
-        float video_aspect = videoResolution.x / videoResolution.y;
+        float video_aspect = (videoResolution.x * pixelAspect) / videoResolution.y;
         float output_aspect = outputResolution.x / outputResolution.y;
 
         vec2 scale = vec2(1.0, 1.0);
@@ -66,10 +241,33 @@ const FS_PASSTHROUGH: &str = r#"#version 330 core
         if (centered_tc.x < 0.0 || centered_tc.x > 1.0 || centered_tc.y < 0.0 || centered_tc.y > 1.0) {
             out_color = vec4(0.0, 0.0, 0.0, 1.0);
         } else {
-            vec3 linear_color = texture(video_texture, centered_tc).rgb;
+            vec3 linear_color = whitePoint * texture(video_texture, sourceRect.xy + centered_tc * sourceRect.zw).rgb;
             out_color = vec4(ToSrgb(linear_color), 1.0);
         }
     }"#;
+// Black frame insertion / rolling scan overlay, drawn over the finished image with blending.
+// The cycle is advanced once per display refresh, so the pass stays in sync with vsync.
+const FS_BFI: &str = r#"#version 330 core
+    in vec2 v_tc;
+    out vec4 out_color;
+    uniform float bfiMode; // 1 = black frame insertion, 2 = rolling scan
+    uniform float bfiStep; // Current refresh within the cycle
+    uniform float bfiCycle; // Display refreshes per lit frame
+
+    void main() {
+        bool lit;
+        if (bfiMode == 1.0) {
+            // Only the first refresh of each cycle shows the image.
+            lit = bfiStep == 0.0;
+        } else {
+            // Only one horizontal band is lit per refresh, rolling from top to bottom.
+            lit = floor(v_tc.y * bfiCycle) == bfiStep;
+        }
+        // Premultiplied alpha: an opaque black pixel blanks the image underneath.
+        out_color = lit ? vec4(0.0) : vec4(0.0, 0.0, 0.0, 1.0);
+    }
+"#;
+
 // Lottes Pass 0: Horizontal blur for bloom
 const FS_PASS0: &str = r#"#version 330 core
     in vec2 v_tc;
@@ -181,12 +379,16 @@ const FS_FINAL: &str = r#"#version 330 core
 
     uniform vec2 videoResolution;
     uniform vec2 outputResolution;
+    uniform float pixelAspect;
 
     uniform float warpX;
     uniform float warpY;
     uniform float shadowMask; // 0-4
     uniform float brightboost;
     uniform float bloomAmount;
+    uniform float glareIntensity; // 0 = no glass reflection
+    uniform vec3 ambientColor; // Color of the room light reflected by the glass, in sRGB
+    uniform mat3 whitePoint; // Color temperature of the tube, applied in linear light
 
     float ToSrgb1(float c) {
         return (c < 0.0031308 ? c * 12.92 : 1.055 * pow(c, 0.41666) - 0.055);
@@ -238,9 +440,19 @@ const FS_FINAL: &str = r#"#version 330 core
         return mask;
     }
 
+    // Room light reflected by the curved glass, with pos running from -1 to 1 across the screen.
+    vec3 Glare(vec2 pos) {
+        // Grazing angles reflect more, so the edges and corners catch more light.
+        float fresnel = 0.08 + 0.25 * pow(clamp(dot(pos, pos) * 0.5, 0.0, 1.0), 1.5);
+        // A soft highlight from a light source above and to the left of the screen.
+        vec2 spot = (pos - vec2(-0.45, -0.55)) * vec2(1.0, 1.6);
+        float highlight = 0.6 * exp(-dot(spot, spot) * 3.0);
+        return pow(ambientColor, vec3(2.2)) * glareIntensity * (fresnel + highlight);
+    }
+
     void main() {
-        // Calculate aspect ratios
-        float video_aspect = videoResolution.x / videoResolution.y;
+        // Calculate aspect ratios, stretching the width by the source's pixel aspect ratio
+        float video_aspect = (videoResolution.x * pixelAspect) / videoResolution.y;
         float output_aspect = outputResolution.x / outputResolution.y;
 
         // Determine scale and offset to letterbox/pillarbox the video
@@ -270,31 +482,74 @@ const FS_FINAL: &str = r#"#version 330 core
         }
 
         final_color *= brightboost;
+        // The room's reflection keeps its own color, so this comes before the glare.
+        final_color = whitePoint * final_color;
+
+        if (glareIntensity > 0.0) {
+            final_color += Glare(warped_pos * 2.0 - 1.0);
+        }
 
         out_color = vec4(ToSrgb(final_color), 1.0);
     }
 "#;
 
+/// State of the GL context the renderer was created in, see `CrtFilterRenderer::check_context`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlContextStatus {
+    Ok,
+    /// Lost (e.g. a driver reset) and not usable until it's reset.
+    Lost,
+    /// Replaced by a new context; the renderer was rebuilt in it.
+    Rebuilt,
+}
+
+/// Pixel art upscalers, run instead of the Lottes passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upscaler {
+    /// Blends along detected edges.
+    Xbr,
+    /// xBR that only ever outputs source colors.
+    XbrHard,
+}
+
 pub struct CrtFilterRenderer {
     passthrough_prog: glow::Program,
+    crop_prog: glow::Program,
     pixelate_prog: glow::Program,
+    sharpen_prog: glow::Program,
+    upscale_prog: glow::Program,
+    phosphor_prog: glow::Program,
     pass0_prog: glow::Program,
     pass1_prog: glow::Program,
     pass2_prog: glow::Program,
     pass3_prog: glow::Program,
     final_prog: glow::Program,
+    bfi_prog: glow::Program,
 
-    fbos: [glow::Framebuffer; 5],
-    pass_textures: [glow::Texture; 5],
+    fbos: [glow::Framebuffer; PASS_TARGETS],
+    pass_textures: [glow::Texture; PASS_TARGETS],
     vertex_array: glow::VertexArray,
     vbo: glow::Buffer,
 
     // Passthrough uniforms
     p_passthrough_video_res_loc: glow::UniformLocation,
     p_passthrough_output_res_loc: glow::UniformLocation,
+    p_passthrough_pixel_aspect_loc: glow::UniformLocation,
+    p_passthrough_white_point_loc: glow::UniformLocation,
+    p_passthrough_source_rect_loc: glow::UniformLocation,
+
+    // Crop uniforms
+    p_crop_source_rect_loc: glow::UniformLocation,
 
     // Pixelate uniforms
     p_pixelate_target_res_loc: glow::UniformLocation,
+    // Sharpen uniforms
+    p_sharpen_sharpness_loc: glow::UniformLocation,
+    // Upscale uniforms
+    p_upscale_scale_loc: glow::UniformLocation,
+    p_upscale_hard_edges_loc: glow::UniformLocation,
+    // Phosphor uniforms
+    p_phosphor_decay_loc: glow::UniformLocation,
     // Pass 0 uniforms
     p0_hard_bloom_pix_loc: glow::UniformLocation,
 
@@ -311,29 +566,69 @@ pub struct CrtFilterRenderer {
     // Final pass uniforms
     final_video_res_loc: glow::UniformLocation,
     final_output_res_loc: glow::UniformLocation,
+    final_pixel_aspect_loc: glow::UniformLocation,
     final_warp_x_loc: glow::UniformLocation,
     final_warp_y_loc: glow::UniformLocation,
     final_shadow_mask_loc: glow::UniformLocation,
     final_brightboost_loc: glow::UniformLocation,
     final_bloom_amount_loc: glow::UniformLocation,
+    final_glare_intensity_loc: glow::UniformLocation,
+    final_ambient_color_loc: glow::UniformLocation,
+    final_white_point_loc: glow::UniformLocation,
+
+    // BFI uniforms
+    bfi_mode_loc: glow::UniformLocation,
+    bfi_step_loc: glow::UniformLocation,
+    bfi_cycle_loc: glow::UniformLocation,
 
     last_size: (u32, u32),
+    /// The upscalers render at a multiple of the source resolution, so they get their own target.
+    upscale_fbo: glow::Framebuffer,
+    upscale_texture: glow::Texture,
+    upscale_size: (u32, u32),
+    /// The display crop, copied out of the video texture at its own size.
+    crop_fbo: glow::Framebuffer,
+    crop_texture: glow::Texture,
+    crop_size: (u32, u32),
+    capture_size: (u32, u32),
+    /// Pixelate grid of the last frame drawn on screen, reused for captures of the same frame.
+    screen_grid: Option<(f32, f32)>,
+    /// Which of `PHOSPHOR_TARGETS` holds the latest phosphor output.
+    phosphor_current: usize,
+    /// When the phosphor history was last drawn; `None` while it holds nothing usable.
+    phosphor_updated: Option<Instant>,
+    /// Video texture of the direct upload path, written by `upload_direct` instead of egui.
+    direct_texture: glow::Texture,
+    direct_size: (u32, u32),
+    /// Same internal format egui picks for its textures, so both paths look the same.
+    direct_format: u32,
 }
 
 impl CrtFilterRenderer {
     pub fn new(gl: &glow::Context) -> Self {
         unsafe {
             let passthrough_prog = compile_program(gl, VS_SRC, FS_PASSTHROUGH);
+            let crop_prog = compile_program(gl, VS_SRC, FS_CROP);
             let pixelate_prog = compile_program(gl, VS_SRC, FS_PIXELATE);
+            let sharpen_prog = compile_program(gl, VS_SRC, FS_SHARPEN);
+            let upscale_prog = compile_program(gl, VS_SRC, FS_UPSCALE);
+            let phosphor_prog = compile_program(gl, VS_SRC, FS_PHOSPHOR);
             let pass0_prog = compile_program(gl, VS_SRC, FS_PASS0);
             let pass1_prog = compile_program(gl, VS_SRC, FS_PASS1);
             let pass2_prog = compile_program(gl, VS_SRC, FS_PASS2);
             let pass3_prog = compile_program(gl, VS_SRC, FS_PASS3);
             let final_prog = compile_program(gl, VS_SRC, FS_FINAL);
+            let bfi_prog = compile_program(gl, VS_SRC, FS_BFI);
 
             // Passthrough
             let p_passthrough_video_res_loc = gl.get_uniform_location(passthrough_prog, "videoResolution").unwrap();
             let p_passthrough_output_res_loc = gl.get_uniform_location(passthrough_prog, "outputResolution").unwrap();
+            let p_passthrough_pixel_aspect_loc = gl.get_uniform_location(passthrough_prog, "pixelAspect").unwrap();
+            let p_passthrough_white_point_loc = gl.get_uniform_location(passthrough_prog, "whitePoint").unwrap();
+            let p_passthrough_source_rect_loc = gl.get_uniform_location(passthrough_prog, "sourceRect").unwrap();
+
+            // Crop
+            let p_crop_source_rect_loc = gl.get_uniform_location(crop_prog, "sourceRect").unwrap();
 
             // Pixelate
             let p_pixelate_target_res_loc =
@@ -356,19 +651,48 @@ impl CrtFilterRenderer {
             // Final Pass
             let final_video_res_loc = gl.get_uniform_location(final_prog, "videoResolution").unwrap();
             let final_output_res_loc = gl.get_uniform_location(final_prog, "outputResolution").unwrap();
+            let final_pixel_aspect_loc = gl.get_uniform_location(final_prog, "pixelAspect").unwrap();
             let final_warp_x_loc = gl.get_uniform_location(final_prog, "warpX").unwrap();
             let final_warp_y_loc = gl.get_uniform_location(final_prog, "warpY").unwrap();
             let final_shadow_mask_loc = gl.get_uniform_location(final_prog, "shadowMask").unwrap();
             let final_brightboost_loc = gl.get_uniform_location(final_prog, "brightboost").unwrap();
             let final_bloom_amount_loc = gl.get_uniform_location(final_prog, "bloomAmount").unwrap();
+            let final_glare_intensity_loc = gl.get_uniform_location(final_prog, "glareIntensity").unwrap();
+            let final_ambient_color_loc = gl.get_uniform_location(final_prog, "ambientColor").unwrap();
+            let final_white_point_loc = gl.get_uniform_location(final_prog, "whitePoint").unwrap();
+
+            // BFI
+            let bfi_mode_loc = gl.get_uniform_location(bfi_prog, "bfiMode").unwrap();
+            let bfi_step_loc = gl.get_uniform_location(bfi_prog, "bfiStep").unwrap();
+            let bfi_cycle_loc = gl.get_uniform_location(bfi_prog, "bfiCycle").unwrap();
 
             // Set sampler uniforms once, as they don't change.
             gl.use_program(Some(passthrough_prog));
             gl.uniform_1_i32(Some(&gl.get_uniform_location(passthrough_prog, "video_texture").unwrap()), 0);
 
+            gl.use_program(Some(crop_prog));
+            gl.uniform_1_i32(Some(&gl.get_uniform_location(crop_prog, "video_texture").unwrap()), 0);
+
             gl.use_program(Some(pixelate_prog));
             gl.uniform_1_i32(Some(&gl.get_uniform_location(pixelate_prog, "video_texture").unwrap()), 0);
 
+            // Sharpen
+            let p_sharpen_sharpness_loc = gl.get_uniform_location(sharpen_prog, "sharpness").unwrap();
+            gl.use_program(Some(sharpen_prog));
+            gl.uniform_1_i32(Some(&gl.get_uniform_location(sharpen_prog, "video_texture").unwrap()), 0);
+
+            // Upscale
+            let p_upscale_scale_loc = gl.get_uniform_location(upscale_prog, "scale").unwrap();
+            let p_upscale_hard_edges_loc = gl.get_uniform_location(upscale_prog, "hardEdges").unwrap();
+            gl.use_program(Some(upscale_prog));
+            gl.uniform_1_i32(Some(&gl.get_uniform_location(upscale_prog, "video_texture").unwrap()), 0);
+
+            // Phosphor
+            let p_phosphor_decay_loc = gl.get_uniform_location(phosphor_prog, "decay").unwrap();
+            gl.use_program(Some(phosphor_prog));
+            gl.uniform_1_i32(Some(&gl.get_uniform_location(phosphor_prog, "video_texture").unwrap()), 0);
+            gl.uniform_1_i32(Some(&gl.get_uniform_location(phosphor_prog, "history_texture").unwrap()), 1);
+
             gl.use_program(Some(pass0_prog));
             gl.uniform_1_i32(Some(&gl.get_uniform_location(pass0_prog, "video_texture").unwrap()), 0);
 
@@ -392,6 +716,10 @@ impl CrtFilterRenderer {
                 gl.create_framebuffer().unwrap(),
                 gl.create_framebuffer().unwrap(),
                 gl.create_framebuffer().unwrap(),
+                gl.create_framebuffer().unwrap(),
+                gl.create_framebuffer().unwrap(),
+                gl.create_framebuffer().unwrap(),
+                gl.create_framebuffer().unwrap(),
             ];
             let pass_textures = [
                 gl.create_texture().unwrap(),
@@ -399,6 +727,10 @@ impl CrtFilterRenderer {
                 gl.create_texture().unwrap(),
                 gl.create_texture().unwrap(),
                 gl.create_texture().unwrap(),
+                gl.create_texture().unwrap(),
+                gl.create_texture().unwrap(),
+                gl.create_texture().unwrap(),
+                gl.create_texture().unwrap(),
             ];
 
             let vertex_array = gl.create_vertex_array().expect("Cannot create vertex array");
@@ -429,24 +761,141 @@ impl CrtFilterRenderer {
             gl.bind_vertex_array(None);
 
             Self {
-                passthrough_prog, pixelate_prog, pass0_prog, pass1_prog, pass2_prog, pass3_prog, final_prog,
+                passthrough_prog, crop_prog, pixelate_prog, sharpen_prog, upscale_prog, phosphor_prog, pass0_prog, pass1_prog, pass2_prog, pass3_prog, final_prog, bfi_prog,
                 fbos, pass_textures, vertex_array, vbo,
-                p_passthrough_video_res_loc, p_passthrough_output_res_loc,
+                p_passthrough_video_res_loc, p_passthrough_output_res_loc, p_passthrough_pixel_aspect_loc, p_passthrough_white_point_loc, p_passthrough_source_rect_loc,
+                p_crop_source_rect_loc,
                 p_pixelate_target_res_loc,
+                p_sharpen_sharpness_loc,
+                p_upscale_scale_loc, p_upscale_hard_edges_loc,
+                p_phosphor_decay_loc,
                 p0_hard_bloom_pix_loc,
                 p1_hard_bloom_scan_loc,
                 p2_hard_pix_loc, p3_hard_scan_loc, p3_shape_loc,
-                final_video_res_loc, final_output_res_loc, final_warp_x_loc, final_warp_y_loc,
+                final_video_res_loc, final_output_res_loc, final_pixel_aspect_loc, final_warp_x_loc, final_warp_y_loc,
                 final_shadow_mask_loc, final_brightboost_loc, final_bloom_amount_loc,
+                final_glare_intensity_loc, final_ambient_color_loc, final_white_point_loc,
+                bfi_mode_loc, bfi_step_loc, bfi_cycle_loc,
                 last_size: (0, 0),
+                upscale_fbo: gl.create_framebuffer().unwrap(),
+                upscale_texture: gl.create_texture().unwrap(),
+                upscale_size: (0, 0),
+                crop_fbo: gl.create_framebuffer().unwrap(),
+                crop_texture: gl.create_texture().unwrap(),
+                crop_size: (0, 0),
+                capture_size: (0, 0),
+                screen_grid: None,
+                phosphor_current: 0,
+                phosphor_updated: None,
+                direct_texture: gl.create_texture().unwrap(),
+                direct_size: (0, 0),
+                direct_format: if gl.supported_extensions().iter().any(|extension| extension.contains("sRGB")) { glow::SRGB8_ALPHA8 } else { glow::RGBA8 },
+            }
+        }
+    }
+
+    /// Detects a lost or replaced GL context (driver reset, switching GPUs). A lost context reports
+    /// `CONTEXT_LOST`; a new one doesn't know our objects, so they're created again in it.
+    pub fn check_context(&mut self, gl: &glow::Context) -> GlContextStatus {
+        unsafe {
+            if gl.get_error() == glow::CONTEXT_LOST {
+                return GlContextStatus::Lost;
+            }
+            // The objects created last are the least likely to share a name with what egui
+            // created in a new context.
+            if gl.is_program(self.bfi_prog) && gl.is_buffer(self.vbo) {
+                return GlContextStatus::Ok;
+            }
+        }
+        tracing::warn!("GL context was replaced, rebuilding the filter renderer");
+        *self = Self::new(gl);
+        GlContextStatus::Rebuilt
+    }
+
+    pub fn paint(&mut self, painter: &egui_glow::Painter, video_texture_id: egui::TextureId, source: SourceRegion, output_size: (f32, f32), params: &ShaderParams, run_pixelate: bool, run_lottes: bool, upscaler: Option<Upscaler>) {
+        // Missing while egui re-uploads its textures after a context reset.
+        let Some(video_texture) = painter.texture(video_texture_id) else { return };
+        self.render(painter.gl(), video_texture, source, output_size, params, run_pixelate, run_lottes, upscaler, None);
+    }
+
+    /// Copies a frame into the direct upload texture. The storage is only allocated again when
+    /// the size changes; egui reallocates its textures on every `set`.
+    pub fn upload_direct(&mut self, gl: &glow::Context, image: &egui::ColorImage) {
+        let size = (image.size[0] as u32, image.size[1] as u32);
+        let data: &[u8] = bytemuck::cast_slice(&image.pixels);
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.direct_texture));
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            if self.direct_size != size {
+                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
+                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
+                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+                gl.tex_image_2d(glow::TEXTURE_2D, 0, self.direct_format as i32, size.0 as i32, size.1 as i32, 0, glow::RGBA, glow::UNSIGNED_BYTE, Some(data));
+                self.direct_size = size;
+            } else {
+                gl.tex_sub_image_2d(glow::TEXTURE_2D, 0, 0, 0, size.0 as i32, size.1 as i32, glow::RGBA, glow::UNSIGNED_BYTE, glow::PixelUnpackData::Slice(data));
             }
+            gl.bind_texture(glow::TEXTURE_2D, None);
         }
     }
 
-    pub fn paint(&mut self, painter: &egui_glow::Painter, video_texture_id: egui::TextureId, resolution: (u32, u32), output_size: (f32, f32), params: &ShaderParams, run_pixelate: bool, run_lottes: bool) {
+    /// The direct upload texture, or `None` before the first `upload_direct`.
+    pub fn direct_texture(&self) -> Option<glow::Texture> {
+        (self.direct_size != (0, 0)).then_some(self.direct_texture)
+    }
+
+    /// Like `paint`, with the direct upload texture as the source.
+    #[allow(clippy::too_many_arguments)]
+    pub fn paint_direct(&mut self, gl: &glow::Context, source: SourceRegion, output_size: (f32, f32), params: &ShaderParams, run_pixelate: bool, run_lottes: bool, upscaler: Option<Upscaler>) {
+        let Some(video_texture) = self.direct_texture() else { return };
+        self.render(gl, video_texture, source, output_size, params, run_pixelate, run_lottes, upscaler, None);
+    }
+
+    /// Runs the filter chain into an offscreen target at the source resolution and reads the
+    /// result back, for recording with the filters burned in. This stalls the GPU pipeline.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_to_image(&mut self, painter: &egui_glow::Painter, video_texture_id: egui::TextureId, source: SourceRegion, params: &ShaderParams, run_pixelate: bool, run_lottes: bool, upscaler: Option<Upscaler>) -> Option<egui::ColorImage> {
         let gl = painter.gl();
-        let video_texture = painter.texture(video_texture_id).unwrap();
+        let video_texture = painter.texture(video_texture_id)?;
+        let resolution = source.size();
+        let output_size = (resolution.0 as f32, resolution.1 as f32);
+        if self.capture_size != resolution {
+            unsafe { allocate_target(gl, self.fbos[CAPTURE_TARGET], self.pass_textures[CAPTURE_TARGET], resolution) };
+            self.capture_size = resolution;
+        }
+        self.render(gl, video_texture, source, output_size, params, run_pixelate, run_lottes, upscaler, Some(self.fbos[CAPTURE_TARGET]));
 
+        let (width, height) = (resolution.0 as usize, resolution.1 as usize);
+        let mut pixels = vec![0u8; width * height * 4];
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbos[CAPTURE_TARGET]));
+            gl.read_pixels(0, 0, width as i32, height as i32, glow::RGBA, glow::UNSIGNED_BYTE, glow::PixelPackData::Slice(&mut pixels));
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        }
+        // GL rows start at the bottom.
+        let flipped: Vec<u8> = pixels.chunks_exact(width * 4).rev().flatten().copied().collect();
+        Some(egui::ColorImage::from_rgba_unmultiplied([width, height], &flipped))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render(&mut self, gl: &glow::Context, video_texture: glow::Texture, source: SourceRegion, output_size: (f32, f32), params: &ShaderParams, run_pixelate: bool, run_lottes: bool, upscaler: Option<Upscaler>, target: Option<glow::Framebuffer>) {
+        let source_resolution = source.size();
+        let grid = match (target, self.screen_grid) {
+            (Some(_), Some(grid)) => grid,
+            _ => pixelate_grid(source_resolution, output_size, params.pixel_aspect),
+        };
+        if target.is_none() {
+            self.screen_grid = Some(grid);
+        }
+        // With the low resolution pixelate pass, everything after it works on the grid instead of
+        // the source. The grid already has the display's aspect ratio, so its pixels are square.
+        let low_res = run_pixelate && params.settings.pixelate_low_res;
+        let (resolution, pixel_aspect) = if low_res {
+            ((grid.0.max(1.0) as u32, grid.1.max(1.0) as u32), 1.0)
+        } else {
+            (source_resolution, params.pixel_aspect)
+        };
         if self.last_size != resolution {
             self.setup_framebuffers(gl, resolution.0, resolution.1);
             self.last_size = resolution;
@@ -457,9 +906,28 @@ impl CrtFilterRenderer {
             let old_vbo = gl.get_parameter_i32(glow::VERTEX_ARRAY_BINDING);
 
             gl.bind_vertex_array(Some(self.vertex_array));
+
+            let video_texture = if source.crop.is_some() {
+                // --- CROP PASS ---
+                if self.crop_size != source_resolution {
+                    allocate_target(gl, self.crop_fbo, self.crop_texture, source_resolution);
+                    self.crop_size = source_resolution;
+                }
+                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.crop_fbo));
+                gl.viewport(0, 0, source_resolution.0 as i32, source_resolution.1 as i32);
+                gl.use_program(Some(self.crop_prog));
+                gl.active_texture(glow::TEXTURE0);
+                gl.bind_texture(glow::TEXTURE_2D, Some(video_texture));
+                gl.uniform_4_f32_slice(Some(&self.p_crop_source_rect_loc), &rect_uniform(source.uv()));
+                gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+                self.crop_texture
+            } else {
+                video_texture
+            };
             gl.viewport(0, 0, resolution.0 as i32, resolution.1 as i32);
 
             let mut lottes_input_texture = video_texture;
+            let run_sharpen = params.settings.sharpen_enabled;
 
             if run_pixelate {
                 // --- PIXELATE PASS ---
@@ -467,19 +935,79 @@ impl CrtFilterRenderer {
                 gl.use_program(Some(self.pixelate_prog));
                 gl.active_texture(glow::TEXTURE0);
                 gl.bind_texture(glow::TEXTURE_2D, Some(video_texture));
-                // Target 480p 16:9
-                gl.uniform_2_f32(Some(&self.p_pixelate_target_res_loc), 854.0, 480.0);
+                gl.uniform_2_f32(Some(&self.p_pixelate_target_res_loc), grid.0, grid.1);
                 gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
                 lottes_input_texture = self.pass_textures[4];
             }
 
+            if run_sharpen {
+                // --- SHARPEN PASS ---
+                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbos[SHARPEN_TARGET]));
+                gl.use_program(Some(self.sharpen_prog));
+                gl.active_texture(glow::TEXTURE0);
+                gl.bind_texture(glow::TEXTURE_2D, Some(lottes_input_texture));
+                gl.uniform_1_f32(Some(&self.p_sharpen_sharpness_loc), params.settings.sharpen_strength);
+                gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+                lottes_input_texture = self.pass_textures[SHARPEN_TARGET];
+            }
+
+            if params.settings.phosphor_enabled {
+                // --- PHOSPHOR PASS ---
+                // Only drawn for the screen: a capture of the same frame reuses its output
+                // rather than decaying the history a second time.
+                if target.is_none() {
+                    let now = Instant::now();
+                    // The persistence is the share left after 1/60 s, whatever the refresh rate.
+                    let decay = match self.phosphor_updated {
+                        Some(last) if now.duration_since(last).as_secs_f32() < MAX_PHOSPHOR_STEP => {
+                            params.settings.phosphor_persistence.powf(now.duration_since(last).as_secs_f32() * 60.0)
+                        }
+                        _ => 0.0,
+                    };
+                    let previous = PHOSPHOR_TARGETS[self.phosphor_current];
+                    self.phosphor_current = 1 - self.phosphor_current;
+                    let next = PHOSPHOR_TARGETS[self.phosphor_current];
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbos[next]));
+                    gl.use_program(Some(self.phosphor_prog));
+                    gl.active_texture(glow::TEXTURE1);
+                    gl.bind_texture(glow::TEXTURE_2D, Some(self.pass_textures[previous]));
+                    gl.active_texture(glow::TEXTURE0);
+                    gl.bind_texture(glow::TEXTURE_2D, Some(lottes_input_texture));
+                    gl.uniform_1_f32(Some(&self.p_phosphor_decay_loc), decay);
+                    gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+                    self.phosphor_updated = Some(now);
+                }
+                lottes_input_texture = self.pass_textures[PHOSPHOR_TARGETS[self.phosphor_current]];
+            } else {
+                self.phosphor_updated = None;
+            }
+
+            if let Some(upscaler) = upscaler {
+                // --- UPSCALE PASS ---
+                let factor = params.settings.upscale_factor.clamp(2, 4);
+                let size = (resolution.0 * factor, resolution.1 * factor);
+                if self.upscale_size != size {
+                    self.setup_upscale_target(gl, size);
+                }
+                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.upscale_fbo));
+                gl.viewport(0, 0, size.0 as i32, size.1 as i32);
+                gl.use_program(Some(self.upscale_prog));
+                gl.active_texture(glow::TEXTURE0);
+                gl.bind_texture(glow::TEXTURE_2D, Some(lottes_input_texture));
+                gl.uniform_1_f32(Some(&self.p_upscale_scale_loc), factor as f32);
+                gl.uniform_1_f32(Some(&self.p_upscale_hard_edges_loc), if upscaler == Upscaler::XbrHard { 1.0 } else { 0.0 });
+                gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+                gl.viewport(0, 0, resolution.0 as i32, resolution.1 as i32);
+                lottes_input_texture = self.upscale_texture;
+            }
+
             if run_lottes {
                 // --- PASS 0 (Horizontal Bloom) ---
                 gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbos[0]));
                 gl.use_program(Some(self.pass0_prog));
                 gl.active_texture(glow::TEXTURE0);
                 gl.bind_texture(glow::TEXTURE_2D, Some(lottes_input_texture));
-                gl.uniform_1_f32(Some(&self.p0_hard_bloom_pix_loc), params.hard_bloom_pix);
+                gl.uniform_1_f32(Some(&self.p0_hard_bloom_pix_loc), params.settings.hard_bloom_pix);
                 gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
 
                 // --- PASS 1 (Vertical Bloom) ---
@@ -487,7 +1015,7 @@ impl CrtFilterRenderer {
                 gl.use_program(Some(self.pass1_prog));
                 gl.active_texture(glow::TEXTURE0);
                 gl.bind_texture(glow::TEXTURE_2D, Some(self.pass_textures[0]));
-                gl.uniform_1_f32(Some(&self.p1_hard_bloom_scan_loc), params.hard_bloom_scan);
+                gl.uniform_1_f32(Some(&self.p1_hard_bloom_scan_loc), params.settings.hard_bloom_scan);
                 gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
 
                 // --- PASS 2 (Horizontal Scanlines) ---
@@ -495,7 +1023,7 @@ impl CrtFilterRenderer {
                 gl.use_program(Some(self.pass2_prog));
                 gl.active_texture(glow::TEXTURE0);
                 gl.bind_texture(glow::TEXTURE_2D, Some(lottes_input_texture));
-                gl.uniform_1_f32(Some(&self.p2_hard_pix_loc), params.hard_pix);
+                gl.uniform_1_f32(Some(&self.p2_hard_pix_loc), params.settings.hard_pix);
                 gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
 
                 // --- PASS 3 (Vertical Scanlines) ---
@@ -503,12 +1031,12 @@ impl CrtFilterRenderer {
                 gl.use_program(Some(self.pass3_prog));
                 gl.active_texture(glow::TEXTURE0);
                 gl.bind_texture(glow::TEXTURE_2D, Some(self.pass_textures[2]));
-                gl.uniform_1_f32(Some(&self.p3_hard_scan_loc), params.hard_scan);
-                gl.uniform_1_f32(Some(&self.p3_shape_loc), params.shape);
+                gl.uniform_1_f32(Some(&self.p3_hard_scan_loc), params.settings.hard_scan);
+                gl.uniform_1_f32(Some(&self.p3_shape_loc), params.settings.shape);
                 gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
 
                 // --- FINAL PASS ---
-                gl.bind_framebuffer(glow::FRAMEBUFFER, None); // Render to screen
+                gl.bind_framebuffer(glow::FRAMEBUFFER, target); // Screen, or the capture target
                 gl.viewport(0, 0, output_size.0 as i32, output_size.1 as i32);
                 gl.use_program(Some(self.final_prog));
                 
@@ -520,26 +1048,42 @@ impl CrtFilterRenderer {
 
                 gl.uniform_2_f32(Some(&self.final_video_res_loc), resolution.0 as f32, resolution.1 as f32);
                 gl.uniform_2_f32(Some(&self.final_output_res_loc), output_size.0, output_size.1);
-                gl.uniform_1_f32(Some(&self.final_warp_x_loc), params.warp_x);
-                gl.uniform_1_f32(Some(&self.final_warp_y_loc), params.warp_y);
-                gl.uniform_1_f32(Some(&self.final_shadow_mask_loc), params.shadow_mask);
-                gl.uniform_1_f32(Some(&self.final_brightboost_loc), params.brightboost);
-                gl.uniform_1_f32(Some(&self.final_bloom_amount_loc), params.bloom_amount);
+                gl.uniform_1_f32(Some(&self.final_pixel_aspect_loc), pixel_aspect);
+                gl.uniform_1_f32(Some(&self.final_warp_x_loc), params.settings.warp_x);
+                gl.uniform_1_f32(Some(&self.final_warp_y_loc), params.settings.warp_y);
+                gl.uniform_1_f32(Some(&self.final_shadow_mask_loc), params.settings.shadow_mask);
+                gl.uniform_1_f32(Some(&self.final_brightboost_loc), params.settings.brightboost);
+                gl.uniform_1_f32(Some(&self.final_bloom_amount_loc), params.settings.bloom_amount);
+                gl.uniform_1_f32(Some(&self.final_glare_intensity_loc), if params.settings.glare_enabled { params.settings.glare_intensity } else { 0.0 });
+                let [r, g, b] = params.settings.glare_ambient_color;
+                gl.uniform_3_f32(Some(&self.final_ambient_color_loc), r, g, b);
+                gl.uniform_matrix_3_f32_slice(Some(&self.final_white_point_loc), false, &white_point_matrix(params.settings.white_point));
 
                 gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
-            } else if run_pixelate {
-                // If only pixelation is enabled, we need to draw its result to the screen.
-                gl.bind_framebuffer(glow::FRAMEBUFFER, None); // Render to screen
+            } else if run_pixelate || run_sharpen || params.settings.phosphor_enabled || upscaler.is_some() {
+                // Without the Lottes passes, the pre-filters' result still has to reach the screen.
+                gl.bind_framebuffer(glow::FRAMEBUFFER, target); // Screen, or the capture target
                 gl.viewport(0, 0, output_size.0 as i32, output_size.1 as i32);
                 gl.use_program(Some(self.passthrough_prog));
 
                 gl.active_texture(glow::TEXTURE0);
                 gl.bind_texture(glow::TEXTURE_2D, Some(lottes_input_texture));
+                // Scaling the low resolution grid up has to keep its cells sharp.
+                let nearest = low_res && upscaler.is_none();
+                if nearest {
+                    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::NEAREST as i32);
+                }
 
                 gl.uniform_2_f32(Some(&self.p_passthrough_video_res_loc), resolution.0 as f32, resolution.1 as f32);
                 gl.uniform_2_f32(Some(&self.p_passthrough_output_res_loc), output_size.0, output_size.1);
+                gl.uniform_1_f32(Some(&self.p_passthrough_pixel_aspect_loc), pixel_aspect);
+                gl.uniform_matrix_3_f32_slice(Some(&self.p_passthrough_white_point_loc), false, &white_point_matrix(params.settings.white_point));
+                gl.uniform_4_f32_slice(Some(&self.p_passthrough_source_rect_loc), &[0.0, 0.0, 1.0, 1.0]);
 
                 gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+                if nearest {
+                    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
+                }
             }
 
             gl.bind_vertex_array(None);
@@ -554,7 +1098,10 @@ impl CrtFilterRenderer {
         }
     }
 
-    pub fn draw_passthrough(&self, gl: &glow::Context, video_texture: glow::Texture, resolution: (u32, u32), output_size: (f32, f32)) {
+    /// Draws the video as it is, apart from the scaling and `white_point` (see
+    /// `color::white_point_matrix`).
+    pub fn draw_passthrough(&self, gl: &glow::Context, video_texture: glow::Texture, source: SourceRegion, output_size: (f32, f32), pixel_aspect: f32, white_point: f32) {
+        let resolution = source.size();
         unsafe {
             let old_vbo = gl.get_parameter_i32(glow::VERTEX_ARRAY_BINDING);
             gl.bind_vertex_array(Some(self.vertex_array));
@@ -568,6 +1115,34 @@ impl CrtFilterRenderer {
 
             gl.uniform_2_f32(Some(&self.p_passthrough_video_res_loc), resolution.0 as f32, resolution.1 as f32);
             gl.uniform_2_f32(Some(&self.p_passthrough_output_res_loc), output_size.0, output_size.1);
+            gl.uniform_1_f32(Some(&self.p_passthrough_pixel_aspect_loc), pixel_aspect);
+            gl.uniform_matrix_3_f32_slice(Some(&self.p_passthrough_white_point_loc), false, &white_point_matrix(white_point));
+            gl.uniform_4_f32_slice(Some(&self.p_passthrough_source_rect_loc), &rect_uniform(source.uv()));
+
+            gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+
+            gl.bind_vertex_array(Some(glow::VertexArray::from(glow::NativeVertexArray(NonZero::new(old_vbo as u32).unwrap()))));
+        }
+    }
+
+    /// Blanks the screen (or all but one band of it) for the current refresh of the BFI cycle.
+    pub fn draw_bfi(&self, gl: &glow::Context, mode: BfiMode, step: u32, cycle: u32, output_size: (f32, f32)) {
+        if mode == BfiMode::Off {
+            return;
+        }
+        unsafe {
+            let old_vbo = gl.get_parameter_i32(glow::VERTEX_ARRAY_BINDING);
+            gl.bind_vertex_array(Some(self.vertex_array));
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None); // Render to screen
+            gl.viewport(0, 0, output_size.0 as i32, output_size.1 as i32);
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+            gl.use_program(Some(self.bfi_prog));
+
+            gl.uniform_1_f32(Some(&self.bfi_mode_loc), mode as u8 as f32);
+            gl.uniform_1_f32(Some(&self.bfi_step_loc), step as f32);
+            gl.uniform_1_f32(Some(&self.bfi_cycle_loc), cycle as f32);
 
             gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
 
@@ -578,12 +1153,17 @@ impl CrtFilterRenderer {
     pub fn destroy(&self, gl: &glow::Context) {
         unsafe {
             gl.delete_program(self.passthrough_prog);
+            gl.delete_program(self.crop_prog);
             gl.delete_program(self.pixelate_prog);
+            gl.delete_program(self.sharpen_prog);
+            gl.delete_program(self.upscale_prog);
+            gl.delete_program(self.phosphor_prog);
             gl.delete_program(self.pass0_prog);
             gl.delete_program(self.pass1_prog);
             gl.delete_program(self.pass2_prog);
             gl.delete_program(self.pass3_prog);
             gl.delete_program(self.final_prog);
+            gl.delete_program(self.bfi_prog);
             gl.delete_vertex_array(self.vertex_array);
             gl.delete_buffer(self.vbo);
             for fbo in self.fbos {
@@ -592,42 +1172,106 @@ impl CrtFilterRenderer {
             for texture in self.pass_textures {
                 gl.delete_texture(texture);
             }
+            gl.delete_framebuffer(self.upscale_fbo);
+            gl.delete_texture(self.upscale_texture);
+            gl.delete_framebuffer(self.crop_fbo);
+            gl.delete_texture(self.crop_texture);
+            gl.delete_texture(self.direct_texture);
         }
     }
 
     fn setup_framebuffers(&mut self, gl: &glow::Context, width: u32, height: u32) {
-        unsafe {
-            for i in 0..self.pass_textures.len() {
-                gl.bind_texture(glow::TEXTURE_2D, Some(self.pass_textures[i]));
-                gl.tex_image_2d(
-                    glow::TEXTURE_2D,
-                    0,
-                    glow::RGBA as i32,
-                    width as i32,
-                    height as i32,
-                    0,
-                    glow::RGBA,
-                    glow::UNSIGNED_BYTE,
-                    None,
-                );
-                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
-                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
-                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
-                gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
-
-                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbos[i]));
-                gl.framebuffer_texture_2d(
-                    glow::FRAMEBUFFER,
-                    glow::COLOR_ATTACHMENT0,
-                    glow::TEXTURE_2D,
-                    Some(self.pass_textures[i]),
-                    0,
-                );
+        for i in 0..self.pass_textures.len() {
+            // The capture target is always at the source resolution, see `render_to_image`.
+            if i != CAPTURE_TARGET {
+                unsafe { allocate_target(gl, self.fbos[i], self.pass_textures[i], (width, height)) };
             }
-            gl.bind_texture(glow::TEXTURE_2D, None);
-            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
         }
+        // The history textures were reallocated with undefined contents.
+        self.phosphor_updated = None;
+    }
+
+    fn setup_upscale_target(&mut self, gl: &glow::Context, size: (u32, u32)) {
+        unsafe { allocate_target(gl, self.upscale_fbo, self.upscale_texture, size) };
+        self.upscale_size = size;
+    }
+}
+
+/// The pixelate filter's grid for the size the video is shown at, in device pixels. Once cells
+/// are two or more pixels tall, the line count is adjusted so every cell covers a whole number of
+/// pixels; otherwise, e.g. under 125% or 150% scaling, cells alternate between sizes.
+fn pixelate_grid(resolution: (u32, u32), output_size: (f32, f32), pixel_aspect: f32) -> (f32, f32) {
+    if resolution.0 == 0 || resolution.1 == 0 {
+        return PIXELATE_GRID;
+    }
+    let video_aspect = resolution.0 as f32 * pixel_aspect / resolution.1 as f32;
+    let shown_height = output_size.1.min(output_size.0 / video_aspect);
+    let cell = (shown_height / PIXELATE_GRID.1).round();
+    if cell < 2.0 {
+        return PIXELATE_GRID;
+    }
+    let lines = (shown_height / cell).floor();
+    let columns = (shown_height * video_aspect / cell).floor();
+    (columns, lines)
+}
+
+/// What the filter chain can run for a source without going over the video memory cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryFit {
+    /// False when even the chain's own render targets don't fit, leaving only the passthrough.
+    pub filters: bool,
+    /// The largest upscale factor that fits, at most the requested one. `None` if the upscaler
+    /// doesn't fit or wasn't requested.
+    pub upscale_factor: Option<u32>,
+}
+
+/// Checks the render targets for a `resolution` source against `budget_bytes` and the GPU's
+/// texture size limit, lowering the upscale factor and then dropping the filters until they fit.
+pub fn fit_to_memory(resolution: (u32, u32), upscale_factor: Option<u32>, budget_bytes: u64, max_texture_size: u32) -> MemoryFit {
+    let pixels = resolution.0 as u64 * resolution.1 as u64;
+    let longest_side = resolution.0.max(resolution.1);
+    let base = render_target_bytes(resolution, None);
+    if base > budget_bytes || longest_side > max_texture_size {
+        return MemoryFit { filters: false, upscale_factor: None };
     }
+    let upscale_factor = upscale_factor.and_then(|requested| {
+        (*FilterSettings::UPSCALE_FACTOR.start()..=requested).rev().find(|&factor| {
+            base + pixels * (factor * factor) as u64 * 4 <= budget_bytes && longest_side * factor <= max_texture_size
+        })
+    });
+    MemoryFit { filters: true, upscale_factor }
+}
+
+/// Width and height limit of textures on this GPU.
+pub fn max_texture_size(gl: &glow::Context) -> u32 {
+    // OpenGL 3.3 guarantees at least 1024; anything smaller means the query failed.
+    unsafe { gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) }.max(1024) as u32
+}
+
+/// Bytes of video memory the chain's render targets take for a `resolution` source.
+pub fn render_target_bytes(resolution: (u32, u32), upscale_factor: Option<u32>) -> u64 {
+    let pixels = resolution.0 as u64 * resolution.1 as u64;
+    let upscaled = upscale_factor.map_or(0, |factor| pixels * (factor * factor) as u64);
+    (pixels * PASS_TARGETS as u64 + upscaled) * 4
+}
+
+/// A rectangle in texture coordinates as a `vec4` of its offset and size.
+fn rect_uniform(rect: egui::Rect) -> [f32; 4] {
+    [rect.min.x, rect.min.y, rect.width(), rect.height()]
+}
+
+/// (Re)allocates a render target's texture at `size` and attaches it to its framebuffer.
+unsafe fn allocate_target(gl: &glow::Context, fbo: glow::Framebuffer, texture: glow::Texture, size: (u32, u32)) {
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+    gl.tex_image_2d(glow::TEXTURE_2D, 0, glow::RGBA as i32, size.0 as i32, size.1 as i32, 0, glow::RGBA, glow::UNSIGNED_BYTE, None);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+    gl.framebuffer_texture_2d(glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0, glow::TEXTURE_2D, Some(texture), 0);
+    gl.bind_texture(glow::TEXTURE_2D, None);
+    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
 }
 
 unsafe fn compile_program(gl: &glow::Context, vs_src: &str, fs_src: &str) -> glow::Program {
@@ -664,50 +1308,18 @@ unsafe fn compile_program(gl: &glow::Context, vs_src: &str, fs_src: &str) -> glo
     program
 }
 
-impl ShaderParams {
-    pub fn from_state(state: &crate::app::AppState) -> Self {
-        Self {
-            hard_scan: state.crt_hard_scan,
-            warp_x: state.crt_warp_x,
-            warp_y: state.crt_warp_y,
-            shadow_mask: state.crt_shadow_mask,
-            brightboost: state.crt_brightboost,
-            hard_bloom_pix: state.crt_hard_bloom_pix,
-            hard_bloom_scan: state.crt_hard_bloom_scan,
-            bloom_amount: state.crt_bloom_amount,
-            shape: state.crt_shape,
-            hard_pix: state.crt_hard_pix,
-        }
-    }
-}
-
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
+/// Everything the filter chain needs besides the textures.
+#[derive(Clone)]
 pub struct ShaderParams {
-    pub hard_scan: f32,
-    pub warp_x: f32,
-    pub warp_y: f32,
-    pub shadow_mask: f32,
-    pub brightboost: f32,
-    pub hard_bloom_pix: f32,
-    pub hard_bloom_scan: f32,
-    pub bloom_amount: f32,
-    pub shape: f32,
-    pub hard_pix: f32,
+    pub settings: FilterSettings,
+    pub pixel_aspect: f32,
 }
 
-impl Default for ShaderParams {
-    fn default() -> Self {
+impl ShaderParams {
+    pub fn from_state(state: &crate::app::AppState) -> Self {
         Self {
-            hard_scan: -8.0,
-            warp_x: 0.031,
-            warp_y: 0.041,
-            shadow_mask: 3.0,
-            brightboost: 1.0,
-            hard_bloom_pix: -1.5,
-            hard_bloom_scan: -2.0,
-            bloom_amount: 0.15,
-            shape: 2.0,
-            hard_pix: -3.0,
+            settings: state.modulated_filter_settings(),
+            pixel_aspect: state.pixel_aspect_ratio(),
         }
     }
-}
\ No newline at end of file
+}
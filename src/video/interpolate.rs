@@ -0,0 +1,245 @@
+use anyhow::{Context, Result};
+use eframe::egui;
+use ffmpeg_next::filter;
+use ffmpeg_next::format::Pixel;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Only sources slower than this are interpolated; faster ones already fill a 60 Hz display.
+const MAX_SOURCE_FPS: f32 = 45.0;
+/// Width motion is estimated at. minterpolate is far too slow for full size frames in real time.
+const PROXY_WIDTH: u32 = 480;
+/// Smoothing factor for the source frame interval average.
+const EMA_ALPHA: f32 = 0.05;
+/// Source frames held back for minterpolate's delay before giving up on them.
+const MAX_HELD_FRAMES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InterpolationMode {
+    Off = 0,
+    /// Shows the average of each two source frames between them.
+    Blend = 1,
+    /// Estimates motion with ffmpeg's minterpolate on a downscaled copy.
+    MotionCompensated = 2,
+}
+
+impl InterpolationMode {
+    pub const ALL: [InterpolationMode; 3] = [InterpolationMode::Off, InterpolationMode::Blend, InterpolationMode::MotionCompensated];
+
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => InterpolationMode::Blend,
+            2 => InterpolationMode::MotionCompensated,
+            _ => InterpolationMode::Off,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            InterpolationMode::Off => "Off",
+            InterpolationMode::Blend => "Blend",
+            InterpolationMode::MotionCompensated => "Motion compensated",
+        }
+    }
+}
+
+/// Doubles the framerate of 30 fps sources for 60 Hz displays by inserting an in-between frame
+/// after each source frame, which holds every source frame back by at least half an interval.
+/// Only for display: recordings, the preview server and the like keep getting the source frames.
+pub struct Interpolator {
+    mode: InterpolationMode,
+    tx: Option<crossbeam_channel::Sender<Arc<egui::ColorImage>>>,
+    rx: crossbeam_channel::Receiver<Arc<egui::ColorImage>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Interpolator {
+    pub fn start(ctx: &egui::Context, mode: InterpolationMode) -> Self {
+        let (tx, source_rx) = crossbeam_channel::bounded(1);
+        let (output_tx, rx) = crossbeam_channel::bounded(4);
+        let ctx = ctx.clone();
+        let thread = std::thread::Builder::new()
+            .name("interpolator".to_string())
+            .spawn(move || interpolator_main(mode, &source_rx, &output_tx, &ctx))
+            .map_err(|e| tracing::warn!("Failed to start the interpolator thread: {}", e))
+            .ok();
+        Self { mode, tx: Some(tx), rx, thread }
+    }
+
+    pub fn mode(&self) -> InterpolationMode {
+        self.mode
+    }
+
+    /// Hands over a source frame. Dropped if the previous one is still being worked on.
+    pub fn push(&self, frame: Arc<egui::ColorImage>) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.try_send(frame);
+        }
+    }
+
+    /// Frames to show, paced half a source interval apart.
+    pub fn frames(&self) -> impl Iterator<Item = Arc<egui::ColorImage>> + '_ {
+        self.rx.try_iter()
+    }
+}
+
+impl Drop for Interpolator {
+    fn drop(&mut self) {
+        self.tx = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn interpolator_main(
+    mode: InterpolationMode,
+    rx: &crossbeam_channel::Receiver<Arc<egui::ColorImage>>,
+    tx: &crossbeam_channel::Sender<Arc<egui::ColorImage>>,
+    ctx: &egui::Context,
+) {
+    let mut previous: Option<Arc<egui::ColorImage>> = None;
+    let mut last_arrival: Option<Instant> = None;
+    let mut source_interval: Option<f32> = None;
+    let mut motion: Option<MotionInterpolator> = None;
+    let mut motion_failed = false;
+    for frame in rx {
+        let now = Instant::now();
+        if let Some(interval) = last_arrival.replace(now).map(|last| (now - last).as_secs_f32()).filter(|interval| *interval < 0.5) {
+            source_interval = Some(source_interval.map_or(interval, |average| average + (interval - average) * EMA_ALPHA));
+        }
+        let interval = source_interval.unwrap_or(0.0);
+        let outputs = if interval * MAX_SOURCE_FPS < 1.0 {
+            motion = None;
+            vec![frame.clone()]
+        } else if mode == InterpolationMode::MotionCompensated && !motion_failed {
+            let fps = (1.0 / interval).round() as u32;
+            if motion.as_ref().is_some_and(|motion| motion.size != frame.size || motion.fps.abs_diff(fps) > 2) {
+                motion = None;
+            }
+            let result = match &mut motion {
+                Some(motion) => motion.push(&frame),
+                None => MotionInterpolator::new(frame.size, fps).and_then(|new| motion.insert(new).push(&frame)),
+            };
+            result.unwrap_or_else(|e| {
+                tracing::warn!("Motion compensated interpolation failed, blending instead: {:#}", e);
+                motion_failed = true;
+                motion = None;
+                blend_frames(previous.as_deref(), &frame)
+            })
+        } else {
+            blend_frames(previous.as_deref(), &frame)
+        };
+        previous = Some(frame);
+        for (index, image) in outputs.into_iter().enumerate() {
+            if index > 0 {
+                std::thread::sleep(Duration::from_secs_f32(interval / 2.0));
+            }
+            if let Err(crossbeam_channel::TrySendError::Disconnected(_)) = tx.try_send(image) {
+                return;
+            }
+            ctx.request_repaint();
+        }
+    }
+}
+
+/// The average of two frames followed by the newer one, or just the newer one at a size change.
+fn blend_frames(previous: Option<&egui::ColorImage>, frame: &Arc<egui::ColorImage>) -> Vec<Arc<egui::ColorImage>> {
+    let Some(previous) = previous.filter(|previous| previous.size == frame.size) else {
+        return vec![frame.clone()];
+    };
+    let average = |a: u8, b: u8| (a as u16 + b as u16).div_ceil(2) as u8;
+    let pixels = previous
+        .pixels
+        .iter()
+        .zip(&frame.pixels)
+        .map(|(a, b)| egui::Color32::from_rgb(average(a.r(), b.r()), average(a.g(), b.g()), average(a.b(), b.b())))
+        .collect();
+    vec![Arc::new(egui::ColorImage { size: frame.size, pixels }), frame.clone()]
+}
+
+/// Runs minterpolate at twice the source framerate on a `PROXY_WIDTH` copy and scales the
+/// in-between frames back up. The source frames themselves are shown as they came, so only
+/// every other frame is softer.
+struct MotionInterpolator {
+    graph: filter::Graph,
+    size: [usize; 2],
+    fps: u32,
+    next_pts: i64,
+    /// Source frames waiting for the in-between frame before them, by their output pts.
+    held: VecDeque<(i64, Arc<egui::ColorImage>)>,
+}
+
+impl MotionInterpolator {
+    fn new(size: [usize; 2], fps: u32) -> Result<Self> {
+        let (width, height) = (size[0] as u32, size[1] as u32);
+        let proxy_width = PROXY_WIDTH.min(width) & !1;
+        let proxy_height = (height * proxy_width / width.max(1)).max(2) & !1;
+        let mut graph = filter::Graph::new();
+        let buffer = filter::find("buffer").context("FFmpeg has no buffer filter")?;
+        let buffersink = filter::find("buffersink").context("FFmpeg has no buffersink filter")?;
+        graph.add(&buffer, "in", &format!("video_size={}x{}:pix_fmt=rgb24:time_base=1/{}:pixel_aspect=1/1", width, height, fps))?;
+        graph.add(&buffersink, "out", "")?.set_pixel_format(Pixel::RGB24);
+        let spec = format!(
+            "scale={}:{}:flags=area,minterpolate=fps={}:mi_mode=mci:mc_mode=aobmc:me_mode=bidir:vsbmc=1,scale={}:{}:flags=bicubic",
+            proxy_width,
+            proxy_height,
+            fps * 2,
+            width,
+            height
+        );
+        graph.output("in", 0)?.input("out", 0)?.parse(&spec).context("Failed to set up minterpolate")?;
+        graph.validate().context("Failed to configure the interpolation filters")?;
+        tracing::info!(fps, proxy_width, proxy_height, "Started motion compensated interpolation");
+        Ok(Self { graph, size, fps, next_pts: 0, held: VecDeque::new() })
+    }
+
+    /// Returns the frames minterpolate has finished, with the source frames in place of its
+    /// copies of them. minterpolate looks ahead, so they come a frame or two late.
+    fn push(&mut self, frame: &Arc<egui::ColorImage>) -> Result<Vec<Arc<egui::ColorImage>>> {
+        let [width, height] = self.size;
+        let mut input = ffmpeg_next::frame::Video::new(Pixel::RGB24, width as u32, height as u32);
+        let stride = input.stride(0);
+        let data = input.data_mut(0);
+        for (y, row) in frame.pixels.chunks_exact(width).enumerate() {
+            for (dst, pixel) in data[y * stride..][..width * 3].chunks_exact_mut(3).zip(row) {
+                dst.copy_from_slice(&[pixel.r(), pixel.g(), pixel.b()]);
+            }
+        }
+        input.set_pts(Some(self.next_pts));
+        // Output timestamps count at twice the rate, so source frame n comes out as 2n.
+        self.held.push_back((self.next_pts * 2, frame.clone()));
+        if self.held.len() > MAX_HELD_FRAMES {
+            self.held.pop_front();
+        }
+        self.next_pts += 1;
+        self.graph.get("in").context("No filter input")?.source().add(&input).context("Failed to send a frame to minterpolate")?;
+
+        let mut sink = self.graph.get("out").context("No filter output")?;
+        let mut outputs = Vec::new();
+        let mut output = ffmpeg_next::frame::Video::empty();
+        while sink.sink().frame(&mut output).is_ok() {
+            let pts = output.pts().unwrap_or_default();
+            while self.held.front().is_some_and(|(held_pts, _)| *held_pts < pts) {
+                self.held.pop_front();
+            }
+            match self.held.front() {
+                Some((held_pts, _)) if *held_pts == pts => outputs.extend(self.held.pop_front().map(|(_, frame)| frame)),
+                _ => outputs.push(Arc::new(to_color_image(&output))),
+            }
+        }
+        Ok(outputs)
+    }
+}
+
+fn to_color_image(frame: &ffmpeg_next::frame::Video) -> egui::ColorImage {
+    let (width, height, stride) = (frame.width() as usize, frame.height() as usize, frame.stride(0));
+    let data = frame.data(0);
+    let pixels = (0..height)
+        .flat_map(|y| data[y * stride..][..width * 3].chunks_exact(3).map(|p| egui::Color32::from_rgb(p[0], p[1], p[2])))
+        .collect();
+    egui::ColorImage { size: [width, height], pixels }
+}
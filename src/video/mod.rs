@@ -1,5 +1,23 @@
+pub mod autocrop;
+pub mod autosplit;
+pub mod clips;
+pub mod color;
 pub mod decoder;
+pub mod display;
+pub mod ffmpeg_log;
+pub mod filter_settings;
 pub mod gpu_filter;
+pub mod interpolate;
+pub mod modulation;
+pub mod overlay;
+pub mod pacing;
+pub mod photon_latency;
+pub mod probe;
+pub mod recorder;
+pub mod signal_log;
+pub mod texture;
+pub mod timer;
 pub mod types;
+pub mod wall;
 
 pub use types::VideoFormat;
\ No newline at end of file
@@ -0,0 +1,262 @@
+use super::filter_settings::FilterSettings;
+use anyhow::{Context, Result};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::ops::RangeInclusive;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const DEFAULT_OSC_PORT: u16 = 9000;
+
+/// A filter parameter that can be modulated. Only continuous ones; the mask type and the
+/// on/off switches would just flicker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum ModParam {
+    #[default]
+    WarpX = 0,
+    WarpY = 1,
+    BloomAmount = 2,
+    HardScan = 3,
+    HardPix = 4,
+    Brightboost = 5,
+    Shape = 6,
+    GlareIntensity = 7,
+    SharpenStrength = 8,
+    PhosphorPersistence = 9,
+}
+
+impl ModParam {
+    pub const ALL: [ModParam; 10] = [
+        ModParam::WarpX,
+        ModParam::WarpY,
+        ModParam::BloomAmount,
+        ModParam::HardScan,
+        ModParam::HardPix,
+        ModParam::Brightboost,
+        ModParam::Shape,
+        ModParam::GlareIntensity,
+        ModParam::SharpenStrength,
+        ModParam::PhosphorPersistence,
+    ];
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            ModParam::WarpX => "Warp X",
+            ModParam::WarpY => "Warp Y",
+            ModParam::BloomAmount => "Bloom Amount",
+            ModParam::HardScan => "Scanline Hardness",
+            ModParam::HardPix => "Pixel Hardness",
+            ModParam::Brightboost => "Brightness Boost",
+            ModParam::Shape => "Bloom Shape",
+            ModParam::GlareIntensity => "Glare Intensity",
+            ModParam::SharpenStrength => "Sharpen Strength",
+            ModParam::PhosphorPersistence => "Phosphor Persistence",
+        }
+    }
+
+    /// The range its slider allows, which modulation never leaves.
+    pub fn range(&self) -> RangeInclusive<f32> {
+        match self {
+            ModParam::WarpX | ModParam::WarpY => FilterSettings::WARP,
+            ModParam::BloomAmount => FilterSettings::BLOOM_AMOUNT,
+            ModParam::HardScan => FilterSettings::HARD_SCAN,
+            ModParam::HardPix => FilterSettings::HARD_PIX,
+            ModParam::Brightboost => FilterSettings::BRIGHTBOOST,
+            ModParam::Shape => FilterSettings::SHAPE,
+            ModParam::GlareIntensity => FilterSettings::GLARE_INTENSITY,
+            ModParam::SharpenStrength => FilterSettings::SHARPEN_STRENGTH,
+            ModParam::PhosphorPersistence => FilterSettings::PHOSPHOR_PERSISTENCE,
+        }
+    }
+
+    pub fn value(&self, settings: &FilterSettings) -> f32 {
+        *self.field(&mut settings.clone())
+    }
+
+    /// Sets the parameter to `position`, from 0 for the start of its range to 1 for the end.
+    pub fn set(&self, settings: &mut FilterSettings, position: f32) {
+        let range = self.range();
+        *self.field(settings) = range.start() + (range.end() - range.start()) * position.clamp(0.0, 1.0);
+    }
+
+    fn field<'a>(&self, settings: &'a mut FilterSettings) -> &'a mut f32 {
+        match self {
+            ModParam::WarpX => &mut settings.warp_x,
+            ModParam::WarpY => &mut settings.warp_y,
+            ModParam::BloomAmount => &mut settings.bloom_amount,
+            ModParam::HardScan => &mut settings.hard_scan,
+            ModParam::HardPix => &mut settings.hard_pix,
+            ModParam::Brightboost => &mut settings.brightboost,
+            ModParam::Shape => &mut settings.shape,
+            ModParam::GlareIntensity => &mut settings.glare_intensity,
+            ModParam::SharpenStrength => &mut settings.sharpen_strength,
+            ModParam::PhosphorPersistence => &mut settings.phosphor_persistence,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum ModSource {
+    #[default]
+    Sine = 0,
+    Triangle = 1,
+    Square = 2,
+    Saw = 3,
+    /// The first number of the OSC messages sent to `osc_address`, a float from 0 to 1 or an
+    /// integer from 0 to 127.
+    Osc = 4,
+    /// A MIDI control change, e.g. a knob on a controller.
+    Midi = 5,
+}
+
+impl ModSource {
+    pub const ALL: [ModSource; 6] = [ModSource::Sine, ModSource::Triangle, ModSource::Square, ModSource::Saw, ModSource::Osc, ModSource::Midi];
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            ModSource::Sine => "Sine LFO",
+            ModSource::Triangle => "Triangle LFO",
+            ModSource::Square => "Square LFO",
+            ModSource::Saw => "Saw LFO",
+            ModSource::Osc => "OSC",
+            ModSource::Midi => "MIDI CC",
+        }
+    }
+
+    pub fn is_lfo(&self) -> bool {
+        !matches!(self, ModSource::Osc | ModSource::Midi)
+    }
+}
+
+/// Moves a filter parameter up and down around the value set with its slider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Modulation {
+    pub param: ModParam,
+    pub source: ModSource,
+    /// LFO cycles per second.
+    pub rate_hz: f32,
+    /// How far the parameter swings either way, as a share of its whole range.
+    pub depth: f32,
+    pub osc_address: String,
+    /// MIDI channel, 1 to 16, and controller number of the control change.
+    pub midi_channel: u8,
+    pub midi_controller: u8,
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Self {
+            param: ModParam::WarpX,
+            source: ModSource::Sine,
+            rate_hz: 0.5,
+            depth: 0.25,
+            osc_address: "/michadame/1".to_string(),
+            midi_channel: 1,
+            midi_controller: 1,
+        }
+    }
+}
+
+impl Modulation {
+    /// Where the source is, from -1 to 1, or `None` if an external source hasn't sent anything yet.
+    fn position(&self, elapsed_secs: f64, inputs: &ExternalValues) -> Option<f32> {
+        let phase = (elapsed_secs * self.rate_hz as f64).fract() as f32;
+        match self.source {
+            ModSource::Sine => Some((phase * std::f32::consts::TAU).sin()),
+            ModSource::Triangle => Some(1.0 - 4.0 * (phase - 0.5).abs()),
+            ModSource::Square => Some(if phase < 0.5 { 1.0 } else { -1.0 }),
+            ModSource::Saw => Some(phase * 2.0 - 1.0),
+            ModSource::Osc => inputs.osc.get(&self.osc_address).map(|value| value * 2.0 - 1.0),
+            ModSource::Midi => inputs.midi.get(&(self.midi_channel, self.midi_controller)).map(|value| value * 2.0 - 1.0),
+        }
+    }
+}
+
+/// Applies the modulations on top of `settings`, keeping every parameter within its range.
+pub fn apply(modulations: &[Modulation], settings: &mut FilterSettings, elapsed_secs: f64, inputs: &ExternalValues) {
+    for modulation in modulations {
+        let Some(position) = modulation.position(elapsed_secs, inputs) else { continue };
+        let range = modulation.param.range();
+        let swing = (range.end() - range.start()) * modulation.depth.clamp(0.0, 1.0);
+        let field = modulation.param.field(settings);
+        let value = *field + position * swing;
+        if value.is_finite() {
+            *field = value.clamp(*range.start(), *range.end());
+        }
+    }
+}
+
+/// Latest values of the external sources, each from 0 to 1.
+#[derive(Debug, Clone, Default)]
+pub struct ExternalValues {
+    pub osc: HashMap<String, f32>,
+    /// Keyed by MIDI channel (1-16) and controller number. Filled in by the app from its
+    /// `MidiInput`, which it shares with the MIDI mappings.
+    pub midi: HashMap<(u8, u8), f32>,
+    /// What arrived last, so users can see which address or controller to enter.
+    pub last_received: Option<String>,
+}
+
+/// Listens for OSC messages on a localhost UDP port and keeps their latest values for the modulations.
+pub struct OscInput {
+    values: Arc<Mutex<ExternalValues>>,
+    stop_flag: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OscInput {
+    pub fn start(port: u16, ctx: &egui::Context) -> Result<Self> {
+        let socket = UdpSocket::bind(("127.0.0.1", port)).with_context(|| format!("Failed to listen for OSC on port {}", port))?;
+        socket.set_read_timeout(Some(Duration::from_millis(100))).context("Failed to configure OSC socket")?;
+        tracing::info!(port, "Listening for OSC");
+        let values = Arc::new(Mutex::new(ExternalValues::default()));
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let values = values.clone();
+            let stop_flag = stop_flag.clone();
+            let ctx = ctx.clone();
+            move || {
+                let mut buffer = [0u8; 1536];
+                while !stop_flag.load(Ordering::Relaxed) {
+                    let Ok(len) = socket.recv(&mut buffer) else { continue };
+                    let messages: Vec<_> = crate::net::osc::parse_packet(&buffer[..len])
+                        .into_iter()
+                        .filter_map(|message| Some((message.first_level()?, message.address)))
+                        .collect();
+                    if messages.is_empty() {
+                        continue;
+                    }
+                    let mut values = values.lock().unwrap();
+                    for (value, address) in messages {
+                        values.last_received = Some(format!("OSC {} = {:.2}", address, value));
+                        values.osc.insert(address, value);
+                    }
+                    ctx.request_repaint();
+                }
+            }
+        });
+        Ok(Self { values, stop_flag, thread: Some(thread) })
+    }
+
+    pub fn values(&self) -> ExternalValues {
+        self.values.lock().unwrap().clone()
+    }
+}
+
+impl Drop for OscInput {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use eframe::egui;
+
+/// Decodes a PNG bezel/overlay. Large bezels take a while, so this runs on a worker thread.
+pub fn load_overlay_image(path: &str) -> Result<egui::ColorImage> {
+    let image = image::open(path).with_context(|| format!("Failed to open overlay image '{}'", path))?;
+    let size = [image.width() as usize, image.height() as usize];
+    let rgba = image.to_rgba8();
+    Ok(egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_flat_samples().as_slice()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextOverlayPosition {
+    Top,
+    Bottom,
+}
+
+impl TextOverlayPosition {
+    pub fn from_u8(val: u8) -> Self {
+        match val {
+            0 => TextOverlayPosition::Top,
+            _ => TextOverlayPosition::Bottom,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            TextOverlayPosition::Top => "Top",
+            TextOverlayPosition::Bottom => "Bottom",
+        }
+    }
+}
+
+/// How often the text file is checked for changes.
+const TEXT_FILE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Keeps the contents of a text file that other tools rewrite, e.g. a "Now playing" file.
+#[derive(Default)]
+pub struct TextFileSource {
+    path: String,
+    modified: Option<std::time::SystemTime>,
+    checked_at: Option<std::time::Instant>,
+    text: String,
+}
+
+impl TextFileSource {
+    /// Returns the file's current contents, re-reading it at most once per poll interval.
+    /// A missing or unreadable file shows nothing rather than an error over the video.
+    pub fn text(&mut self, path: &str) -> &str {
+        if self.path != path {
+            *self = Self { path: path.to_string(), ..Self::default() };
+        }
+        if !matches!(self.checked_at, Some(at) if at.elapsed() < TEXT_FILE_POLL_INTERVAL) {
+            self.checked_at = Some(std::time::Instant::now());
+            let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+            if modified != self.modified {
+                self.modified = modified;
+                self.text = std::fs::read_to_string(path).map(|s| s.trim().to_string()).unwrap_or_default();
+            }
+        }
+        &self.text
+    }
+}
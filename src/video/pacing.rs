@@ -0,0 +1,141 @@
+use eframe::egui;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Frames queued beyond this are dropped (oldest first) to keep latency bounded.
+const MAX_QUEUED_FRAMES: usize = 3;
+/// Smoothing factor for the frame interval averages.
+const EMA_ALPHA: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PacingMode {
+    /// Show every frame as soon as it arrives (lowest latency).
+    Immediate = 0,
+    /// Spread frames evenly over display refreshes, e.g. 2-3-2-3 for 60 fps on 144 Hz.
+    VsyncLocked = 1,
+    /// Only present on every other refresh, for 30 fps content on 60 Hz or 60 fps on 120 Hz.
+    HalfRate = 2,
+}
+
+impl PacingMode {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => PacingMode::VsyncLocked,
+            2 => PacingMode::HalfRate,
+            _ => PacingMode::Immediate,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            PacingMode::Immediate => "Immediate",
+            PacingMode::VsyncLocked => "VSync Locked",
+            PacingMode::HalfRate => "Half Refresh Rate",
+        }
+    }
+}
+
+/// Decides on which display refresh each captured frame is shown.
+/// egui doesn't expose the monitor's refresh rate, so it is estimated from the (vsynced)
+/// interval between UI frames while video keeps the UI repainting continuously.
+pub struct FramePacer {
+    queue: VecDeque<Arc<egui::ColorImage>>,
+    last_arrival: Option<Instant>,
+    source_interval: f32,
+    refresh_interval: f32,
+    /// Display time accumulated since the last presented frame, in seconds.
+    phase: f32,
+    tick: u64,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            last_arrival: None,
+            source_interval: 1.0 / 60.0,
+            refresh_interval: 1.0 / 60.0,
+            phase: 0.0,
+            tick: 0,
+        }
+    }
+}
+
+impl FramePacer {
+    pub fn push(&mut self, frame: Arc<egui::ColorImage>) {
+        let now = Instant::now();
+        if let Some(last) = self.last_arrival.replace(now) {
+            let interval = (now - last).as_secs_f32();
+            // Ignore stalls (e.g. a stream restart) so they don't skew the average.
+            if interval < 0.5 {
+                self.source_interval += (interval - self.source_interval) * EMA_ALPHA;
+            }
+        }
+        self.queue.push_back(frame);
+        while self.queue.len() > MAX_QUEUED_FRAMES {
+            self.queue.pop_front();
+        }
+    }
+
+    /// Called once per UI frame with the time since the previous one; returns the frame to show, if any.
+    pub fn next_frame(&mut self, mode: PacingMode, frame_dt: f32) -> Option<Arc<egui::ColorImage>> {
+        if frame_dt > 0.0 && frame_dt < 0.1 {
+            self.refresh_interval += (frame_dt - self.refresh_interval) * EMA_ALPHA;
+        }
+        self.tick = self.tick.wrapping_add(1);
+        match mode {
+            PacingMode::Immediate => {
+                let newest = self.queue.pop_back();
+                self.queue.clear();
+                newest
+            }
+            PacingMode::VsyncLocked => self.advance(self.refresh_interval),
+            PacingMode::HalfRate => {
+                if self.tick % 2 == 0 {
+                    self.advance(self.refresh_interval * 2.0)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn advance(&mut self, step: f32) -> Option<Arc<egui::ColorImage>> {
+        self.phase += step;
+        if self.phase < self.source_interval {
+            return None;
+        }
+        match self.queue.pop_front() {
+            Some(frame) => {
+                // Don't let a long stall build up a debt of frames to rush through.
+                self.phase = (self.phase - self.source_interval).min(self.source_interval);
+                Some(frame)
+            }
+            None => {
+                // Nothing arrived yet: repeat the current frame and present as soon as one does.
+                self.phase = self.source_interval;
+                None
+            }
+        }
+    }
+
+    /// Roughly how long a frame waits between arriving and being scanned out: behind the queued
+    /// frames, then one refresh for the buffer flip.
+    pub fn queue_latency(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f32(self.queue.len() as f32 * self.source_interval + self.refresh_interval)
+    }
+
+    pub fn refresh_rate(&self) -> f32 {
+        1.0 / self.refresh_interval
+    }
+
+    pub fn source_rate(&self) -> f32 {
+        1.0 / self.source_interval
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self { refresh_interval: self.refresh_interval, ..Self::default() };
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// The phone video is resampled to this rate, so lags are measured in steps of ~8 ms.
+/// A phone filming at 60 fps still only resolves ~17 ms.
+const ANALYSIS_FPS: u32 = 120;
+/// The video is shrunk to this grid; the pattern and the viewer only need a few cells each.
+const GRID: (usize, usize) = (32, 18);
+const MAX_SECONDS: u32 = 30;
+/// Delays searched between the two flashing screens.
+const MAX_LATENCY: Duration = Duration::from_millis(500);
+/// Cells whose brightness swings less than this (out of 255) aren't showing the pattern.
+const MIN_SWING: f32 = 60.0;
+/// How closely a cell has to follow the reference cell, from -1 to 1, to count as a copy of it.
+const MIN_CORRELATION: f32 = 0.6;
+/// The pattern repeats after this long.
+const PATTERN_PERIOD: Duration = Duration::from_secs(30);
+
+/// Whether the calibration pattern is lit `elapsed` into it. It switches at irregular
+/// intervals of 250-650 ms, so the filmed copy seen through the capture chain lines up with
+/// the direct one at exactly one delay.
+pub fn pattern_lit(elapsed: Duration) -> bool {
+    let target = (elapsed.as_millis() % PATTERN_PERIOD.as_millis()) as u64;
+    let mut seed: u32 = 0x2545_f491;
+    let mut switch_at = 0;
+    let mut lit = false;
+    loop {
+        // xorshift32, fixed seed: the same sequence every time.
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        switch_at += 250 + (seed % 400) as u64;
+        if switch_at > target {
+            return lit;
+        }
+        lit = !lit;
+    }
+}
+
+/// Measures the delay between the pattern window and its copy in the viewer, both filmed with
+/// a phone, in milliseconds.
+pub fn measure_video(path: &Path) -> Result<u32> {
+    let frames = decode_grid(path)?;
+    let latency = estimate_latency(&frames)?;
+    tracing::info!(path = %path.display(), latency_ms = latency.as_millis() as u64, "Measured photon latency");
+    Ok(latency.as_millis() as u32)
+}
+
+/// Decodes the video into small grayscale frames at `ANALYSIS_FPS`.
+fn decode_grid(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let (width, height) = GRID;
+    let mut child = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-t", &MAX_SECONDS.to_string(), "-i"])
+        .arg(path)
+        .args(["-an", "-vf", &format!("fps={},scale={}:{},format=gray", ANALYSIS_FPS, width, height), "-f", "rawvideo", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to execute 'ffmpeg'")?;
+    let mut data = Vec::new();
+    child.stdout.take().context("ffmpeg has no stdout")?.read_to_end(&mut data)?;
+    let status = child.wait()?;
+    if data.is_empty() {
+        anyhow::bail!("ffmpeg could not read '{}' ({})", path.display(), status);
+    }
+    Ok(data.chunks_exact(width * height).map(|frame| frame.to_vec()).collect())
+}
+
+/// Finds the grid cells that flash with the pattern, then the delay between the cells that
+/// follow the brightest one directly and those that follow it late.
+fn estimate_latency(frames: &[Vec<u8>]) -> Result<Duration> {
+    let max_lag = (MAX_LATENCY.as_secs_f32() * ANALYSIS_FPS as f32) as usize;
+    if frames.len() < ANALYSIS_FPS as usize * 5 {
+        anyhow::bail!("The video is too short, film the pattern for at least 5 seconds");
+    }
+    // Each flashing cell as +1/-1 per frame, with its brightness swing.
+    let mut flashing: Vec<(f32, Vec<i8>)> = (0..GRID.0 * GRID.1)
+        .filter_map(|cell| {
+            let series: Vec<f32> = frames.iter().map(|frame| frame[cell] as f32).collect();
+            let (min, max) = series.iter().fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+            let middle = (min + max) / 2.0;
+            (max - min >= MIN_SWING).then(|| (max - min, series.iter().map(|&v| if v > middle { 1 } else { -1 }).collect()))
+        })
+        .collect();
+    if flashing.len() < 2 {
+        anyhow::bail!("No flashing pattern found. Film the pattern window and the viewer together, filling most of the picture");
+    }
+    flashing.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let reference = &flashing[0].1;
+
+    let mut delayed: Vec<isize> = Vec::new();
+    for (_, series) in &flashing[1..] {
+        let (lag, correlation) = best_lag(reference, series, max_lag);
+        // Cells showing the same screen as the reference line up at (about) no delay.
+        if correlation >= MIN_CORRELATION && lag.abs() > 1 {
+            delayed.push(lag);
+        }
+    }
+    if delayed.is_empty() {
+        anyhow::bail!("Only one flashing screen found. Make sure both the pattern window and the viewer are in the video");
+    }
+    delayed.sort();
+    let median = delayed[delayed.len() / 2].unsigned_abs();
+    Ok(Duration::from_secs_f64(median as f64 / ANALYSIS_FPS as f64))
+}
+
+/// The shift of `series` against `reference`, in frames, at which they agree most, with how
+/// well they agree at that shift (1 is identical).
+fn best_lag(reference: &[i8], series: &[i8], max_lag: usize) -> (isize, f32) {
+    let len = reference.len().min(series.len());
+    let mut best = (0, f32::MIN);
+    for lag in -(max_lag as isize)..=max_lag as isize {
+        let shift = lag.unsigned_abs();
+        if shift >= len {
+            continue;
+        }
+        let (a, b) = if lag >= 0 { (&reference[..len - shift], &series[shift..len]) } else { (&reference[shift..len], &series[..len - shift]) };
+        let sum: i32 = a.iter().zip(b).map(|(x, y)| (*x as i32) * (*y as i32)).sum();
+        let correlation = sum as f32 / a.len() as f32;
+        if correlation > best.1 {
+            best = (lag, correlation);
+        }
+    }
+    best
+}
@@ -0,0 +1,98 @@
+use crate::video::types::VideoFormat;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+/// How long each mode is read for. The first frames are often slow, so it's measured after a warm-up.
+const WARM_UP: Duration = Duration::from_millis(500);
+const MEASURE: Duration = Duration::from_millis(1500);
+/// Modes delivering less than this share of the advertised rate are reported as unsustainable.
+const SUSTAINED_RATIO: f32 = 0.9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeOutcome {
+    Ok { measured_fps: f32 },
+    /// Frames arrive, but slower than advertised.
+    Slow { measured_fps: f32 },
+    NoFrames,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub format: String,
+    pub resolution: (u32, u32),
+    pub fps: u32,
+    pub outcome: ProbeOutcome,
+}
+
+/// Opens every advertised format/resolution/framerate briefly and reports which ones deliver
+/// frames, one result per mode as it finishes. Takes about two seconds per mode.
+pub fn probe_device(
+    device: &str,
+    formats: &[VideoFormat],
+    results: crossbeam_channel::Sender<ProbeResult>,
+    stop_flag: Arc<AtomicBool>,
+) {
+    if let Err(e) = ffmpeg_next::init() {
+        tracing::error!("Failed to initialize FFmpeg for probing: {}", e);
+        return;
+    }
+    for format in formats {
+        for resolution in &format.resolutions {
+            for &fps in &resolution.framerates {
+                if stop_flag.load(Ordering::Relaxed) {
+                    return;
+                }
+                let resolution = (resolution.width, resolution.height);
+                let outcome = probe_mode(device, format, resolution, fps, &stop_flag);
+                if stop_flag.load(Ordering::Relaxed) {
+                    return;
+                }
+                tracing::info!(format = %format.fourcc, ?resolution, fps, ?outcome, "Probed capture mode");
+                let result = ProbeResult { format: format.description.clone(), resolution, fps, outcome };
+                if results.send(result).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn probe_mode(device: &str, format: &VideoFormat, resolution: (u32, u32), fps: u32, stop_flag: &AtomicBool) -> ProbeOutcome {
+    let (_, mut options) = super::decoder::setup_ffmpeg_options(format, resolution, fps);
+    // Without this a mode that never delivers would block the read forever.
+    options.set("fflags", "nobuffer+discardcorrupt+nonblock");
+    let mut ictx = match ffmpeg_next::format::input_with_dictionary(&device, options) {
+        Ok(ictx) => ictx,
+        Err(e) => return ProbeOutcome::Failed(e.to_string()),
+    };
+    let Some(video_stream_index) = ictx.streams().best(ffmpeg_next::media::Type::Video).map(|s| s.index()) else {
+        return ProbeOutcome::Failed("No video stream".to_string());
+    };
+
+    let start = Instant::now();
+    let mut frames = 0u32;
+    let mut packet = ffmpeg_next::Packet::empty();
+    while start.elapsed() < WARM_UP + MEASURE && !stop_flag.load(Ordering::Relaxed) {
+        match packet.read(&mut ictx) {
+            Ok(()) => {
+                if packet.stream() == video_stream_index && start.elapsed() >= WARM_UP && !packet.is_corrupt() {
+                    frames += 1;
+                }
+            }
+            Err(ffmpeg_next::Error::Other { errno }) if errno == libc::EAGAIN => std::thread::sleep(Duration::from_millis(5)),
+            Err(e) => return ProbeOutcome::Failed(e.to_string()),
+        }
+    }
+    let measured_fps = frames as f32 / MEASURE.as_secs_f32();
+    if frames == 0 {
+        ProbeOutcome::NoFrames
+    } else if measured_fps < fps as f32 * SUSTAINED_RATIO {
+        ProbeOutcome::Slow { measured_fps }
+    } else {
+        ProbeOutcome::Ok { measured_fps }
+    }
+}
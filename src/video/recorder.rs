@@ -0,0 +1,579 @@
+use anyhow::{Context, Result};
+use eframe::egui;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// A region of the captured frame, as (x, y, width, height) in source pixels.
+pub type CropRect = (u32, u32, u32, u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TimelapseMode {
+    Off = 0,
+    /// Keep one frame every N seconds.
+    EverySeconds = 1,
+    /// Keep every Nth captured frame.
+    EveryFrames = 2,
+}
+
+impl TimelapseMode {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => TimelapseMode::EverySeconds,
+            2 => TimelapseMode::EveryFrames,
+            _ => TimelapseMode::Off,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            TimelapseMode::Off => "Off",
+            TimelapseMode::EverySeconds => "Every N seconds",
+            TimelapseMode::EveryFrames => "Every Nth frame",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum VideoEncoder {
+    /// Software H.264, available everywhere but competes with decoding for the CPU.
+    X264 = 0,
+    Vaapi = 1,
+    Nvenc = 2,
+    Qsv = 3,
+}
+
+impl VideoEncoder {
+    pub const ALL: [VideoEncoder; 4] = [VideoEncoder::X264, VideoEncoder::Vaapi, VideoEncoder::Nvenc, VideoEncoder::Qsv];
+
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => VideoEncoder::Vaapi,
+            2 => VideoEncoder::Nvenc,
+            3 => VideoEncoder::Qsv,
+            _ => VideoEncoder::X264,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            VideoEncoder::X264 => "x264 (CPU)",
+            VideoEncoder::Vaapi => "VAAPI (AMD/Intel)",
+            VideoEncoder::Nvenc => "NVENC (NVIDIA)",
+            VideoEncoder::Qsv => "Quick Sync (Intel)",
+        }
+    }
+
+    /// The encoder's name in `ffmpeg -encoders`.
+    fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            VideoEncoder::X264 => "libx264",
+            VideoEncoder::Vaapi => "h264_vaapi",
+            VideoEncoder::Nvenc => "h264_nvenc",
+            VideoEncoder::Qsv => "h264_qsv",
+        }
+    }
+
+    /// Encoder arguments for a constant-quality level (lower is better, x264 CRF scale).
+    fn args(&self, quality: u32) -> Vec<String> {
+        let q = quality.to_string();
+        let args: Vec<&str> = match self {
+            VideoEncoder::X264 => vec!["-c:v", "libx264", "-preset", "veryfast", "-crf", &q, "-pix_fmt", "yuv420p"],
+            VideoEncoder::Vaapi => vec!["-vf", "format=nv12,hwupload", "-c:v", "h264_vaapi", "-rc_mode", "CQP", "-qp", &q],
+            VideoEncoder::Nvenc => vec!["-c:v", "h264_nvenc", "-preset", "p4", "-rc", "vbr", "-cq", &q, "-b:v", "0", "-pix_fmt", "yuv420p"],
+            VideoEncoder::Qsv => vec!["-vf", "format=nv12", "-c:v", "h264_qsv", "-preset", "faster", "-global_quality", &q],
+        };
+        args.into_iter().map(String::from).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RecordQuality {
+    High = 0,
+    Balanced = 1,
+    Small = 2,
+}
+
+impl RecordQuality {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => RecordQuality::Balanced,
+            2 => RecordQuality::Small,
+            _ => RecordQuality::High,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            RecordQuality::High => "High (large files)",
+            RecordQuality::Balanced => "Balanced",
+            RecordQuality::Small => "Small files",
+        }
+    }
+
+    fn level(&self) -> u32 {
+        match self {
+            RecordQuality::High => 18,
+            RecordQuality::Balanced => 23,
+            RecordQuality::Small => 28,
+        }
+    }
+}
+
+/// The DRM render node VA-API encodes on, unless another one is configured.
+pub const DEFAULT_VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
+/// Lists the encoders the installed `ffmpeg` was built with. Whether the GPU actually
+/// supports them only shows once recording starts. Runs `ffmpeg`, so it belongs on a worker thread.
+pub fn available_encoders() -> Vec<VideoEncoder> {
+    let output = match Command::new("ffmpeg").args(["-hide_banner", "-encoders"]).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Err(e) => {
+            tracing::warn!("Failed to list ffmpeg encoders: {}", e);
+            return vec![VideoEncoder::X264];
+        }
+    };
+    VideoEncoder::ALL
+        .into_iter()
+        .filter(|encoder| output.split_whitespace().any(|word| word == encoder.ffmpeg_name()))
+        .collect()
+}
+
+/// A PulseAudio source recorded as its own audio track, so tracks can be remixed later.
+#[derive(Debug, Clone)]
+pub struct AudioTrack {
+    pub title: String,
+    pub source: String,
+    /// Linear gain, 1.0 leaves the level unchanged.
+    pub volume: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecorderSettings {
+    pub output_path: PathBuf,
+    /// Output frame rate. Input frames are timestamped on arrival and duplicated or dropped to match.
+    pub framerate: u32,
+    /// Records only this part of the frame. Independent of how the viewer displays the video.
+    pub crop: Option<CropRect>,
+    pub audio_tracks: Vec<AudioTrack>,
+    pub encoder: VideoEncoder,
+    /// DRM render node for `VideoEncoder::Vaapi`, e.g. `/dev/dri/renderD129` for a second GPU.
+    pub vaapi_device: String,
+    pub quality: RecordQuality,
+    /// In time-lapse mode kept frames are played back at `framerate` and audio is not recorded.
+    pub timelapse: TimelapseMode,
+    pub timelapse_interval: u32,
+}
+
+/// Records frames to a file by piping raw RGBA into the `ffmpeg` command line tool, which
+/// also grabs the audio tracks straight from PulseAudio.
+pub struct Recorder {
+    frame_sender: crossbeam_channel::Sender<Arc<egui::ColorImage>>,
+    stop_flag: Arc<AtomicBool>,
+    frames_written: Arc<AtomicU64>,
+    started_at: Instant,
+    output_path: PathBuf,
+    thread: Option<JoinHandle<Result<()>>>,
+    /// Chapter start times in the output timeline.
+    chapters: Vec<Duration>,
+    /// Time-lapse output runs at a different speed than the wall clock.
+    timelapse_framerate: Option<u32>,
+}
+
+impl Recorder {
+    pub fn start(settings: RecorderSettings) -> Result<Self> {
+        if let Some(dir) = settings.output_path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+        }
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let frames_written = Arc::new(AtomicU64::new(0));
+        // A few frames of slack so a slow encoder start doesn't drop the first frames.
+        let (frame_sender, frame_receiver) = crossbeam_channel::bounded::<Arc<egui::ColorImage>>(8);
+        let output_path = settings.output_path.clone();
+        let timelapse_framerate = (settings.timelapse != TimelapseMode::Off).then_some(settings.framerate.max(1));
+
+        let thread = thread::Builder::new().name("recorder".to_string()).spawn({
+            let stop_flag = stop_flag.clone();
+            let frames_written = frames_written.clone();
+            move || writer_thread_main(settings, frame_receiver, stop_flag, frames_written)
+        }).context("Failed to start the recording thread")?;
+        tracing::info!(path = %output_path.display(), "Recording started");
+        if let Err(e) = write_journal(&output_path) {
+            tracing::warn!("Failed to write the recording journal: {:#}", e);
+        }
+        Ok(Self {
+            frame_sender,
+            stop_flag,
+            frames_written,
+            started_at: Instant::now(),
+            output_path,
+            thread: Some(thread),
+            chapters: Vec::new(),
+            timelapse_framerate,
+        })
+    }
+
+    /// Queues a frame for encoding. Dropped if the encoder can't keep up.
+    pub fn push_frame(&self, image: Arc<egui::ColorImage>) {
+        if self.frame_sender.try_send(image).is_err() {
+            tracing::debug!("Recorder queue full, dropping frame");
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written.load(Ordering::Relaxed)
+    }
+
+    pub fn output_path(&self) -> &std::path::Path {
+        &self.output_path
+    }
+
+    /// Position in the recorded video, which differs from `elapsed` in time-lapse mode.
+    pub fn position(&self) -> Duration {
+        match self.timelapse_framerate {
+            Some(fps) => Duration::from_secs_f64(self.frames_written() as f64 / fps as f64),
+            None => self.elapsed(),
+        }
+    }
+
+    /// Marks the start of a new chapter at the current position and returns its number.
+    pub fn add_chapter(&mut self) -> usize {
+        self.chapters.push(self.position());
+        self.chapters.len()
+    }
+
+    pub fn chapter_count(&self) -> usize {
+        self.chapters.len()
+    }
+
+    /// True once the writer has stopped on its own, e.g. because ffmpeg exited.
+    pub fn has_failed(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| t.is_finished())
+    }
+
+    /// Finishes the file and returns where it was written.
+    pub fn stop(mut self) -> Result<PathBuf> {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        let end = self.position();
+        if let Some(handle) = self.thread.take() {
+            handle.join().map_err(|_| anyhow::anyhow!("Recorder thread panicked"))??;
+        }
+        tracing::info!(path = %self.output_path.display(), frames = self.frames_written(), "Recording finished");
+        if !self.chapters.is_empty() {
+            write_chapters(&self.output_path, &self.chapters, end)?;
+        }
+        Ok(self.output_path.clone())
+    }
+}
+
+/// Builds an ffmpeg metadata file with one chapter per marker, plus one for the part before
+/// the first marker.
+fn chapter_metadata(markers: &[Duration], end: Duration) -> String {
+    let mut starts: Vec<u128> = markers.iter().map(|d| d.as_millis()).collect();
+    let has_start_chapter = starts.first() != Some(&0);
+    if has_start_chapter {
+        starts.insert(0, 0);
+    }
+    let end = end.as_millis().max(starts.last().copied().unwrap_or(0) + 1);
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for (index, start) in starts.iter().enumerate() {
+        let chapter_end = starts.get(index + 1).copied().unwrap_or(end);
+        let title = match (has_start_chapter, index) {
+            (true, 0) => "Start".to_string(),
+            (true, _) => format!("Marker {}", index),
+            (false, _) => format!("Marker {}", index + 1),
+        };
+        metadata.push_str(&format!("[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n", start, chapter_end, title));
+    }
+    metadata
+}
+
+/// Muxes the chapters into the finished file. The metadata is kept as a sidecar file if that
+/// fails, so the markers aren't lost.
+fn write_chapters(path: &std::path::Path, markers: &[Duration], end: Duration) -> Result<()> {
+    let sidecar = path.with_extension("chapters.txt");
+    std::fs::write(&sidecar, chapter_metadata(markers, end)).with_context(|| format!("Failed to write '{}'", sidecar.display()))?;
+    let remuxed = path.with_extension("chapters.mkv");
+    let status = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(path)
+        .arg("-i")
+        .arg(&sidecar)
+        .args(["-map", "0", "-map_chapters", "1", "-c", "copy"])
+        .arg(&remuxed)
+        .status()
+        .context("Failed to execute 'ffmpeg' to add chapters")?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&remuxed);
+        anyhow::bail!("Failed to add chapters, they were saved to '{}'", sidecar.display());
+    }
+    std::fs::rename(&remuxed, path).context("Failed to replace recording with the chaptered file")?;
+    let _ = std::fs::remove_file(&sidecar);
+    Ok(())
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.thread.take() {
+            let _ = handle.join();
+        }
+        // ffmpeg has finished the file, whether or not it succeeded.
+        remove_journal();
+    }
+}
+
+/// Holds the path of the recording in progress. Left behind if the app dies mid-recording,
+/// so the next launch can offer to recover the file.
+fn journal_path() -> Result<PathBuf> {
+    let config_path = confy::get_configuration_file_path("michadame", None).context("Failed to locate config directory")?;
+    Ok(config_path.parent().context("Config path has no parent directory")?.join("recording.journal"))
+}
+
+fn write_journal(recording: &std::path::Path) -> Result<()> {
+    let path = journal_path()?;
+    std::fs::write(&path, recording.to_string_lossy().as_bytes()).with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+pub fn remove_journal() {
+    if let Ok(path) = journal_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// The recording a previous run didn't get to finish, if its file still has anything in it.
+pub fn unfinished_recording() -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(journal_path().ok()?).ok()?;
+    let recording = PathBuf::from(contents.trim());
+    if std::fs::metadata(&recording).is_ok_and(|m| m.len() > 0) {
+        Some(recording)
+    } else {
+        remove_journal();
+        None
+    }
+}
+
+/// Remuxes what ffmpeg managed to write of an interrupted recording into a new, properly
+/// closed file next to it. Matroska is written in self-contained clusters, so everything up to
+/// the last complete one survives.
+pub fn recover_recording(path: &std::path::Path) -> Result<PathBuf> {
+    let dir = path.parent().unwrap_or(std::path::Path::new("."));
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "recording".to_string());
+    let recovered = unique_output_path(dir, &format!("{}_recovered", stem), FILE_EXTENSION);
+    let status = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-err_detect", "ignore_err", "-i"])
+        .arg(path)
+        .args(["-map", "0", "-c", "copy"])
+        .arg(&recovered)
+        .status()
+        .context("Failed to execute 'ffmpeg' to recover the recording")?;
+    // ffmpeg reports the truncated end as an error even when everything before it was saved.
+    if !std::fs::metadata(&recovered).is_ok_and(|m| m.len() > 0) {
+        let _ = std::fs::remove_file(&recovered);
+        anyhow::bail!("Nothing could be recovered from '{}' (ffmpeg exited with {})", path.display(), status);
+    }
+    tracing::info!(path = %recovered.display(), "Recovered interrupted recording");
+    Ok(recovered)
+}
+
+/// Clamps the crop to the frame and rounds it to even sizes, which yuv420p requires.
+fn effective_region(crop: Option<CropRect>, frame_size: (u32, u32)) -> CropRect {
+    let (frame_w, frame_h) = frame_size;
+    let (x, y, w, h) = crop.unwrap_or((0, 0, frame_w, frame_h));
+    let x = x.min(frame_w.saturating_sub(2));
+    let y = y.min(frame_h.saturating_sub(2));
+    let w = w.min(frame_w - x).max(2) & !1;
+    let h = h.min(frame_h - y).max(2) & !1;
+    (x, y, w, h)
+}
+
+fn writer_thread_main(
+    settings: RecorderSettings,
+    frame_receiver: crossbeam_channel::Receiver<Arc<egui::ColorImage>>,
+    stop_flag: Arc<AtomicBool>,
+    frames_written: Arc<AtomicU64>,
+) -> Result<()> {
+    // The encoder is only spawned once the first frame tells us the input size.
+    let mut encoder: Option<(Child, ChildStdin, (u32, u32), CropRect)> = None;
+    let mut row_buffer = Vec::new();
+    let mut frames_seen: u64 = 0;
+    let mut last_kept: Option<Instant> = None;
+    let interval = settings.timelapse_interval.max(1);
+    let result = loop {
+        if stop_flag.load(Ordering::Relaxed) {
+            break Ok(());
+        }
+        let Ok(image) = frame_receiver.recv_timeout(Duration::from_millis(100)) else { continue };
+        frames_seen += 1;
+        let keep = match settings.timelapse {
+            TimelapseMode::Off => true,
+            TimelapseMode::EveryFrames => (frames_seen - 1).is_multiple_of(interval as u64),
+            TimelapseMode::EverySeconds => last_kept.is_none_or(|t| t.elapsed() >= Duration::from_secs(interval as u64)),
+        };
+        if !keep {
+            continue;
+        }
+        last_kept = Some(Instant::now());
+        let frame_size = (image.size[0] as u32, image.size[1] as u32);
+        if encoder.is_none() {
+            let region = effective_region(settings.crop, frame_size);
+            match spawn_ffmpeg(&settings, region) {
+                Ok((child, stdin)) => encoder = Some((child, stdin, frame_size, region)),
+                Err(e) => break Err(e),
+            }
+        }
+        let Some((_, stdin, input_size, region)) = encoder.as_mut() else { continue };
+        if frame_size != *input_size {
+            tracing::warn!(?frame_size, ?input_size, "Frame size changed during recording, skipping frame");
+            continue;
+        }
+
+        let (x, y, w, h) = *region;
+        let mut write_result = Ok(());
+        for row in y..y + h {
+            let start = (row * frame_size.0 + x) as usize;
+            row_buffer.clear();
+            row_buffer.extend_from_slice(bytemuck::cast_slice(&image.pixels[start..start + w as usize]));
+            write_result = stdin.write_all(&row_buffer);
+            if write_result.is_err() {
+                break;
+            }
+        }
+        if let Err(e) = write_result {
+            break Err(anyhow::Error::new(e).context("ffmpeg stopped accepting frames"));
+        }
+        frames_written.fetch_add(1, Ordering::Relaxed);
+    };
+
+    if let Some((mut child, stdin, _, _)) = encoder {
+        // Closing stdin lets ffmpeg flush and write the container trailer.
+        drop(stdin);
+        let status = child.wait().context("Failed to wait for ffmpeg")?;
+        if !status.success() && result.is_ok() {
+            anyhow::bail!("ffmpeg exited with {}", status);
+        }
+    }
+    if let Err(e) = &result {
+        tracing::error!("Recording failed: {:#}", e);
+    }
+    result
+}
+
+fn spawn_ffmpeg(settings: &RecorderSettings, region: CropRect) -> Result<(Child, ChildStdin)> {
+    let (_, _, width, height) = region;
+    let framerate = settings.framerate.max(1).to_string();
+    let timelapse = settings.timelapse != TimelapseMode::Off;
+    let audio_tracks: &[AudioTrack] = if timelapse { &[] } else { &settings.audio_tracks };
+    let mut command = Command::new("ffmpeg");
+    command.args(["-hide_banner", "-loglevel", "error", "-y"]);
+    if settings.encoder == VideoEncoder::Vaapi {
+        command.args(["-vaapi_device", &settings.vaapi_device]);
+    }
+    if timelapse {
+        // Every kept frame becomes one output frame.
+        command.args(["-framerate", &framerate]);
+    } else {
+        command.args(["-use_wallclock_as_timestamps", "1"]);
+    }
+    command
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{}x{}", width, height), "-i", "-"]);
+    for track in audio_tracks {
+        command.args(["-f", "pulse", "-i", &track.source]);
+    }
+    command
+        .args(["-map", "0:v"])
+        .args(settings.encoder.args(settings.quality.level()))
+        .args(["-r", &framerate]);
+    for (index, track) in audio_tracks.iter().enumerate() {
+        command
+            .args(["-map", &format!("{}:a", index + 1)])
+            .args([format!("-filter:a:{}", index), format!("volume={:.2}", track.volume)])
+            .args([format!("-metadata:s:a:{}", index), format!("title={}", track.title)]);
+    }
+    if !audio_tracks.is_empty() {
+        command.args(["-c:a", "aac", "-b:a", "192k"]);
+    }
+    // Short, immediately flushed clusters keep the loss small if the app dies mid-recording.
+    command
+        .args(["-flush_packets", "1", "-cluster_time_limit", "1000"])
+        .arg(&settings.output_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null());
+    let mut child = command.spawn().context("Failed to execute 'ffmpeg'. Is the ffmpeg command line tool installed?")?;
+    let stdin = child.stdin.take().context("Failed to open ffmpeg input")?;
+    Ok((child, stdin))
+}
+
+pub const DEFAULT_FILE_TEMPLATE: &str = "michadame_{date}_{time}";
+pub const FILE_EXTENSION: &str = "mkv";
+
+/// Values for the placeholders in a file name template.
+pub struct FileNameFields<'a> {
+    /// The active look, or "default".
+    pub profile: &'a str,
+    pub resolution: (u32, u32),
+    /// Short name of the video source, e.g. `video0` or the file name.
+    pub source: &'a str,
+}
+
+/// Current local time as (`YYYY-MM-DD`, `HH-MM-SS`).
+pub fn local_date_time() -> (String, String) {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    (
+        format!("{:04}-{:02}-{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday),
+        format!("{:02}-{:02}-{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec),
+    )
+}
+
+/// Keeps names portable: anything that isn't alphanumeric, `-`, `_` or `.` becomes `_`.
+fn sanitize_file_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' }).collect()
+}
+
+/// Expands `{date}`, `{time}`, `{profile}`, `{res}` and `{source}` in `template`.
+pub fn expand_file_template(template: &str, fields: &FileNameFields) -> String {
+    let (date, time) = local_date_time();
+    let expanded = template
+        .replace("{date}", &date)
+        .replace("{time}", &time)
+        .replace("{profile}", fields.profile)
+        .replace("{res}", &format!("{}x{}", fields.resolution.0, fields.resolution.1))
+        .replace("{source}", fields.source);
+    let name = sanitize_file_name(expanded.trim());
+    if name.is_empty() { "recording".to_string() } else { name }
+}
+
+/// `dir/stem.extension`, or the first free `dir/stem_N.extension` if that already exists.
+pub fn unique_output_path(dir: &std::path::Path, stem: &str, extension: &str) -> PathBuf {
+    let path = dir.join(format!("{}.{}", stem, extension));
+    if !path.exists() {
+        return path;
+    }
+    (2..)
+        .map(|n| dir.join(format!("{}_{}.{}", stem, n, extension)))
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+/// `~/Videos`, falling back to the home directory.
+pub fn default_output_dir() -> PathBuf {
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".to_string()));
+    let videos = home.join("Videos");
+    if videos.is_dir() { videos } else { home }
+}
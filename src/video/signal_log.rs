@@ -0,0 +1,185 @@
+use eframe::egui;
+use std::time::{Duration, Instant};
+
+/// Pixels at or below this luma (out of 255) count as black, as in `autocrop`.
+const BLACK_LEVEL: f32 = 24.0;
+/// A frame with at most this share of brighter pixels is black, allowing for noise.
+const MAX_BRIGHT_SHARE: f32 = 0.02;
+/// Only every this many pixels are looked at. Odd, so it doesn't line up with columns.
+const SAMPLE_STEP: usize = 17;
+/// No frame from the capture card for this long counts as a signal drop.
+const DROP_TIMEOUT: Duration = Duration::from_millis(500);
+/// Events kept per session; later ones are only counted.
+const MAX_EVENTS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignalEventKind {
+    /// A run of black frames, e.g. an HDMI handshake or a loose cable.
+    BlackFrames { frames: u32 },
+    /// The card stopped delivering frames.
+    SignalDrop,
+    /// The stream stopped with an error.
+    StreamFailure(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SignalEvent {
+    /// Local time it was noticed, as `YYYY-MM-DD HH:MM:SS`.
+    pub timestamp: String,
+    /// When it began, counted from the start of the session.
+    pub session_time: Duration,
+    pub kind: SignalEventKind,
+    /// How long it lasted, `None` while it's ongoing and for failures.
+    pub duration: Option<Duration>,
+}
+
+impl SignalEvent {
+    pub fn describe(&self) -> String {
+        let what = match &self.kind {
+            SignalEventKind::BlackFrames { frames } => format!("Black frames ({})", frames),
+            SignalEventKind::SignalDrop => "Signal drop".to_string(),
+            SignalEventKind::StreamFailure(message) => format!("Stream failed: {}", message),
+        };
+        let duration = match (&self.kind, self.duration) {
+            (SignalEventKind::StreamFailure(_), _) => String::new(),
+            (_, Some(duration)) => format!(", {:.2} s", duration.as_secs_f32()),
+            (_, None) => ", ongoing".to_string(),
+        };
+        let session = self.session_time.as_secs();
+        format!("{} (+{}:{:02}:{:02}) {}{}", self.timestamp, session / 3600, session / 60 % 60, session % 60, what, duration)
+    }
+}
+
+fn is_black_frame(image: &egui::ColorImage) -> bool {
+    let (mut total, mut bright) = (0usize, 0usize);
+    for pixel in image.pixels.iter().step_by(SAMPLE_STEP) {
+        total += 1;
+        let luma = 0.2126 * pixel.r() as f32 + 0.7152 * pixel.g() as f32 + 0.0722 * pixel.b() as f32;
+        bright += usize::from(luma > BLACK_LEVEL);
+    }
+    total > 0 && bright as f32 <= total as f32 * MAX_BRIGHT_SHARE
+}
+
+fn local_timestamp() -> String {
+    let (date, time) = super::recorder::local_date_time();
+    format!("{} {}", date, time.replace('-', ":"))
+}
+
+/// Records black frames and signal drops of the capture over a session, for telling flaky
+/// cables and handshake problems apart from the app over hours of streaming.
+pub struct SignalLog {
+    started: Instant,
+    events: Vec<SignalEvent>,
+    /// Events that didn't fit in `MAX_EVENTS`.
+    missed: usize,
+    last_frame: Option<Instant>,
+    /// Indices of the black run and the drop in progress, `usize::MAX` if they weren't kept.
+    black_run: Option<usize>,
+    signal_drop: Option<usize>,
+}
+
+impl Default for SignalLog {
+    fn default() -> Self {
+        Self { started: Instant::now(), events: Vec::new(), missed: 0, last_frame: None, black_run: None, signal_drop: None }
+    }
+}
+
+impl SignalLog {
+    fn record(&mut self, kind: SignalEventKind, began: Instant) -> Option<usize> {
+        if self.events.len() == MAX_EVENTS {
+            self.missed += 1;
+            return None;
+        }
+        let event = SignalEvent { timestamp: local_timestamp(), session_time: began.saturating_duration_since(self.started), kind, duration: None };
+        tracing::info!("Signal event: {}", event.describe());
+        self.events.push(event);
+        Some(self.events.len() - 1)
+    }
+
+    fn finish(&mut self, index: Option<usize>, now: Instant) {
+        if let Some(event) = index.and_then(|index| self.events.get_mut(index)) {
+            event.duration = Some(now.saturating_duration_since(self.started).saturating_sub(event.session_time));
+            tracing::info!("Signal event ended: {}", event.describe());
+        }
+    }
+
+    /// Called with every frame from the capture card.
+    pub fn push_frame(&mut self, image: &egui::ColorImage) {
+        let now = Instant::now();
+        // In case the UI wasn't repainted during the gap.
+        self.check();
+        if let Some(index) = self.signal_drop.take() {
+            self.finish(Some(index), now);
+        }
+        self.last_frame = Some(now);
+        match (is_black_frame(image), self.black_run) {
+            (true, None) => self.black_run = Some(self.record(SignalEventKind::BlackFrames { frames: 1 }, now).unwrap_or(usize::MAX)),
+            (true, Some(index)) => {
+                if let Some(SignalEventKind::BlackFrames { frames }) = self.events.get_mut(index).map(|event| &mut event.kind) {
+                    *frames += 1;
+                }
+            }
+            (false, Some(index)) => {
+                self.finish(Some(index), now);
+                self.black_run = None;
+            }
+            (false, None) => {}
+        }
+    }
+
+    /// Called every UI frame while capturing, to notice frames no longer arriving.
+    pub fn check(&mut self) {
+        if self.signal_drop.is_some() {
+            return;
+        }
+        if let Some(last) = self.last_frame.filter(|last| last.elapsed() > DROP_TIMEOUT) {
+            // Kept as in progress even when the log is full, so it's only counted once.
+            self.signal_drop = Some(self.record(SignalEventKind::SignalDrop, last).unwrap_or(usize::MAX));
+        }
+    }
+
+    /// Ends whatever is in progress; a stopped stream isn't a signal drop.
+    pub fn stream_stopped(&mut self) {
+        let now = Instant::now();
+        let (black_run, signal_drop) = (self.black_run.take(), self.signal_drop.take());
+        self.finish(black_run, now);
+        self.finish(signal_drop, now);
+        self.last_frame = None;
+    }
+
+    pub fn stream_failed(&mut self, message: &str) {
+        self.stream_stopped();
+        self.record(SignalEventKind::StreamFailure(message.to_string()), Instant::now());
+    }
+
+    pub fn events(&self) -> &[SignalEvent] {
+        &self.events
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Counts and the full list, as text.
+    pub fn report(&self) -> String {
+        let count = |matches: fn(&SignalEventKind) -> bool| self.events.iter().filter(|event| matches(&event.kind)).count();
+        let session = self.started.elapsed().as_secs();
+        let mut report = format!(
+            "Signal events over {}:{:02}:{:02}: {} black frame runs, {} signal drops, {} stream failures\n",
+            session / 3600,
+            session / 60 % 60,
+            session % 60,
+            count(|kind| matches!(kind, SignalEventKind::BlackFrames { .. })),
+            count(|kind| *kind == SignalEventKind::SignalDrop),
+            count(|kind| matches!(kind, SignalEventKind::StreamFailure(_))),
+        );
+        for event in &self.events {
+            report += &event.describe();
+            report.push('\n');
+        }
+        if self.missed > 0 {
+            report += &format!("... and {} more not kept\n", self.missed);
+        }
+        report
+    }
+}
@@ -0,0 +1,169 @@
+use eframe::egui;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// `crop` (x, y, width, height) clamped to an image of `size`. `None` if nothing of the crop
+/// lies inside the image.
+fn clamp_crop(size: [usize; 2], crop: (u32, u32, u32, u32)) -> Option<(usize, usize, usize, usize)> {
+    let [width, height] = size;
+    let (x, y) = (crop.0 as usize, crop.1 as usize);
+    let w = (crop.2 as usize).min(width.saturating_sub(x));
+    let h = (crop.3 as usize).min(height.saturating_sub(y));
+    (w > 0 && h > 0).then_some((x, y, w, h))
+}
+
+/// Copies `crop` (x, y, width, height) out of `image`, clamped to its size. `None` if nothing
+/// of the crop lies inside the image.
+pub fn crop_image(image: &egui::ColorImage, crop: (u32, u32, u32, u32)) -> Option<egui::ColorImage> {
+    let width = image.size[0];
+    let (x, y, w, h) = clamp_crop(image.size, crop)?;
+    let mut pixels = Vec::with_capacity(w * h);
+    for row in y..y + h {
+        pixels.extend_from_slice(&image.pixels[row * width + x..row * width + x + w]);
+    }
+    Some(egui::ColorImage { size: [w, h], pixels })
+}
+
+/// The part of the video texture that is shown. The display crop is drawn by sampling only this
+/// part, so frames are uploaded whole instead of being copied on the CPU first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceRegion {
+    pub texture_size: (u32, u32),
+    /// (x, y, width, height) in texels, within the texture.
+    pub crop: Option<(u32, u32, u32, u32)>,
+}
+
+impl SourceRegion {
+    /// The part of a `texture_size` frame to show for the display crop `crop`, the whole frame
+    /// if it's `None` or lies outside the frame.
+    pub fn new(texture_size: [usize; 2], crop: Option<(u32, u32, u32, u32)>) -> Self {
+        let crop = crop.and_then(|crop| clamp_crop(texture_size, crop)).map(|(x, y, w, h)| (x as u32, y as u32, w as u32, h as u32));
+        Self { texture_size: (texture_size[0] as u32, texture_size[1] as u32), crop }
+    }
+
+    /// Size of the shown part in texels.
+    pub fn size(&self) -> (u32, u32) {
+        self.crop.map_or(self.texture_size, |(_, _, w, h)| (w, h))
+    }
+
+    /// The shown part in texture coordinates, with (0, 0) at the top left of the frame.
+    pub fn uv(&self) -> egui::Rect {
+        let Some((x, y, w, h)) = self.crop else { return egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)) };
+        let (width, height) = (self.texture_size.0 as f32, self.texture_size.1 as f32);
+        egui::Rect::from_min_size(egui::pos2(x as f32 / width, y as f32 / height), egui::vec2(w as f32 / width, h as f32 / height))
+    }
+}
+
+/// The latest frame for the direct upload path, with when it was handed over.
+pub type DirectFrame = Arc<Mutex<Option<(Arc<egui::ColorImage>, Instant)>>>;
+
+/// Averages of how long frames take from `VideoTexture::upload` until a paint callback draws
+/// them, per upload path, in milliseconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UploadStats {
+    pub egui_ms: Option<f32>,
+    pub direct_ms: Option<f32>,
+    /// Time spent in `glTexSubImage2D` on the direct path.
+    pub direct_upload_ms: Option<f32>,
+}
+
+impl UploadStats {
+    fn blend(average: &mut Option<f32>, sample: Duration) {
+        let sample = sample.as_secs_f32() * 1000.0;
+        *average = Some(average.map_or(sample, |average| average * 0.95 + sample * 0.05));
+    }
+
+    pub fn record_egui(&mut self, latency: Duration) {
+        Self::blend(&mut self.egui_ms, latency);
+    }
+
+    pub fn record_direct(&mut self, latency: Duration, upload: Duration) {
+        Self::blend(&mut self.direct_ms, latency);
+        Self::blend(&mut self.direct_upload_ms, upload);
+    }
+}
+
+/// The video texture. Frames uploaded during a UI frame are drawn in that same frame.
+///
+/// In direct mode frames skip egui's texture manager: `upload` only hands the frame to the
+/// paint callback, which copies it into the filter renderer's own texture and draws it in the
+/// same frame.
+pub struct VideoTexture {
+    texture: egui::TextureHandle,
+    /// When the texture was uploaded, until it's first drawn.
+    uploaded_at: Option<Instant>,
+    direct: Option<DirectFrame>,
+    direct_size: [usize; 2],
+    pub stats: Arc<Mutex<UploadStats>>,
+}
+
+impl VideoTexture {
+    /// Pre-allocates the texture to prevent panics before the first frame arrives.
+    pub fn new(ctx: &egui::Context) -> Self {
+        let tex_manager = ctx.tex_manager();
+        let tex_id = tex_manager.write().alloc(
+            "video_stream".to_string(),
+            egui::ImageData::Color(egui::ColorImage::new([1, 1], egui::Color32::BLACK).into()),
+            egui::TextureOptions::LINEAR,
+        );
+        Self {
+            texture: egui::TextureHandle::new(tex_manager, tex_id),
+            uploaded_at: None,
+            direct: None,
+            direct_size: [1, 1],
+            stats: Arc::default(),
+        }
+    }
+
+    /// Switches between the direct and the egui upload path. Takes effect with the next frame.
+    pub fn set_direct(&mut self, enabled: bool) {
+        if enabled != self.direct.is_some() {
+            self.direct = enabled.then(DirectFrame::default);
+            self.direct_size = self.texture.size();
+        }
+    }
+
+    /// The frame slot read by the paint callback, in direct mode.
+    pub fn direct_frame(&self) -> Option<&DirectFrame> {
+        self.direct.as_ref()
+    }
+
+    /// Size of the frame being shown.
+    pub fn size(&self) -> [usize; 2] {
+        if self.direct.is_some() { self.direct_size } else { self.texture.size() }
+    }
+
+    /// The part of the frame to show for the display crop `crop`.
+    pub fn region(&self, crop: Option<(u32, u32, u32, u32)>) -> SourceRegion {
+        SourceRegion::new(self.size(), crop)
+    }
+
+    /// When the texture was uploaded, if it hasn't been drawn yet.
+    pub fn take_upload_time(&mut self) -> Option<Instant> {
+        self.uploaded_at.take()
+    }
+
+    pub fn texture(&self) -> &egui::TextureHandle {
+        &self.texture
+    }
+
+    pub fn upload(&mut self, image: Arc<egui::ColorImage>) {
+        if let Some(direct) = &self.direct {
+            self.direct_size = image.size;
+            *direct.lock().unwrap() = Some((image, Instant::now()));
+            return;
+        }
+        self.texture.set(image, egui::TextureOptions::LINEAR);
+        self.uploaded_at = Some(Instant::now());
+    }
+
+    /// Resets the texture to a black screen instead of removing it.
+    pub fn clear(&mut self) {
+        self.texture.set(egui::ImageData::Color(egui::ColorImage::new([1, 1], egui::Color32::BLACK).into()), egui::TextureOptions::LINEAR);
+        self.uploaded_at = None;
+        if let Some(direct) = &self.direct {
+            *direct.lock().unwrap() = Some((Arc::new(egui::ColorImage::new([1, 1], egui::Color32::BLACK)), Instant::now()));
+            self.direct_size = [1, 1];
+        }
+    }
+}
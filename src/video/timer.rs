@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerSource {
+    /// The built-in stopwatch, controlled by the hotkeys.
+    Builtin,
+    /// The current time of a LiveSplit Server; the hotkeys control LiveSplit's timer.
+    LiveSplit,
+}
+
+impl TimerSource {
+    pub fn from_u8(val: u8) -> Self {
+        match val {
+            1 => TimerSource::LiveSplit,
+            _ => TimerSource::Builtin,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            TimerSource::Builtin => "Built-in",
+            TimerSource::LiveSplit => "LiveSplit Server",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Stopwatch {
+    running_since: Option<Instant>,
+    accumulated: Duration,
+}
+
+impl Stopwatch {
+    pub fn is_running(&self) -> bool {
+        self.running_since.is_some()
+    }
+
+    /// Starts the stopwatch, or pauses it while running.
+    pub fn toggle(&mut self) {
+        match self.running_since.take() {
+            Some(since) => self.accumulated += since.elapsed(),
+            None => self.running_since = Some(Instant::now()),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.accumulated + self.running_since.map(|since| since.elapsed()).unwrap_or_default()
+    }
+}
+
+/// Formats like speedrun timers do: `1:02:03.45`, `2:03.45` or `3.45`.
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    let (hours, minutes, seconds, centis) = (centis / 360_000, centis / 6000 % 60, centis / 100 % 60, centis % 100);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}.{:02}", hours, minutes, seconds, centis)
+    } else if minutes > 0 {
+        format!("{}:{:02}.{:02}", minutes, seconds, centis)
+    } else {
+        format!("{}.{:02}", seconds, centis)
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{app::AppState, config::MichadameConfig};
+use crate::{app::AppState, config::MichadameConfig, video::decoder::PlaybackControl};
+use std::sync::{atomic::{AtomicBool, AtomicI64, AtomicU64}, Arc, Mutex};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Resolution {
@@ -14,6 +15,135 @@ pub struct VideoFormat {
     pub resolutions: Vec<Resolution>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SourceKind {
+    /// A local V4L2 capture device.
+    Capture = 0,
+    /// Any URL ffmpeg can open (RTSP/RTP/SRT/HTTP MJPEG).
+    Network = 1,
+    /// A local video or image file, played back in real time.
+    File = 2,
+    /// A window or monitor picked through the xdg-desktop-portal screencast dialog.
+    Screen = 3,
+}
+
+impl SourceKind {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => SourceKind::Network,
+            2 => SourceKind::File,
+            3 => SourceKind::Screen,
+            _ => SourceKind::Capture,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            SourceKind::Capture => "Capture Device",
+            SourceKind::Network => "Network Stream",
+            SourceKind::File => "Video / Image File",
+            SourceKind::Screen => "Window / Screen Capture",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ThreadPriority {
+    Normal = 0,
+    /// A lower nice value. Needs CAP_SYS_NICE or a raised `RLIMIT_NICE`.
+    High = 1,
+    /// SCHED_RR, falling back to `High` when the system doesn't allow it.
+    Realtime = 2,
+}
+
+impl ThreadPriority {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ThreadPriority::High,
+            2 => ThreadPriority::Realtime,
+            _ => ThreadPriority::Normal,
+        }
+    }
+
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            ThreadPriority::Normal => "Normal",
+            ThreadPriority::High => "High",
+            ThreadPriority::Realtime => "Realtime (SCHED_RR)",
+        }
+    }
+}
+
+/// Everything the video thread needs to know to open its input.
+#[derive(Debug, Clone)]
+pub struct CaptureSettings {
+    pub source_kind: SourceKind,
+    /// Device path for capture devices, URL for network streams, path for files.
+    pub input: String,
+    pub format: VideoFormat,
+    pub resolution: (u32, u32),
+    pub framerate: u32,
+    pub playback: Arc<PlaybackControl>,
+    /// Shared with the UI so the power saver can be toggled while streaming.
+    pub power_saver: Arc<AtomicBool>,
+    pub info_sender: crossbeam_channel::Sender<StreamInfo>,
+    pub auto_downscale: Arc<AtomicBool>,
+    /// Size of the video area in physical pixels, packed with `pack_size`, for auto downscale.
+    pub display_size: Arc<AtomicU64>,
+    pub format_override: FormatOverride,
+    /// Applied to the decoding and packet reading threads.
+    pub thread_priority: ThreadPriority,
+    /// How long the capture buffer read last waited in the driver's queue, in microseconds, or
+    /// -1 if unknown. See `decoder::buffer_age_us`.
+    pub buffer_age_us: Arc<AtomicI64>,
+    /// Shared with the UI so the color correction can be changed while streaming.
+    pub color_adjust: Arc<Mutex<super::color::ColorAdjust>>,
+}
+
+/// Strings passed to ffmpeg's v4l2 input as-is, instead of the ones derived from the fourcc,
+/// for cards whose V4L2 fourcc doesn't match what ffmpeg expects.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormatOverride {
+    pub input_format: Option<String>,
+    pub pixel_format: Option<String>,
+}
+
+pub fn pack_size(size: (u32, u32)) -> u64 {
+    ((size.0 as u64) << 32) | size.1 as u64
+}
+
+pub fn unpack_size(packed: u64) -> (u32, u32) {
+    ((packed >> 32) as u32, packed as u32)
+}
+
+/// What ffmpeg actually negotiated, since capture cards don't always honor the requested settings.
+#[derive(Debug, Clone, Default)]
+pub struct StreamInfo {
+    pub container: String,
+    pub codec: String,
+    pub resolution: (u32, u32),
+    pub pixel_format: String,
+    pub color_range: String,
+    pub color_space: String,
+    pub frame_rate: String,
+    pub time_base: String,
+    /// Framerate asked of the capture card, `None` for sources that play at their own rate.
+    pub requested_fps: Option<u32>,
+    /// The stream's average framerate as reported by ffmpeg, if known.
+    pub device_fps: Option<f64>,
+}
+
+/// Common pixel aspect ratios of retro sources, as (label, width, height).
+pub const PAR_PRESETS: &[(&str, u32, u32)] = &[
+    ("Square (1:1)", 1, 1),
+    ("SNES / NES (8:7)", 8, 7),
+    ("Genesis H32 (10:7)", 10, 7),
+    ("Genesis H40 (32:35)", 32, 35),
+    ("NTSC DV (10:11)", 10, 11),
+];
+
 impl Default for VideoFormat {
     fn default() -> Self {
         Self { fourcc: "0000".to_string(), description: "None".to_string(), resolutions: vec![] }
@@ -21,21 +151,27 @@ impl Default for VideoFormat {
 }
 
 pub fn apply_saved_format_config(state: &mut AppState, cfg: &MichadameConfig) {
+    state.video_usb_speed = crate::devices::video::usb_speed_mbps(&state.selected_video_device);
     if let Ok(formats) = crate::devices::video::find_video_formats(&state.selected_video_device)
     {
         state.supported_formats = formats;
-        if let Some(saved_fourcc) = &cfg.video_format_fourcc {
-            if let Some(idx) = state.supported_formats.iter().position(|f| f.fourcc == *saved_fourcc)
-            {
-                state.selected_format_index = idx;
-                if let Some(saved_res) = cfg.video_resolution {
-                    if state.supported_formats[idx].resolutions.iter().any(|r| r.width == saved_res.0 && r.height == saved_res.1) {
-                        state.selected_resolution = saved_res;
-                        if let Some(saved_fps) = cfg.video_framerate {
-                            if let Some(res_info) = state.supported_formats[idx].resolutions.iter().find(|r| r.width == saved_res.0 && r.height == saved_res.1) {
-                                if res_info.framerates.contains(&saved_fps) {
-                                    state.selected_framerate = saved_fps;
-                                }
+        select_saved_format(state, cfg);
+    }
+}
+
+/// Selects the saved format, resolution and framerate among the already scanned `supported_formats`.
+pub fn select_saved_format(state: &mut AppState, cfg: &MichadameConfig) {
+    if let Some(saved_fourcc) = &cfg.video_format_fourcc {
+        if let Some(idx) = state.supported_formats.iter().position(|f| f.fourcc == *saved_fourcc)
+        {
+            state.selected_format_index = idx;
+            if let Some(saved_res) = cfg.video_resolution {
+                if state.supported_formats[idx].resolutions.iter().any(|r| r.width == saved_res.0 && r.height == saved_res.1) {
+                    state.selected_resolution = saved_res;
+                    if let Some(saved_fps) = cfg.video_framerate {
+                        if let Some(res_info) = state.supported_formats[idx].resolutions.iter().find(|r| r.width == saved_res.0 && r.height == saved_res.1) {
+                            if res_info.framerates.contains(&saved_fps) {
+                                state.selected_framerate = saved_fps;
                             }
                         }
                     }
@@ -43,4 +179,114 @@ pub fn apply_saved_format_config(state: &mut AppState, cfg: &MichadameConfig) {
             }
         }
     }
-}
\ No newline at end of file
+    if cfg.auto_format.unwrap_or(false) {
+        apply_auto_format(state);
+    }
+}
+
+/// Bandwidth an uncompressed format needs in Mbit/s, or None for compressed formats whose
+/// size depends on the picture.
+pub fn estimated_bandwidth_mbps(fourcc: &str, resolution: (u32, u32), fps: u32) -> Option<f64> {
+    let bits_per_pixel = match fourcc.trim_end_matches('\0') {
+        "YUYV" | "YUY2" | "UYVY" | "YVYU" | "RGBP" => 16.0,
+        "NV12" | "NV21" | "YU12" | "YV12" | "I420" => 12.0,
+        "RGB3" | "BGR3" => 24.0,
+        "GREY" => 8.0,
+        _ => return None,
+    };
+    Some(resolution.0 as f64 * resolution.1 as f64 * fps as f64 * bits_per_pixel / 1_000_000.0)
+}
+
+/// Rough video payload a USB link can carry in Mbit/s. USB 2.0 isochronous transfers top out at
+/// 3 x 1024 bytes per microframe (~196 Mbit/s), well below the 480 Mbit/s signalling rate.
+pub fn usable_usb_bandwidth_mbps(link_mbps: u32) -> f64 {
+    match link_mbps {
+        0..=12 => link_mbps as f64 * 0.8,
+        13..=480 => 196.6,
+        _ => link_mbps as f64 * 0.64,
+    }
+}
+
+pub fn usb_speed_name(link_mbps: u32) -> &'static str {
+    match link_mbps {
+        0..=12 => "USB 1.1",
+        13..=480 => "USB 2.0",
+        481..=5000 => "USB 3.0",
+        5001..=10000 => "USB 3.1",
+        _ => "USB 3.2",
+    }
+}
+
+/// Largest frame area where uncompressed formats still fit USB 2.0 bandwidth at 60 fps.
+const RAW_PREFERRED_MAX_AREA: u32 = 1280 * 720;
+
+/// Picks a format for users who don't want to think about fourccs, as (format index, resolution, fps):
+/// the highest resolution that reaches 60 fps (else 30 fps, else anything), preferring raw formats
+/// at low resolutions where they avoid MJPEG artifacts, and MJPEG at high resolutions.
+/// Fourccs in `priority` win over that rule at the same resolution, earlier ones first.
+pub fn pick_auto_format(formats: &[VideoFormat], priority: &[String]) -> Option<(usize, (u32, u32), u32)> {
+    formats
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, format)| {
+            let fourcc = format.fourcc.trim_end_matches('\0');
+            let compressed = matches!(fourcc, "MJPG" | "JPEG" | "H264");
+            // Listed formats rank above unlisted ones, and earlier above later.
+            let rank = priority.iter().position(|p| p.eq_ignore_ascii_case(fourcc)).map_or(0, |pos| priority.len() - pos);
+            format.resolutions.iter().filter_map(move |res| {
+                let fps = res.framerates.iter().max().copied()?;
+                Some((idx, (res.width, res.height), fps, rank, compressed))
+            })
+        })
+        .max_by_key(|&(_, (width, height), fps, rank, compressed)| {
+            let area = width * height;
+            let preferred_codec = if area <= RAW_PREFERRED_MAX_AREA { !compressed } else { compressed };
+            (fps >= 60, fps >= 30, area, rank, preferred_codec, fps)
+        })
+        .map(|(idx, resolution, fps, _, _)| (idx, resolution, fps))
+}
+
+/// Splits a comma or space separated list of fourccs, e.g. "NV12, YUYV".
+pub fn parse_format_priority(list: &str) -> Vec<String> {
+    list.split(|c: char| c == ',' || c.is_whitespace()).filter(|s| !s.is_empty()).map(|s| s.to_uppercase()).collect()
+}
+
+pub fn apply_auto_format(state: &mut AppState) {
+    if let Some((idx, resolution, fps)) = pick_auto_format(&state.supported_formats, &parse_format_priority(&state.format_priority)) {
+        state.selected_format_index = idx;
+        state.selected_resolution = resolution;
+        state.selected_framerate = fps;
+    }
+}
+
+/// Why the video thread stopped on its own, as reported to the UI.
+#[derive(Debug, Clone)]
+pub struct VideoThreadFailure {
+    pub message: String,
+    /// Set when the capture device was opened exclusively by another program.
+    pub busy: Option<DeviceBusyDetails>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceBusyDetails {
+    /// Processes holding the device, as (pid, command name).
+    pub users: Vec<(u32, String)>,
+    /// Other video devices as (path, is_v4l2loopback).
+    pub alternatives: Vec<(String, bool)>,
+}
+
+impl DeviceBusyDetails {
+    /// Shells out to v4l2-ctl for every device, so call this off the UI thread.
+    pub fn gather(device: &str) -> Self {
+        let alternatives = crate::devices::video::find_video_devices()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|path| path != device)
+            .map(|path| {
+                let loopback = crate::devices::video::is_loopback_device(&path);
+                (path, loopback)
+            })
+            .collect();
+        Self { users: crate::devices::video::find_device_users(device), alternatives }
+    }
+}
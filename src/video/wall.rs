@@ -0,0 +1,42 @@
+use eframe::egui;
+
+/// Splits the video into a grid of tiles, one per monitor, for multi-screen cabinets.
+/// Tile 0 is shown in the main video window, the others in their own windows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WallLayout {
+    pub columns: u32,
+    pub rows: u32,
+    /// Width of the bezels between monitors, as a percentage of a tile's size. The video
+    /// behind a bezel is hidden, so lines crossing from one screen to the next stay straight.
+    pub bezel_percent: f32,
+}
+
+impl Default for WallLayout {
+    fn default() -> Self {
+        Self { columns: 2, rows: 1, bezel_percent: 0.0 }
+    }
+}
+
+impl WallLayout {
+    pub fn tile_count(&self) -> usize {
+        (self.columns.max(1) * self.rows.max(1)) as usize
+    }
+
+    /// The part of the video shown by tile `index` (row-major), in texture coordinates.
+    pub fn tile_uv(&self, index: usize) -> egui::Rect {
+        let columns = self.columns.max(1);
+        let rows = self.rows.max(1);
+        let column = index as u32 % columns;
+        let row = (index as u32 / columns).min(rows - 1);
+        let bezel = self.bezel_percent.max(0.0) / 100.0;
+        // The video spans all tiles plus the bezels between them, measured in tile sizes.
+        let axis = |cell: u32, cells: u32| {
+            let span = cells as f32 + (cells - 1) as f32 * bezel;
+            let start = cell as f32 * (1.0 + bezel) / span;
+            (start, start + 1.0 / span)
+        };
+        let (x0, x1) = axis(column, columns);
+        let (y0, y1) = axis(row, rows);
+        egui::Rect::from_min_max(egui::pos2(x0, y0), egui::pos2(x1, y1))
+    }
+}